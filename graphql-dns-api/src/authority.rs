@@ -0,0 +1,695 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Authoritative DNS server backed by the SurrealDB record store
+//!
+//! Answers UDP/TCP queries straight from `Database::query_records`:
+//! - AA set on every answer for names inside a served zone, except referrals
+//! - referrals for names at or below a zone cut: the delegation NS RRset in
+//!   authority with AA clear, glue in additional, and the DS RRset or its
+//!   denial proof when the query sets DO
+//! - answers synthesised from wildcards for names that do not exist (RFC 4592)
+//! - NXDOMAIN when nothing exists at or below the queried name
+//! - NODATA (NOERROR, empty answer, SOA in authority) when the name exists
+//!   but holds no RRset of the queried type
+//! - CNAME answers for aliased names
+//! - RRSIGs from `DNSRecord.rrsig` when the query sets the EDNS DO bit
+//! - NSEC/NSEC3 denial proofs from the zone's chain in signed negative answers
+//! - the DNSKEY RRset of signed zones from the key store
+//! - REFUSED for names outside every served zone
+//! - AXFR/IXFR to TSIG-authorised secondaries, see [`crate::xfr`]
+//! - TSIG-signed RFC 2136 UPDATE, see [`crate::update`]
+//!
+//! A zone is served when an SOA record exists at its apex.
+
+use crate::{
+    consent::ConsentClient,
+    db::Database,
+    denial,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    models::{DNSRecord, DNSRecordType},
+    policy::PolicyEnforcer,
+    rdata, rollover, update, xfr,
+};
+use base64::Engine as _;
+use hickory_proto::{
+    op::{Edns, Header, MessageType, OpCode, ResponseCode},
+    rr::{
+        dnssec::{
+            rdata::{DNSSECRData, RRSIG},
+            Algorithm,
+        },
+        Name, RData, Record, RecordType,
+    },
+};
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo, ServerFuture},
+};
+use std::{collections::BTreeSet, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, UdpSocket},
+    sync::RwLock,
+};
+use tracing::{debug, error, info};
+
+/// Idle timeout for TCP connections.
+const TCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum records fetched for one RRset lookup.
+const RRSET_LIMIT: i32 = 1000;
+
+/// EDNS UDP payload size advertised in responses.
+pub(crate) const EDNS_MAX_PAYLOAD: u16 = 1232;
+
+/// Authoritative request handler over the record store.
+pub struct DnsAuthority {
+    db: Database,
+    manager: Arc<DNSSECManager>,
+    policy: Arc<RwLock<PolicyEnforcer>>,
+    consent: Arc<ConsentClient>,
+}
+
+/// Outcome of resolving a single question against the store.
+#[derive(Debug, Default)]
+struct Answer {
+    response_code: ResponseCode,
+    authoritative: bool,
+    answers: Vec<Record>,
+    authority: Vec<Record>,
+    additionals: Vec<Record>,
+}
+
+impl DnsAuthority {
+    /// Create a handler answering from `db`; `manager` unseals TSIG keys for
+    /// transfers and updates, which `policy` and `consent` authorise like the
+    /// GraphQL mutations.
+    pub fn new(
+        db: Database,
+        manager: Arc<DNSSECManager>,
+        policy: Arc<RwLock<PolicyEnforcer>>,
+        consent: Arc<ConsentClient>,
+    ) -> Self {
+        Self {
+            db,
+            manager,
+            policy,
+            consent,
+        }
+    }
+}
+
+/// Resolve a question to an answer, following the RFC 1034 §4.3.2 algorithm
+/// restricted to the zones held in the store.
+async fn resolve(db: &Database, qname: &str, qtype: RecordType, dnssec_ok: bool) -> Result<Answer> {
+    let Some((apex, soa)) = find_zone(db, qname).await? else {
+        return Ok(Answer {
+            response_code: ResponseCode::Refused,
+            ..Default::default()
+        });
+    };
+
+    // At or below a zone cut the child is authoritative, except for the DS
+    // RRset the parent holds at the cut itself (RFC 4035 §3.1.4.1).
+    if let Some(cut) = zone_cut(db, &apex, qname).await? {
+        if cut != qname || qtype != RecordType::DS {
+            return referral(db, &apex, &cut, dnssec_ok).await;
+        }
+    }
+
+    let mut answer = Answer {
+        response_code: ResponseCode::NoError,
+        authoritative: true,
+        ..Default::default()
+    };
+
+    let mut at_name = db.query_records(Some(qname.to_string()), None, RRSET_LIMIT, 0).await?;
+
+    // A name that does not exist matches the wildcard at its closest encloser
+    let mut nxdomain = false;
+    let mut wildcard = None;
+    if at_name.is_empty() && !db.has_names_below(qname).await? {
+        match expand_wildcard(db, &apex, qname).await? {
+            Some((encloser, records)) => {
+                at_name = records;
+                wildcard = Some(encloser);
+            }
+            None => nxdomain = true,
+        }
+    }
+
+    let matching: Vec<&DNSRecord> = at_name
+        .iter()
+        .filter(|r| qtype == RecordType::ANY || wire_type(r.record_type) == qtype)
+        .collect();
+
+    if !matching.is_empty() {
+        answer.answers = rrset_with_signatures(&matching, dnssec_ok)?;
+        if let (Some(encloser), true) = (&wildcard, dnssec_ok) {
+            answer.authority = denial::wildcard_proof(db, &apex, qname, encloser, false).await?;
+        }
+        return Ok(answer);
+    }
+
+    // DNSKEYs live in the key store, not the record store
+    let dnskeys = rollover::answer(db, &apex, qname, qtype, dnssec_ok).await?;
+    if !dnskeys.is_empty() {
+        answer.answers = dnskeys;
+        return Ok(answer);
+    }
+
+    // NSEC/NSEC3/NSEC3PARAM live in the zone's denial chain, not the record store
+    let chain = denial::answer(db, &apex, qname, qtype, dnssec_ok).await?;
+    if !chain.is_empty() {
+        answer.answers = chain;
+        return Ok(answer);
+    }
+
+    // Alias at the queried name: answer with the CNAME and, when the target
+    // lives in the same zone, the target RRset.
+    let cnames: Vec<&DNSRecord> = at_name
+        .iter()
+        .filter(|r| r.record_type == DNSRecordType::CNAME)
+        .collect();
+    if let Some(cname) = cnames.first() {
+        answer.answers = rrset_with_signatures(&cnames, dnssec_ok)?;
+        let target = normalize_name(&cname.value);
+        if in_zone(&target, &apex) {
+            let targets = db
+                .query_records(Some(target), dns_type(qtype), RRSET_LIMIT, 0)
+                .await?;
+            let targets: Vec<&DNSRecord> = targets.iter().collect();
+            answer.answers.extend(rrset_with_signatures(&targets, dnssec_ok)?);
+        }
+        if let (Some(encloser), true) = (&wildcard, dnssec_ok) {
+            answer.authority = denial::wildcard_proof(db, &apex, qname, encloser, false).await?;
+        }
+        return Ok(answer);
+    }
+
+    // Negative answer: distinguish NODATA from NXDOMAIN, SOA in authority
+    // with the RFC 2308 §3 negative TTL, plus the NSEC/NSEC3 proof when DO is set.
+    if nxdomain {
+        answer.response_code = ResponseCode::NXDomain;
+    }
+    answer.authority = negative_soa(&soa, dnssec_ok)?;
+    if dnssec_ok {
+        let proof = match &wildcard {
+            Some(encloser) => denial::wildcard_proof(db, &apex, qname, encloser, true).await?,
+            None => denial::negative_proof(db, &apex, qname, nxdomain).await?,
+        };
+        answer.authority.extend(proof);
+    }
+    Ok(answer)
+}
+
+/// Find the closest enclosing zone for `qname`: the longest ancestor holding an SOA.
+async fn find_zone(db: &Database, qname: &str) -> Result<Option<(String, DNSRecord)>> {
+    let mut candidate = qname;
+    loop {
+        let soa = db
+            .query_records(Some(candidate.to_string()), Some(DNSRecordType::SOA), 1, 0)
+            .await?;
+        if let Some(soa) = soa.into_iter().next() {
+            return Ok(Some((candidate.to_string(), soa)));
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if !parent.is_empty() => candidate = parent,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// The first delegation point on the way down from the apex to `qname`:
+/// the highest name below the apex, at or above `qname`, holding an NS RRset.
+async fn zone_cut(db: &Database, apex: &str, qname: &str) -> Result<Option<String>> {
+    for name in descent(apex, qname) {
+        let ns = db
+            .query_records(Some(name.to_string()), Some(DNSRecordType::NS), 1, 0)
+            .await?;
+        if !ns.is_empty() {
+            return Ok(Some(name.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Names strictly below `apex` down to and including `qname`, apex side first.
+fn descent<'a>(apex: &str, qname: &'a str) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    let mut name = qname;
+    while name != apex && in_zone(name, apex) {
+        names.push(name);
+        match name.split_once('.') {
+            Some((_, parent)) => name = parent,
+            None => break,
+        }
+    }
+    names.reverse();
+    names
+}
+
+/// Referral to the child zone at `cut` (RFC 1034 §4.3.2 step 3b): AA clear,
+/// the delegation NS RRset in authority with, when DO is set, the signed DS
+/// RRset or the proof there is none (RFC 4035 §3.1.4), and glue for name
+/// servers inside the zone in additional.
+async fn referral(db: &Database, apex: &str, cut: &str, dnssec_ok: bool) -> Result<Answer> {
+    let at_cut = db.query_records(Some(cut.to_string()), None, RRSET_LIMIT, 0).await?;
+    let ns: Vec<&DNSRecord> = at_cut
+        .iter()
+        .filter(|r| r.record_type == DNSRecordType::NS)
+        .collect();
+    let ds: Vec<&DNSRecord> = at_cut
+        .iter()
+        .filter(|r| r.record_type == DNSRecordType::DS)
+        .collect();
+
+    // The parent's NS RRset is not authoritative data and is never signed
+    let mut authority = rrset_with_signatures(&ns, false)?;
+    if dnssec_ok {
+        if ds.is_empty() {
+            authority.extend(denial::negative_proof(db, apex, cut, false).await?);
+        } else {
+            authority.extend(rrset_with_signatures(&ds, true)?);
+        }
+    }
+
+    let targets: BTreeSet<String> = ns.iter().map(|r| normalize_name(&r.value)).collect();
+    let mut additionals = Vec::new();
+    for target in targets.into_iter().filter(|t| in_zone(t, apex)) {
+        let records = db.query_records(Some(target), None, RRSET_LIMIT, 0).await?;
+        let glue: Vec<&DNSRecord> = records
+            .iter()
+            .filter(|r| matches!(r.record_type, DNSRecordType::A | DNSRecordType::AAAA))
+            .collect();
+        additionals.extend(rrset_with_signatures(&glue, false)?);
+    }
+
+    Ok(Answer {
+        response_code: ResponseCode::NoError,
+        authoritative: false,
+        answers: Vec::new(),
+        authority,
+        additionals,
+    })
+}
+
+/// Records of the wildcard at the closest encloser of `qname`, which does not
+/// exist, renamed to `qname` (RFC 4592 §3.3.1), together with that encloser.
+async fn expand_wildcard(
+    db: &Database,
+    apex: &str,
+    qname: &str,
+) -> Result<Option<(String, Vec<DNSRecord>)>> {
+    let encloser = denial::closest_encloser(db, apex, qname).await?;
+    let source = format!("*.{}", encloser);
+    let records = db.query_records(Some(source), None, RRSET_LIMIT, 0).await?;
+    if records.is_empty() {
+        return Ok(None);
+    }
+    let records = records
+        .into_iter()
+        .map(|mut record| {
+            record.name = qname.to_string();
+            record
+        })
+        .collect();
+    Ok(Some((encloser, records)))
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for DnsAuthority {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        let builder = MessageResponseBuilder::from_message_request(request);
+
+        if request.message_type() == MessageType::Query && request.op_code() == OpCode::Update {
+            return update::serve(
+                &self.db,
+                &self.manager,
+                &self.policy,
+                &self.consent,
+                request,
+                response_handle,
+            )
+            .await;
+        }
+        if request.message_type() != MessageType::Query || request.op_code() != OpCode::Query {
+            let response = builder.error_msg(request.header(), ResponseCode::NotImp);
+            return send(&mut response_handle, response).await;
+        }
+
+        let query = request.query();
+        let qname = normalize_name(&query.name().to_string());
+        let qtype = query.query_type();
+        if matches!(qtype, RecordType::AXFR | RecordType::IXFR) {
+            return xfr::serve(&self.db, &self.manager, request, response_handle).await;
+        }
+        let dnssec_ok = request.edns().map(|e| e.dnssec_ok()).unwrap_or(false);
+        debug!("query {} {} from {}", qname, qtype, request.src());
+
+        let mut builder = builder;
+        if request.edns().is_some() {
+            let mut edns = Edns::new();
+            edns.set_max_payload(EDNS_MAX_PAYLOAD).set_dnssec_ok(dnssec_ok);
+            builder.edns(edns);
+        }
+
+        let answer = match resolve(&self.db, &qname, qtype, dnssec_ok).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                error!("failed to answer {} {}: {}", qname, qtype, e);
+                let response = builder.error_msg(request.header(), ResponseCode::ServFail);
+                return send(&mut response_handle, response).await;
+            }
+        };
+
+        let mut header = Header::response_from_request(request.header());
+        header.set_authoritative(answer.authoritative);
+        header.set_recursion_available(false);
+        header.set_response_code(answer.response_code);
+
+        let response = builder.build(
+            header,
+            answer.answers.iter(),
+            std::iter::empty(),
+            answer.authority.iter(),
+            answer.additionals.iter(),
+        );
+        send(&mut response_handle, response).await
+    }
+}
+
+/// Send a response, mapping I/O failures to SERVFAIL for the server's bookkeeping.
+pub(crate) async fn send<'a, R: ResponseHandler>(
+    response_handle: &mut R,
+    response: hickory_server::authority::MessageResponse<
+        '_,
+        'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+    >,
+) -> ResponseInfo {
+    match response_handle.send_response(response).await {
+        Ok(info) => info,
+        Err(e) => {
+            error!("failed to send DNS response: {}", e);
+            let mut header = Header::new();
+            header.set_response_code(ResponseCode::ServFail);
+            header.into()
+        }
+    }
+}
+
+/// Bind UDP and TCP listeners on `addr` and serve the record store in the background.
+pub async fn spawn(
+    db: Database,
+    manager: Arc<DNSSECManager>,
+    policy: Arc<RwLock<PolicyEnforcer>>,
+    consent: Arc<ConsentClient>,
+    addr: SocketAddr,
+) -> Result<()> {
+    let udp = UdpSocket::bind(addr)
+        .await
+        .map_err(|e| AppError::Internal(format!("DNS UDP bind {} failed: {}", addr, e)))?;
+    let tcp = TcpListener::bind(addr)
+        .await
+        .map_err(|e| AppError::Internal(format!("DNS TCP bind {} failed: {}", addr, e)))?;
+
+    let mut server = ServerFuture::new(DnsAuthority::new(db, manager, policy, consent));
+    server.register_socket(udp);
+    server.register_listener(tcp, TCP_TIMEOUT);
+    info!("Authoritative DNS listening on {} (udp/tcp)", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = server.block_until_done().await {
+            error!("DNS server stopped: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Lowercase a domain name and strip the trailing root dot, matching stored record names.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `name` is `apex` or a descendant of it.
+pub(crate) fn in_zone(name: &str, apex: &str) -> bool {
+    name == apex || name.ends_with(&format!(".{}", apex))
+}
+
+/// Wire record type for a stored record type.
+pub(crate) fn wire_type(record_type: DNSRecordType) -> RecordType {
+    RecordType::from(record_type.to_type_code())
+}
+
+/// Stored record type for a queried wire type, if the store can hold it.
+pub(crate) fn dns_type(record_type: RecordType) -> Option<DNSRecordType> {
+    use DNSRecordType::*;
+    [
+        A, AAAA, CNAME, MX, TXT, SRV, CAA, TLSA, NS, SOA, PTR, HTTPS, SVCB, DS, DNSKEY, CDS,
+        CDNSKEY, SSHFP, NAPTR, URI, OPENPGPKEY, SMIMEA, LOC,
+    ]
+        .into_iter()
+        .find(|t| wire_type(*t) == record_type)
+}
+
+/// Convert a stored record into a wire-format resource record.
+pub(crate) fn to_wire_record(record: &DNSRecord) -> Result<Record> {
+    let name = Name::from_str(&format!("{}.", normalize_name(&record.name)))
+        .map_err(|e| AppError::InvalidRecord(format!("{}: {}", record.name, e)))?;
+    let rdata = rdata::to_rdata(record.record_type, &record.value)
+        .map_err(|e| AppError::InvalidRecord(format!("{}: {}", record.name, e)))?;
+    Ok(Record::from_rdata(name, record.ttl as u32, rdata))
+}
+
+/// Convert an RRset to wire records, appending its RRSIGs when DO is set.
+///
+/// Signatures are RRset-level, so identical `rrsig` values across the set are emitted once.
+/// A multi-algorithm zone stores one RRSIG per line.
+fn rrset_with_signatures(rrset: &[&DNSRecord], dnssec_ok: bool) -> Result<Vec<Record>> {
+    let mut records = rrset
+        .iter()
+        .map(|r| to_wire_record(r))
+        .collect::<Result<Vec<_>>>()?;
+
+    if dnssec_ok {
+        let mut seen: Vec<&str> = Vec::new();
+        for record in rrset {
+            for rrsig in record.rrsig.iter().flat_map(|s| s.lines()) {
+                if !seen.contains(&rrsig) {
+                    seen.push(rrsig);
+                    records.push(rrsig_record(record, rrsig)?);
+                }
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// SOA for the authority section of a negative answer, TTL capped at SOA MINIMUM.
+fn negative_soa(soa: &DNSRecord, dnssec_ok: bool) -> Result<Vec<Record>> {
+    let mut records = rrset_with_signatures(&[soa], dnssec_ok)?;
+    let minimum = match records.first().and_then(|r| r.data()) {
+        Some(RData::SOA(rdata)) => Some(rdata.minimum()),
+        _ => None,
+    };
+    if let Some(minimum) = minimum {
+        records[0].set_ttl(minimum.min(soa.ttl as u32));
+    }
+    Ok(records)
+}
+
+/// Build an RRSIG record from the stored presentation form (RFC 4034 §3.2):
+/// `<type covered> <algorithm> <labels> <original TTL> <expiration> <inception> <key tag> <signer> <signature>`
+pub(crate) fn rrsig_record(record: &DNSRecord, rrsig: &str) -> Result<Record> {
+    rrsig_wire(&record.name, record.ttl as u32, rrsig)
+}
+
+/// Build an RRSIG record for `owner` from its stored presentation form.
+pub(crate) fn rrsig_wire(owner: &str, ttl: u32, rrsig: &str) -> Result<Record> {
+    let invalid = |what: &str| AppError::DNSSEC(format!("Invalid RRSIG on {}: {}", owner, what));
+    let fields: Vec<&str> = rrsig.split_whitespace().collect();
+    if fields.len() < 9 {
+        return Err(invalid("expected 9 fields"));
+    }
+
+    let type_covered = RecordType::from_str(fields[0]).map_err(|_| invalid("type covered"))?;
+    let algorithm = fields[1].parse::<u8>().map_err(|_| invalid("algorithm"))?;
+    let labels = fields[2].parse::<u8>().map_err(|_| invalid("labels"))?;
+    let original_ttl = fields[3].parse::<u32>().map_err(|_| invalid("original TTL"))?;
+    let expiration = parse_sig_time(fields[4]).ok_or_else(|| invalid("expiration"))?;
+    let inception = parse_sig_time(fields[5]).ok_or_else(|| invalid("inception"))?;
+    let key_tag = fields[6].parse::<u16>().map_err(|_| invalid("key tag"))?;
+    let signer = Name::from_str(fields[7]).map_err(|_| invalid("signer name"))?;
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(fields[8..].concat())
+        .map_err(|_| invalid("signature"))?;
+
+    let rdata = RRSIG::new(
+        type_covered,
+        Algorithm::from_u8(algorithm),
+        labels,
+        original_ttl,
+        expiration,
+        inception,
+        key_tag,
+        signer,
+        signature,
+    );
+
+    let name = Name::from_str(&format!("{}.", normalize_name(owner)))
+        .map_err(|_| invalid("owner name"))?;
+    Ok(Record::from_rdata(
+        name,
+        ttl,
+        RData::DNSSEC(DNSSECRData::RRSIG(rdata)),
+    ))
+}
+
+/// Parse an RRSIG timestamp: `YYYYMMDDHHmmSS` or seconds since the epoch.
+fn parse_sig_time(value: &str) -> Option<u32> {
+    if value.len() == 14 {
+        let time = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S").ok()?;
+        u32::try_from(time.and_utc().timestamp()).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_wire_record() {
+        let record = DNSRecord::new(
+            "Example.com".to_string(),
+            DNSRecordType::A,
+            3600,
+            "192.0.2.1".to_string(),
+        );
+        let wire = to_wire_record(&record).expect("A record should convert");
+        assert_eq!(wire.name().to_string(), "example.com.");
+        assert_eq!(wire.record_type(), RecordType::A);
+        assert_eq!(wire.ttl(), 3600);
+    }
+
+    #[test]
+    fn test_to_wire_record_rejects_bad_rdata() {
+        let record = DNSRecord::new(
+            "example.com".to_string(),
+            DNSRecordType::MX,
+            3600,
+            "not-a-preference".to_string(),
+        );
+        assert!(to_wire_record(&record).is_err());
+    }
+
+    #[test]
+    fn test_in_zone() {
+        assert!(in_zone("example.com", "example.com"));
+        assert!(in_zone("www.example.com", "example.com"));
+        assert!(!in_zone("badexample.com", "example.com"));
+    }
+
+    #[test]
+    fn test_rrsig_record_parsing() {
+        let mut record = DNSRecord::new(
+            "example.com".to_string(),
+            DNSRecordType::A,
+            3600,
+            "192.0.2.1".to_string(),
+        );
+        let rrsig = "A 15 2 3600 20261116000000 20261016000000 12345 example.com. AAECAw==";
+        record.rrsig = Some(rrsig.to_string());
+
+        let wire = rrsig_record(&record, rrsig).expect("RRSIG should parse");
+        assert_eq!(wire.record_type(), RecordType::RRSIG);
+        assert!(rrsig_record(&record, "A 15 2").is_err());
+    }
+
+    #[test]
+    fn test_descent() {
+        assert_eq!(
+            descent("example.com", "a.b.example.com"),
+            ["b.example.com", "a.b.example.com"]
+        );
+        assert!(descent("example.com", "example.com").is_empty());
+    }
+
+    async fn zone_with(records: &[(&str, DNSRecordType, &str)]) -> Database {
+        use crate::zones;
+
+        let db = Database::connect("memory").await.expect("in-memory database");
+        zones::example_zone(&db).await;
+        for (name, record_type, value) in records {
+            let record = DNSRecord::new(name.to_string(), *record_type, 300, value.to_string());
+            db.create_record(record).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_delegated_child_is_referred() {
+        let db = zone_with(&[
+            ("sub.example.com", DNSRecordType::NS, "ns1.sub.example.com"),
+            ("ns1.sub.example.com", DNSRecordType::A, "192.0.2.53"),
+            ("www.sub.example.com", DNSRecordType::A, "192.0.2.80"),
+        ])
+        .await;
+
+        // Occluded data and names the child may not hold are both referred
+        for qname in ["www.sub.example.com", "missing.sub.example.com", "sub.example.com"] {
+            let answer = resolve(&db, qname, RecordType::A, true).await.unwrap();
+            assert_eq!(answer.response_code, ResponseCode::NoError, "{}", qname);
+            assert!(!answer.authoritative);
+            assert!(answer.answers.is_empty());
+            assert_eq!(answer.authority.len(), 1);
+            assert_eq!(answer.authority[0].record_type(), RecordType::NS);
+            assert_eq!(answer.additionals.len(), 1);
+            assert_eq!(answer.additionals[0].name().to_string(), "ns1.sub.example.com.");
+        }
+
+        // The parent answers DS at the cut itself
+        let answer = resolve(&db, "sub.example.com", RecordType::DS, false).await.unwrap();
+        assert!(answer.authoritative);
+        assert_eq!(answer.response_code, ResponseCode::NoError);
+        assert_eq!(answer.authority[0].record_type(), RecordType::SOA);
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_is_synthesised() {
+        let db = zone_with(&[
+            ("*.example.com", DNSRecordType::A, "192.0.2.7"),
+            ("host.example.com", DNSRecordType::TXT, "v=spf1 -all"),
+        ])
+        .await;
+
+        for qname in ["anything.example.com", "a.b.example.com"] {
+            let answer = resolve(&db, qname, RecordType::A, false).await.unwrap();
+            assert!(answer.authoritative);
+            assert_eq!(answer.answers.len(), 1);
+            assert_eq!(answer.answers[0].name().to_string(), format!("{}.", qname));
+        }
+
+        // The wildcard owns no MX: NODATA rather than NXDOMAIN
+        let answer = resolve(&db, "anything.example.com", RecordType::MX, false).await.unwrap();
+        assert_eq!(answer.response_code, ResponseCode::NoError);
+        assert!(answer.answers.is_empty());
+
+        // Existing names and names below them are not matched
+        let answer = resolve(&db, "host.example.com", RecordType::A, false).await.unwrap();
+        assert_eq!(answer.response_code, ResponseCode::NoError);
+        assert!(answer.answers.is_empty());
+        let answer = resolve(&db, "x.host.example.com", RecordType::A, false).await.unwrap();
+        assert_eq!(answer.response_code, ResponseCode::NXDomain);
+    }
+}
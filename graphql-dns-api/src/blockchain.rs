@@ -5,7 +5,14 @@
 //! - Ethereum mainnet and testnets (Sepolia)
 //! - Polygon mainnet and testnets (Amoy)
 
-use crate::error::{AppError, Result};
+use crate::{
+    db::Database,
+    error::{AppError, Result},
+    events::Event,
+    models::BlockchainProvenance,
+};
+use async_graphql::ID;
+use chrono::Utc;
 use ethers::{
     core::types::{Transaction, TransactionReceipt, TransactionRequest, TxHash, U256},
     middleware::SignerMiddleware,
@@ -57,6 +64,11 @@ pub fn new(network: &str) -> Result<Self> {
         })
     }
 
+    /// Network this client anchors to
+    pub fn network(&self) -> &str {
+        &self.network
+    }
+
     /// Anchor a content hash to the blockchain
     ///
     /// This creates a transaction with the hash embedded in the data field,
@@ -127,7 +139,7 @@ pub async fn verify_hash(&self, tx_hash: &str, expected_hash: &str) -> Result<bo
 
         // Extract hash from transaction data
         let data = tx.input;
-        let data_str = hex::encode(data.to_vec());
+        let data_str = hex::encode(&data);
 
         // Remove "DNS:" prefix
         let prefix_len = "DNS:".len() * 2; // hex encoded
@@ -163,6 +175,32 @@ pub async fn get_block_number(&self) -> Result<i64> {
     }
 }
 
+/// Anchor the content hash of a stored record to `network`, store the
+/// provenance and publish it
+pub async fn anchor_record(
+    db: &Database,
+    record_id: &ID,
+    network: &str,
+) -> Result<BlockchainProvenance> {
+    let record = db.get_record(record_id).await?;
+    let content_hash = record.content_hash();
+    let (tx_hash, block_number) = BlockchainClient::new(network)?
+        .anchor_hash(&content_hash)
+        .await?;
+
+    let provenance = BlockchainProvenance {
+        record_id: record_id.clone(),
+        content_hash,
+        network: network.to_string(),
+        tx_hash,
+        block_number,
+        timestamp: Utc::now(),
+    };
+    let stored = db.store_provenance(provenance).await?;
+    db.events().publish(Event::AnchorConfirmed(stored.clone()));
+    Ok(stored)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
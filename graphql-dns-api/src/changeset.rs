@@ -0,0 +1,666 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Changesets: several record changes to one zone, made as one
+//!
+//! A changeset lists records to create, update and delete in a zone. The
+//! changes are applied in order to a working copy of the zone and validated as
+//! they go, exactly as the single-record mutations validate theirs: RDATA is
+//! parsed for its type, a record joining an RRset gives the whole RRset its
+//! TTL, the SOA is left to the zone and a signed zone's apex key RRsets to its
+//! keys. The RRset invariants ([`lint::check`]) are then checked at every name
+//! the changeset touches.
+//!
+//! [`preview`] stops there and reports the diff and the lint of the zone as it
+//! would be. [`commit`] also works out the zone's next serial and SOA, and for
+//! a signed zone the RRSIGs of the changed RRsets and the patch to the denial
+//! chain, then writes all of it in one transaction, so the changeset lands
+//! whole or not at all. The transaction is refused if the zone or a record
+//! the changeset replaces changed since it was read. Once it commits, the
+//! change is journaled, the secondaries notified and the records published.
+
+use crate::{
+    authority::{in_zone, normalize_name},
+    db::Database,
+    denial,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    lint,
+    models::{
+        ChangeAction, ChangesetResult, DNSRecord, DNSRecordInput, DNSRecordType, DNSSECZone,
+        RecordChangeInput, RecordChangeKind, RecordDiff, Zone, ZoneWrite,
+    },
+    rdata, rollover, signer, zones,
+};
+use async_graphql::ID;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use tracing::info;
+
+/// The names a changeset may touch: the zone's, less those of zones below it.
+struct Scope {
+    apex: String,
+    children: Vec<String>,
+    /// Whether the zone is signed, so that its records are signed and its apex
+    /// key RRsets managed
+    signed: bool,
+    /// TTL of a record joining a new RRset without one of its own
+    default_ttl: i32,
+}
+
+impl Scope {
+    fn contains(&self, name: &str) -> bool {
+        in_zone(name, &self.apex) && !self.children.iter().any(|c| in_zone(name, c))
+    }
+}
+
+/// A zone before and after a changeset.
+struct Plan {
+    scope: Scope,
+    /// The zone as it was read
+    zone: Zone,
+    /// The signed zone the records are signed for
+    dnssec: Option<DNSSECZone>,
+    before: Vec<DNSRecord>,
+    after: Vec<DNSRecord>,
+}
+
+/// Work out what `changes` do to `zone` and lint the result, without
+/// writing anything.
+pub async fn preview(
+    db: &Database,
+    zone: &str,
+    changes: Vec<RecordChangeInput>,
+) -> Result<ChangesetResult> {
+    let plan = plan(db, zone, changes).await?;
+    Ok(ChangesetResult {
+        lint: lint::lint(&plan.scope.apex, &plan.after),
+        zone: plan.scope.apex.clone(),
+        applied: false,
+        changes: diff(&plan.before, &plan.after),
+    })
+}
+
+/// Apply `changes` to `zone` in one transaction, refusing them all if any
+/// is invalid or the result breaks an RRset invariant.
+pub async fn commit(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    changes: Vec<RecordChangeInput>,
+) -> Result<ChangesetResult> {
+    let plan = plan(db, zone, changes).await?;
+    write(db, manager, plan).await
+}
+
+/// Write a planned changeset, unless the zone changed since it was planned.
+async fn write(db: &Database, manager: &DNSSECManager, plan: Plan) -> Result<ChangesetResult> {
+    let apex = plan.scope.apex.clone();
+    let changes = diff(&plan.before, &plan.after);
+    let touched: BTreeSet<&str> = changes
+        .iter()
+        .flat_map(|c| [&c.before, &c.after])
+        .flatten()
+        .map(|r| r.name.as_str())
+        .collect();
+    let at_touched: Vec<DNSRecord> = plan
+        .after
+        .iter()
+        .filter(|r| touched.contains(r.name.as_str()))
+        .cloned()
+        .collect();
+    lint::check(&apex, &at_touched)?;
+
+    if changes.is_empty() {
+        return Ok(ChangesetResult {
+            lint: lint::lint(&apex, &plan.after),
+            zone: apex,
+            applied: true,
+            changes,
+        });
+    }
+
+    info!(
+        "Changeset for {}: {} records created, {} updated, {} deleted",
+        apex,
+        changes.iter().filter(|c| c.kind == RecordChangeKind::Created).count(),
+        changes.iter().filter(|c| c.kind == RecordChangeKind::Updated).count(),
+        changes.iter().filter(|c| c.kind == RecordChangeKind::Deleted).count(),
+    );
+    let changes = store(db, manager, &plan.zone, plan.dnssec.as_ref(), &plan.before, plan.after)
+        .await?;
+    let stored = db.zone_records(&apex).await?;
+
+    Ok(ChangesetResult {
+        lint: lint::lint(&apex, &stored),
+        zone: apex,
+        applied: true,
+        changes,
+    })
+}
+
+/// Write `after` in place of `before`, the records of `zone` as read at its
+/// serial, in one transaction, signed for `dnssec` when the zone is signed.
+///
+/// This is how every record write reaches the database: changesets, the
+/// single-record mutations and dynamic updates alike. The transaction is
+/// refused if the zone or a record replaced changed since it was read. Once it
+/// commits, the change is journaled, the secondaries notified and the records
+/// published. Returns the changes with the records as stored, or none when
+/// `after` changes nothing.
+pub(crate) async fn store(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &Zone,
+    dnssec: Option<&DNSSECZone>,
+    before: &[DNSRecord],
+    after: Vec<DNSRecord>,
+) -> Result<Vec<RecordDiff>> {
+    let changes = diff(before, &after);
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    let write = prepare(db, manager, zone, dnssec, before, after, Utc::now()).await?;
+    let zone = write.zone.clone();
+    db.commit_zone_write(write).await?;
+    zones::announce(db, manager, &zone).await?;
+    info!("Zone {} is at serial {}", zone.apex, zone.serial);
+
+    // Report the records as stored, signatures included
+    let stored: HashMap<ID, DNSRecord> = db
+        .zone_records(&zone.apex)
+        .await?
+        .into_iter()
+        .map(|r| (r.id.clone(), r))
+        .collect();
+    let changes: Vec<RecordDiff> = changes
+        .into_iter()
+        .map(|change| RecordDiff {
+            after: change
+                .after
+                .map(|r| stored.get(&r.id).cloned().unwrap_or(r)),
+            ..change
+        })
+        .collect();
+    for change in &changes {
+        let record = change.after.as_ref().or(change.before.as_ref());
+        db.events().records(change.kind, record.cloned());
+    }
+    Ok(changes)
+}
+
+/// Everything a write stores, worked out before anything is: the changed
+/// records, the zone with its next serial and SOA and, for a signed zone, the
+/// RRSIGs of the changed RRsets and the patch to the denial chain.
+async fn prepare(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &Zone,
+    dnssec: Option<&DNSSECZone>,
+    before: &[DNSRecord],
+    mut after: Vec<DNSRecord>,
+    now: DateTime<Utc>,
+) -> Result<ZoneWrite> {
+    let serial = zone.serial;
+    let apex = &zone.apex;
+    let mut zone = zone.clone();
+    zones::advance_serial(&mut zone, now);
+
+    let soa = after
+        .iter_mut()
+        .find(|r| r.name == *apex && r.record_type == DNSRecordType::SOA);
+    match soa {
+        Some(soa) => {
+            soa.value = zones::soa_value(&zone);
+            soa.updated_at = now;
+        }
+        None => {
+            let value = zones::soa_value(&zone);
+            let mut soa = DNSRecord::new(apex.clone(), DNSRecordType::SOA, zone.default_ttl, value);
+            soa.zone = Some(apex.clone());
+            soa.dnssec = dnssec.is_some();
+            after.push(soa);
+        }
+    }
+
+    let mut chain = None;
+    if let Some(dnssec) = dnssec {
+        let mut changed: BTreeSet<(String, DNSRecordType)> = diff(before, &after)
+            .iter()
+            .flat_map(|c| [&c.before, &c.after])
+            .flatten()
+            .map(|r| (r.name.clone(), r.record_type))
+            .collect();
+        changed.insert((apex.clone(), DNSRecordType::SOA));
+        for (name, record_type) in &changed {
+            let in_rrset = |r: &DNSRecord| &r.name == name && r.record_type == *record_type;
+            let rrset: Vec<DNSRecord> = after.iter().filter(|r| in_rrset(r)).cloned().collect();
+            if rrset.is_empty() {
+                continue;
+            }
+            let rrsig = manager.sign_rrset(&dnssec.zone, &rrset, now)?;
+            for record in after.iter_mut().filter(|r| in_rrset(r)) {
+                record.rrsig = Some(rrsig.clone());
+                record.dnssec = true;
+            }
+        }
+
+        let current = db.denial_records(Some(apex)).await?;
+        chain = Some(denial::chain_patch(manager, dnssec, &after, &current, now)?);
+    }
+
+    let mut before: HashMap<&ID, &DNSRecord> = before.iter().map(|r| (&r.id, r)).collect();
+    let mut seen = Vec::new();
+    let mut updates = Vec::new();
+    let mut creates = Vec::new();
+    for record in after {
+        match before.remove(&record.id) {
+            None => creates.push(record),
+            Some(old) if !same_data(old, &record) || old.rrsig != record.rrsig => {
+                seen.push(old.clone());
+                updates.push(record);
+            }
+            Some(_) => {}
+        }
+    }
+    let deletes = before.keys().map(|id| id.to_string()).collect();
+    seen.extend(before.into_values().cloned());
+
+    Ok(ZoneWrite {
+        serial,
+        zone,
+        seen,
+        deletes,
+        updates,
+        creates,
+        chain,
+    })
+}
+
+/// Load the zone and apply `changes` to a working copy of it.
+async fn plan(db: &Database, zone: &str, changes: Vec<RecordChangeInput>) -> Result<Plan> {
+    let zone = db.get_zone(&normalize_name(zone)).await?;
+    let apex = zone.apex.clone();
+    let children = db
+        .zones()
+        .await?
+        .into_iter()
+        .map(|zone| zone.apex)
+        .filter(|child| child != &apex && in_zone(child, &apex))
+        .collect();
+    let dnssec = signer::find_signed_zone(db, &apex).await?;
+    let scope = Scope {
+        apex: apex.clone(),
+        children,
+        signed: dnssec.is_some(),
+        default_ttl: zone.default_ttl,
+    };
+
+    let before = db.zone_records(&apex).await?;
+    let mut after = before.clone();
+    apply(&scope, &mut after, changes)?;
+    Ok(Plan {
+        scope,
+        zone,
+        dnssec,
+        before,
+        after,
+    })
+}
+
+/// Apply `changes` in order to `records`, the zone's records. An invalid
+/// change is reported with its position in the changeset.
+fn apply(
+    scope: &Scope,
+    records: &mut Vec<DNSRecord>,
+    changes: Vec<RecordChangeInput>,
+) -> Result<()> {
+    for (i, change) in changes.into_iter().enumerate() {
+        if let Err(message) = apply_one(scope, records, change) {
+            let message = format!("change {}: {}", i + 1, message);
+            return Err(AppError::InvalidRecord(message));
+        }
+    }
+    Ok(())
+}
+
+/// Apply one change, or say why it cannot be.
+fn apply_one(
+    scope: &Scope,
+    records: &mut Vec<DNSRecord>,
+    change: RecordChangeInput,
+) -> std::result::Result<(), String> {
+    let action = match change.action {
+        ChangeAction::Create => "CREATE",
+        ChangeAction::Update => "UPDATE",
+        ChangeAction::Delete => "DELETE",
+    };
+    let stored = match (change.action, &change.id) {
+        (ChangeAction::Create, None) => None,
+        (ChangeAction::Create, Some(_)) => return Err("CREATE takes no id".to_string()),
+        (_, None) => return Err(format!("{} needs the id of a record", action)),
+        (_, Some(id)) => {
+            let index = records
+                .iter()
+                .position(|r| r.id == *id)
+                .ok_or_else(|| format!("no record {} in zone {}", id.as_str(), scope.apex))?;
+            if records[index].record_type == DNSRecordType::SOA {
+                return Err(format!("the SOA of {} is managed by its zone", scope.apex));
+            }
+            Some(records.remove(index))
+        }
+    };
+
+    let input = match (change.action, change.record) {
+        (ChangeAction::Delete, None) => return Ok(()),
+        (ChangeAction::Delete, Some(_)) => return Err("DELETE takes no record".to_string()),
+        (_, None) => return Err(format!("{} needs a record", action)),
+        (_, Some(input)) => input,
+    };
+    let value = validate(scope, &input)?;
+    let name = normalize_name(&input.name);
+    let ttl = input
+        .ttl
+        .or_else(|| {
+            records
+                .iter()
+                .find(|r| r.name == name && r.record_type == input.record_type)
+                .map(|r| r.ttl)
+        })
+        .unwrap_or(scope.default_ttl);
+
+    let mut record = match stored {
+        Some(mut record) => {
+            record.name = name;
+            record.record_type = input.record_type;
+            record.ttl = ttl;
+            record.value = value;
+            record.updated_at = Utc::now();
+            record.rrsig = None;
+            record
+        }
+        None => DNSRecord::new(name, input.record_type, ttl, value),
+    };
+    record.zone = Some(scope.apex.clone());
+    record.dnssec = scope.signed;
+
+    // The record's RRset takes its TTL as a whole
+    for other in records
+        .iter_mut()
+        .filter(|r| r.name == record.name && r.record_type == record.record_type)
+    {
+        other.ttl = record.ttl;
+    }
+    records.push(record);
+    Ok(())
+}
+
+/// Validate a record input for the zone, returning its normalised RDATA.
+fn validate(scope: &Scope, input: &DNSRecordInput) -> std::result::Result<String, String> {
+    if input.name.is_empty() {
+        return Err("DNS name cannot be empty".to_string());
+    }
+    let name = normalize_name(&input.name);
+    if !scope.contains(&name) {
+        return Err(format!("{} is not in zone {}", name, scope.apex));
+    }
+    if input.ttl.is_some_and(|ttl| ttl < 0) {
+        return Err("TTL must be positive".to_string());
+    }
+    if input.record_type == DNSRecordType::SOA {
+        return Err(format!("the SOA of {} is managed by its zone", scope.apex));
+    }
+    if input.dnssec == Some(true) && !scope.signed {
+        return Err(format!(
+            "DNSSEC requested but zone {} is not signed",
+            scope.apex
+        ));
+    }
+    if scope.signed && rollover::is_key_rrset(&name, input.record_type, &scope.apex) {
+        return Err(format!(
+            "{} records at {} are published from the zone's DNSSEC keys",
+            input.record_type, scope.apex
+        ));
+    }
+    rdata::input_value(input).map_err(|e| match e {
+        AppError::InvalidRecord(message) => message,
+        other => other.to_string(),
+    })
+}
+
+/// The records created, updated and deleted between `before` and `after`,
+/// by name and type. Records whose data is unchanged are left out.
+fn diff(before: &[DNSRecord], after: &[DNSRecord]) -> Vec<RecordDiff> {
+    let mut old: HashMap<&ID, &DNSRecord> = before.iter().map(|r| (&r.id, r)).collect();
+    let mut changes = Vec::new();
+    for record in after {
+        match old.remove(&record.id) {
+            None => changes.push(RecordDiff {
+                kind: RecordChangeKind::Created,
+                before: None,
+                after: Some(record.clone()),
+            }),
+            Some(previous) if !same_data(previous, record) => changes.push(RecordDiff {
+                kind: RecordChangeKind::Updated,
+                before: Some(previous.clone()),
+                after: Some(record.clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(old.into_values().map(|record| RecordDiff {
+        kind: RecordChangeKind::Deleted,
+        before: Some(record.clone()),
+        after: None,
+    }));
+
+    let key = |c: &RecordDiff| {
+        let record = c.after.as_ref().or(c.before.as_ref()).unwrap();
+        (
+            record.name.clone(),
+            record.record_type,
+            record.value.clone(),
+        )
+    };
+    changes.sort_by_key(key);
+    changes
+}
+
+/// Whether two versions of a record carry the same data, signatures aside.
+fn same_data(a: &DNSRecord, b: &DNSRecord) -> bool {
+    (&a.name, a.record_type, a.ttl, &a.value, &a.zone, a.dnssec)
+        == (&b.name, b.record_type, b.ttl, &b.value, &b.zone, b.dnssec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope() -> Scope {
+        Scope {
+            apex: "example.com".to_string(),
+            children: vec!["sub.example.com".to_string()],
+            signed: false,
+            default_ttl: 3600,
+        }
+    }
+
+    fn zone() -> Vec<DNSRecord> {
+        [
+            (
+                "example.com",
+                DNSRecordType::SOA,
+                "ns1.example.com. h.example.com. 1 2 3 4 5",
+            ),
+            ("www.example.com", DNSRecordType::A, "192.0.2.1"),
+            ("www.example.com", DNSRecordType::A, "192.0.2.2"),
+            ("old.example.com", DNSRecordType::A, "192.0.2.9"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, record_type, value))| {
+            let mut record = DNSRecord::new(name.to_string(), record_type, 300, value.to_string());
+            record.id = ID(format!("r{}", i));
+            record.zone = Some("example.com".to_string());
+            record
+        })
+        .collect()
+    }
+
+    fn input(
+        name: &str,
+        record_type: DNSRecordType,
+        ttl: Option<i32>,
+        value: &str,
+    ) -> DNSRecordInput {
+        DNSRecordInput {
+            name: name.to_string(),
+            record_type,
+            ttl,
+            value: Some(value.to_string()),
+            dnssec: None,
+            mx: None,
+            srv: None,
+            caa: None,
+            tlsa: None,
+        }
+    }
+
+    fn change(
+        action: ChangeAction,
+        id: Option<&str>,
+        record: Option<DNSRecordInput>,
+    ) -> RecordChangeInput {
+        RecordChangeInput {
+            action,
+            id: id.map(|id| ID(id.to_string())),
+            record,
+        }
+    }
+
+    #[test]
+    fn test_changeset_diff() {
+        let before = zone();
+        let mut after = before.clone();
+        let changes = vec![
+            change(
+                ChangeAction::Create,
+                None,
+                Some(input(
+                    "WWW.example.com.",
+                    DNSRecordType::A,
+                    Some(60),
+                    "192.0.2.3",
+                )),
+            ),
+            change(
+                ChangeAction::Update,
+                Some("r1"),
+                Some(input(
+                    "www.example.com",
+                    DNSRecordType::A,
+                    None,
+                    "192.0.2.10",
+                )),
+            ),
+            change(ChangeAction::Delete, Some("r3"), None),
+        ];
+        apply(&scope(), &mut after, changes).unwrap();
+
+        let changes: Vec<(RecordChangeKind, String, i32)> = diff(&before, &after)
+            .into_iter()
+            .map(|c| {
+                let record = c.after.or(c.before).unwrap();
+                (c.kind, record.value, record.ttl)
+            })
+            .collect();
+        // The new record sets its RRset's TTL, so its siblings change too
+        assert_eq!(
+            changes,
+            [
+                (RecordChangeKind::Deleted, "192.0.2.9".to_string(), 300),
+                (RecordChangeKind::Updated, "192.0.2.10".to_string(), 60),
+                (RecordChangeKind::Updated, "192.0.2.2".to_string(), 60),
+                (RecordChangeKind::Created, "192.0.2.3".to_string(), 60),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_changes_are_refused_by_position() {
+        let refused = |changes: Vec<RecordChangeInput>| {
+            let mut records = zone();
+            apply(&scope(), &mut records, changes)
+                .unwrap_err()
+                .to_string()
+        };
+        let ok = change(ChangeAction::Delete, Some("r3"), None);
+
+        let error = refused(vec![
+            ok.clone(),
+            change(ChangeAction::Delete, Some("r9"), None),
+        ]);
+        assert!(error.contains("change 2: no record r9"), "{}", error);
+        let error = refused(vec![change(ChangeAction::Delete, Some("r0"), None)]);
+        assert!(error.contains("SOA"), "{}", error);
+        let error = refused(vec![change(ChangeAction::Update, None, None)]);
+        assert!(error.contains("UPDATE needs the id"), "{}", error);
+
+        // Names outside the zone, or in a zone below it, belong to another zone
+        for name in ["www.example.org", "www.sub.example.com"] {
+            let create = input(name, DNSRecordType::A, None, "192.0.2.1");
+            let error = refused(vec![change(ChangeAction::Create, None, Some(create))]);
+            assert!(error.contains("is not in zone example.com"), "{}", error);
+        }
+        let create = input("www.example.com", DNSRecordType::A, None, "not-an-address");
+        let error = refused(vec![change(ChangeAction::Create, None, Some(create))]);
+        assert!(
+            error.starts_with("Invalid DNS record: change 1:"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit_refuses_a_zone_changed_since_planning() {
+        let db = Database::connect("memory").await.unwrap();
+        let manager = DNSSECManager::new();
+        zones::example_zone(&db).await;
+        let mut www = DNSRecord::new(
+            "www.example.com".to_string(),
+            DNSRecordType::A,
+            300,
+            "192.0.2.1".to_string(),
+        );
+        www.zone = Some("example.com".to_string());
+        let www = db.create_record(www).await.unwrap();
+        let update = || {
+            let record = input("www.example.com", DNSRecordType::A, None, "192.0.2.2");
+            vec![change(ChangeAction::Update, Some(&www.id), Some(record))]
+        };
+
+        // A record the changeset replaces is rewritten after it was read
+        let planned = plan(&db, "example.com", update()).await.unwrap();
+        let mut rewritten = www.clone();
+        rewritten.value = "192.0.2.9".to_string();
+        rewritten.updated_at = Utc::now();
+        db.update_record(&www.id, rewritten).await.unwrap();
+        let error = write(&db, &manager, planned).await.unwrap_err().to_string();
+        assert!(error.contains("changed while the change was applied"), "{}", error);
+        assert_eq!(db.get_record(&www.id).await.unwrap().value, "192.0.2.9");
+        assert_eq!(db.get_zone("example.com").await.unwrap().serial, 1);
+
+        // The zone moves on after it was read
+        let planned = plan(&db, "example.com", update()).await.unwrap();
+        zones::bump_serial(&db, &manager, "example.com").await.unwrap();
+        assert!(write(&db, &manager, planned).await.is_err());
+        assert_eq!(db.get_record(&www.id).await.unwrap().value, "192.0.2.9");
+        assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+
+        // Nothing moved: the record and the zone's SOA are written together
+        let planned = plan(&db, "example.com", update()).await.unwrap();
+        write(&db, &manager, planned).await.unwrap();
+        assert_eq!(db.get_record(&www.id).await.unwrap().value, "192.0.2.2");
+        let soa = db.records_at("example.com").await.unwrap();
+        assert!(soa[0].value.contains(" 3 "), "{}", soa[0].value);
+    }
+}
@@ -2,9 +2,17 @@
 //! SurrealDB integration for DNS records and provenance graph
 
 use crate::{
+    authority::normalize_name,
     error::{AppError, Result},
-    models::{BlockchainProvenance, DNSRecord, DNSRecordType, DNSSECZone, RecordTypeCount},
+    events::EventBus,
+    models::{
+        BlockchainProvenance, ChainPatch, DNSRecord, DNSRecordFilter, DNSRecordOrder,
+        DNSRecordSortField, DNSRecordType, DNSSECKey, DNSSECZone, DenialKind, DenialRecord,
+        KeyStoreMeta, RecordCursor, RecordHistoryEntry, RecordTypeCount, SortDirection, TSIGKey,
+        Zone, ZoneJournalEntry, ZoneNotification, ZoneSnapshot, ZoneWrite,
+    },
 };
+use chrono::{DateTime, Utc};
 use surrealdb::{
     engine::local::Db,
     Surreal,
@@ -12,10 +20,95 @@
 #[cfg(feature = "rocksdb")]
 use surrealdb::engine::local::RocksDb;
 
-/// Database connection wrapper
+/// Serde helpers storing `chrono` timestamps as SurrealDB datetimes.
+///
+/// `DateTime<Utc>` serializes as a string, which `TYPE datetime` fields
+/// refuse; `surrealdb::sql::Datetime` serializes as a datetime and reads back
+/// as RFC 3339 like the string did. Outside SurrealDB it is the same string.
+pub mod datetime {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use surrealdb::sql::Datetime;
+
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        Datetime::from(*time).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        DateTime::deserialize(deserializer)
+    }
+
+    /// The same for optional timestamps
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            time.map(Datetime::from).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::deserialize(deserializer)
+        }
+    }
+}
+
+/// DNS record IDs: written to SurrealDB as `dns_records` record IDs, elsewhere
+/// as their key, and read back from either form
+pub mod record_id {
+    use async_graphql::ID;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use surrealdb::sql::Thing;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Key {
+        Plain(String),
+        Tagged {
+            #[serde(rename = "String")]
+            string: String,
+        },
+        Number(i64),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RecordId {
+        Key(Key),
+        Thing { id: Key },
+    }
+
+    pub fn serialize<S: Serializer>(id: &ID, serializer: S) -> Result<S::Ok, S::Error> {
+        // SurrealDB's own serializer is the one that isn't human readable
+        if serializer.is_human_readable() {
+            serializer.serialize_str(id)
+        } else {
+            Thing::from(("dns_records", id.as_str())).serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ID, D::Error> {
+        let key = match RecordId::deserialize(deserializer)? {
+            RecordId::Key(key) | RecordId::Thing { id: key } => key,
+        };
+        Ok(ID(match key {
+            Key::Plain(key) | Key::Tagged { string: key } => key,
+            Key::Number(key) => key.to_string(),
+        }))
+    }
+}
+
+/// Database connection wrapper, carrying the bus its committed changes are
+/// announced on and the identity record changes are attributed to
 #[derive(Clone)]
 pub struct Database {
     db: Surreal<Db>,
+    events: EventBus,
+    actor: Option<String>,
 }
 
 impl Database {
@@ -47,7 +140,31 @@ pub async fn connect(path: &str) -> Result<Self> {
         // Initialize schema
         Self::init_schema(&db).await?;
 
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            events: EventBus::new(),
+            actor: None,
+        })
+    }
+
+    /// The same connection, attributing the record changes it writes to
+    /// `identity` in the record history. Without it they are attributed to
+    /// no one: the server made them itself.
+    pub fn acting_as(&self, identity: &str) -> Self {
+        Self {
+            actor: Some(identity.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// Start a query that writes records, binding `$actor` for the history event
+    fn write(&self, query: &str) -> surrealdb::method::Query<'_, Db> {
+        self.db.query(query).bind(("actor", self.actor.clone()))
+    }
+
+    /// Bus that committed changes are published to
+    pub fn events(&self) -> &EventBus {
+        &self.events
     }
 
     /// Initialize database schema
@@ -55,19 +172,93 @@ async fn init_schema(db: &Surreal<Db>) -> Result<()> {
         // DNS records table
         db.query(
             r#"
-            DEFINE TABLE IF NOT EXISTS dns_records SCHEMAFULL;
-            DEFINE FIELD IF NOT EXISTS name ON dns_records TYPE string;
-            DEFINE FIELD IF NOT EXISTS type ON dns_records TYPE string;
-            DEFINE FIELD IF NOT EXISTS ttl ON dns_records TYPE int;
-            DEFINE FIELD IF NOT EXISTS value ON dns_records TYPE string;
-            DEFINE FIELD IF NOT EXISTS dnssec ON dns_records TYPE bool;
-            DEFINE FIELD IF NOT EXISTS rrsig ON dns_records TYPE option<string>;
-            DEFINE FIELD IF NOT EXISTS blockchain_tx_hash ON dns_records TYPE option<string>;
-            DEFINE FIELD IF NOT EXISTS created_at ON dns_records TYPE datetime;
-            DEFINE FIELD IF NOT EXISTS updated_at ON dns_records TYPE datetime;
-
-            DEFINE INDEX IF NOT EXISTS name_idx ON dns_records COLUMNS name;
-            DEFINE INDEX IF NOT EXISTS type_idx ON dns_records COLUMNS type;
+            DEFINE TABLE dns_records SCHEMAFULL;
+            DEFINE FIELD name ON dns_records TYPE string;
+            DEFINE FIELD type ON dns_records TYPE string;
+            DEFINE FIELD ttl ON dns_records TYPE int;
+            DEFINE FIELD value ON dns_records TYPE string;
+            DEFINE FIELD zone ON dns_records TYPE option<string>;
+            DEFINE FIELD dnssec ON dns_records TYPE bool;
+            DEFINE FIELD rrsig ON dns_records TYPE option<string>;
+            DEFINE FIELD blockchain_tx_hash ON dns_records TYPE option<string>;
+            DEFINE FIELD created_at ON dns_records TYPE datetime;
+            DEFINE FIELD updated_at ON dns_records TYPE datetime;
+
+            DEFINE INDEX name_idx ON dns_records COLUMNS name;
+            DEFINE INDEX type_idx ON dns_records COLUMNS type;
+            DEFINE INDEX record_zone_idx ON dns_records COLUMNS zone;
+        "#,
+        )
+        .await?;
+
+        // Zones: apex, SOA parameters, default TTL and owner
+        db.query(
+            r#"
+            DEFINE TABLE zones SCHEMAFULL;
+            DEFINE FIELD apex ON zones TYPE string;
+            DEFINE FIELD primary_ns ON zones TYPE string;
+            DEFINE FIELD admin_mailbox ON zones TYPE string;
+            DEFINE FIELD serial ON zones TYPE int;
+            DEFINE FIELD serial_policy ON zones TYPE string;
+            DEFINE FIELD refresh ON zones TYPE int;
+            DEFINE FIELD retry ON zones TYPE int;
+            DEFINE FIELD expire ON zones TYPE int;
+            DEFINE FIELD minimum ON zones TYPE int;
+            DEFINE FIELD default_ttl ON zones TYPE int;
+            DEFINE FIELD owner ON zones TYPE string;
+            DEFINE FIELD created_at ON zones TYPE datetime;
+            DEFINE FIELD updated_at ON zones TYPE datetime;
+            DEFINE FIELD transfer_acl ON zones TYPE array<object> DEFAULT [];
+            DEFINE FIELD transfer_acl.*.address ON zones TYPE string;
+            DEFINE FIELD transfer_acl.*.tsig_key ON zones TYPE string;
+            DEFINE FIELD transfer_acl.*.notify ON zones TYPE option<string>;
+
+            DEFINE INDEX apex_idx ON zones COLUMNS apex UNIQUE;
+        "#,
+        )
+        .await?;
+
+        // Zone transfers: TSIG keys, the IXFR journal with the snapshot it diffs against,
+        // and NOTIFY delivery status per secondary
+        db.query(
+            r#"
+            DEFINE TABLE tsig_keys SCHEMAFULL;
+            DEFINE FIELD name ON tsig_keys TYPE string;
+            DEFINE FIELD algorithm ON tsig_keys TYPE string;
+            DEFINE FIELD owner ON tsig_keys TYPE string DEFAULT '';
+            DEFINE FIELD created_at ON tsig_keys TYPE datetime;
+            DEFINE FIELD secret_unclaimed ON tsig_keys TYPE bool DEFAULT false;
+            DEFINE FIELD sealed_secret ON tsig_keys TYPE string;
+
+            DEFINE INDEX tsig_name_idx ON tsig_keys COLUMNS name UNIQUE;
+
+            DEFINE TABLE zone_journal SCHEMAFULL;
+            DEFINE FIELD zone ON zone_journal TYPE string;
+            DEFINE FIELD serial_from ON zone_journal TYPE int;
+            DEFINE FIELD serial_to ON zone_journal TYPE int;
+            DEFINE FIELD removed ON zone_journal TYPE array<string>;
+            DEFINE FIELD added ON zone_journal TYPE array<string>;
+            DEFINE FIELD created_at ON zone_journal TYPE datetime;
+
+            DEFINE INDEX journal_idx ON zone_journal COLUMNS zone, created_at;
+
+            DEFINE TABLE zone_snapshots SCHEMAFULL;
+            DEFINE FIELD zone ON zone_snapshots TYPE string;
+            DEFINE FIELD serial ON zone_snapshots TYPE int;
+            DEFINE FIELD records ON zone_snapshots TYPE array<string>;
+
+            DEFINE TABLE zone_notifications SCHEMAFULL;
+            DEFINE FIELD zone ON zone_notifications TYPE string;
+            DEFINE FIELD target ON zone_notifications TYPE string;
+            DEFINE FIELD tsig_key ON zone_notifications TYPE string;
+            DEFINE FIELD serial ON zone_notifications TYPE int;
+            DEFINE FIELD state ON zone_notifications TYPE string;
+            DEFINE FIELD attempts ON zone_notifications TYPE int;
+            DEFINE FIELD last_attempt_at ON zone_notifications TYPE option<datetime>;
+            DEFINE FIELD acknowledged_at ON zone_notifications TYPE option<datetime>;
+            DEFINE FIELD last_error ON zone_notifications TYPE option<string>;
+
+            DEFINE INDEX notification_idx ON zone_notifications COLUMNS zone, target;
         "#,
         )
         .await?;
@@ -75,15 +266,124 @@ async fn init_schema(db: &Surreal<Db>) -> Result<()> {
         // DNSSEC zones table
         db.query(
             r#"
-            DEFINE TABLE IF NOT EXISTS dnssec_zones SCHEMAFULL;
-            DEFINE FIELD IF NOT EXISTS zone ON dnssec_zones TYPE string;
-            DEFINE FIELD IF NOT EXISTS enabled ON dnssec_zones TYPE bool;
-            DEFINE FIELD IF NOT EXISTS ksk ON dnssec_zones TYPE option<string>;
-            DEFINE FIELD IF NOT EXISTS zsk ON dnssec_zones TYPE option<string>;
-            DEFINE FIELD IF NOT EXISTS ds_record ON dnssec_zones TYPE option<string>;
-            DEFINE FIELD IF NOT EXISTS last_rotation ON dnssec_zones TYPE option<datetime>;
-
-            DEFINE INDEX IF NOT EXISTS zone_idx ON dnssec_zones COLUMNS zone UNIQUE;
+            DEFINE TABLE dnssec_zones SCHEMAFULL;
+            DEFINE FIELD zone ON dnssec_zones TYPE string;
+            DEFINE FIELD enabled ON dnssec_zones TYPE bool;
+            DEFINE FIELD ksk ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD zsk ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD ds_record ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD algorithms ON dnssec_zones TYPE array<string> DEFAULT [];
+            DEFINE FIELD ds_records ON dnssec_zones TYPE array<string> DEFAULT [];
+            DEFINE FIELD nsec3 ON dnssec_zones TYPE option<object>;
+            DEFINE FIELD nsec3.salt ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD nsec3.iterations ON dnssec_zones TYPE option<int>;
+            DEFINE FIELD nsec3.opt_out ON dnssec_zones TYPE option<bool>;
+            DEFINE FIELD dnskey_rrsig ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD cds_rrsig ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD cdnskey_rrsig ON dnssec_zones TYPE option<string>;
+            DEFINE FIELD cds_delete ON dnssec_zones TYPE bool DEFAULT false;
+            DEFINE FIELD parent_ds_ttl ON dnssec_zones TYPE option<int>;
+            DEFINE FIELD last_rotation ON dnssec_zones TYPE option<datetime>;
+
+            DEFINE INDEX zone_idx ON dnssec_zones COLUMNS zone UNIQUE;
+        "#,
+        )
+        .await?;
+
+        // DNSSEC key store: sealed signing keys and KEK parameters
+        db.query(
+            r#"
+            DEFINE TABLE dnssec_keys SCHEMAFULL;
+            DEFINE FIELD key_id ON dnssec_keys TYPE string;
+            DEFINE FIELD zone ON dnssec_keys TYPE string;
+            DEFINE FIELD role ON dnssec_keys TYPE string;
+            DEFINE FIELD algorithm ON dnssec_keys TYPE int;
+            DEFINE FIELD key_tag ON dnssec_keys TYPE int;
+            DEFINE FIELD public_key ON dnssec_keys TYPE string;
+            DEFINE FIELD state ON dnssec_keys TYPE string;
+            DEFINE FIELD created_at ON dnssec_keys TYPE datetime;
+            DEFINE FIELD state_changed_at ON dnssec_keys TYPE option<datetime>;
+            DEFINE FIELD ds_record ON dnssec_keys TYPE option<string>;
+            DEFINE FIELD ds_submitted_at ON dnssec_keys TYPE option<datetime>;
+            DEFINE FIELD sealed_secret ON dnssec_keys TYPE string;
+
+            DEFINE INDEX key_id_idx ON dnssec_keys COLUMNS key_id UNIQUE;
+            DEFINE INDEX key_zone_idx ON dnssec_keys COLUMNS zone;
+
+            -- Keys retired before staged rollovers existed were replaced outright
+            UPDATE dnssec_keys SET state = 'Removed'
+                WHERE state = 'Retired' AND state_changed_at IS NONE;
+
+            DEFINE TABLE dnssec_keystore SCHEMAFULL;
+            DEFINE FIELD salt ON dnssec_keystore TYPE string;
+            DEFINE FIELD m_cost ON dnssec_keystore TYPE int;
+            DEFINE FIELD t_cost ON dnssec_keystore TYPE int;
+            DEFINE FIELD p_cost ON dnssec_keystore TYPE int;
+            DEFINE FIELD check ON dnssec_keystore TYPE string;
+        "#,
+        )
+        .await?;
+
+        // NSEC/NSEC3 denial chains of signed zones
+        db.query(
+            r#"
+            DEFINE TABLE dnssec_denial SCHEMAFULL;
+            DEFINE FIELD zone ON dnssec_denial TYPE string;
+            DEFINE FIELD kind ON dnssec_denial TYPE string;
+            DEFINE FIELD owner ON dnssec_denial TYPE string;
+            DEFINE FIELD name ON dnssec_denial TYPE string;
+            DEFINE FIELD next ON dnssec_denial TYPE string;
+            DEFINE FIELD types ON dnssec_denial TYPE array<string>;
+            DEFINE FIELD ttl ON dnssec_denial TYPE int;
+            DEFINE FIELD rrsig ON dnssec_denial TYPE option<string>;
+            DEFINE FIELD sort_key ON dnssec_denial TYPE string;
+
+            DEFINE INDEX denial_idx ON dnssec_denial COLUMNS zone, kind, sort_key;
+        "#,
+        )
+        .await?;
+
+        // Record history: every change to a record's content is appended,
+        // in the transaction that made it, and never changed afterwards.
+        // Re-signing alone is not a change.
+        db.query(
+            r#"
+            DEFINE TABLE record_history SCHEMAFULL;
+            DEFINE FIELD record_id ON record_history TYPE string;
+            DEFINE FIELD version ON record_history TYPE int;
+            DEFINE FIELD kind ON record_history TYPE string;
+            DEFINE FIELD actor ON record_history TYPE option<string>;
+            DEFINE FIELD before ON record_history FLEXIBLE TYPE option<object>;
+            DEFINE FIELD after ON record_history FLEXIBLE TYPE option<object>;
+            DEFINE FIELD changed_at ON record_history TYPE datetime;
+
+            DEFINE INDEX history_record_idx ON record_history
+                COLUMNS record_id, version UNIQUE;
+            DEFINE INDEX history_time_idx ON record_history COLUMNS changed_at;
+
+            DEFINE EVENT record_history ON TABLE dns_records
+                WHEN $event != "UPDATE"
+                    OR $before.name != $after.name OR $before.type != $after.type
+                    OR $before.ttl != $after.ttl OR $before.value != $after.value
+                    OR $before.zone != $after.zone OR $before.dnssec != $after.dnssec
+                    OR $before.blockchain_tx_hash != $after.blockchain_tx_hash
+                THEN (
+                    CREATE record_history CONTENT {
+                        record_id: meta::id($value.id),
+                        version: count((SELECT VALUE version FROM record_history
+                            WHERE record_id = meta::id($value.id))) + 1,
+                        kind: IF $event = "CREATE" THEN "Created"
+                            ELSE IF $event = "UPDATE" THEN "Updated"
+                            ELSE "Deleted" END,
+                        actor: $actor,
+                        before: $before,
+                        after: $after,
+                        changed_at: time::now()
+                    }
+                );
+            DEFINE EVENT history_immutable ON TABLE record_history
+                WHEN $event != "CREATE"
+                THEN { THROW "record history cannot be changed" };
         "#,
         )
         .await?;
@@ -91,15 +391,15 @@ async fn init_schema(db: &Surreal<Db>) -> Result<()> {
         // Blockchain provenance table
         db.query(
             r#"
-            DEFINE TABLE IF NOT EXISTS blockchain_provenance SCHEMAFULL;
-            DEFINE FIELD IF NOT EXISTS record_id ON blockchain_provenance TYPE string;
-            DEFINE FIELD IF NOT EXISTS content_hash ON blockchain_provenance TYPE string;
-            DEFINE FIELD IF NOT EXISTS network ON blockchain_provenance TYPE string;
-            DEFINE FIELD IF NOT EXISTS tx_hash ON blockchain_provenance TYPE string;
-            DEFINE FIELD IF NOT EXISTS block_number ON blockchain_provenance TYPE int;
-            DEFINE FIELD IF NOT EXISTS timestamp ON blockchain_provenance TYPE datetime;
-
-            DEFINE INDEX IF NOT EXISTS record_idx ON blockchain_provenance COLUMNS record_id;
+            DEFINE TABLE blockchain_provenance SCHEMAFULL;
+            DEFINE FIELD record_id ON blockchain_provenance TYPE string;
+            DEFINE FIELD content_hash ON blockchain_provenance TYPE string;
+            DEFINE FIELD network ON blockchain_provenance TYPE string;
+            DEFINE FIELD tx_hash ON blockchain_provenance TYPE string;
+            DEFINE FIELD block_number ON blockchain_provenance TYPE int;
+            DEFINE FIELD timestamp ON blockchain_provenance TYPE datetime;
+
+            DEFINE INDEX record_idx ON blockchain_provenance COLUMNS record_id;
         "#,
         )
         .await?;
@@ -110,12 +410,10 @@ async fn init_schema(db: &Surreal<Db>) -> Result<()> {
     /// Create a new DNS record
     pub async fn create_record(&self, record: DNSRecord) -> Result<DNSRecord> {
         let created: Option<DNSRecord> = self
-            .db
-            .create("dns_records")
-            .content(&record)
+            .write("CREATE dns_records CONTENT $record")
+            .bind(("record", record))
             .await?
-            .into_iter()
-            .next();
+            .take(0)?;
 
         created.ok_or_else(|| AppError::Internal("Failed to create record".to_string()))
     }
@@ -176,23 +474,495 @@ pub async fn query_records(
         Ok(records)
     }
 
+    /// Page through the records matching `filter` in `order`, strictly
+    /// between the cursors, returning at most `limit`.
+    ///
+    /// With `from_end` the page is taken from the `before` end and comes
+    /// back in reverse order.
+    pub async fn page_records(
+        &self,
+        filter: &DNSRecordFilter,
+        order: DNSRecordOrder,
+        after: Option<&RecordCursor>,
+        before: Option<&RecordCursor>,
+        limit: usize,
+        from_end: bool,
+    ) -> Result<Vec<DNSRecord>> {
+        let (mut conditions, mut binds) = record_filter(filter)?;
+        let field = match order.field {
+            DNSRecordSortField::Name => "name",
+            DNSRecordSortField::Ttl => "ttl",
+            DNSRecordSortField::CreatedAt => "created_at",
+            DNSRecordSortField::UpdatedAt => "updated_at",
+        };
+        let cast = match order.field {
+            DNSRecordSortField::Name => "",
+            DNSRecordSortField::Ttl => "<int>",
+            DNSRecordSortField::CreatedAt | DNSRecordSortField::UpdatedAt => "<datetime>",
+        };
+        let ascending = order.direction == SortDirection::Asc;
+
+        // Keyset conditions: the sort key, then the record ID, past each cursor
+        for (bound, cursor, past) in [("after", after, ascending), ("before", before, !ascending)] {
+            let Some(cursor) = cursor else { continue };
+            if cursor.order != order {
+                return Err(AppError::InvalidQuery(format!(
+                    "{} cursor was issued for a different order",
+                    bound
+                )));
+            }
+            let op = if past { ">" } else { "<" };
+            conditions.push(format!(
+                "({field} {op} {cast}${bound}_key OR ({field} = {cast}${bound}_key \
+                 AND id {op} type::thing('dns_records', ${bound}_id)))"
+            ));
+            binds.push((format!("{}_key", bound), cursor.key.clone().into()));
+            binds.push((format!("{}_id", bound), cursor.id.clone().into()));
+        }
+
+        let direction = if ascending != from_end { "ASC" } else { "DESC" };
+        let mut query = String::from("SELECT * FROM dns_records");
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        query.push_str(&format!(
+            " ORDER BY {field} {direction}, id {direction} LIMIT $limit"
+        ));
+
+        let mut db_query = self.db.query(&query).bind(("limit", limit));
+        for bind in binds {
+            db_query = db_query.bind(bind);
+        }
+
+        let mut result = db_query.await?;
+        let records: Vec<DNSRecord> = result.take(0)?;
+        Ok(records)
+    }
+
+    /// Count the records matching `filter`
+    pub async fn count_records(&self, filter: &DNSRecordFilter) -> Result<i32> {
+        let (conditions, binds) = record_filter(filter)?;
+        let mut query = String::from("SELECT count() FROM dns_records");
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        query.push_str(" GROUP ALL");
+
+        let mut db_query = self.db.query(&query);
+        for bind in binds {
+            db_query = db_query.bind(bind);
+        }
+
+        let mut result = db_query.await?;
+        let count: Option<i32> = result.take("count")?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Get all DNS records of a zone; records of zones below it are theirs
+    pub async fn zone_records(&self, zone: &str) -> Result<Vec<DNSRecord>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM dns_records WHERE zone = $zone")
+            .bind(("zone", zone))
+            .await?;
+
+        let records: Vec<DNSRecord> = result.take(0)?;
+        Ok(records)
+    }
+
+    /// Load records into a zone in one transaction, optionally clearing the
+    /// zone first; records of zones below it are kept
+    pub async fn import_zone_records(
+        &self,
+        zone: &str,
+        records: Vec<DNSRecord>,
+        replace: bool,
+    ) -> Result<()> {
+        let mut query = String::from("BEGIN TRANSACTION;");
+        if replace {
+            query.push_str("DELETE dns_records WHERE zone = $zone;");
+        }
+        query.push_str("INSERT INTO dns_records $records; COMMIT TRANSACTION;");
+
+        self.write(&query)
+            .bind(("zone", zone))
+            .bind(("records", records))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Write a change to a zone in one transaction: the records with their
+    /// signatures, the zone's new serial and the changes to its denial chain.
+    /// Nothing is written if the serial has moved or a record in `seen`
+    /// changed since the change was planned, so a concurrent write is never
+    /// overwritten.
+    pub async fn commit_zone_write(&self, write: ZoneWrite) -> Result<()> {
+        let mut query = String::from(
+            "BEGIN TRANSACTION; \
+             LET $stored = type::thing('zones', $apex); \
+             IF $stored.serial != $serial { \
+                 THROW string::concat('zone ', $apex, ' changed while the change was applied'); \
+             }; \
+             FOR $record IN $seen { \
+                 LET $current = type::thing('dns_records', $record.id); \
+                 IF $current.updated_at != $record.updated_at { \
+                     THROW string::concat('record ', <string> $record.id, ' changed while the change was applied'); \
+                 }; \
+             }; \
+             FOR $id IN $deletes { DELETE type::thing('dns_records', $id); }; \
+             FOR $record IN $updates { \
+                 UPDATE type::thing('dns_records', $record.id) CONTENT $record; \
+             }; \
+             UPDATE type::thing('zones', $apex) \
+             SET serial = $zone.serial, updated_at = $zone.updated_at;",
+        );
+        if !write.creates.is_empty() {
+            query.push_str("INSERT INTO dns_records $creates;");
+        }
+        let chain = write.chain.unwrap_or(ChainPatch {
+            zone: String::new(),
+            kind: DenialKind::NSEC,
+            replace: false,
+            remove: Vec::new(),
+            records: Vec::new(),
+        });
+        query.push_str(
+            "DELETE dnssec_denial WHERE zone = $chain_zone \
+             AND ($replace OR (kind = $kind AND sort_key INSIDE $remove));",
+        );
+        if !chain.records.is_empty() {
+            query.push_str("INSERT INTO dnssec_denial $chain;");
+        }
+        query.push_str("COMMIT TRANSACTION;");
+
+        let mut response = self
+            .write(&query)
+            .bind(("apex", write.zone.apex.clone()))
+            .bind(("serial", write.serial))
+            .bind(("zone", write.zone))
+            .bind(("seen", write.seen))
+            .bind(("deletes", write.deletes))
+            .bind(("updates", write.updates))
+            .bind(("creates", write.creates))
+            .bind(("chain_zone", chain.zone))
+            .bind(("kind", format!("{:?}", chain.kind)))
+            .bind(("replace", chain.replace))
+            .bind(("remove", chain.remove))
+            .bind(("chain", chain.records))
+            .await?;
+
+        // The statement that threw says why; the others only report the
+        // failed transaction
+        let mut errors: Vec<(usize, surrealdb::Error)> =
+            response.take_errors().into_iter().collect();
+        errors.sort_by_key(|(index, _)| *index);
+        let thrown = errors
+            .iter()
+            .position(|(_, e)| matches!(e, surrealdb::Error::Db(surrealdb::error::Db::Thrown(_))));
+        match thrown.or((!errors.is_empty()).then_some(0)) {
+            Some(index) => Err(errors.swap_remove(index).1.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Get all records marked DNSSEC-signed
+    pub async fn signed_records(&self) -> Result<Vec<DNSRecord>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM dns_records WHERE dnssec = true")
+            .await?;
+
+        let records: Vec<DNSRecord> = result.take(0)?;
+        Ok(records)
+    }
+
+    /// Check whether any record exists strictly below `name` (RFC 8020 empty non-terminals)
+    pub async fn has_names_below(&self, name: &str) -> Result<bool> {
+        let mut result = self
+            .db
+            .query("SELECT count() FROM dns_records WHERE string::endsWith(name, $suffix) GROUP ALL")
+            .bind(("suffix", format!(".{}", name)))
+            .await?;
+        let count: Option<i32> = result.take("count")?;
+        Ok(count.unwrap_or(0) > 0)
+    }
+
+    /// Get every record at one owner name
+    pub async fn records_at(&self, name: &str) -> Result<Vec<DNSRecord>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM dns_records WHERE name = $name")
+            .bind(("name", name))
+            .await?;
+
+        let records: Vec<DNSRecord> = result.take(0)?;
+        Ok(records)
+    }
+
     /// Update DNS record
     pub async fn update_record(&self, id: &str, record: DNSRecord) -> Result<DNSRecord> {
         let updated: Option<DNSRecord> = self
-            .db
-            .update(("dns_records", id))
-            .content(&record)
-            .await?;
+            .write("UPDATE type::thing('dns_records', $id) CONTENT $record")
+            .bind(("id", id))
+            .bind(("record", record))
+            .await?
+            .take(0)?;
 
         updated.ok_or_else(|| AppError::RecordNotFound(id.to_string()))
     }
 
     /// Delete DNS record
     pub async fn delete_record(&self, id: &str) -> Result<bool> {
-        let _: Option<DNSRecord> = self.db.delete(("dns_records", id)).await?;
+        self.write("DELETE type::thing('dns_records', $id)")
+            .bind(("id", id))
+            .await?
+            .check()?;
         Ok(true)
     }
 
+    /// Get a record's history, oldest first
+    pub async fn record_history(&self, id: &str) -> Result<Vec<RecordHistoryEntry>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM record_history WHERE record_id = $id ORDER BY version")
+            .bind(("id", id))
+            .await?;
+
+        let entries: Vec<RecordHistoryEntry> = result.take(0)?;
+        Ok(entries)
+    }
+
+    /// Get the history of every record that was in `zone` at some point up to
+    /// `until`, oldest first
+    pub async fn zone_history(
+        &self,
+        zone: &str,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<RecordHistoryEntry>> {
+        let mut result = self
+            .db
+            .query(
+                "SELECT * FROM record_history \
+                 WHERE changed_at <= <datetime>$until \
+                 AND (before.zone = $zone OR after.zone = $zone) \
+                 ORDER BY changed_at, version",
+            )
+            .bind(("zone", zone))
+            .bind(("until", until))
+            .await?;
+
+        let entries: Vec<RecordHistoryEntry> = result.take(0)?;
+        Ok(entries)
+    }
+
+    /// Get a zone by apex
+    pub async fn get_zone(&self, apex: &str) -> Result<Zone> {
+        let zone: Option<Zone> = self.db.select(("zones", apex)).await?;
+        zone.ok_or_else(|| AppError::ZoneNotFound(apex.to_string()))
+    }
+
+    /// List zones ordered by apex
+    pub async fn zones(&self) -> Result<Vec<Zone>> {
+        let mut result = self.db.query("SELECT * FROM zones ORDER BY apex").await?;
+        let zones: Vec<Zone> = result.take(0)?;
+        Ok(zones)
+    }
+
+    /// Create or replace a zone
+    pub async fn upsert_zone(&self, zone: Zone) -> Result<Zone> {
+        let updated: Option<Zone> = self
+            .db
+            .update(("zones", &*zone.apex))
+            .content(&zone)
+            .await?;
+
+        updated.ok_or_else(|| AppError::Internal("Failed to upsert zone".to_string()))
+    }
+
+    /// Move records at or below `apex` into it, except those already in it or
+    /// in a zone below it. Returns the records moved, as they are now.
+    pub async fn assign_zone_records(&self, apex: &str) -> Result<Vec<DNSRecord>> {
+        let mut result = self
+            .write(
+                "UPDATE dns_records SET zone = $zone \
+                 WHERE (name = $zone OR string::endsWith(name, $suffix)) \
+                 AND (zone IS NONE OR (zone != $zone AND !string::endsWith(zone, $suffix)))",
+            )
+            .bind(("zone", apex))
+            .bind(("suffix", format!(".{}", apex)))
+            .await?;
+        let moved: Vec<DNSRecord> = result.take(0)?;
+
+        Ok(moved)
+    }
+
+    /// Delete a zone with its records and leftover DNSSEC configuration in one
+    /// transaction; records of zones below it are kept
+    pub async fn delete_zone(&self, apex: &str) -> Result<()> {
+        self.write(
+            "BEGIN TRANSACTION; \
+             DELETE dns_records WHERE zone = $zone; \
+             DELETE dnssec_denial WHERE zone = $zone; \
+             DELETE type::thing('dnssec_zones', $zone); \
+             DELETE zone_journal WHERE zone = $zone; \
+             DELETE type::thing('zone_snapshots', $zone); \
+             DELETE zone_notifications WHERE zone = $zone; \
+             DELETE type::thing('zones', $zone); \
+             COMMIT TRANSACTION;",
+        )
+        .bind(("zone", apex))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// Get a TSIG key by name
+    pub async fn get_tsig_key(&self, name: &str) -> Result<TSIGKey> {
+        let key: Option<TSIGKey> = self.db.select(("tsig_keys", name)).await?;
+        key.ok_or_else(|| AppError::RecordNotFound(format!("TSIG key {}", name)))
+    }
+
+    /// Mark the secret of an unclaimed TSIG key claimed. Returns false if it
+    /// was not unclaimed, so that only one caller ever claims it.
+    pub async fn claim_tsig_secret(&self, name: &str) -> Result<bool> {
+        let claimed: Vec<TSIGKey> = self
+            .db
+            .query(
+                "UPDATE type::thing('tsig_keys', $name) SET secret_unclaimed = false \
+                 WHERE secret_unclaimed = true RETURN BEFORE",
+            )
+            .bind(("name", name))
+            .await?
+            .take(0)?;
+        Ok(!claimed.is_empty())
+    }
+
+    /// List TSIG keys ordered by name
+    pub async fn tsig_keys(&self) -> Result<Vec<TSIGKey>> {
+        let mut result = self.db.query("SELECT * FROM tsig_keys ORDER BY name").await?;
+        let keys: Vec<TSIGKey> = result.take(0)?;
+        Ok(keys)
+    }
+
+    /// Store a new TSIG key; fails if the name is taken
+    pub async fn create_tsig_key(&self, key: TSIGKey) -> Result<TSIGKey> {
+        let created: Option<TSIGKey> = self
+            .db
+            .create(("tsig_keys", key.name.as_str()))
+            .content(&key)
+            .await?;
+
+        created.ok_or_else(|| AppError::Internal("Failed to store TSIG key".to_string()))
+    }
+
+    /// Delete a TSIG key
+    pub async fn delete_tsig_key(&self, name: &str) -> Result<bool> {
+        let deleted: Option<TSIGKey> = self.db.delete(("tsig_keys", name)).await?;
+        Ok(deleted.is_some())
+    }
+
+    /// Get the snapshot a zone's next change is diffed against
+    pub async fn zone_snapshot(&self, zone: &str) -> Result<Option<ZoneSnapshot>> {
+        let snapshot: Option<ZoneSnapshot> = self.db.select(("zone_snapshots", zone)).await?;
+        Ok(snapshot)
+    }
+
+    /// List a zone's journal entries, oldest first
+    pub async fn zone_journal(&self, zone: &str) -> Result<Vec<ZoneJournalEntry>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM zone_journal WHERE zone = $zone ORDER BY created_at")
+            .bind(("zone", zone))
+            .await?;
+
+        let entries: Vec<ZoneJournalEntry> = result.take(0)?;
+        Ok(entries)
+    }
+
+    /// Replace a zone's snapshot and journal the change that led to it in one
+    /// transaction, dropping journal entries older than `prune_before`
+    pub async fn record_zone_change(
+        &self,
+        snapshot: ZoneSnapshot,
+        entry: Option<ZoneJournalEntry>,
+        prune_before: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut query = String::from(
+            "BEGIN TRANSACTION; \
+             UPDATE type::thing('zone_snapshots', $zone) CONTENT $snapshot; \
+             DELETE zone_journal WHERE zone = $zone AND created_at < <datetime>$prune_before;",
+        );
+        if entry.is_some() {
+            query.push_str("CREATE zone_journal CONTENT $entry;");
+        }
+        query.push_str("COMMIT TRANSACTION;");
+
+        self.db
+            .query(&query)
+            .bind(("zone", snapshot.zone.clone()))
+            .bind(("snapshot", snapshot))
+            .bind(("entry", entry))
+            .bind(("prune_before", prune_before))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Get the NOTIFY status of one secondary of a zone
+    pub async fn get_notification(
+        &self,
+        zone: &str,
+        target: &str,
+    ) -> Result<Option<ZoneNotification>> {
+        let key = format!("{}@{}", zone, target);
+        let notification: Option<ZoneNotification> =
+            self.db.select(("zone_notifications", key.as_str())).await?;
+        Ok(notification)
+    }
+
+    /// List the NOTIFY status of a zone's secondaries ordered by target
+    pub async fn zone_notifications(&self, zone: &str) -> Result<Vec<ZoneNotification>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM zone_notifications WHERE zone = $zone ORDER BY target")
+            .bind(("zone", zone))
+            .await?;
+
+        let notifications: Vec<ZoneNotification> = result.take(0)?;
+        Ok(notifications)
+    }
+
+    /// Create or replace the NOTIFY status of one secondary
+    pub async fn upsert_notification(
+        &self,
+        notification: ZoneNotification,
+    ) -> Result<ZoneNotification> {
+        let key = format!("{}@{}", notification.zone, notification.target);
+        let updated: Option<ZoneNotification> = self
+            .db
+            .update(("zone_notifications", key.as_str()))
+            .content(notification)
+            .await?;
+
+        updated.ok_or_else(|| AppError::Internal("Failed to store NOTIFY status".to_string()))
+    }
+
+    /// Drop the NOTIFY status of a zone's secondaries, except the targets in `keep`
+    pub async fn prune_notifications(&self, zone: &str, keep: &[String]) -> Result<()> {
+        self.db
+            .query("DELETE zone_notifications WHERE zone = $zone AND target NOTINSIDE $keep")
+            .bind(("zone", zone))
+            .bind(("keep", keep.to_vec()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
     /// Get DNSSEC zone configuration
     pub async fn get_dnssec_zone(&self, zone: &str) -> Result<DNSSECZone> {
         let mut result = self
@@ -219,6 +989,223 @@ pub async fn upsert_dnssec_zone(&self, zone: DNSSECZone) -> Result<DNSSECZone> {
         updated.ok_or_else(|| AppError::Internal("Failed to upsert zone".to_string()))
     }
 
+    /// List DNSSEC-enabled zones
+    pub async fn signed_zones(&self) -> Result<Vec<DNSSECZone>> {
+        let mut result = self
+            .db
+            .query("SELECT * FROM dnssec_zones WHERE enabled = true ORDER BY zone")
+            .await?;
+
+        let zones: Vec<DNSSECZone> = result.take(0)?;
+        Ok(zones)
+    }
+
+    /// Turn DNSSEC off for a zone in one transaction: its records lose their
+    /// RRSIGs, its denial chain is dropped and the key RRset signatures cleared
+    pub async fn unsign_zone(&self, zone: &str) -> Result<()> {
+        self.write(
+            "BEGIN TRANSACTION; \
+             UPDATE dns_records SET dnssec = false, rrsig = NONE WHERE zone = $zone; \
+             DELETE dnssec_denial WHERE zone = $zone; \
+             UPDATE type::thing('dnssec_zones', $zone) SET enabled = false, \
+                 cds_delete = false, dnskey_rrsig = NONE, cds_rrsig = NONE, \
+                 cdnskey_rrsig = NONE; \
+             COMMIT TRANSACTION;",
+        )
+        .bind(("zone", zone))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// Get the key store KEK parameters, if the store has been initialised
+    pub async fn get_keystore_meta(&self) -> Result<Option<KeyStoreMeta>> {
+        let meta: Option<KeyStoreMeta> = self.db.select(("dnssec_keystore", "kek")).await?;
+        Ok(meta)
+    }
+
+    /// Initialise the key store KEK parameters
+    pub async fn create_keystore_meta(&self, meta: KeyStoreMeta) -> Result<KeyStoreMeta> {
+        let created: Option<KeyStoreMeta> = self
+            .db
+            .create(("dnssec_keystore", "kek"))
+            .content(&meta)
+            .await?;
+
+        created.ok_or_else(|| AppError::Internal("Failed to initialise key store".to_string()))
+    }
+
+    /// Store a sealed DNSSEC key
+    pub async fn store_dnssec_key(&self, key: DNSSECKey) -> Result<DNSSECKey> {
+        let created: Option<DNSSECKey> = self
+            .db
+            .create(("dnssec_keys", key.key_id.as_str()))
+            .content(&key)
+            .await?;
+
+        created.ok_or_else(|| AppError::Internal("Failed to store DNSSEC key".to_string()))
+    }
+
+    /// List DNSSEC keys, optionally for one zone, oldest first
+    pub async fn dnssec_keys(&self, zone: Option<&str>) -> Result<Vec<DNSSECKey>> {
+        let mut result = match zone {
+            Some(zone) => {
+                self.db
+                    .query("SELECT * FROM dnssec_keys WHERE zone = $zone ORDER BY created_at")
+                    .bind(("zone", zone))
+                    .await?
+            }
+            None => {
+                self.db
+                    .query("SELECT * FROM dnssec_keys ORDER BY created_at")
+                    .await?
+            }
+        };
+
+        let keys: Vec<DNSSECKey> = result.take(0)?;
+        Ok(keys)
+    }
+
+    /// Update a stored DNSSEC key's lifecycle fields
+    pub async fn update_dnssec_key(&self, key: DNSSECKey) -> Result<DNSSECKey> {
+        let updated: Option<DNSSECKey> = self
+            .db
+            .update(("dnssec_keys", key.key_id.as_str()))
+            .content(&key)
+            .await?;
+
+        updated.ok_or_else(|| AppError::Internal("Failed to update DNSSEC key".to_string()))
+    }
+
+    /// Remove a zone's keys outright, except those listed in `keep`
+    pub async fn remove_dnssec_keys(&self, zone: &str, keep: &[String]) -> Result<()> {
+        self.db
+            .query(
+                "UPDATE dnssec_keys SET state = 'Removed', state_changed_at = time::now() \
+                 WHERE zone = $zone AND state != 'Removed' AND key_id NOTINSIDE $keep",
+            )
+            .bind(("zone", zone))
+            .bind(("keep", keep.to_vec()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// List denial records, optionally for one zone, in chain order
+    pub async fn denial_records(&self, zone: Option<&str>) -> Result<Vec<DenialRecord>> {
+        let mut result = match zone {
+            Some(zone) => {
+                self.db
+                    .query("SELECT * FROM dnssec_denial WHERE zone = $zone ORDER BY kind, sort_key")
+                    .bind(("zone", zone))
+                    .await?
+            }
+            None => {
+                self.db
+                    .query("SELECT * FROM dnssec_denial ORDER BY zone, kind, sort_key")
+                    .await?
+            }
+        };
+
+        let records: Vec<DenialRecord> = result.take(0)?;
+        Ok(records)
+    }
+
+    /// Get the denial record at a chain position
+    pub async fn denial_record(
+        &self,
+        zone: &str,
+        kind: DenialKind,
+        sort_key: &str,
+    ) -> Result<Option<DenialRecord>> {
+        let mut result = self
+            .db
+            .query(
+                "SELECT * FROM dnssec_denial \
+                 WHERE zone = $zone AND kind = $kind AND sort_key = $key LIMIT 1",
+            )
+            .bind(("zone", zone))
+            .bind(("kind", format!("{:?}", kind)))
+            .bind(("key", sort_key))
+            .await?;
+
+        let records: Vec<DenialRecord> = result.take(0)?;
+        Ok(records.into_iter().next())
+    }
+
+    /// Get the denial record whose span covers `sort_key`: the last one before it,
+    /// wrapping around to the end of the chain
+    pub async fn covering_denial_record(
+        &self,
+        zone: &str,
+        kind: DenialKind,
+        sort_key: &str,
+    ) -> Result<Option<DenialRecord>> {
+        let mut result = self
+            .db
+            .query(
+                "SELECT * FROM dnssec_denial \
+                 WHERE zone = $zone AND kind = $kind AND sort_key < $key \
+                 ORDER BY sort_key DESC LIMIT 1; \
+                 SELECT * FROM dnssec_denial WHERE zone = $zone AND kind = $kind \
+                 ORDER BY sort_key DESC LIMIT 1;",
+            )
+            .bind(("zone", zone))
+            .bind(("kind", format!("{:?}", kind)))
+            .bind(("key", sort_key))
+            .await?;
+
+        let before: Vec<DenialRecord> = result.take(0)?;
+        let last: Vec<DenialRecord> = result.take(1)?;
+        Ok(before.into_iter().next().or_else(|| last.into_iter().next()))
+    }
+
+    /// Replace a zone's whole denial chain in one transaction
+    pub async fn replace_denial_chain(&self, zone: &str, records: Vec<DenialRecord>) -> Result<()> {
+        self.db
+            .query(
+                "BEGIN TRANSACTION; \
+                 DELETE dnssec_denial WHERE zone = $zone; \
+                 INSERT INTO dnssec_denial $records; \
+                 COMMIT TRANSACTION;",
+            )
+            .bind(("zone", zone))
+            .bind(("records", records))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Rewrite part of a zone's denial chain in one transaction: records at the
+    /// `remove` positions are deleted, then `records` are written
+    pub async fn update_denial_chain(
+        &self,
+        zone: &str,
+        kind: DenialKind,
+        remove: Vec<String>,
+        records: Vec<DenialRecord>,
+    ) -> Result<()> {
+        self.db
+            .query(
+                "BEGIN TRANSACTION; \
+                 DELETE dnssec_denial \
+                 WHERE zone = $zone AND kind = $kind AND sort_key INSIDE $remove; \
+                 INSERT INTO dnssec_denial $records; \
+                 COMMIT TRANSACTION;",
+            )
+            .bind(("zone", zone))
+            .bind(("kind", format!("{:?}", kind)))
+            .bind(("remove", remove))
+            .bind(("records", records))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
     /// Store blockchain provenance
     pub async fn store_provenance(&self, provenance: BlockchainProvenance) -> Result<BlockchainProvenance> {
         let created: Option<BlockchainProvenance> = self
@@ -272,7 +1259,7 @@ pub async fn get_statistics(&self) -> Result<(i32, Vec<RecordTypeCount>, i32, i3
         // Blockchain anchored
         let mut blockchain_result = self
             .db
-            .query("SELECT count() FROM dns_records WHERE blockchain_tx_hash IS NOT NULL GROUP ALL")
+            .query("SELECT count() FROM dns_records WHERE blockchain_tx_hash IS NOT NONE GROUP ALL")
             .await?;
         let blockchain: Option<i32> = blockchain_result.take("count")?;
         let blockchain_anchored = blockchain.unwrap_or(0);
@@ -280,3 +1267,86 @@ pub async fn get_statistics(&self) -> Result<(i32, Vec<RecordTypeCount>, i32, i3
         Ok((total_records, type_counts, dnssec_zones, blockchain_anchored))
     }
 }
+
+/// Parameters bound by a compiled query, by name
+type Binds = Vec<(String, serde_json::Value)>;
+
+/// Compile a record filter to SurrealQL conditions and the parameters they bind
+fn record_filter(filter: &DNSRecordFilter) -> Result<(Vec<String>, Binds)> {
+    let mut conditions = Vec::new();
+    let mut binds: Binds = Vec::new();
+    let mut bind = |param: &str, value: serde_json::Value| {
+        binds.push((param.to_string(), value));
+    };
+
+    if let Some(zone) = &filter.zone {
+        conditions.push("zone = $zone".to_string());
+        bind("zone", normalize_name(zone).into());
+    }
+    if let Some(name) = &filter.name {
+        conditions.push("name = $name".to_string());
+        bind("name", normalize_name(name).into());
+    }
+    if let Some(suffix) = &filter.name_suffix {
+        conditions.push("string::endsWith(name, $name_suffix)".to_string());
+        bind("name_suffix", normalize_name(suffix).into());
+    }
+    if let Some(prefix) = &filter.name_prefix {
+        conditions.push("string::startsWith(name, $name_prefix)".to_string());
+        bind("name_prefix", prefix.to_ascii_lowercase().into());
+    }
+    if let Some(pattern) = &filter.name_pattern {
+        let pattern = normalize_name(pattern);
+        let Some((head, tail)) = pattern.split_once('*') else {
+            return Err(AppError::InvalidQuery(format!(
+                "name pattern {} has no *; filter on name instead",
+                pattern
+            )));
+        };
+        if tail.contains('*') {
+            return Err(AppError::InvalidQuery(format!(
+                "name pattern {} has more than one *",
+                pattern
+            )));
+        }
+        // The head and tail may not overlap, so `a*a` does not match `a`
+        conditions.push(
+            "string::startsWith(name, $pattern_head) AND string::endsWith(name, $pattern_tail) \
+             AND string::len(name) >= $pattern_len"
+                .to_string(),
+        );
+        bind("pattern_len", (head.len() + tail.len()).into());
+        bind("pattern_head", head.into());
+        bind("pattern_tail", tail.into());
+    }
+    if let Some(record_type) = filter.record_type {
+        conditions.push("type = $record_type".to_string());
+        bind("record_type", format!("{:?}", record_type).into());
+    }
+    if let Some(substring) = &filter.value_contains {
+        conditions.push("string::contains(value, $value_contains)".to_string());
+        bind("value_contains", substring.as_str().into());
+    }
+    if let Some(dnssec) = filter.dnssec {
+        conditions.push("dnssec = $dnssec".to_string());
+        bind("dnssec", dnssec.into());
+    }
+    match filter.anchored {
+        Some(true) => conditions.push("blockchain_tx_hash IS NOT NONE".to_string()),
+        Some(false) => conditions.push("blockchain_tx_hash IS NONE".to_string()),
+        None => {}
+    }
+    for (field, op, param, time) in [
+        ("created_at", ">=", "created_after", filter.created_after),
+        ("created_at", "<", "created_before", filter.created_before),
+        ("updated_at", ">=", "updated_after", filter.updated_after),
+        ("updated_at", "<", "updated_before", filter.updated_before),
+    ] {
+        if let Some(time) = time {
+            conditions.push(format!("{} {} <datetime>${}", field, op, param));
+            bind(param, time.to_rfc3339().into());
+        }
+    }
+
+    Ok((conditions, binds))
+}
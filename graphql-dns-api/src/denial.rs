@@ -0,0 +1,1001 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Authenticated denial of existence: NSEC (RFC 4034 §4) and NSEC3 (RFC 5155) chains
+//!
+//! Every DNSSEC-enabled zone keeps one chain in the `dnssec_denial` table:
+//! NSEC by default, NSEC3 when `DNSSECZone.nsec3` is set. The chain is rebuilt
+//! whole when signing is enabled, keys rotate or the parameters change, and
+//! patched in place when records are written through the API: only entries
+//! whose type bitmap or successor changed are re-signed and stored.
+//!
+//! Negative answers carry the proof (RFC 4035 §3.1.3, RFC 5155 §7.2):
+//! - NODATA: the record matching the queried name
+//! - NXDOMAIN: the records covering the name and the wildcard at its closest
+//!   encloser (NSEC), or the closest encloser proof plus wildcard (NSEC3)
+//!
+//! Names below a delegation point (glue) stay out of the chain. NSEC3 also
+//! lists empty non-terminals; with opt-out, delegations without a DS are skipped.
+
+use crate::{
+    authority::{in_zone, normalize_name, rrsig_wire, wire_type},
+    db::Database,
+    dnssec::{canonical_name_wire, needs_resign, DNSSECManager},
+    error::{AppError, Result},
+    models::{
+        ChainPatch, DNSRecord, DNSRecordType, DNSSECZone, DenialKind, DenialRecord, NSEC3Config,
+        NSEC3Input,
+    },
+    rollover::APEX_KEY_TYPES,
+};
+use chrono::{DateTime, Utc};
+use hickory_proto::rr::{
+    dnssec::{
+        rdata::{DNSSECRData, NSEC, NSEC3, NSEC3PARAM},
+        Nsec3HashAlgorithm,
+    },
+    Name, RData, Record, RecordType,
+};
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    str::FromStr,
+};
+use tracing::warn;
+
+/// NSEC3 hash algorithm number for SHA-1, the only one defined (RFC 5155 §11).
+const NSEC3_HASH_SHA1: u8 = 1;
+/// NSEC3 flags field with the opt-out bit set (RFC 5155 §3.1.2.1).
+const NSEC3_FLAG_OPT_OUT: u8 = 1;
+/// Highest accepted NSEC3 iteration count; validators may treat more as insecure
+/// (RFC 9276 §3.2).
+pub const MAX_NSEC3_ITERATIONS: i32 = 100;
+/// Longest NSEC3 salt: its length is a single octet (RFC 5155 §3.2).
+const MAX_NSEC3_SALT_LEN: usize = 255;
+/// TTL for chain records when the zone has no SOA.
+const DEFAULT_NEGATIVE_TTL: i32 = 3600;
+/// RFC 4648 §7 "base32hex" alphabet, lowercase as NSEC3 owner labels are written.
+const BASE32HEX: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// Validate NSEC3 input into the parameters stored on the zone.
+pub fn nsec3_config(input: NSEC3Input) -> Result<NSEC3Config> {
+    let salt = match input.salt.trim() {
+        "" | "-" => String::new(),
+        salt => salt.to_ascii_lowercase(),
+    };
+    let bytes = hex::decode(&salt)
+        .map_err(|_| AppError::DNSSEC(format!("NSEC3 salt must be hex: {}", input.salt)))?;
+    if bytes.len() > MAX_NSEC3_SALT_LEN {
+        return Err(AppError::DNSSEC(format!(
+            "NSEC3 salt is {} bytes, at most {} allowed",
+            bytes.len(),
+            MAX_NSEC3_SALT_LEN
+        )));
+    }
+    if !(0..=MAX_NSEC3_ITERATIONS).contains(&input.iterations) {
+        return Err(AppError::DNSSEC(format!(
+            "NSEC3 iterations must be between 0 and {}",
+            MAX_NSEC3_ITERATIONS
+        )));
+    }
+
+    Ok(NSEC3Config {
+        salt,
+        iterations: input.iterations,
+        opt_out: input.opt_out,
+    })
+}
+
+/// NSEC3 hash of a name (RFC 5155 §5), base32hex encoded.
+pub fn nsec3_hash(name: &str, config: &NSEC3Config) -> Result<String> {
+    let salt = salt_bytes(config)?;
+    let mut digest = Sha1::new()
+        .chain_update(canonical_name_wire(name)?)
+        .chain_update(&salt)
+        .finalize();
+    for _ in 0..config.iterations {
+        digest = Sha1::new()
+            .chain_update(digest)
+            .chain_update(&salt)
+            .finalize();
+    }
+    Ok(base32hex_encode(&digest))
+}
+
+/// Regenerate and re-sign a signed zone's whole chain.
+///
+/// Returns the number of chain records written.
+pub async fn rebuild_chain(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &DNSSECZone,
+) -> Result<usize> {
+    let apex = normalize_name(&zone.zone);
+    let records = db.zone_records(&apex).await?;
+    let mut chain = build_chain(&apex, &records, zone.nsec3.as_ref())?;
+    sign_entries(manager, &apex, &mut chain, zone.nsec3.as_ref(), Utc::now())?;
+
+    let written = chain.len();
+    db.replace_denial_chain(&apex, chain).await?;
+    Ok(written)
+}
+
+/// Switch a signed zone between NSEC (`nsec3: None`) and NSEC3, or change its
+/// NSEC3 parameters, and rebuild its chain. The caller bumps the serial.
+pub async fn set_denial_of_existence(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    nsec3: Option<NSEC3Input>,
+) -> Result<DNSSECZone> {
+    let mut dnssec_zone = db.get_dnssec_zone(&normalize_name(zone)).await?;
+    if !dnssec_zone.enabled {
+        return Err(AppError::DNSSEC(format!("DNSSEC is not enabled for {}", zone)));
+    }
+    dnssec_zone.nsec3 = nsec3.map(nsec3_config).transpose()?;
+
+    let updated = db.upsert_dnssec_zone(dnssec_zone).await?;
+    rebuild_chain(db, manager, &updated).await?;
+    Ok(updated)
+}
+
+/// The changes bringing a signed zone's stored chain, `current`, in line with
+/// `records`, the zone's records as they will be, for writing with them.
+///
+/// Only entries whose type bitmap or successor changed are signed and
+/// written; without a stored chain of the zone's kind the whole chain is.
+pub fn chain_patch(
+    manager: &DNSSECManager,
+    zone: &DNSSECZone,
+    records: &[DNSRecord],
+    current: &[DenialRecord],
+    now: DateTime<Utc>,
+) -> Result<ChainPatch> {
+    let apex = normalize_name(&zone.zone);
+    let nsec3 = zone.nsec3.as_ref();
+    let kind = chain_kind(nsec3);
+    let chain = build_chain(&apex, records, nsec3)?;
+    let current: Vec<DenialRecord> = current.iter().filter(|r| r.kind == kind).cloned().collect();
+
+    let replace = current.is_empty();
+    let (mut records, mut remove) = if replace {
+        (chain, Vec::new())
+    } else {
+        let entries = chain.into_iter().filter(|e| e.kind == kind).collect();
+        chain_diff(&current, entries)
+    };
+    sign_entries(manager, &apex, &mut records, nsec3, now)?;
+    if !replace {
+        remove.extend(records.iter().map(|e| e.sort_key.clone()));
+    }
+    Ok(ChainPatch {
+        zone: apex,
+        kind,
+        replace,
+        remove,
+        records,
+    })
+}
+
+/// The entries of a linked chain that are new or changed against `current`,
+/// and the positions in `current` the chain no longer has.
+fn chain_diff(
+    current: &[DenialRecord],
+    entries: Vec<DenialRecord>,
+) -> (Vec<DenialRecord>, Vec<String>) {
+    let before: BTreeMap<&str, &DenialRecord> =
+        current.iter().map(|r| (r.sort_key.as_str(), r)).collect();
+    let kept: BTreeSet<String> = entries.iter().map(|e| e.sort_key.clone()).collect();
+    let remove = before
+        .keys()
+        .filter(|k| !kept.contains(**k))
+        .map(|k| k.to_string())
+        .collect();
+    let changed = entries
+        .into_iter()
+        .filter(|e| {
+            before.get(e.sort_key.as_str()).map_or(true, |old| {
+                old.next != e.next || old.types != e.types || old.owner != e.owner
+            })
+        })
+        .collect();
+    (changed, remove)
+}
+
+/// Re-sign chain records with missing or soon-to-expire signatures.
+///
+/// Returns the number of records re-signed. Zones that fail are skipped with a warning.
+pub async fn resign_expiring(db: &Database, manager: &DNSSECManager) -> Result<usize> {
+    let now = Utc::now();
+    let mut stale: BTreeMap<(String, String), Vec<DenialRecord>> = BTreeMap::new();
+    for record in db.denial_records(None).await? {
+        if needs_resign(record.rrsig.as_deref(), now) {
+            stale
+                .entry((record.zone.clone(), format!("{:?}", record.kind)))
+                .or_default()
+                .push(record);
+        }
+    }
+
+    let mut resigned = 0;
+    for ((zone, _), mut records) in stale {
+        let kind = records[0].kind;
+        let result = async {
+            let config = db.get_dnssec_zone(&zone).await?;
+            sign_entries(manager, &zone, &mut records, config.nsec3.as_ref(), now)?;
+            let keys = records.iter().map(|r| r.sort_key.clone()).collect();
+            let count = records.len();
+            db.update_denial_chain(&zone, kind, keys, records).await?;
+            Ok::<_, AppError>(count)
+        }
+        .await;
+        match result {
+            Ok(count) => resigned += count,
+            Err(e) => warn!("Could not re-sign {:?} chain of {}: {}", kind, zone, e),
+        }
+    }
+    Ok(resigned)
+}
+
+/// Chain records (with RRSIGs) proving a negative answer from a signed zone.
+///
+/// Returns nothing for unsigned zones.
+pub async fn negative_proof(
+    db: &Database,
+    apex: &str,
+    qname: &str,
+    nxdomain: bool,
+) -> Result<Vec<Record>> {
+    let zone = match db.get_dnssec_zone(apex).await {
+        Ok(zone) if zone.enabled => zone,
+        _ => return Ok(Vec::new()),
+    };
+    let nsec3 = zone.nsec3.as_ref();
+    let kind = chain_kind(nsec3);
+
+    let mut proof: Vec<DenialRecord> = Vec::new();
+    if !nxdomain {
+        let key = sort_key(qname, nsec3)?;
+        match db.denial_record(apex, kind, &key).await? {
+            Some(record) => proof.push(record),
+            // An NSEC empty non-terminal: the NSEC covering it shows names below
+            None if nsec3.is_none() => {
+                proof.extend(db.covering_denial_record(apex, kind, &key).await?);
+            }
+            None => {}
+        }
+    } else {
+        let encloser = closest_encloser(db, apex, qname).await?;
+        let wildcard = format!("*.{}", encloser);
+        if nsec3.is_some() {
+            let next_closer = next_closer_name(qname, &encloser);
+            let encloser_key = sort_key(&encloser, nsec3)?;
+            proof.extend(db.denial_record(apex, kind, &encloser_key).await?);
+            let next_closer_key = sort_key(next_closer, nsec3)?;
+            proof.extend(
+                db.covering_denial_record(apex, kind, &next_closer_key)
+                    .await?,
+            );
+        } else {
+            let qname_key = sort_key(qname, nsec3)?;
+            proof.extend(db.covering_denial_record(apex, kind, &qname_key).await?);
+        }
+        let wildcard_key = sort_key(&wildcard, nsec3)?;
+        proof.extend(db.covering_denial_record(apex, kind, &wildcard_key).await?);
+    }
+    signed_proof(proof, nsec3)
+}
+
+/// Proof for an answer synthesised from the wildcard at `encloser` (RFC 4035
+/// §3.1.3.3-4, RFC 5155 §7.2.5-6): the record covering `qname` (NSEC) or its
+/// next closer name (NSEC3), plus for NODATA the wildcard's own record and,
+/// with NSEC3, the closest encloser's.
+pub async fn wildcard_proof(
+    db: &Database,
+    apex: &str,
+    qname: &str,
+    encloser: &str,
+    nodata: bool,
+) -> Result<Vec<Record>> {
+    let zone = match db.get_dnssec_zone(apex).await {
+        Ok(zone) if zone.enabled => zone,
+        _ => return Ok(Vec::new()),
+    };
+    let nsec3 = zone.nsec3.as_ref();
+    let kind = chain_kind(nsec3);
+
+    let covered = match nsec3 {
+        Some(_) => next_closer_name(qname, encloser),
+        None => qname,
+    };
+    let mut proof: Vec<DenialRecord> = Vec::new();
+    let covered_key = sort_key(covered, nsec3)?;
+    proof.extend(db.covering_denial_record(apex, kind, &covered_key).await?);
+    if nodata {
+        if nsec3.is_some() {
+            let encloser_key = sort_key(encloser, nsec3)?;
+            proof.extend(db.denial_record(apex, kind, &encloser_key).await?);
+        }
+        let wildcard_key = sort_key(&format!("*.{}", encloser), nsec3)?;
+        proof.extend(db.denial_record(apex, kind, &wildcard_key).await?);
+    }
+    signed_proof(proof, nsec3)
+}
+
+/// Chain records with their RRSIGs, each entry once.
+fn signed_proof(proof: Vec<DenialRecord>, nsec3: Option<&NSEC3Config>) -> Result<Vec<Record>> {
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    let mut records = Vec::new();
+    for entry in proof {
+        if seen.insert(entry.sort_key.clone()) {
+            records.extend(with_signatures(&entry, nsec3, true)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Answer a direct NSEC, NSEC3 or NSEC3PARAM query from the chain.
+///
+/// Returns nothing for other types, unsigned zones or names without a chain record.
+pub async fn answer(
+    db: &Database,
+    apex: &str,
+    qname: &str,
+    qtype: RecordType,
+    dnssec_ok: bool,
+) -> Result<Vec<Record>> {
+    let kind = match qtype {
+        RecordType::NSEC => DenialKind::NSEC,
+        RecordType::NSEC3 => DenialKind::NSEC3,
+        RecordType::NSEC3PARAM => DenialKind::NSEC3PARAM,
+        _ => return Ok(Vec::new()),
+    };
+    let zone = match db.get_dnssec_zone(apex).await {
+        Ok(zone) if zone.enabled => zone,
+        _ => return Ok(Vec::new()),
+    };
+
+    let key = match kind {
+        DenialKind::NSEC => canonical_sort_key(qname),
+        DenialKind::NSEC3 => match qname.split_once('.') {
+            Some((hash, parent)) if parent == apex => hash.to_string(),
+            _ => return Ok(Vec::new()),
+        },
+        DenialKind::NSEC3PARAM if qname == apex => String::new(),
+        DenialKind::NSEC3PARAM => return Ok(Vec::new()),
+    };
+    match db.denial_record(apex, kind, &key).await? {
+        Some(record) => with_signatures(&record, zone.nsec3.as_ref(), dnssec_ok),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Build a zone's complete chain from its records, unsigned.
+pub fn build_chain(
+    apex: &str,
+    records: &[DNSRecord],
+    nsec3: Option<&NSEC3Config>,
+) -> Result<Vec<DenialRecord>> {
+    let apex = normalize_name(apex);
+    let ttl = negative_ttl(&apex, records);
+    let kind = chain_kind(nsec3);
+
+    let mut entries = Vec::new();
+    for (name, types) in chain_names(&apex, records) {
+        if types.is_empty() && nsec3.is_none() {
+            continue;
+        }
+        if let Some(entry) = chain_entry(&apex, &name, &types, nsec3, ttl)? {
+            entries.push(entry);
+        }
+    }
+    link(&mut entries, kind);
+
+    if nsec3.is_some() {
+        entries.push(DenialRecord {
+            zone: apex.clone(),
+            kind: DenialKind::NSEC3PARAM,
+            owner: apex.clone(),
+            name: apex.clone(),
+            next: String::new(),
+            types: Vec::new(),
+            ttl,
+            rrsig: None,
+            sort_key: String::new(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Convert a chain record to its wire form.
+pub fn to_wire(record: &DenialRecord, nsec3: Option<&NSEC3Config>) -> Result<Record> {
+    let invalid = |what: &str| {
+        AppError::DNSSEC(format!(
+            "Invalid {:?} record at {}: {}",
+            record.kind, record.owner, what
+        ))
+    };
+    let owner = Name::from_str(&format!("{}.", record.owner)).map_err(|_| invalid("owner name"))?;
+    let types = record
+        .types
+        .iter()
+        .map(|t| parse_type(t).ok_or_else(|| invalid("type bitmap")))
+        .collect::<Result<Vec<_>>>()?;
+
+    let rdata = match record.kind {
+        DenialKind::NSEC => {
+            let next = Name::from_str(&format!("{}.", record.next))
+                .map_err(|_| invalid("next owner name"))?;
+            DNSSECRData::NSEC(NSEC::new(next, types))
+        }
+        DenialKind::NSEC3 => {
+            let config = nsec3.ok_or_else(|| invalid("zone has no NSEC3 parameters"))?;
+            let next =
+                base32hex_decode(&record.next).ok_or_else(|| invalid("next hashed owner"))?;
+            DNSSECRData::NSEC3(NSEC3::new(
+                Nsec3HashAlgorithm::SHA1,
+                config.opt_out,
+                config.iterations as u16,
+                salt_bytes(config)?,
+                next,
+                types,
+            ))
+        }
+        DenialKind::NSEC3PARAM => {
+            let config = nsec3.ok_or_else(|| invalid("zone has no NSEC3 parameters"))?;
+            // Flags are always zero in NSEC3PARAM (RFC 5155 §4.1.2)
+            DNSSECRData::NSEC3PARAM(NSEC3PARAM::new(
+                Nsec3HashAlgorithm::SHA1,
+                false,
+                config.iterations as u16,
+                salt_bytes(config)?,
+            ))
+        }
+    };
+
+    Ok(Record::from_rdata(
+        owner,
+        record.ttl as u32,
+        RData::DNSSEC(rdata),
+    ))
+}
+
+/// Append a chain to a master file, in chain order, each record followed by its RRSIGs.
+pub fn write_chain(out: &mut String, chain: &[DenialRecord], nsec3: Option<&NSEC3Config>) {
+    for record in chain {
+        let rdata = match (record.kind, nsec3) {
+            (DenialKind::NSEC, _) => format!("{}. {}", record.next, record.types.join(" ")),
+            (DenialKind::NSEC3, Some(config)) => format!(
+                "{} {} {} {} {} {}",
+                NSEC3_HASH_SHA1,
+                if config.opt_out {
+                    NSEC3_FLAG_OPT_OUT
+                } else {
+                    0
+                },
+                config.iterations,
+                presentation_salt(config),
+                record.next,
+                record.types.join(" ")
+            ),
+            (DenialKind::NSEC3PARAM, Some(config)) => format!(
+                "{} 0 {} {}",
+                NSEC3_HASH_SHA1,
+                config.iterations,
+                presentation_salt(config)
+            ),
+            (_, None) => continue,
+        };
+        let _ = writeln!(
+            out,
+            "{}.\t{}\tIN\t{:?}\t{}",
+            record.owner,
+            record.ttl,
+            record.kind,
+            rdata.trim_end()
+        );
+
+        let mut signatures: Vec<&str> = record.rrsig.iter().flat_map(|s| s.lines()).collect();
+        signatures.sort_unstable();
+        for rrsig in signatures {
+            let _ = writeln!(
+                out,
+                "{}.\t{}\tIN\tRRSIG\t{}",
+                record.owner, record.ttl, rrsig
+            );
+        }
+    }
+}
+
+/// A chain record in wire form, followed by its RRSIGs when DO is set.
+pub(crate) fn with_signatures(
+    record: &DenialRecord,
+    nsec3: Option<&NSEC3Config>,
+    dnssec_ok: bool,
+) -> Result<Vec<Record>> {
+    let mut records = vec![to_wire(record, nsec3)?];
+    if dnssec_ok {
+        for rrsig in record.rrsig.iter().flat_map(|s| s.lines()) {
+            records.push(rrsig_wire(&record.owner, record.ttl as u32, rrsig)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Sign each chain record as its own RRset.
+fn sign_entries(
+    manager: &DNSSECManager,
+    apex: &str,
+    entries: &mut [DenialRecord],
+    nsec3: Option<&NSEC3Config>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    for entry in entries {
+        let wire = to_wire(entry, nsec3)?;
+        entry.rrsig = Some(manager.sign_records(apex, &[wire], now)?);
+    }
+    Ok(())
+}
+
+/// Chain kind used by a zone.
+fn chain_kind(nsec3: Option<&NSEC3Config>) -> DenialKind {
+    if nsec3.is_some() {
+        DenialKind::NSEC3
+    } else {
+        DenialKind::NSEC
+    }
+}
+
+/// Chain position of a name: its NSEC3 hash, or its canonical NSEC sort key.
+fn sort_key(name: &str, nsec3: Option<&NSEC3Config>) -> Result<String> {
+    match nsec3 {
+        Some(config) => nsec3_hash(name, config),
+        None => Ok(canonical_sort_key(name)),
+    }
+}
+
+/// NSEC chain position: hex-encoded labels, rightmost first, joined with '.'.
+///
+/// Plain string order then matches RFC 4034 §6.1 canonical order: each label
+/// byte becomes two hex digits, and '.' sorts before any of them.
+fn canonical_sort_key(name: &str) -> String {
+    normalize_name(name)
+        .split('.')
+        .filter(|l| !l.is_empty())
+        .rev()
+        .map(hex::encode)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Build the unlinked chain entry for a name, or `None` when the name is left out.
+fn chain_entry(
+    apex: &str,
+    name: &str,
+    types: &BTreeSet<RecordType>,
+    nsec3: Option<&NSEC3Config>,
+    ttl: i32,
+) -> Result<Option<DenialRecord>> {
+    let mut bitmap = types.clone();
+    let (owner, sort_key) = match nsec3 {
+        None => {
+            if types.is_empty() {
+                return Ok(None);
+            }
+            bitmap.insert(RecordType::RRSIG);
+            bitmap.insert(RecordType::NSEC);
+            if name == apex {
+                bitmap.extend(APEX_KEY_TYPES);
+            }
+            (name.to_string(), canonical_sort_key(name))
+        }
+        Some(config) => {
+            let insecure_delegation =
+                name != apex && types.contains(&RecordType::NS) && !types.contains(&RecordType::DS);
+            if insecure_delegation && config.opt_out {
+                return Ok(None);
+            }
+            if !types.is_empty() && !insecure_delegation {
+                bitmap.insert(RecordType::RRSIG);
+            }
+            if name == apex {
+                bitmap.extend(APEX_KEY_TYPES);
+                bitmap.insert(RecordType::NSEC3PARAM);
+            }
+            let hash = nsec3_hash(name, config)?;
+            (format!("{}.{}", hash, apex), hash)
+        }
+    };
+
+    Ok(Some(DenialRecord {
+        zone: apex.to_string(),
+        kind: chain_kind(nsec3),
+        owner,
+        name: name.to_string(),
+        next: String::new(),
+        types: bitmap.iter().map(|t| type_name(*t)).collect(),
+        ttl,
+        rrsig: None,
+        sort_key,
+    }))
+}
+
+/// Bitmap type mnemonic; `TYPEnnn` (RFC 3597 §5) for types hickory has no name for.
+fn type_name(record_type: RecordType) -> String {
+    match record_type {
+        RecordType::Unknown(code) => format!("TYPE{}", code),
+        other => other.to_string(),
+    }
+}
+
+/// Parse a mnemonic written by [`type_name`].
+fn parse_type(name: &str) -> Option<RecordType> {
+    match name.strip_prefix("TYPE").and_then(|code| code.parse::<u16>().ok()) {
+        Some(code) => Some(RecordType::from(code)),
+        None => RecordType::from_str(name).ok(),
+    }
+}
+
+/// Sort entries into chain order and point each at its successor, the last back at the first.
+fn link(entries: &mut [DenialRecord], kind: DenialKind) {
+    entries.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+    let count = entries.len();
+    for i in 0..count {
+        let successor = &entries[(i + 1) % count];
+        let next = match kind {
+            DenialKind::NSEC3 => successor.sort_key.clone(),
+            _ => successor.owner.clone(),
+        };
+        entries[i].next = next;
+    }
+}
+
+/// Names in a zone's chain with the RR types present at each.
+///
+/// Empty non-terminals get an empty set; names below a delegation point are
+/// dropped, and a delegation point keeps only its parent-side NS and DS.
+fn chain_names(apex: &str, records: &[DNSRecord]) -> BTreeMap<String, BTreeSet<RecordType>> {
+    let mut names: BTreeMap<String, BTreeSet<RecordType>> = BTreeMap::new();
+    for record in records {
+        let name = normalize_name(&record.name);
+        if in_zone(&name, apex) {
+            names
+                .entry(name)
+                .or_default()
+                .insert(wire_type(record.record_type));
+        }
+    }
+
+    let cuts: BTreeSet<String> = names
+        .iter()
+        .filter(|(name, types)| name.as_str() != apex && types.contains(&RecordType::NS))
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.retain(|name, _| {
+        !ancestors(name, apex)
+            .iter()
+            .any(|a| *a != apex && cuts.contains(*a))
+    });
+    for cut in &cuts {
+        if let Some(types) = names.get_mut(cut) {
+            types.retain(|t| matches!(t, RecordType::NS | RecordType::DS));
+        }
+    }
+
+    let present: Vec<String> = names.keys().cloned().collect();
+    for name in &present {
+        for ancestor in ancestors(name, apex) {
+            names.entry(ancestor.to_string()).or_default();
+        }
+    }
+    names
+}
+
+/// Proper ancestors of `name` down to and including `apex`, nearest first.
+fn ancestors<'a>(name: &'a str, apex: &str) -> Vec<&'a str> {
+    let mut ancestors = Vec::new();
+    let mut current = name;
+    while current != apex {
+        match current.split_once('.') {
+            Some((_, parent)) if in_zone(parent, apex) => {
+                ancestors.push(parent);
+                current = parent;
+            }
+            _ => break,
+        }
+    }
+    ancestors
+}
+
+/// Closest existing ancestor of a non-existent name (RFC 5155 §1.3).
+pub(crate) async fn closest_encloser(db: &Database, apex: &str, qname: &str) -> Result<String> {
+    for ancestor in ancestors(qname, apex) {
+        let exists = !db
+            .query_records(Some(ancestor.to_string()), None, 1, 0)
+            .await?
+            .is_empty()
+            || db.has_names_below(ancestor).await?;
+        if exists {
+            return Ok(ancestor.to_string());
+        }
+    }
+    Ok(apex.to_string())
+}
+
+/// The ancestor of `qname` one label longer than its closest encloser.
+fn next_closer_name<'a>(qname: &'a str, encloser: &str) -> &'a str {
+    let mut name = qname;
+    while let Some((_, parent)) = name.split_once('.') {
+        if parent == encloser {
+            break;
+        }
+        name = parent;
+    }
+    name
+}
+
+/// TTL for chain records: the lesser of the SOA TTL and its MINIMUM (RFC 9077 §3).
+fn negative_ttl(apex: &str, records: &[DNSRecord]) -> i32 {
+    records
+        .iter()
+        .find(|r| r.record_type == DNSRecordType::SOA && normalize_name(&r.name) == apex)
+        .map(|soa| {
+            let minimum = soa
+                .value
+                .split_whitespace()
+                .nth(6)
+                .and_then(|m| m.parse::<i32>().ok());
+            minimum.map_or(soa.ttl, |minimum| minimum.min(soa.ttl))
+        })
+        .unwrap_or(DEFAULT_NEGATIVE_TTL)
+}
+
+/// Decoded NSEC3 salt.
+fn salt_bytes(config: &NSEC3Config) -> Result<Vec<u8>> {
+    hex::decode(&config.salt)
+        .map_err(|_| AppError::DNSSEC(format!("Invalid NSEC3 salt: {}", config.salt)))
+}
+
+/// NSEC3 salt in presentation form: hex, or "-" when empty.
+fn presentation_salt(config: &NSEC3Config) -> &str {
+    if config.salt.is_empty() {
+        "-"
+    } else {
+        &config.salt
+    }
+}
+
+/// Encode bytes as unpadded base32hex (RFC 4648 §7).
+fn base32hex_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in bytes {
+        buffer = ((buffer << 8) | byte as u32) & 0xFFFF;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32HEX[((buffer >> bits) & 0x1F) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32HEX[((buffer << (5 - bits)) & 0x1F) as usize] as char);
+    }
+    out
+}
+
+/// Decode unpadded base32hex, case-insensitively.
+fn base32hex_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in text.bytes() {
+        let value = BASE32HEX
+            .iter()
+            .position(|&a| a == c.to_ascii_lowercase())? as u32;
+        buffer = ((buffer << 5) | value) & 0xFFFF;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, record_type: DNSRecordType, value: &str) -> DNSRecord {
+        DNSRecord::new(name.to_string(), record_type, 3600, value.to_string())
+    }
+
+    fn zone() -> Vec<DNSRecord> {
+        vec![
+            record(
+                "example",
+                DNSRecordType::SOA,
+                "ns1.example. h.example. 1 2 3 4 300",
+            ),
+            record("example", DNSRecordType::NS, "ns1.example."),
+            record("ns1.example", DNSRecordType::A, "192.0.2.53"),
+            record("www.b.example", DNSRecordType::A, "192.0.2.1"),
+            record("sub.example", DNSRecordType::NS, "ns.sub.example."),
+            record("ns.sub.example", DNSRecordType::A, "192.0.2.54"),
+        ]
+    }
+
+    fn nsec3(opt_out: bool) -> NSEC3Config {
+        NSEC3Config {
+            salt: "aabbccdd".to_string(),
+            iterations: 12,
+            opt_out,
+        }
+    }
+
+    #[test]
+    fn test_nsec3_hash_matches_rfc5155() {
+        // RFC 5155 Appendix A: salt aabbccdd, 12 iterations
+        let config = nsec3(false);
+        assert_eq!(
+            nsec3_hash("example", &config).unwrap(),
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"
+        );
+        assert_eq!(
+            nsec3_hash("a.example", &config).unwrap(),
+            "35mthgpgcu1qg68fab165klnsnk3dpvl"
+        );
+        assert_eq!(
+            nsec3_hash("A.EXAMPLE.", &config).unwrap(),
+            "35mthgpgcu1qg68fab165klnsnk3dpvl"
+        );
+    }
+
+    #[test]
+    fn test_base32hex_roundtrip() {
+        let bytes: Vec<u8> = (0u8..20).collect();
+        let encoded = base32hex_encode(&bytes);
+        assert_eq!(encoded.len(), 32);
+        assert_eq!(base32hex_decode(&encoded).unwrap(), bytes);
+        assert_eq!(base32hex_decode(&encoded.to_uppercase()).unwrap(), bytes);
+        assert!(base32hex_decode("xyz").is_none());
+    }
+
+    #[test]
+    fn test_bitmap_type_names_roundtrip() {
+        for record_type in [RecordType::AAAA, RecordType::Unknown(256)] {
+            assert_eq!(parse_type(&type_name(record_type)), Some(record_type));
+        }
+        assert_eq!(type_name(RecordType::Unknown(29)), "TYPE29");
+        assert_eq!(parse_type("TYPEX"), None);
+    }
+
+    #[test]
+    fn test_canonical_sort_key_order() {
+        // RFC 4034 §6.1 example, restricted to plain labels
+        let expected = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "*.z.example",
+        ];
+        let mut names = expected.to_vec();
+        names.reverse();
+        names.sort_by_key(|n| canonical_sort_key(n));
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_nsec_chain_skips_glue_and_links_around() {
+        let chain = build_chain("example", &zone(), None).unwrap();
+        let owners: Vec<&str> = chain.iter().map(|r| r.owner.as_str()).collect();
+        assert_eq!(
+            owners,
+            ["example", "www.b.example", "ns1.example", "sub.example"]
+        );
+
+        let nexts: Vec<&str> = chain.iter().map(|r| r.next.as_str()).collect();
+        assert_eq!(
+            nexts,
+            ["www.b.example", "ns1.example", "sub.example", "example"]
+        );
+
+        assert_eq!(
+            chain[0].types,
+            ["NS", "SOA", "RRSIG", "NSEC", "DNSKEY", "CDS", "CDNSKEY"]
+        );
+        assert_eq!(chain[3].types, ["NS", "RRSIG", "NSEC"]);
+        assert!(chain.iter().all(|r| r.ttl == 300));
+    }
+
+    #[test]
+    fn test_nsec3_chain_covers_empty_non_terminals() {
+        let config = nsec3(false);
+        let chain = build_chain("example", &zone(), Some(&config)).unwrap();
+
+        let names: BTreeSet<&str> = chain
+            .iter()
+            .filter(|r| r.kind == DenialKind::NSEC3)
+            .map(|r| r.name.as_str())
+            .collect();
+        let expected: BTreeSet<&str> = [
+            "example",
+            "b.example",
+            "ns1.example",
+            "sub.example",
+            "www.b.example",
+        ]
+        .into();
+        assert_eq!(names, expected);
+
+        let ent = chain.iter().find(|r| r.name == "b.example").unwrap();
+        assert!(ent.types.is_empty());
+        let delegation = chain.iter().find(|r| r.name == "sub.example").unwrap();
+        assert_eq!(delegation.types, ["NS"]);
+        assert!(chain.iter().any(|r| r.kind == DenialKind::NSEC3PARAM));
+
+        // Each next hash is the following owner's hash, wrapping around
+        let hashes: Vec<&str> = chain
+            .iter()
+            .filter(|r| r.kind == DenialKind::NSEC3)
+            .map(|r| r.sort_key.as_str())
+            .collect();
+        let mut sorted = hashes.clone();
+        sorted.sort_unstable();
+        assert_eq!(hashes, sorted);
+        let first = &chain[0];
+        let last = chain
+            .iter()
+            .rfind(|r| r.kind == DenialKind::NSEC3)
+            .unwrap();
+        assert_eq!(last.next, first.sort_key);
+    }
+
+    #[test]
+    fn test_nsec3_opt_out_skips_insecure_delegations() {
+        let config = nsec3(true);
+        let chain = build_chain("example", &zone(), Some(&config)).unwrap();
+        assert!(chain.iter().all(|r| r.name != "sub.example"));
+    }
+
+    #[test]
+    fn test_to_wire_builds_nsec3() {
+        let config = nsec3(true);
+        let chain = build_chain("example", &zone(), Some(&config)).unwrap();
+        for entry in &chain {
+            let wire = to_wire(entry, Some(&config)).expect("chain record should convert");
+            assert_eq!(wire.ttl(), 300);
+        }
+        assert!(to_wire(&chain[0], None).is_err());
+    }
+
+    #[test]
+    fn test_nsec3_config_validation() {
+        let config = nsec3_config(NSEC3Input {
+            salt: "-".into(),
+            iterations: 0,
+            opt_out: false,
+        });
+        assert_eq!(config.unwrap().salt, "");
+        assert!(nsec3_config(NSEC3Input {
+            salt: "zz".into(),
+            ..Default::default()
+        })
+        .is_err());
+        let too_many = NSEC3Input {
+            iterations: MAX_NSEC3_ITERATIONS + 1,
+            ..Default::default()
+        };
+        assert!(nsec3_config(too_many).is_err());
+    }
+
+    #[test]
+    fn test_next_closer_name() {
+        assert_eq!(
+            next_closer_name("a.b.c.example", "c.example"),
+            "b.c.example"
+        );
+        assert_eq!(next_closer_name("a.example", "example"), "a.example");
+    }
+}
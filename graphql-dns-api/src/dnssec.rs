@@ -6,25 +6,76 @@
 //! Implements:
 //! - KSK (Key Signing Key) generation: hybrid Ed448 + Dilithium5
 //! - ZSK (Zone Signing Key) generation: hybrid Ed448 + Dilithium5
-//! - DS record generation for parent zone (BLAKE3 digest per CPR-009)
+//! - Interoperable algorithms ECDSA P-256 (13), Ed25519 (15) and Ed448 (16),
+//!   usable alone or in parallel with the hybrid scheme
+//! - DNSKEY RDATA in wire format with RFC 4034 Appendix B key tags
+//! - DS record generation for parent zone over owner name + DNSKEY RDATA
+//!   (SHA-256, SHA-384, or BLAKE3 per CPR-009)
 //! - RRSIG generation and verification using hybrid signatures
+//! - RRset-level RFC 4034 §3.1.8.1 signing with the zone's ZSKs, one RRSIG per algorithm
 //!
 //! Algorithm number 253 (private-use per RFC 4034 §A.1.1) for hybrid scheme.
 //! Wire format: [Ed448 (57/114 bytes)] [Dilithium5 (2592/4627 bytes)]
 
-use crate::error::{AppError, Result};
+use crate::{
+    authority::{normalize_name, to_wire_record},
+    db::Database,
+    error::{AppError, Result},
+    keystore::KeyStore,
+    models::{
+        DNSRecord, DNSSECAlgorithm, DNSSECKey, DNSSECKeyRole, DNSSECKeyState, DSDigestType,
+        TSIGAlgorithm, TSIGKey,
+    },
+};
 use base64::Engine as _;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use ed448_goldilocks_plus::{SigningKey, VerifyingKey};
+use hickory_proto::rr::{
+    dnssec::{
+        rdata::{DNSSECRData, DNSKEY},
+        tbs::rrset_tbs,
+        Algorithm,
+    },
+    DNSClass, Name, RData, Record,
+};
 use pqcrypto_dilithium::dilithium5;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256, Sha384};
 use pqcrypto_traits::sign::{
-    DetachedSignature as DilDetachedSigTrait, PublicKey as DilPkTrait,
+    DetachedSignature as DilDetachedSigTrait, PublicKey as DilPkTrait, SecretKey as DilSkTrait,
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
 };
+use tracing::{info, warn};
 
 /// DNSSEC algorithm number for hybrid Ed448+Dilithium5 (private-use, RFC 4034 §A.1.1).
 pub const ALGORITHM_HYBRID_ED448_DIL5: u8 = 253;
 
+/// DNSKEY flags for a key signing key (Zone Key + Secure Entry Point).
+pub const DNSKEY_FLAGS_KSK: u16 = 257;
+/// DNSKEY flags for a zone signing key (Zone Key).
+pub const DNSKEY_FLAGS_ZSK: u16 = 256;
+/// DNSKEY protocol field, always 3 (RFC 4034 §2.1.2).
+pub const DNSKEY_PROTOCOL: u8 = 3;
+
+/// RRSIG validity period.
+pub const SIGNATURE_VALIDITY_DAYS: i64 = 30;
+/// Re-sign once less than this much validity remains.
+pub const RESIGN_BEFORE_DAYS: i64 = 7;
+/// Inception is backdated to tolerate validator clock skew.
+const INCEPTION_SKEW_HOURS: i64 = 1;
+/// RRSIG timestamp presentation format (RFC 4034 §3.2).
+const SIG_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
 /// Ed448 public key size (bytes).
 const ED448_PK_LEN: usize = 57;
+/// Ed448 secret key size (bytes).
+const ED448_SK_LEN: usize = 57;
+/// Ed448 signature size (bytes).
+const ED448_SIG_LEN: usize = 114;
 /// Dilithium5 public key size (bytes).
 const DIL5_PK_LEN: usize = 2592;
 
@@ -38,21 +89,61 @@ pub struct HybridDNSSECKey {
 
 impl HybridDNSSECKey {
     /// Generate a new hybrid keypair.
-    fn generate() -> Self {
-        let ed448_sk = SigningKey::generate(&mut rand::rngs::OsRng);
+    pub(crate) fn generate() -> Self {
+        let ed448_sk = SigningKey::generate(&mut OsRng);
         let ed448_vk = ed448_sk.verifying_key();
         let (dil5_pk, dil5_sk) = dilithium5::keypair();
         Self { ed448_sk, ed448_vk, dil5_pk, dil5_sk }
     }
 
+    /// Rebuild a keypair from its secret and public serializations.
+    pub(crate) fn from_bytes(secret: &[u8], public: &[u8]) -> Result<Self> {
+        if secret.len() != ED448_SK_LEN + dilithium5::secret_key_bytes() {
+            return Err(AppError::DNSSEC(format!(
+                "Invalid hybrid secret key length: {}",
+                secret.len()
+            )));
+        }
+        if public.len() != ED448_PK_LEN + DIL5_PK_LEN {
+            return Err(AppError::DNSSEC(format!(
+                "Invalid hybrid public key length: {}",
+                public.len()
+            )));
+        }
+
+        let ed448_sk = SigningKey::try_from(&secret[..ED448_SK_LEN])
+            .map_err(|_| AppError::DNSSEC("Invalid Ed448 secret key".into()))?;
+        let ed448_vk = ed448_sk.verifying_key();
+        if ed448_vk.to_bytes().as_slice() != &public[..ED448_PK_LEN] {
+            return Err(AppError::DNSSEC("Ed448 secret key does not match public key".into()));
+        }
+
+        let dil5_sk = dilithium5::SecretKey::from_bytes(&secret[ED448_SK_LEN..])
+            .map_err(|_| AppError::DNSSEC("Invalid Dilithium5 secret key".into()))?;
+        let dil5_pk = dilithium5::PublicKey::from_bytes(&public[ED448_PK_LEN..])
+            .map_err(|_| AppError::DNSSEC("Invalid Dilithium5 public key".into()))?;
+
+        Ok(Self { ed448_sk, ed448_vk, dil5_pk, dil5_sk })
+    }
+
     /// Serialize the public key: [Ed448 vk (57)] [Dilithium5 pk (2592)].
-    fn public_key_bytes(&self) -> Vec<u8> {
+    pub(crate) fn public_key_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(ED448_PK_LEN + DIL5_PK_LEN);
         bytes.extend_from_slice(&self.ed448_vk.to_bytes());
         bytes.extend_from_slice(self.dil5_pk.as_bytes());
         bytes
     }
 
+    /// Serialize the secret key: [Ed448 sk (57)] [Dilithium5 sk].
+    ///
+    /// Only ever written to storage sealed by the key store.
+    pub(crate) fn secret_key_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ED448_SK_LEN + dilithium5::secret_key_bytes());
+        bytes.extend_from_slice(self.ed448_sk.to_bytes().as_slice());
+        bytes.extend_from_slice(self.dil5_sk.as_bytes());
+        bytes
+    }
+
     /// Sign data with both Ed448 and Dilithium5.
     /// Returns concatenated signature: [Ed448 sig (114)] [Dilithium5 sig (4627)].
     fn sign(&self, data: &[u8]) -> Vec<u8> {
@@ -114,72 +205,681 @@ fn hybrid_verify(data: &[u8], signature: &[u8], public_key: &[u8]) -> Result<boo
     Ok(true)
 }
 
+/// DNSSEC signing keypair for one algorithm.
+// The expanded Ed448 key outweighs the others, but key pairs are few and
+// long-lived; only the multi-kilobyte hybrid key is worth a box.
+#[allow(clippy::large_enum_variant)]
+pub enum DNSSECKeyPair {
+    /// ECDSA P-256 with SHA-256 (13)
+    EcdsaP256(p256::ecdsa::SigningKey),
+    /// Ed25519 (15)
+    Ed25519(ed25519_dalek::SigningKey),
+    /// Ed448 (16)
+    Ed448(SigningKey),
+    /// Hybrid Ed448 + Dilithium5 (253), boxed for its Dilithium5 keys
+    Hybrid(Box<HybridDNSSECKey>),
+}
+
+impl DNSSECKeyPair {
+    /// Generate a new keypair for `algorithm`.
+    pub(crate) fn generate(algorithm: DNSSECAlgorithm) -> Self {
+        match algorithm {
+            DNSSECAlgorithm::EcdsaP256Sha256 => {
+                Self::EcdsaP256(p256::ecdsa::SigningKey::random(&mut OsRng))
+            }
+            DNSSECAlgorithm::Ed25519 => {
+                Self::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng))
+            }
+            DNSSECAlgorithm::Ed448 => Self::Ed448(SigningKey::generate(&mut OsRng)),
+            DNSSECAlgorithm::HybridEd448Dilithium5 => Self::Hybrid(Box::new(HybridDNSSECKey::generate())),
+        }
+    }
+
+    /// Rebuild a keypair from its secret and public serializations.
+    pub(crate) fn from_bytes(
+        algorithm: DNSSECAlgorithm,
+        secret: &[u8],
+        public: &[u8],
+    ) -> Result<Self> {
+        let invalid = || AppError::DNSSEC(format!("Invalid {:?} secret key", algorithm));
+        let pair = match algorithm {
+            DNSSECAlgorithm::EcdsaP256Sha256 => Self::EcdsaP256(
+                p256::ecdsa::SigningKey::from_slice(secret).map_err(|_| invalid())?,
+            ),
+            DNSSECAlgorithm::Ed25519 => {
+                let bytes: [u8; 32] = secret.try_into().map_err(|_| invalid())?;
+                Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(&bytes))
+            }
+            DNSSECAlgorithm::Ed448 => {
+                Self::Ed448(SigningKey::try_from(secret).map_err(|_| invalid())?)
+            }
+            DNSSECAlgorithm::HybridEd448Dilithium5 => {
+                let key = HybridDNSSECKey::from_bytes(secret, public)?;
+                return Ok(Self::Hybrid(Box::new(key)));
+            }
+        };
+
+        if pair.public_key_bytes() != public {
+            return Err(AppError::DNSSEC(format!(
+                "{:?} secret key does not match public key",
+                algorithm
+            )));
+        }
+        Ok(pair)
+    }
+
+    /// DNSSEC algorithm of this keypair.
+    pub fn algorithm(&self) -> DNSSECAlgorithm {
+        match self {
+            Self::EcdsaP256(_) => DNSSECAlgorithm::EcdsaP256Sha256,
+            Self::Ed25519(_) => DNSSECAlgorithm::Ed25519,
+            Self::Ed448(_) => DNSSECAlgorithm::Ed448,
+            Self::Hybrid(_) => DNSSECAlgorithm::HybridEd448Dilithium5,
+        }
+    }
+
+    /// DNSKEY public key field: P-256 `x || y` (RFC 6605 §4), raw EdDSA keys
+    /// (RFC 8080 §3), or the hybrid concatenation.
+    pub(crate) fn public_key_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::EcdsaP256(sk) => {
+                // Uncompressed SEC1 point without the 0x04 prefix
+                sk.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec()
+            }
+            Self::Ed25519(sk) => sk.verifying_key().to_bytes().to_vec(),
+            Self::Ed448(sk) => sk.verifying_key().to_bytes()[..].to_vec(),
+            Self::Hybrid(key) => key.public_key_bytes(),
+        }
+    }
+
+    /// Serialize the secret key. Only ever written to storage sealed by the key store.
+    pub(crate) fn secret_key_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::EcdsaP256(sk) => sk.to_bytes().to_vec(),
+            Self::Ed25519(sk) => sk.to_bytes().to_vec(),
+            Self::Ed448(sk) => sk.to_bytes()[..].to_vec(),
+            Self::Hybrid(key) => key.secret_key_bytes(),
+        }
+    }
+
+    /// Sign data, returning the RRSIG signature field for this algorithm.
+    ///
+    /// ECDSA signatures are the fixed-size `r || s` form (RFC 6605 §4).
+    pub(crate) fn sign(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::EcdsaP256(sk) => {
+                use p256::ecdsa::signature::Signer;
+                let signature: p256::ecdsa::Signature = sk.sign(data);
+                signature.to_bytes().to_vec()
+            }
+            Self::Ed25519(sk) => ed25519_dalek::Signer::sign(sk, data).to_bytes().to_vec(),
+            Self::Ed448(sk) => sk.sign_raw(data).to_bytes()[..].to_vec(),
+            Self::Hybrid(key) => key.sign(data),
+        }
+    }
+}
+
+/// Verify a DNSSEC signature made with any supported algorithm.
+pub fn verify_with_algorithm(
+    algorithm: DNSSECAlgorithm,
+    data: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+) -> Result<bool> {
+    let bad_key = || AppError::DNSSEC(format!("Invalid {:?} public key", algorithm));
+    let bad_sig = || AppError::DNSSEC(format!("Invalid {:?} signature", algorithm));
+
+    match algorithm {
+        DNSSECAlgorithm::EcdsaP256Sha256 => {
+            use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+            let mut sec1 = Vec::with_capacity(1 + public_key.len());
+            sec1.push(0x04);
+            sec1.extend_from_slice(public_key);
+            let vk = VerifyingKey::from_sec1_bytes(&sec1).map_err(|_| bad_key())?;
+            let sig = Signature::from_slice(signature).map_err(|_| bad_sig())?;
+            Ok(vk.verify(data, &sig).is_ok())
+        }
+        DNSSECAlgorithm::Ed25519 => {
+            use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+            let pk: [u8; 32] = public_key.try_into().map_err(|_| bad_key())?;
+            let vk = VerifyingKey::from_bytes(&pk).map_err(|_| bad_key())?;
+            let sig: [u8; 64] = signature.try_into().map_err(|_| bad_sig())?;
+            Ok(vk.verify(data, &Signature::from_bytes(&sig)).is_ok())
+        }
+        DNSSECAlgorithm::Ed448 => {
+            let pk: [u8; ED448_PK_LEN] = public_key.try_into().map_err(|_| bad_key())?;
+            let vk = VerifyingKey::from_bytes(&pk).map_err(|_| bad_key())?;
+            let sig: [u8; ED448_SIG_LEN] = signature.try_into().map_err(|_| bad_sig())?;
+            let sig = ed448_goldilocks_plus::Signature::from_bytes(&sig).map_err(|_| bad_sig())?;
+            Ok(vk.verify_raw(&sig, data).is_ok())
+        }
+        DNSSECAlgorithm::HybridEd448Dilithium5 => hybrid_verify(data, signature, public_key),
+    }
+}
+
+/// One published or active key of a zone.
+struct ZoneKey {
+    role: DNSSECKeyRole,
+    state: DNSSECKeyState,
+    key_tag: u16,
+    pair: DNSSECKeyPair,
+}
+
+/// Signing keys held for a zone: a KSK/ZSK pair per algorithm, plus any
+/// keys pre-published by a rollover in progress.
+pub struct ZoneKeys {
+    keys: Vec<ZoneKey>,
+}
+
+impl ZoneKeys {
+    /// Active keys of one role, ordered by algorithm number.
+    fn signing(&self, role: DNSSECKeyRole) -> Vec<&ZoneKey> {
+        let mut keys: Vec<&ZoneKey> = self
+            .keys
+            .iter()
+            .filter(|k| k.role == role && k.state == DNSSECKeyState::Active)
+            .collect();
+        keys.sort_by_key(|k| k.pair.algorithm().number());
+        keys
+    }
+}
+
+/// Public material for one algorithm's newly generated KSK/ZSK pair.
+#[derive(Debug, Clone)]
+pub struct PublishedKeys {
+    /// Signing algorithm
+    pub algorithm: DNSSECAlgorithm,
+    /// KSK public key (base64)
+    pub ksk: String,
+    /// ZSK public key (base64)
+    pub zsk: String,
+    /// DS record for the KSK
+    pub ds_record: String,
+}
+
 /// DNSSEC key manager — Hybrid Ed448 + Dilithium5 (CPR-005).
+///
+/// Holds the signing keys of every zone it generated or loaded keys for, so
+/// that RRsets can be signed after `generate_keys` returns. When built with
+/// [`DNSSECManager::unlock`] the secret halves are also sealed into the
+/// persistent key store.
 pub struct DNSSECManager {
-    // Configuration placeholder
+    keys: RwLock<HashMap<String, Arc<ZoneKeys>>>,
+    store: Option<KeyStore>,
 }
 
 impl DNSSECManager {
-    /// Create a new DNSSEC manager.
+    /// Create a new DNSSEC manager without a key store (keys live in memory only).
     pub fn new() -> Self {
-        Self {}
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            store: None,
+        }
     }
 
-    /// Generate DNSSEC keys for a zone.
+    /// Unlock the persistent key store and load every published or active zone key.
+    pub async fn unlock(db: Database, passphrase: &str) -> Result<Self> {
+        let store = KeyStore::unlock(db, passphrase).await?;
+
+        let mut zones: HashMap<String, Vec<ZoneKey>> = HashMap::new();
+        for (key, pair) in store.signing_keys(None).await? {
+            zones.entry(key.zone).or_default().push(ZoneKey {
+                role: key.role,
+                state: key.state,
+                key_tag: key.key_tag as u16,
+                pair,
+            });
+        }
+
+        let mut keys = HashMap::new();
+        for (zone, zone_keys) in zones {
+            let zone_keys = ZoneKeys { keys: zone_keys };
+            let ksks = zone_keys.signing(DNSSECKeyRole::KSK);
+            for zsk in zone_keys.signing(DNSSECKeyRole::ZSK) {
+                let algorithm = zsk.pair.algorithm();
+                if !ksks.iter().any(|k| k.pair.algorithm() == algorithm) {
+                    warn!("Zone {} has an active {:?} ZSK but no matching KSK", zone, algorithm);
+                }
+            }
+            keys.insert(zone, Arc::new(zone_keys));
+        }
+        info!("Unlocked DNSSEC key store ({} zones)", keys.len());
+
+        Ok(Self {
+            keys: RwLock::new(keys),
+            store: Some(store),
+        })
+    }
+
+    /// Whether generated keys are persisted to an unlocked key store.
+    pub fn is_unlocked(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Generate hybrid DNSSEC keys for a zone and keep them in memory for signing.
     ///
     /// Returns (KSK public key base64, ZSK public key base64, DS record).
     pub fn generate_keys(&self, zone: &str) -> Result<(String, String, String)> {
+        let hybrid = [DNSSECAlgorithm::HybridEd448Dilithium5];
+        let published = self.generate_zone_keys(zone, &hybrid, None)?;
+        let PublishedKeys { ksk, zsk, ds_record, .. } = published
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::Internal("No keys generated".to_string()))?;
+        Ok((ksk, zsk, ds_record))
+    }
+
+    /// Generate a KSK/ZSK pair per algorithm for a zone and keep them in memory for signing.
+    ///
+    /// `digest` overrides each algorithm's default DS digest type.
+    pub fn generate_zone_keys(
+        &self,
+        zone: &str,
+        algorithms: &[DNSSECAlgorithm],
+        digest: Option<DSDigestType>,
+    ) -> Result<Vec<PublishedKeys>> {
+        let (keys, published) = self.new_zone_keys(zone, algorithms, digest)?;
+        self.register(zone, keys)?;
+        Ok(published)
+    }
+
+    /// Generate a KSK/ZSK pair per algorithm, seal them into the key store and
+    /// load them for signing. Every other key of the zone is removed at once,
+    /// so validators caching the old DNSKEY or DS break; prefer a staged rollover.
+    pub async fn provision_keys(
+        &self,
+        zone: &str,
+        algorithms: &[DNSSECAlgorithm],
+        digest: Option<DSDigestType>,
+    ) -> Result<Vec<PublishedKeys>> {
+        let store = self.store()?;
+        let zone = normalize_name(zone);
+        let (keys, published) = self.new_zone_keys(&zone, algorithms, digest)?;
+
+        let mut stored = Vec::with_capacity(keys.keys.len());
+        for key in &keys.keys {
+            let ds_record = match key.role {
+                DNSSECKeyRole::KSK => published
+                    .iter()
+                    .find(|p| p.algorithm == key.pair.algorithm())
+                    .map(|p| p.ds_record.clone()),
+                DNSSECKeyRole::ZSK => None,
+            };
+            let record = store
+                .store_key(&zone, key.role, &key.pair, key.key_tag, key.state, ds_record)
+                .await?;
+            stored.push(record.key_id.to_string());
+        }
+        store.remove_zone_keys(&zone, &stored).await?;
+
+        self.register(&zone, keys)?;
+        Ok(published)
+    }
+
+    /// Generate one key of `role` per algorithm and seal it into the key store
+    /// as published, ahead of a staged rollover. The zone's current keys are untouched.
+    pub async fn stage_keys(
+        &self,
+        zone: &str,
+        role: DNSSECKeyRole,
+        algorithms: &[DNSSECAlgorithm],
+        digest: Option<DSDigestType>,
+    ) -> Result<Vec<DNSSECKey>> {
+        let store = self.store()?;
+        let zone = normalize_name(zone);
+
+        let mut staged = Vec::with_capacity(algorithms.len());
+        for &algorithm in algorithms {
+            let (key, ds_record) = self.new_key(&zone, role, algorithm, digest)?;
+            let state = DNSSECKeyState::Published;
+            let record = store
+                .store_key(&zone, role, &key.pair, key.key_tag, state, ds_record)
+                .await?;
+            staged.push(record);
+        }
+
+        self.reload_zone(&zone).await?;
+        Ok(staged)
+    }
+
+    /// Remove every key of a zone, in memory and in the key store, once DNSSEC
+    /// has been disabled.
+    pub async fn remove_keys(&self, zone: &str) -> Result<()> {
+        let zone = normalize_name(zone);
+        if let Some(store) = &self.store {
+            store.remove_zone_keys(&zone, &[]).await?;
+        }
+        self.keys
+            .write()
+            .map_err(|_| AppError::Internal("DNSSEC key registry poisoned".to_string()))?
+            .remove(&zone);
+        info!("Removed DNSSEC keys for {}", zone);
+        Ok(())
+    }
+
+    /// Reload a zone's published and active keys from the key store after
+    /// their lifecycle states changed.
+    pub async fn reload_zone(&self, zone: &str) -> Result<()> {
+        let zone = normalize_name(zone);
+        let keys = self
+            .store()?
+            .signing_keys(Some(&zone))
+            .await?
+            .into_iter()
+            .map(|(key, pair)| ZoneKey {
+                role: key.role,
+                state: key.state,
+                key_tag: key.key_tag as u16,
+                pair,
+            })
+            .collect();
+        self.register(&zone, ZoneKeys { keys })
+    }
+
+    /// Generate a TSIG secret of the algorithm's output size and seal it into
+    /// the key store under `name`, left for its owner to claim if `unclaimed`.
+    /// Returns the stored key and the secret.
+    pub async fn create_tsig_key(
+        &self,
+        name: &str,
+        algorithm: TSIGAlgorithm,
+        owner: &str,
+        unclaimed: bool,
+    ) -> Result<(TSIGKey, Vec<u8>)> {
+        let store = self.store()?;
+        let mut secret = vec![0u8; algorithm.mac_len()];
+        OsRng.fill_bytes(&mut secret);
+        let key = store
+            .store_tsig_key(name, algorithm, owner, &secret, unclaimed)
+            .await?;
+        Ok((key, secret))
+    }
+
+    /// Unseal the shared secret of a TSIG key.
+    pub fn tsig_secret(&self, key: &TSIGKey) -> Result<Vec<u8>> {
+        self.store()?.tsig_secret(key)
+    }
+
+    /// The unlocked key store.
+    fn store(&self) -> Result<&KeyStore> {
+        self.store.as_ref().ok_or_else(|| {
+            AppError::DNSSEC(
+                "DNSSEC key store is locked; set DNSSEC_KEYSTORE_PASSPHRASE and restart".into(),
+            )
+        })
+    }
+
+    /// Generate fresh KSK/ZSK pairs and their published material.
+    fn new_zone_keys(
+        &self,
+        zone: &str,
+        algorithms: &[DNSSECAlgorithm],
+        digest: Option<DSDigestType>,
+    ) -> Result<(ZoneKeys, Vec<PublishedKeys>)> {
+        let mut algorithms = algorithms.to_vec();
+        algorithms.sort();
+        algorithms.dedup();
+        if algorithms.is_empty() {
+            return Err(AppError::DNSSEC("At least one DNSSEC algorithm is required".into()));
+        }
+
         let b64 = &base64::engine::general_purpose::STANDARD;
+        let mut keys = Vec::with_capacity(algorithms.len() * 2);
+        let mut published = Vec::with_capacity(algorithms.len());
 
-        // Generate KSK (Key Signing Key) — hybrid Ed448 + Dilithium5
-        let ksk = HybridDNSSECKey::generate();
-        let ksk_public = b64.encode(ksk.public_key_bytes());
+        for algorithm in algorithms {
+            // Generate KSK (Key Signing Key) and ZSK (Zone Signing Key)
+            let (ksk, ds_record) = self.new_key(zone, DNSSECKeyRole::KSK, algorithm, digest)?;
+            let (zsk, _) = self.new_key(zone, DNSSECKeyRole::ZSK, algorithm, digest)?;
 
-        // Generate ZSK (Zone Signing Key) — hybrid Ed448 + Dilithium5
-        let zsk = HybridDNSSECKey::generate();
-        let zsk_public = b64.encode(zsk.public_key_bytes());
+            published.push(PublishedKeys {
+                algorithm,
+                ksk: b64.encode(ksk.pair.public_key_bytes()),
+                zsk: b64.encode(zsk.pair.public_key_bytes()),
+                ds_record: ds_record.unwrap_or_default(),
+            });
+            keys.push(ksk);
+            keys.push(zsk);
+        }
+
+        Ok((ZoneKeys { keys }, published))
+    }
+
+    /// Generate one active key, plus its DS record when it is a KSK.
+    fn new_key(
+        &self,
+        zone: &str,
+        role: DNSSECKeyRole,
+        algorithm: DNSSECAlgorithm,
+        digest: Option<DSDigestType>,
+    ) -> Result<(ZoneKey, Option<String>)> {
+        let pair = DNSSECKeyPair::generate(algorithm);
+
+        // DNSKEY RDATA in wire format, from which the key tag and DS are derived
+        let flags = match role {
+            DNSSECKeyRole::KSK => DNSKEY_FLAGS_KSK,
+            DNSSECKeyRole::ZSK => DNSKEY_FLAGS_ZSK,
+        };
+        let rdata = dnskey_rdata(flags, algorithm.number(), &pair.public_key_bytes());
+        let key_tag = self.calculate_key_tag(&rdata);
 
         // Generate DS record (Delegation Signer for parent zone)
-        // Algorithm 253 = private-use hybrid Ed448+Dilithium5
-        let ds_record = self.generate_ds_record(zone, &ksk_public)?;
+        let ds_record = match role {
+            DNSSECKeyRole::KSK => {
+                let digest = digest.unwrap_or_else(|| algorithm.default_digest());
+                Some(self.generate_ds_record(zone, &rdata, digest)?)
+            }
+            DNSSECKeyRole::ZSK => None,
+        };
 
-        Ok((ksk_public, zsk_public, ds_record))
+        let key = ZoneKey { role, state: DNSSECKeyState::Active, key_tag, pair };
+        Ok((key, ds_record))
     }
 
-    /// Generate DS record for parent zone.
-    fn generate_ds_record(&self, zone: &str, ksk_public: &str) -> Result<String> {
-        // DNSKEY record format: <zone> IN DNSKEY <flags> <protocol> <algorithm> <public key>
-        // Flags: 257 for KSK (bit 0 = Zone Key, bit 15 = Secure Entry Point)
-        // Protocol: always 3
-        // Algorithm: 253 for hybrid Ed448+Dilithium5
-        let dnskey = format!(
-            "{} IN DNSKEY 257 3 {} {}",
-            zone, ALGORITHM_HYBRID_ED448_DIL5, ksk_public
-        );
+    /// Make a zone's keys available for signing, replacing any previous ones.
+    fn register(&self, zone: &str, keys: ZoneKeys) -> Result<()> {
+        self.keys
+            .write()
+            .map_err(|_| AppError::Internal("DNSSEC key registry poisoned".to_string()))?
+            .insert(normalize_name(zone), Arc::new(keys));
+        Ok(())
+    }
+
+    /// Whether signing keys are loaded for a zone.
+    pub fn has_keys(&self, zone: &str) -> bool {
+        self.keys
+            .read()
+            .map(|keys| keys.contains_key(&normalize_name(zone)))
+            .unwrap_or(false)
+    }
+
+    /// Get the signing keys for a zone.
+    fn zone_keys(&self, zone: &str) -> Result<Arc<ZoneKeys>> {
+        self.keys
+            .read()
+            .map_err(|_| AppError::Internal("DNSSEC key registry poisoned".to_string()))?
+            .get(&normalize_name(zone))
+            .cloned()
+            .ok_or_else(|| AppError::DNSSEC(format!("No signing key loaded for zone {}", zone)))
+    }
+
+    /// Sign one RRset with each of the zone's ZSKs (RFC 4034 §3.1.8.1).
+    ///
+    /// All records must share owner name and type. Returns one RRSIG RDATA per
+    /// algorithm, newline-separated, in presentation form:
+    /// `<type> <alg> <labels> <orig TTL> <expiration> <inception> <key tag> <signer> <signature>`.
+    pub fn sign_rrset(&self, zone: &str, rrset: &[DNSRecord], now: DateTime<Utc>) -> Result<String> {
+        let first = rrset
+            .first()
+            .ok_or_else(|| AppError::DNSSEC("Cannot sign an empty RRset".to_string()))?;
+        if rrset
+            .iter()
+            .any(|r| r.name != first.name || r.record_type != first.record_type)
+        {
+            return Err(AppError::DNSSEC(format!(
+                "RRset for {} {} mixes owners or types",
+                first.name, first.record_type
+            )));
+        }
+
+        let records = rrset.iter().map(to_wire_record).collect::<Result<Vec<_>>>()?;
+        self.sign_records(zone, &records, now)
+    }
+
+    /// Sign an RRset already in wire form (e.g. NSEC/NSEC3) with each of the zone's ZSKs.
+    ///
+    /// Same output as [`DNSSECManager::sign_rrset`]; the caller guarantees the
+    /// records share owner name and type.
+    pub fn sign_records(
+        &self,
+        zone: &str,
+        records: &[Record],
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let keys = self.zone_keys(zone)?;
+        let zsks = keys.signing(DNSSECKeyRole::ZSK);
+        if zsks.is_empty() {
+            return Err(AppError::DNSSEC(format!("No ZSK loaded for zone {}", zone)));
+        }
+        self.sign_with(zone, &zsks, records, now)
+    }
+
+    /// Sign the apex DNSKEY RRset with each of the zone's active KSKs.
+    ///
+    /// `dnskeys` is the published key set (see [`DNSSECKey::in_dnskey_rrset`]).
+    pub fn sign_dnskeys(
+        &self,
+        zone: &str,
+        dnskeys: &[DNSSECKey],
+        ttl: u32,
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let records = dnskeys
+            .iter()
+            .map(|key| dnskey_record(zone, key, ttl))
+            .collect::<Result<Vec<_>>>()?;
+        self.sign_key_rrset(zone, &records, now)
+    }
+
+    /// Sign an apex key RRset (DNSKEY, CDS or CDNSKEY) with each of the zone's active KSKs.
+    pub fn sign_key_rrset(
+        &self,
+        zone: &str,
+        records: &[Record],
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let keys = self.zone_keys(zone)?;
+        let ksks = keys.signing(DNSSECKeyRole::KSK);
+        if ksks.is_empty() {
+            return Err(AppError::DNSSEC(format!("No KSK loaded for zone {}", zone)));
+        }
+        self.sign_with(zone, &ksks, records, now)
+    }
+
+    /// One RRSIG line per signing key over an RRset sharing owner and type.
+    fn sign_with(
+        &self,
+        zone: &str,
+        signing_keys: &[&ZoneKey],
+        records: &[Record],
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let first = records
+            .first()
+            .ok_or_else(|| AppError::DNSSEC("Cannot sign an empty RRset".to_string()))?;
 
-        // BLAKE3 digest per CPR-009 (replacing SHA-256)
-        let digest_value = blake3::hash(dnskey.as_bytes());
-        let digest_hex = hex::encode(digest_value.as_bytes());
+        let owner = first.name().clone();
+        let signer = Name::from_str(&format!("{}.", normalize_name(zone)))
+            .map_err(|e| AppError::DNSSEC(format!("Invalid signer name {}: {}", zone, e)))?;
+        let type_covered = first.record_type();
+        let labels = owner.num_labels();
+        let original_ttl = first.ttl();
+        let inception = now - Duration::hours(INCEPTION_SKEW_HOURS);
+        let expiration = now + Duration::days(SIGNATURE_VALIDITY_DAYS);
 
-        let key_tag = self.calculate_key_tag(&dnskey);
+        let mut rrsigs = Vec::new();
+        for key in signing_keys {
+            let algorithm = key.pair.algorithm().number();
+            let tbs = rrset_tbs(
+                &owner,
+                DNSClass::IN,
+                labels,
+                type_covered,
+                Algorithm::from_u8(algorithm),
+                original_ttl,
+                expiration.timestamp() as u32,
+                inception.timestamp() as u32,
+                key.key_tag,
+                &signer,
+                records,
+            )
+            .map_err(|e| AppError::DNSSEC(format!("Failed to build signing data: {}", e)))?;
+
+            let signature = key.pair.sign(tbs.as_ref());
+
+            rrsigs.push(format!(
+                "{} {} {} {} {} {} {} {} {}",
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration.format(SIG_TIME_FORMAT),
+                inception.format(SIG_TIME_FORMAT),
+                key.key_tag,
+                signer,
+                base64::engine::general_purpose::STANDARD.encode(signature)
+            ));
+        }
+
+        Ok(rrsigs.join("\n"))
+    }
+
+    /// Generate DS record for parent zone (RFC 4034 §5.1.4).
+    ///
+    /// The digest covers the canonical owner name followed by the KSK's DNSKEY RDATA.
+    fn generate_ds_record(
+        &self,
+        zone: &str,
+        ksk_rdata: &[u8],
+        digest_type: DSDigestType,
+    ) -> Result<String> {
+        let mut digest_input = canonical_name_wire(zone)?;
+        digest_input.extend_from_slice(ksk_rdata);
+
+        let digest = match digest_type {
+            DSDigestType::Sha256 => Sha256::digest(&digest_input).to_vec(),
+            DSDigestType::Sha384 => Sha384::digest(&digest_input).to_vec(),
+            // BLAKE3 digest per CPR-009, private-use digest type 253
+            DSDigestType::Blake3 => blake3::hash(&digest_input).as_bytes().to_vec(),
+        };
+
+        let key_tag = self.calculate_key_tag(ksk_rdata);
 
         // DS record: <zone> IN DS <key tag> <algorithm> <digest type> <digest>
-        // Digest type 253 = private-use BLAKE3 (matching algorithm private-use range)
         let ds_record = format!(
-            "{} IN DS {} {} 253 {}",
-            zone, key_tag, ALGORITHM_HYBRID_ED448_DIL5, digest_hex
+            "{} IN DS {} {} {} {}",
+            zone,
+            key_tag,
+            ksk_rdata[3],
+            digest_type.number(),
+            hex::encode(digest)
         );
 
         Ok(ds_record)
     }
 
-    /// Calculate DNSSEC key tag per RFC 4034 §B.1 (simplified).
+    /// Calculate DNSSEC key tag over DNSKEY RDATA (RFC 4034 Appendix B).
     ///
-    /// Uses BLAKE3 for internal hashing (CPR-009).
-    fn calculate_key_tag(&self, dnskey: &str) -> u16 {
-        let hash = blake3::hash(dnskey.as_bytes());
-        let bytes = hash.as_bytes();
-        u16::from_be_bytes([bytes[0], bytes[1]])
+    /// Not valid for algorithm 1 (RSA/MD5), which this manager never issues.
+    fn calculate_key_tag(&self, dnskey_rdata: &[u8]) -> u16 {
+        let mut ac: u32 = 0;
+        for (i, byte) in dnskey_rdata.iter().enumerate() {
+            ac += if i & 1 == 0 { (*byte as u32) << 8 } else { *byte as u32 };
+        }
+        ac += (ac >> 16) & 0xFFFF;
+        (ac & 0xFFFF) as u16
     }
 
     /// Sign a DNS record with hybrid Ed448 + Dilithium5.
@@ -208,6 +908,71 @@ pub fn verify_signature(
     }
 }
 
+/// Build DNSKEY RDATA in wire format: flags, protocol, algorithm, public key (RFC 4034 §2.1).
+pub fn dnskey_rdata(flags: u16, algorithm: u8, public_key: &[u8]) -> Vec<u8> {
+    let mut rdata = Vec::with_capacity(4 + public_key.len());
+    rdata.extend_from_slice(&flags.to_be_bytes());
+    rdata.push(DNSKEY_PROTOCOL);
+    rdata.push(algorithm);
+    rdata.extend_from_slice(public_key);
+    rdata
+}
+
+/// Wire-format DNSKEY record for a stored key at the zone apex.
+pub fn dnskey_record(zone: &str, key: &DNSSECKey, ttl: u32) -> Result<Record> {
+    let owner = Name::from_str(&format!("{}.", normalize_name(zone)))
+        .map_err(|e| AppError::DNSSEC(format!("Invalid zone name {}: {}", zone, e)))?;
+    let public_key = base64::engine::general_purpose::STANDARD
+        .decode(&key.public_key)
+        .map_err(|e| AppError::DNSSEC(format!("Invalid public key for {}: {}", *key.key_id, e)))?;
+    let algorithm = u8::try_from(key.algorithm)
+        .map_err(|_| AppError::DNSSEC(format!("Invalid algorithm {}", key.algorithm)))?;
+
+    let dnskey = DNSKEY::new(
+        true,
+        key.role == DNSSECKeyRole::KSK,
+        false,
+        Algorithm::from_u8(algorithm),
+        public_key,
+    );
+    let rdata = RData::DNSSEC(DNSSECRData::DNSKEY(dnskey));
+    Ok(Record::from_rdata(owner, ttl, rdata))
+}
+
+/// Encode a domain name in canonical (lowercase, uncompressed) wire format (RFC 4034 §6.2).
+pub fn canonical_name_wire(name: &str) -> Result<Vec<u8>> {
+    let fqdn = format!("{}.", normalize_name(name));
+    let name = Name::from_str(&fqdn)
+        .map_err(|e| AppError::DNSSEC(format!("Invalid owner name {}: {}", fqdn, e)))?
+        .to_lowercase();
+
+    let mut wire = Vec::with_capacity(fqdn.len() + 1);
+    for label in name.iter() {
+        wire.push(label.len() as u8);
+        wire.extend_from_slice(label);
+    }
+    wire.push(0);
+    Ok(wire)
+}
+
+/// Expiration time of a stored RRSIG, if it parses.
+pub fn rrsig_expiration(rrsig: &str) -> Option<DateTime<Utc>> {
+    let field = rrsig.split_whitespace().nth(4)?;
+    let naive = chrono::NaiveDateTime::parse_from_str(field, SIG_TIME_FORMAT).ok()?;
+    Some(Utc.from_utc_datetime(&naive))
+}
+
+/// Whether stored RRSIGs (one per line) are missing, unparseable or inside the re-sign window.
+pub fn needs_resign(rrsig: Option<&str>, now: DateTime<Utc>) -> bool {
+    let Some(rrsig) = rrsig.filter(|s| !s.trim().is_empty()) else {
+        return true;
+    };
+    rrsig.lines().any(|line| match rrsig_expiration(line) {
+        Some(expiration) => expiration - now < Duration::days(RESIGN_BEFORE_DAYS),
+        None => true,
+    })
+}
+
 impl Default for DNSSECManager {
     fn default() -> Self {
         Self::new()
@@ -232,12 +997,129 @@ fn test_generate_keys() {
         assert!(ds.contains("253")); // algorithm 253
     }
 
+    /// RFC 4509 §2.3 example: dskey.example.com DNSKEY 256 3 5 (key tag 60485).
+    fn rfc4509_dnskey_rdata() -> Vec<u8> {
+        use base64::Engine;
+        let public_key = base64::engine::general_purpose::STANDARD
+            .decode(
+                "AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMm\
+                 mAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9XzcnOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+            )
+            .expect("valid base64");
+        dnskey_rdata(DNSKEY_FLAGS_ZSK, 5, &public_key)
+    }
+
     #[test]
     fn test_key_tag_calculation() {
         let manager = DNSSECManager::new();
-        let dnskey = "example.com IN DNSKEY 257 3 253 YWJjZGVm";
-        let tag = manager.calculate_key_tag(dnskey);
-        assert!(tag > 0);
+        let rdata = rfc4509_dnskey_rdata();
+        assert_eq!(&rdata[..4], &[0x01, 0x00, 0x03, 0x05]);
+        assert_eq!(manager.calculate_key_tag(&rdata), 60485);
+    }
+
+    #[test]
+    fn test_ds_sha256_matches_rfc4509() {
+        let manager = DNSSECManager::new();
+        let ds = manager
+            .generate_ds_record("dskey.example.com", &rfc4509_dnskey_rdata(), DSDigestType::Sha256)
+            .unwrap();
+        assert_eq!(
+            ds,
+            "dskey.example.com IN DS 60485 5 2 \
+             d4b7d520e7bb5f0f67674a0cceb1e3e0614b93c4f9e99b8383f6a1e4469da50a"
+        );
+    }
+
+    #[test]
+    fn test_ds_digest_covers_owner_and_rdata() {
+        assert_eq!(
+            canonical_name_wire("DSKey.Example.COM.").unwrap(),
+            b"\x05dskey\x07example\x03com\x00".to_vec()
+        );
+
+        let manager = DNSSECManager::new();
+        let rdata = dnskey_rdata(DNSKEY_FLAGS_KSK, ALGORITHM_HYBRID_ED448_DIL5, b"key material");
+        let ds = manager
+            .generate_ds_record("example.com", &rdata, DSDigestType::Blake3)
+            .unwrap();
+
+        let mut input = canonical_name_wire("example.com").unwrap();
+        input.extend_from_slice(&rdata);
+        let expected = format!(
+            "example.com IN DS {} 253 253 {}",
+            manager.calculate_key_tag(&rdata),
+            hex::encode(blake3::hash(&input).as_bytes())
+        );
+        assert_eq!(ds, expected);
+
+        let sha384 = manager
+            .generate_ds_record("example.com", &rdata, DSDigestType::Sha384)
+            .unwrap();
+        assert_eq!(sha384.split_whitespace().nth(5), Some("4"));
+        assert_eq!(sha384.split_whitespace().nth(6).map(str::len), Some(96));
+    }
+
+    #[test]
+    fn test_standard_algorithms_sign_and_verify() {
+        for algorithm in [
+            DNSSECAlgorithm::EcdsaP256Sha256,
+            DNSSECAlgorithm::Ed25519,
+            DNSSECAlgorithm::Ed448,
+        ] {
+            let pair = DNSSECKeyPair::generate(algorithm);
+            let public_key = pair.public_key_bytes();
+            let expected_len = match algorithm {
+                DNSSECAlgorithm::EcdsaP256Sha256 => 64,
+                DNSSECAlgorithm::Ed25519 => 32,
+                _ => 57,
+            };
+            assert_eq!(public_key.len(), expected_len, "{:?} public key", algorithm);
+
+            // Secret material survives a round trip through its serialization
+            let restored =
+                DNSSECKeyPair::from_bytes(algorithm, &pair.secret_key_bytes(), &public_key)
+                    .expect("restore keypair");
+            let signature = restored.sign(b"rrset data");
+
+            let verify = |data: &[u8]| {
+                verify_with_algorithm(algorithm, data, &signature, &public_key).unwrap()
+            };
+            assert!(verify(b"rrset data"));
+            assert!(!verify(b"tampered"));
+        }
+    }
+
+    #[test]
+    fn test_parallel_algorithms_sign_each_rrset_twice() {
+        use crate::models::DNSRecordType;
+
+        let manager = DNSSECManager::new();
+        let published = manager
+            .generate_zone_keys(
+                "example.com",
+                &[DNSSECAlgorithm::HybridEd448Dilithium5, DNSSECAlgorithm::Ed25519],
+                None,
+            )
+            .expect("generate_zone_keys should succeed");
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].algorithm, DNSSECAlgorithm::Ed25519);
+        assert!(published[0].ds_record.contains(" 15 2 "));
+        assert!(published[1].ds_record.contains(" 253 253 "));
+
+        let rrset = vec![DNSRecord::new(
+            "example.com".into(),
+            DNSRecordType::A,
+            300,
+            "192.0.2.1".into(),
+        )];
+        let now = Utc::now();
+        let rrsig = manager.sign_rrset("example.com", &rrset, now).unwrap();
+        let algorithms: Vec<&str> = rrsig
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .collect();
+        assert_eq!(algorithms, vec!["15", "253"]);
+        assert!(!needs_resign(Some(&rrsig), now));
     }
 
     #[test]
@@ -269,6 +1151,73 @@ fn test_verify_rejects_wrong_data() {
         assert!(!verified);
     }
 
+    #[test]
+    fn test_sign_rrset() {
+        use crate::models::DNSRecordType;
+
+        let manager = DNSSECManager::new();
+        manager.generate_keys("example.com").expect("generate_keys should succeed");
+
+        let rrset = vec![
+            DNSRecord::new("www.example.com".into(), DNSRecordType::A, 300, "192.0.2.1".into()),
+            DNSRecord::new("www.example.com".into(), DNSRecordType::A, 300, "192.0.2.2".into()),
+        ];
+        let now = Utc::now();
+        let rrsig = manager
+            .sign_rrset("example.com", &rrset, now)
+            .expect("signing should succeed");
+
+        let fields: Vec<&str> = rrsig.split_whitespace().collect();
+        assert_eq!(fields[0], "A");
+        assert_eq!(fields[1], "253");
+        assert_eq!(fields[2], "3");
+        assert_eq!(fields[3], "300");
+        assert_eq!(fields[7], "example.com.");
+        assert!(!needs_resign(Some(&rrsig), now));
+        assert!(needs_resign(Some(&rrsig), now + Duration::days(SIGNATURE_VALIDITY_DAYS)));
+
+        assert!(manager.sign_rrset("other.org", &rrset, now).is_err());
+    }
+
+    #[test]
+    fn test_sign_dnskeys_with_ksk() {
+        use base64::Engine;
+
+        let manager = DNSSECManager::new();
+        let published = manager
+            .generate_zone_keys("example.com", &[DNSSECAlgorithm::Ed25519], None)
+            .expect("generate_zone_keys should succeed");
+
+        let key = |role, public_key: &str| DNSSECKey {
+            key_id: async_graphql::ID("k".into()),
+            zone: "example.com".into(),
+            role,
+            algorithm: 15,
+            key_tag: 0,
+            public_key: public_key.to_string(),
+            state: DNSSECKeyState::Active,
+            created_at: Utc::now(),
+            state_changed_at: None,
+            ds_record: None,
+            ds_submitted_at: None,
+            sealed_secret: String::new(),
+        };
+        let dnskeys = vec![
+            key(DNSSECKeyRole::KSK, &published[0].ksk),
+            key(DNSSECKeyRole::ZSK, &published[0].zsk),
+        ];
+
+        let now = Utc::now();
+        let rrsig = manager.sign_dnskeys("example.com", &dnskeys, 3600, now).unwrap();
+        let fields: Vec<&str> = rrsig.split_whitespace().collect();
+        assert_eq!(fields[0], "DNSKEY");
+
+        // Signed by the KSK, whose tag covers the SEP flag
+        let ksk = base64::engine::general_purpose::STANDARD.decode(&published[0].ksk).unwrap();
+        let ksk_tag = manager.calculate_key_tag(&dnskey_rdata(DNSKEY_FLAGS_KSK, 15, &ksk));
+        assert_eq!(fields[6], ksk_tag.to_string());
+    }
+
     #[test]
     fn test_verify_rejects_wrong_key() {
         let manager = DNSSECManager::new();
@@ -6,8 +6,9 @@
 /// Application error types
 #[derive(Debug, Error)]
 pub enum AppError {
+    /// Boxed: a SurrealDB error is larger than every other variant together
     #[error("Database error: {0}")]
-    Database(#[from] surrealdb::Error),
+    Database(Box<surrealdb::Error>),
 
     #[error("Blockchain error: {0}")]
     Blockchain(String),
@@ -24,6 +25,9 @@ pub enum AppError {
     #[error("Invalid IP address: {0}")]
     InvalidIP(String),
 
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+
     #[error("Zone not found: {0}")]
     ZoneNotFound(String),
 
@@ -31,5 +35,11 @@ pub enum AppError {
     Internal(String),
 }
 
+impl From<surrealdb::Error> for AppError {
+    fn from(e: surrealdb::Error) -> Self {
+        Self::Database(Box::new(e))
+    }
+}
+
 /// Result type alias
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! In-process event bus for committed changes
+//!
+//! Every mutation publishes what it changed once the change is committed:
+//! records written or removed (through GraphQL, dynamic updates or zone file
+//! imports), serial bumps, proposal status changes and confirmed blockchain
+//! anchors. GraphQL subscriptions listen on the bus.
+//!
+//! Delivery is best-effort. An event published with nobody listening is
+//! dropped, and a listener more than [`CAPACITY`] events behind skips the
+//! oldest rather than holding up publishers.
+
+use crate::{
+    models::{BlockchainProvenance, DNSRecord, RecordChange, RecordChangeKind, Zone},
+    policy::MutationProposal,
+};
+use async_graphql::futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// Events buffered per listener before the oldest are skipped.
+pub const CAPACITY: usize = 1024;
+
+/// A committed change.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A record was created, updated or deleted
+    RecordChanged(RecordChange),
+    /// A zone's serial advanced
+    ZoneSerialChanged(Zone),
+    /// A mutation proposal was created or changed status
+    ProposalStatusChanged(MutationProposal),
+    /// A record hash was anchored and the transaction confirmed
+    AnchorConfirmed(BlockchainProvenance),
+}
+
+/// Publishes events to every current listener.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    /// Create a bus with no listeners.
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Publish an event; without listeners it is dropped.
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Publish a change of the same kind for each record.
+    pub fn records(&self, kind: RecordChangeKind, records: impl IntoIterator<Item = DNSRecord>) {
+        for record in records {
+            self.publish(Event::RecordChanged(RecordChange { kind, record }));
+        }
+    }
+
+    /// Every event published from now on, until the bus is dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> {
+        stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Event listener fell behind, skipped {} events", skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::futures_util::StreamExt;
+
+    fn record(value: &str) -> DNSRecord {
+        DNSRecord::new(
+            "www.example.com".to_string(),
+            crate::models::DNSRecordType::A,
+            300,
+            value.to_string(),
+        )
+    }
+
+    fn value(event: Event) -> String {
+        match event {
+            Event::RecordChanged(change) => change.record.value,
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listeners_see_events_published_after_subscribing() {
+        let bus = EventBus::new();
+        bus.records(RecordChangeKind::Created, [record("192.0.2.1")]);
+
+        let mut first = Box::pin(bus.subscribe());
+        let mut second = Box::pin(bus.subscribe());
+        bus.records(RecordChangeKind::Deleted, [record("192.0.2.2")]);
+
+        assert_eq!(value(first.next().await.unwrap()), "192.0.2.2");
+        assert_eq!(value(second.next().await.unwrap()), "192.0.2.2");
+    }
+
+    #[tokio::test]
+    async fn test_lagging_listener_skips_oldest() {
+        let bus = EventBus::new();
+        let mut listener = Box::pin(bus.subscribe());
+        let values: Vec<String> = (0..CAPACITY + 2).map(|i| i.to_string()).collect();
+        bus.records(RecordChangeKind::Updated, values.iter().map(|v| record(v)));
+
+        assert_eq!(value(listener.next().await.unwrap()), "2");
+        drop(bus);
+        assert_eq!(listener.count().await, CAPACITY - 1);
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Record history and the mutation audit log
+//!
+//! Every change to a record's content is appended to `record_history` by the
+//! database itself, in the transaction that made it, so no write path can
+//! skip it: GraphQL mutations, dynamic updates, zone file imports, serial
+//! bumps and zone deletion alike. Each entry holds the record before and
+//! after, the identity the change was made as and when it was committed.
+//! Entries cannot be changed or removed. Re-signing alone is not a change.
+//!
+//! [`zone_at`] replays the history to read a zone as it was at an earlier
+//! time. When the policy sets `log_all_mutations`, [`MutationLog`] also logs
+//! every GraphQL mutation attempt, refused ones included.
+
+use crate::{
+    db::Database,
+    error::Result,
+    models::{DNSRecord, RecordHistoryEntry},
+    policy::PolicyEnforcer,
+    resolvers::MutationRoot,
+};
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    OutputType, ServerResult, Value,
+};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// The records of `zone` as they were at `at`, sorted by name and type.
+pub async fn zone_at(db: &Database, zone: &str, at: DateTime<Utc>) -> Result<Vec<DNSRecord>> {
+    let entries = db.zone_history(zone, at).await?;
+    Ok(replay(zone, entries))
+}
+
+/// The records in `zone` after applying `entries` in order.
+fn replay(zone: &str, entries: Vec<RecordHistoryEntry>) -> Vec<DNSRecord> {
+    let mut latest = HashMap::new();
+    for entry in entries {
+        latest.insert(entry.record_id.to_string(), entry.after);
+    }
+    let mut records: Vec<DNSRecord> = latest
+        .into_values()
+        .flatten()
+        .filter(|record| record.zone.as_deref() == Some(zone))
+        .collect();
+    records.sort_by(|a, b| {
+        (&a.name, a.record_type, &a.value).cmp(&(&b.name, b.record_type, &b.value))
+    });
+    records
+}
+
+/// Logs every GraphQL mutation attempt with its identity and outcome while
+/// the policy sets `log_all_mutations`.
+pub struct MutationLog;
+
+impl ExtensionFactory for MutationLog {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(MutationLogExtension)
+    }
+}
+
+struct MutationLogExtension;
+
+#[async_trait::async_trait]
+impl Extension for MutationLogExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if info.parent_type != MutationRoot::type_name() {
+            return next.run(ctx, info).await;
+        }
+        let logged = match ctx.data_opt::<Arc<RwLock<PolicyEnforcer>>>() {
+            Some(enforcer) => enforcer.read().await.policy.constraints.log_all_mutations,
+            None => false,
+        };
+        let mutation = info.name.to_string();
+        let result = next.run(ctx, info).await;
+        if logged {
+            let identity = ctx.data_opt::<String>().map_or("anonymous", |i| i.as_str());
+            match &result {
+                Ok(_) => info!(target: "audit", "{} by {}: ok", mutation, identity),
+                Err(e) => info!(target: "audit", "{} by {}: {}", mutation, identity, e.message),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DNSRecordType, RecordChangeKind};
+    use async_graphql::ID;
+
+    fn entry(
+        id: &str,
+        version: i32,
+        before: Option<&DNSRecord>,
+        after: Option<&DNSRecord>,
+    ) -> RecordHistoryEntry {
+        let kind = match (before, after) {
+            (None, _) => RecordChangeKind::Created,
+            (_, None) => RecordChangeKind::Deleted,
+            _ => RecordChangeKind::Updated,
+        };
+        RecordHistoryEntry {
+            record_id: ID(id.to_string()),
+            version,
+            kind,
+            actor: Some("identity:alice".to_string()),
+            before: before.cloned(),
+            after: after.cloned(),
+            changed_at: Utc::now(),
+        }
+    }
+
+    fn record(name: &str, value: &str, zone: &str) -> DNSRecord {
+        let mut record = DNSRecord::new(name.to_string(), DNSRecordType::A, 300, value.to_string());
+        record.zone = Some(zone.to_string());
+        record
+    }
+
+    #[test]
+    fn test_replay_keeps_each_records_latest_state() {
+        let www = record("www.example.com", "192.0.2.1", "example.com");
+        let www2 = record("www.example.com", "192.0.2.2", "example.com");
+        let mail = record("mail.example.com", "192.0.2.25", "example.com");
+        let moved = record("mail.example.org", "192.0.2.25", "example.org");
+        let api = record("api.example.com", "192.0.2.3", "example.com");
+
+        let entries = vec![
+            entry("www", 1, None, Some(&www)),
+            entry("mail", 1, None, Some(&mail)),
+            entry("api", 1, None, Some(&api)),
+            entry("www", 2, Some(&www), Some(&www2)),
+            entry("api", 2, Some(&api), None),
+            entry("mail", 2, Some(&mail), Some(&moved)),
+        ];
+        let records = replay("example.com", entries.clone());
+        let values: Vec<&str> = records.iter().map(|r| r.value.as_str()).collect();
+        assert_eq!(values, ["192.0.2.2"]);
+
+        // Stopping before the later changes gives the earlier zone
+        let records = replay("example.com", entries[..3].to_vec());
+        let names: Vec<&str> = records.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["api.example.com", "mail.example.com", "www.example.com"]
+        );
+    }
+}
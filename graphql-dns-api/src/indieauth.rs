@@ -0,0 +1,423 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! IndieAuth bearer tokens
+//!
+//! Clients without a client certificate, such as the WordPress plugin and the
+//! CLI, send the IndieAuth access token they hold as `Authorization: Bearer`.
+//! The token is resolved by introspection (RFC 7662) at the configured
+//! endpoint, which answers with the profile URL (`me`) it was issued for and
+//! its scopes. Results are cached by token hash until the token expires, for
+//! at most [`CACHE_TTL`]; inactive tokens for [`INACTIVE_TTL`].
+//!
+//! The profile URL maps to the same `identity:` form client certificates use,
+//! which the policy and the consent API know callers by: scheme and trailing
+//! slash dropped, host lowercased, so `https://Alice.example/` is
+//! `identity:alice.example`. A token only allows mutations with the
+//! [`WRITE_SCOPE`] scope; queries need none.
+
+use crate::{resolvers::MutationRoot, tls::ClientIdentity};
+use anyhow::{anyhow, Result};
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    OutputType, ServerError, ServerResult, Value,
+};
+use axum::response::{IntoResponse, Response};
+use http::{header, HeaderValue, StatusCode};
+use reqwest::Url;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Environment variable naming the token introspection endpoint.
+pub const ENDPOINT_ENV: &str = "INDIEAUTH_INTROSPECTION_ENDPOINT";
+/// Environment variable holding the bearer credential this server presents to
+/// the introspection endpoint, if it requires one.
+pub const CREDENTIAL_ENV: &str = "INDIEAUTH_INTROSPECTION_TOKEN";
+
+/// Scope a token needs for mutations.
+pub const WRITE_SCOPE: &str = "dns:write";
+
+/// Longest an introspection result is reused.
+pub const CACHE_TTL: Duration = Duration::from_secs(300);
+/// How long a token found inactive stays refused without asking again.
+pub const INACTIVE_TTL: Duration = Duration::from_secs(60);
+/// Cached results kept before expired ones are swept out.
+const CACHE_CAPACITY: usize = 10_000;
+/// Longest wait to connect to the introspection endpoint.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest an introspection request may take, so a slow endpoint cannot hold
+/// requests open.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An active token, as introspected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    /// Identity of the profile URL the token was issued for
+    pub identity: String,
+    /// Profile URL the token was issued for
+    pub me: String,
+    /// Client the token was issued to
+    pub client_id: Option<String>,
+    /// Scopes granted
+    pub scopes: Vec<String>,
+}
+
+/// Scopes of the bearer token a request was authenticated by. Requests
+/// authenticated by client certificate carry none and are not limited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenScopes(pub Vec<String>);
+
+impl TokenScopes {
+    pub fn allows(&self, scope: &str) -> bool {
+        self.0.iter().any(|s| s == scope)
+    }
+}
+
+/// Introspection response (RFC 7662 §2.2, IndieAuth §6.2)
+#[derive(Debug, Deserialize)]
+struct Introspection {
+    active: bool,
+    me: Option<String>,
+    client_id: Option<String>,
+    scope: Option<String>,
+    exp: Option<i64>,
+}
+
+/// Introspection results by BLAKE3 hash of the token, with when they expire
+type TokenCache = HashMap<[u8; 32], (Option<TokenInfo>, Instant)>;
+
+/// Client of the token introspection endpoint
+pub struct IndieAuthClient {
+    endpoint: String,
+    credential: Option<String>,
+    client: reqwest::Client,
+    cache: Mutex<TokenCache>,
+}
+
+impl IndieAuthClient {
+    /// Create a client of `endpoint`, presenting `credential` to it if given
+    pub fn new(endpoint: String, credential: Option<String>) -> Self {
+        Self {
+            endpoint,
+            credential,
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("HTTP client for token introspection"),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a client from the environment; `None` when no endpoint is set
+    pub fn from_env() -> Option<Self> {
+        let endpoint = std::env::var(ENDPOINT_ENV).ok()?;
+        Some(Self::new(endpoint, std::env::var(CREDENTIAL_ENV).ok()))
+    }
+
+    /// Introspect `token`; `None` when it is not active or names no valid
+    /// profile URL
+    pub async fn introspect(&self, token: &str) -> Result<Option<TokenInfo>> {
+        let key = *blake3::hash(token.as_bytes()).as_bytes();
+        if let Some((info, expires)) = self.cache.lock().unwrap().get(&key) {
+            if *expires > Instant::now() {
+                return Ok(info.clone());
+            }
+        }
+
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[("token", token)]);
+        if let Some(credential) = &self.credential {
+            request = request.bearer_auth(credential);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Token introspection error: {}", response.status()));
+        }
+        let introspection: Introspection = response.json().await?;
+
+        let now = chrono::Utc::now().timestamp();
+        let (info, ttl) = match token_info(introspection, now) {
+            Some((info, lifetime)) => (Some(info), lifetime.min(CACHE_TTL)),
+            None => (None, INACTIVE_TTL),
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_CAPACITY {
+            let now = Instant::now();
+            cache.retain(|_, (_, expires)| *expires > now);
+            if cache.len() >= CACHE_CAPACITY {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (info.clone(), Instant::now() + ttl));
+        Ok(info)
+    }
+}
+
+/// The token an introspection response describes and how long it remains
+/// valid, at `now` (Unix seconds); `None` unless it is active.
+fn token_info(introspection: Introspection, now: i64) -> Option<(TokenInfo, Duration)> {
+    if !introspection.active {
+        return None;
+    }
+    let lifetime = match introspection.exp {
+        Some(exp) if exp <= now => return None,
+        Some(exp) => Duration::from_secs((exp - now) as u64),
+        None => CACHE_TTL,
+    };
+    let me = introspection.me?;
+    let info = TokenInfo {
+        identity: profile_identity(&me)?,
+        me,
+        client_id: introspection.client_id,
+        scopes: introspection
+            .scope
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    };
+    Some((info, lifetime))
+}
+
+/// The identity of an IndieAuth profile URL: `https://alice.example/` is
+/// `identity:alice.example`. `None` for anything that is not a profile URL
+/// (IndieAuth §3.2).
+pub fn profile_identity(me: &str) -> Option<String> {
+    let url = Url::parse(me).ok()?;
+    if !matches!(url.scheme(), "http" | "https")
+        || !url.username().is_empty()
+        || url.password().is_some()
+        || url.query().is_some()
+        || url.fragment().is_some()
+        || url.path().split('/').any(|s| s == "." || s == "..")
+    {
+        return None;
+    }
+    let host = url.host_str()?;
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let path = url.path().trim_end_matches('/');
+    Some(format!("identity:{}{}{}", host, port, path))
+}
+
+/// The token of an `Authorization` header value, if it is a bearer token.
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Who a request is made by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// Identity the resolvers check
+    pub identity: String,
+    /// Scopes of the bearer token the caller was identified by
+    pub scopes: Option<TokenScopes>,
+}
+
+impl Caller {
+    /// Add the caller to GraphQL request data.
+    pub fn insert_into(self, data: &mut async_graphql::Data) {
+        data.insert(self.identity);
+        if let Some(scopes) = self.scopes {
+            data.insert(scopes);
+        }
+    }
+}
+
+/// Why a request's credentials were refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// The `Authorization` header is not a bearer token
+    InvalidRequest,
+    /// The token is not active, or bearer tokens are not accepted
+    InvalidToken,
+    /// The introspection endpoint could not be asked
+    Unavailable,
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthError::InvalidRequest => "Authorization is not a bearer token",
+            AuthError::InvalidToken => "Bearer token is not active",
+            AuthError::Unavailable => "Bearer token could not be checked",
+        })
+    }
+}
+
+impl IntoResponse for AuthError {
+    /// 401 with the RFC 6750 §3 challenge, or 503 if the token was not checked
+    fn into_response(self) -> Response {
+        let (status, challenge) = match self {
+            AuthError::InvalidRequest => (StatusCode::UNAUTHORIZED, "invalid_request"),
+            AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid_token"),
+            AuthError::Unavailable => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+        };
+        let challenge = HeaderValue::from_str(&format!("Bearer error=\"{}\"", challenge))
+            .expect("challenge is ASCII");
+        (
+            status,
+            [(header::WWW_AUTHENTICATE, challenge)],
+            self.to_string(),
+        )
+            .into_response()
+    }
+}
+
+/// Identify the caller by client certificate, else by the bearer token of
+/// `authorization` (an `Authorization` header value). No credentials is no
+/// caller; a token that does not resolve is refused.
+pub async fn authenticate(
+    indieauth: Option<&IndieAuthClient>,
+    certificate: Option<ClientIdentity>,
+    authorization: Option<&str>,
+) -> std::result::Result<Option<Caller>, AuthError> {
+    if let Some(ClientIdentity(identity)) = certificate {
+        return Ok(Some(Caller {
+            identity,
+            scopes: None,
+        }));
+    }
+    let Some(authorization) = authorization else {
+        return Ok(None);
+    };
+    let token = bearer_token(authorization).ok_or(AuthError::InvalidRequest)?;
+    let indieauth = indieauth.ok_or(AuthError::InvalidToken)?;
+    match indieauth.introspect(token).await {
+        Ok(Some(info)) => Ok(Some(Caller {
+            identity: info.identity,
+            scopes: Some(TokenScopes(info.scopes)),
+        })),
+        Ok(None) => Err(AuthError::InvalidToken),
+        Err(e) => {
+            tracing::warn!("{}", e);
+            Err(AuthError::Unavailable)
+        }
+    }
+}
+
+/// Refuses mutations to callers identified by a token without [`WRITE_SCOPE`].
+pub struct ScopeGuard;
+
+impl ExtensionFactory for ScopeGuard {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ScopeGuardExtension)
+    }
+}
+
+struct ScopeGuardExtension;
+
+#[async_trait::async_trait]
+impl Extension for ScopeGuardExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if info.parent_type == MutationRoot::type_name() {
+            if let Some(scopes) = ctx.data_opt::<TokenScopes>() {
+                if !scopes.allows(WRITE_SCOPE) {
+                    let message = format!("Token lacks the {} scope", WRITE_SCOPE);
+                    return Err(ServerError::new(message, None));
+                }
+            }
+        }
+        next.run(ctx, info).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn introspection(active: bool, me: &str, exp: Option<i64>) -> Introspection {
+        Introspection {
+            active,
+            me: Some(me.to_string()),
+            client_id: Some("https://wordpress.example/".to_string()),
+            scope: Some("create dns:write".to_string()),
+            exp,
+        }
+    }
+
+    #[test]
+    fn test_profile_identity() {
+        let identity = profile_identity;
+        assert_eq!(
+            identity("https://Alice.example/").as_deref(),
+            Some("identity:alice.example")
+        );
+        assert_eq!(
+            identity("https://alice.example").as_deref(),
+            Some("identity:alice.example")
+        );
+        assert_eq!(
+            identity("https://example.com:8443/~bob/").as_deref(),
+            Some("identity:example.com:8443/~bob")
+        );
+        assert_eq!(identity("mailto:alice@example.com"), None);
+        assert_eq!(identity("https://alice.example/?q=1"), None);
+        assert_eq!(identity("https://mallory@alice.example/"), None);
+        assert_eq!(identity("not a url"), None);
+    }
+
+    #[test]
+    fn test_token_info() {
+        let (info, lifetime) = token_info(
+            introspection(true, "https://alice.example/", Some(1_060)),
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(info.identity, "identity:alice.example");
+        assert_eq!(info.scopes, ["create", "dns:write"]);
+        assert_eq!(lifetime, Duration::from_secs(60));
+
+        assert!(token_info(introspection(false, "https://alice.example/", None), 1_000).is_none());
+        assert!(token_info(
+            introspection(true, "https://alice.example/", Some(900)),
+            1_000
+        )
+        .is_none());
+        assert!(token_info(introspection(true, "ftp://alice.example/", None), 1_000).is_none());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc123"), Some("abc123"));
+        assert_eq!(bearer_token("bearer  abc123 "), Some("abc123"));
+        assert_eq!(bearer_token("Basic YWxpY2U6"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+    }
+
+    #[tokio::test]
+    async fn test_certificate_identity_comes_first() {
+        let certificate = Some(ClientIdentity("identity:alice".to_string()));
+        let caller = authenticate(None, certificate, Some("Bearer abc123")).await;
+        assert_eq!(
+            caller,
+            Ok(Some(Caller {
+                identity: "identity:alice".to_string(),
+                scopes: None,
+            }))
+        );
+
+        assert_eq!(authenticate(None, None, None).await, Ok(None));
+        assert_eq!(
+            authenticate(None, None, Some("Basic YWxpY2U6")).await,
+            Err(AuthError::InvalidRequest)
+        );
+        // Without an introspection endpoint no token is accepted
+        assert_eq!(
+            authenticate(None, None, Some("Bearer abc123")).await,
+            Err(AuthError::InvalidToken)
+        );
+    }
+}
@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+// Copyright (c) 2026 Jonathan D.A. Jewell (hyperpolymath) <jonathan.jewell@open.ac.uk>
+//
+//! Encrypted persistent DNSSEC key store
+//!
+//! Secret halves of zone KSKs/ZSKs are sealed with XChaCha20-Poly1305 (CPR-006)
+//! under a key-encryption key derived from an operator passphrase with Argon2id
+//! (CPR-001), and stored in SurrealDB alongside their public metadata. Shared
+//! TSIG secrets for zone transfers and dynamic updates are sealed the same way.
+//!
+//! Sealed format: base64([24-byte nonce] [ciphertext + 16-byte tag]). The key
+//! ID, zone and role (for TSIG: the key name, algorithm and owner) are bound as
+//! associated data, so a sealed secret cannot be moved to another key record.
+
+use crate::{
+    db::Database,
+    dnssec::DNSSECKeyPair,
+    error::{AppError, Result},
+    models::{
+        DNSSECAlgorithm, DNSSECKey, DNSSECKeyRole, DNSSECKeyState, KeyStoreMeta, TSIGAlgorithm,
+        TSIGKey,
+    },
+};
+use argon2::{Argon2, Params, Version};
+use async_graphql::ID;
+use base64::Engine as _;
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use chrono::Utc;
+use rand::RngCore;
+use uuid::Uuid;
+
+/// Argon2id memory cost (KiB) — CPR-001.
+const ARGON2_M_COST: u32 = 524_288;
+/// Argon2id iterations — CPR-001.
+const ARGON2_T_COST: u32 = 8;
+/// Argon2id lanes — CPR-001.
+const ARGON2_P_COST: u32 = 4;
+/// Argon2id salt size (bytes).
+const SALT_LEN: usize = 16;
+/// KEK size (bytes).
+const KEK_LEN: usize = 32;
+/// XChaCha20-Poly1305 nonce size (bytes).
+const NONCE_LEN: usize = 24;
+/// Plaintext sealed into the store metadata to verify the passphrase.
+const CHECK_PLAINTEXT: &[u8] = b"indieweb2-dnssec-keystore-v1";
+/// Associated data for the check value.
+const CHECK_AAD: &[u8] = b"kek-check";
+
+/// Environment variable holding the key store passphrase.
+pub const PASSPHRASE_ENV: &str = "DNSSEC_KEYSTORE_PASSPHRASE";
+/// Environment variable naming a file that holds the key store passphrase.
+pub const PASSPHRASE_FILE_ENV: &str = "DNSSEC_KEYSTORE_PASSPHRASE_FILE";
+
+/// Read the key store passphrase from the environment, preferring the file.
+///
+/// Returns `None` when neither variable is set.
+pub fn passphrase_from_env() -> Result<Option<String>> {
+    if let Ok(path) = std::env::var(PASSPHRASE_FILE_ENV) {
+        let passphrase = std::fs::read_to_string(&path).map_err(|e| {
+            AppError::Internal(format!("Cannot read {} ({}): {}", PASSPHRASE_FILE_ENV, path, e))
+        })?;
+        return Ok(Some(passphrase.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Ok(std::env::var(PASSPHRASE_ENV).ok())
+}
+
+/// Unlocked key store: holds the KEK in memory for the life of the process.
+pub struct KeyStore {
+    db: Database,
+    cipher: XChaCha20Poly1305,
+}
+
+impl KeyStore {
+    /// Derive the KEK from `passphrase` and verify it against the store.
+    ///
+    /// The first unlock initialises the store with a fresh salt; later unlocks
+    /// fail if the passphrase does not match.
+    pub async fn unlock(db: Database, passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(AppError::DNSSEC("Key store passphrase is empty".into()));
+        }
+
+        match db.get_keystore_meta().await? {
+            Some(meta) => {
+                let salt = decode(&meta.salt)?;
+                let params = (meta.m_cost, meta.t_cost, meta.p_cost);
+                let kek = derive_kek_blocking(passphrase, salt, params).await?;
+                let store = Self::with_kek(db, &kek);
+
+                let check = store
+                    .open(&meta.check, CHECK_AAD)
+                    .map_err(|_| AppError::DNSSEC("Wrong key store passphrase".into()))?;
+                if check != CHECK_PLAINTEXT {
+                    return Err(AppError::DNSSEC("Wrong key store passphrase".into()));
+                }
+                Ok(store)
+            }
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                rand::rngs::OsRng.fill_bytes(&mut salt);
+                let params = (ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST);
+                let kek = derive_kek_blocking(passphrase, salt.clone(), params).await?;
+                let store = Self::with_kek(db, &kek);
+
+                let meta = KeyStoreMeta {
+                    salt: encode(&salt),
+                    m_cost: params.0,
+                    t_cost: params.1,
+                    p_cost: params.2,
+                    check: store.seal(CHECK_PLAINTEXT, CHECK_AAD)?,
+                };
+                store.db.create_keystore_meta(meta).await?;
+                Ok(store)
+            }
+        }
+    }
+
+    fn with_kek(db: Database, kek: &[u8; KEK_LEN]) -> Self {
+        Self {
+            db,
+            cipher: XChaCha20Poly1305::new(GenericArray::from_slice(kek)),
+        }
+    }
+
+    /// Seal and persist a zone key in its initial lifecycle state.
+    pub async fn store_key(
+        &self,
+        zone: &str,
+        role: DNSSECKeyRole,
+        key: &DNSSECKeyPair,
+        key_tag: u16,
+        state: DNSSECKeyState,
+        ds_record: Option<String>,
+    ) -> Result<DNSSECKey> {
+        let key_id = Uuid::new_v4().to_string();
+        let sealed_secret = self.seal(&key.secret_key_bytes(), &key_aad(&key_id, zone, role))?;
+        let now = Utc::now();
+
+        let record = DNSSECKey {
+            key_id: ID(key_id),
+            zone: zone.to_string(),
+            role,
+            algorithm: key.algorithm().number() as i32,
+            key_tag: key_tag as i32,
+            public_key: encode(&key.public_key_bytes()),
+            state,
+            created_at: now,
+            state_changed_at: Some(now),
+            ds_record,
+            ds_submitted_at: None,
+            sealed_secret,
+        };
+        self.db.store_dnssec_key(record).await
+    }
+
+    /// Load and unseal every published or active key, optionally for one zone.
+    ///
+    /// Published keys are loaded too so a rollover can activate them without a restart.
+    pub async fn signing_keys(
+        &self,
+        zone: Option<&str>,
+    ) -> Result<Vec<(DNSSECKey, DNSSECKeyPair)>> {
+        let mut keys = Vec::new();
+        for record in self.db.dnssec_keys(zone).await? {
+            if !matches!(record.state, DNSSECKeyState::Published | DNSSECKeyState::Active) {
+                continue;
+            }
+            let key = self.unseal_key(&record)?;
+            keys.push((record, key));
+        }
+        Ok(keys)
+    }
+
+    /// Remove a zone's keys outright, except those listed in `keep`.
+    pub async fn remove_zone_keys(&self, zone: &str, keep: &[String]) -> Result<()> {
+        self.db.remove_dnssec_keys(zone, keep).await
+    }
+
+    /// Seal and persist a TSIG secret under `name`, owned by `owner`, who has
+    /// yet to claim it if `unclaimed`.
+    pub async fn store_tsig_key(
+        &self,
+        name: &str,
+        algorithm: TSIGAlgorithm,
+        owner: &str,
+        secret: &[u8],
+        unclaimed: bool,
+    ) -> Result<TSIGKey> {
+        let record = TSIGKey {
+            name: name.to_string(),
+            algorithm,
+            owner: owner.to_string(),
+            created_at: Utc::now(),
+            secret_unclaimed: unclaimed,
+            sealed_secret: self.seal(secret, &tsig_aad(name, algorithm, owner))?,
+        };
+        self.db.create_tsig_key(record).await
+    }
+
+    /// Unseal a stored TSIG secret.
+    pub fn tsig_secret(&self, key: &TSIGKey) -> Result<Vec<u8>> {
+        self.open(&key.sealed_secret, &tsig_aad(&key.name, key.algorithm, &key.owner))
+    }
+
+    /// Unseal the secret half of a stored key.
+    fn unseal_key(&self, record: &DNSSECKey) -> Result<DNSSECKeyPair> {
+        let algorithm = u8::try_from(record.algorithm)
+            .ok()
+            .and_then(DNSSECAlgorithm::from_number)
+            .ok_or_else(|| {
+                AppError::DNSSEC(format!(
+                    "Unsupported algorithm {} for key {}",
+                    record.algorithm, *record.key_id
+                ))
+            })?;
+
+        let aad = key_aad(&record.key_id, &record.zone, record.role);
+        let secret = self.open(&record.sealed_secret, &aad)?;
+        DNSSECKeyPair::from_bytes(algorithm, &secret, &decode(&record.public_key)?)
+    }
+
+    /// Encrypt `plaintext` under the KEK with a random nonce.
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(GenericArray::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .map_err(|_| AppError::DNSSEC("Key sealing failed".into()))?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(encode(&sealed))
+    }
+
+    /// Decrypt a value produced by [`KeyStore::seal`].
+    fn open(&self, sealed: &str, aad: &[u8]) -> Result<Vec<u8>> {
+        let sealed = decode(sealed)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(AppError::DNSSEC("Sealed key is truncated".into()));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(GenericArray::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| AppError::DNSSEC("Key unsealing failed: authentication failure".into()))
+    }
+}
+
+/// Associated data binding a sealed secret to its key record.
+fn key_aad(key_id: &str, zone: &str, role: DNSSECKeyRole) -> Vec<u8> {
+    format!("{}|{}|{:?}", key_id, zone, role).into_bytes()
+}
+
+/// Associated data binding a sealed TSIG secret to its key name, algorithm and owner.
+fn tsig_aad(name: &str, algorithm: TSIGAlgorithm, owner: &str) -> Vec<u8> {
+    format!("tsig|{}|{:?}|{}", name, algorithm, owner).into_bytes()
+}
+
+/// Derive the KEK with Argon2id (RFC 9106).
+fn derive_kek(
+    passphrase: &str,
+    salt: &[u8],
+    (m_cost, t_cost, p_cost): (u32, u32, u32),
+) -> Result<[u8; KEK_LEN]> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(KEK_LEN))
+        .map_err(|e| AppError::DNSSEC(format!("Invalid Argon2id parameters: {}", e)))?;
+
+    let mut kek = [0u8; KEK_LEN];
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut kek)
+        .map_err(|e| AppError::DNSSEC(format!("KEK derivation failed: {}", e)))?;
+    Ok(kek)
+}
+
+/// Run [`derive_kek`] off the async runtime (it is deliberately expensive).
+async fn derive_kek_blocking(
+    passphrase: &str,
+    salt: Vec<u8>,
+    params: (u32, u32, u32),
+) -> Result<[u8; KEK_LEN]> {
+    let passphrase = passphrase.to_string();
+    tokio::task::spawn_blocking(move || derive_kek(&passphrase, &salt, params))
+        .await
+        .map_err(|e| AppError::Internal(format!("KEK derivation task failed: {}", e)))?
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| AppError::DNSSEC(format!("Invalid base64 in key store: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap Argon2id parameters so tests stay fast.
+    const TEST_PARAMS: (u32, u32, u32) = (64, 1, 1);
+
+    async fn test_store(passphrase: &str) -> KeyStore {
+        let db = Database::connect("memory").await.expect("in-memory database");
+        let kek = derive_kek(passphrase, b"0123456789abcdef", TEST_PARAMS).expect("derive");
+        KeyStore::with_kek(db, &kek)
+    }
+
+    #[test]
+    fn test_derive_kek_depends_on_passphrase_and_salt() {
+        let a = derive_kek("correct horse", b"salt-one-16bytes", TEST_PARAMS).unwrap();
+        let b = derive_kek("correct horse", b"salt-one-16bytes", TEST_PARAMS).unwrap();
+        let c = derive_kek("battery staple", b"salt-one-16bytes", TEST_PARAMS).unwrap();
+        let d = derive_kek("correct horse", b"salt-two-16bytes", TEST_PARAMS).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+    }
+
+    #[tokio::test]
+    async fn test_seal_open_roundtrip() {
+        let store = test_store("passphrase").await;
+        let sealed = store.seal(b"secret", b"aad").unwrap();
+        assert_eq!(store.open(&sealed, b"aad").unwrap(), b"secret");
+
+        // Wrong associated data or wrong KEK must fail authentication
+        assert!(store.open(&sealed, b"other").is_err());
+        let other = test_store("different").await;
+        assert!(other.open(&sealed, b"aad").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_and_load_key() {
+        let store = test_store("passphrase").await;
+        let key = DNSSECKeyPair::generate(DNSSECAlgorithm::HybridEd448Dilithium5);
+        let stored = store
+            .store_key("example.com", DNSSECKeyRole::ZSK, &key, 4242, DNSSECKeyState::Active, None)
+            .await
+            .unwrap();
+        assert_eq!(stored.state, DNSSECKeyState::Active);
+        assert!(stored.state_changed_at.is_some());
+
+        let loaded = store.signing_keys(None).await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        let (record, restored) = &loaded[0];
+        assert_eq!(record.key_id, stored.key_id);
+        assert_eq!(record.key_tag, 4242);
+        assert_eq!(restored.public_key_bytes(), key.public_key_bytes());
+        assert_eq!(restored.secret_key_bytes(), key.secret_key_bytes());
+
+        assert!(store.signing_keys(Some("example.org")).await.unwrap().is_empty());
+
+        store.remove_zone_keys("example.com", &[]).await.unwrap();
+        assert!(store.signing_keys(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_tsig_key() {
+        let store = test_store("passphrase").await;
+        let key = store
+            .store_tsig_key(
+                "xfr.example.net",
+                TSIGAlgorithm::HmacSha256,
+                "identity:alice",
+                b"shared secret",
+                false,
+            )
+            .await
+            .unwrap();
+        assert_ne!(key.sealed_secret.as_bytes(), b"shared secret");
+        assert_eq!(store.tsig_secret(&key).unwrap(), b"shared secret");
+
+        // The secret is bound to its name and owner
+        let moved = TSIGKey {
+            name: "other.example.net".into(),
+            ..key.clone()
+        };
+        assert!(store.tsig_secret(&moved).is_err());
+        let reassigned = TSIGKey {
+            owner: "identity:mallory".into(),
+            ..key
+        };
+        assert!(store.tsig_secret(&reassigned).is_err());
+
+        // An unclaimed secret is claimed once
+        assert!(!store.db.claim_tsig_secret("xfr.example.net").await.unwrap());
+        let name = "acme.example.net";
+        let secret = b"proposed secret";
+        let key = store
+            .store_tsig_key(name, TSIGAlgorithm::HmacSha256, "identity:alice", secret, true)
+            .await
+            .unwrap();
+        assert!(key.secret_unclaimed);
+        assert!(store.db.claim_tsig_secret(name).await.unwrap());
+        assert!(!store.db.claim_tsig_secret(name).await.unwrap());
+        assert!(!store.db.get_tsig_key(name).await.unwrap().secret_unclaimed);
+    }
+}
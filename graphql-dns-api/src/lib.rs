@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! GraphQL DNS API library for indieweb2-bastion
+//!
+//! Exposes the record store, DNSSEC, policy and zone file modules so that the
+//! server binary, integration tests and tooling share one implementation.
+
+pub mod authority;
+pub mod blockchain;
+pub mod changeset;
+pub mod consent;
+pub mod db;
+pub mod denial;
+pub mod dnssec;
+pub mod error;
+pub mod events;
+pub mod history;
+pub mod indieauth;
+pub mod keystore;
+pub mod lint;
+pub mod models;
+pub mod notify;
+pub mod policy;
+pub mod proposals;
+pub mod rdata;
+pub mod records;
+pub mod resolvers;
+pub mod rollover;
+pub mod schema;
+pub mod signer;
+pub mod tls;
+pub mod update;
+pub mod xfr;
+pub mod zonefile;
+pub mod zones;
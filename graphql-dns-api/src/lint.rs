@@ -0,0 +1,476 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Zone consistency: RRset invariants and lint warnings
+//!
+//! Three invariants hold in every zone and are enforced on write, whether a
+//! change arrives as a GraphQL mutation, a dynamic update or a zone file:
+//!
+//! - a CNAME is the only data at its name, and there is one (RFC 2181 §10.1);
+//! - the zone has one SOA, at its apex (RFC 1035 §5.2);
+//! - the RRs of an RRset share one TTL (RFC 2181 §5.2).
+//!
+//! [`violations`] finds breaches among a set of records and [`check`] refuses
+//! them. [`lint`] adds warnings for problems resolvers tolerate but clients
+//! trip over: targets inside the zone that lead nowhere, MX, NS and SRV
+//! targets that are aliases, delegations missing glue, an apex without CAA
+//! and TLSA records for hosts without addresses. Targets outside the zone are
+//! not looked up.
+
+use crate::{
+    authority::{in_zone, normalize_name},
+    db::Database,
+    error::{AppError, Result},
+    models::{DNSRecord, DNSRecordType, LintCode, LintSeverity, LintWarning},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Address types a host name needs to be reachable.
+const ADDRESS_TYPES: [DNSRecordType; 2] = [DNSRecordType::A, DNSRecordType::AAAA];
+
+/// Records grouped by owner name, then by type.
+type Names<'a> = BTreeMap<&'a str, BTreeMap<DNSRecordType, Vec<&'a DNSRecord>>>;
+
+/// Lint the zone at `zone` ([`lint`]), leaving out the zones below it.
+pub async fn lint_zone(db: &Database, zone: &str) -> Result<Vec<LintWarning>> {
+    let apex = db.get_zone(&normalize_name(zone)).await?.apex;
+    let records = db.zone_records(&apex).await?;
+    Ok(lint(&apex, &records))
+}
+
+/// Breaches of the RRset invariants among `records` of the zone at `apex`.
+///
+/// Only the names present in `records` are considered, so a write is checked
+/// by passing the records at the names it touches, as they would be after it.
+pub fn violations(apex: &str, records: &[DNSRecord]) -> Vec<LintWarning> {
+    let mut found = Vec::new();
+    for (name, rrsets) in names(records) {
+        if let Some(cnames) = rrsets.get(&DNSRecordType::CNAME) {
+            let others: Vec<String> = rrsets
+                .keys()
+                .filter(|t| **t != DNSRecordType::CNAME)
+                .map(|t| t.to_string())
+                .collect();
+            if !others.is_empty() {
+                found.push(finding(
+                    LintSeverity::Error,
+                    LintCode::CnameConflict,
+                    name,
+                    Some(DNSRecordType::CNAME),
+                    format!(
+                        "CNAME at {} cannot share its name with other data ({})",
+                        name,
+                        others.join(", ")
+                    ),
+                ));
+            } else if cnames.len() > 1 {
+                found.push(finding(
+                    LintSeverity::Error,
+                    LintCode::CnameConflict,
+                    name,
+                    Some(DNSRecordType::CNAME),
+                    format!(
+                        "{} has {} CNAME records; an alias has one target",
+                        name,
+                        cnames.len()
+                    ),
+                ));
+            }
+        }
+
+        if let Some(soas) = rrsets.get(&DNSRecordType::SOA) {
+            let message = if name != apex {
+                Some(format!("SOA at {} is not at the zone apex {}", name, apex))
+            } else if soas.len() > 1 {
+                Some(format!(
+                    "{} has {} SOA records; a zone has one",
+                    name,
+                    soas.len()
+                ))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                found.push(finding(
+                    LintSeverity::Error,
+                    LintCode::SoaConflict,
+                    name,
+                    Some(DNSRecordType::SOA),
+                    message,
+                ));
+            }
+        }
+
+        for (record_type, rrs) in &rrsets {
+            let ttls: BTreeSet<i32> = rrs.iter().map(|r| r.ttl).collect();
+            if ttls.len() > 1 {
+                let ttls: Vec<String> = ttls.iter().map(|ttl| ttl.to_string()).collect();
+                found.push(finding(
+                    LintSeverity::Error,
+                    LintCode::TtlMismatch,
+                    name,
+                    Some(*record_type),
+                    format!(
+                        "{} RRset at {} mixes TTLs {}; an RRset has one TTL",
+                        record_type,
+                        name,
+                        ttls.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// Refuse `records` of the zone at `apex` if they breach an RRset invariant.
+pub fn check(apex: &str, records: &[DNSRecord]) -> Result<()> {
+    match violations(apex, records).into_iter().next() {
+        Some(violation) => Err(AppError::InvalidRecord(violation.message)),
+        None => Ok(()),
+    }
+}
+
+/// Every finding for the zone at `apex`, given all of its records: invariant
+/// breaches stored before they were enforced, then warnings, each by name.
+pub fn lint(apex: &str, records: &[DNSRecord]) -> Vec<LintWarning> {
+    let zone = names(records);
+    let has = |name: &str, types: &[DNSRecordType]| {
+        zone.get(name)
+            .is_some_and(|rrsets| types.iter().any(|t| rrsets.contains_key(t)))
+    };
+
+    // Names at or below a delegation are answered by the child zone
+    let cuts: Vec<&str> = zone
+        .iter()
+        .filter(|(name, rrsets)| **name != apex && rrsets.contains_key(&DNSRecordType::NS))
+        .map(|(name, _)| *name)
+        .collect();
+    let delegated = |name: &str| cuts.iter().any(|cut| in_zone(name, cut));
+
+    let mut found = violations(apex, records);
+    for record in records {
+        let Some(target) = target(record) else {
+            continue;
+        };
+        if !in_zone(&target, apex) {
+            continue;
+        }
+        let (name, record_type) = (record.name.as_str(), record.record_type);
+
+        // Name servers below their own cut can only be reached through glue
+        if record_type == DNSRecordType::NS && name != apex && in_zone(&target, name) {
+            if !has(&target, &ADDRESS_TYPES) {
+                found.push(finding(
+                    LintSeverity::Warning,
+                    LintCode::MissingGlue,
+                    name,
+                    Some(record_type),
+                    format!(
+                        "Delegation {} names server {} but has no glue A or AAAA records for it",
+                        name, target
+                    ),
+                ));
+            }
+            continue;
+        }
+        if delegated(&target) {
+            continue;
+        }
+
+        let problem = match record_type {
+            DNSRecordType::MX | DNSRecordType::NS | DNSRecordType::SRV
+                if has(&target, &[DNSRecordType::CNAME]) =>
+            {
+                Some((LintCode::TargetIsCname, "which is an alias"))
+            }
+            DNSRecordType::MX | DNSRecordType::NS | DNSRecordType::SRV
+                if !has(&target, &ADDRESS_TYPES) =>
+            {
+                Some((LintCode::DanglingTarget, "which has no A or AAAA records"))
+            }
+            _ if !zone.contains_key(target.as_str()) => {
+                Some((LintCode::DanglingTarget, "which has no records"))
+            }
+            _ => None,
+        };
+        if let Some((code, reason)) = problem {
+            found.push(finding(
+                LintSeverity::Warning,
+                code,
+                name,
+                Some(record_type),
+                format!(
+                    "{} at {} points at {}, {}",
+                    record_type, name, target, reason
+                ),
+            ));
+        }
+    }
+
+    if !has(apex, &[DNSRecordType::CAA]) {
+        found.push(finding(
+            LintSeverity::Warning,
+            LintCode::MissingCaa,
+            apex,
+            Some(DNSRecordType::CAA),
+            format!(
+                "{} has no CAA records, so any certificate authority may issue for it",
+                apex
+            ),
+        ));
+    }
+
+    for (name, rrsets) in &zone {
+        if !rrsets.contains_key(&DNSRecordType::TLSA) {
+            continue;
+        }
+        let Some(host) = tlsa_host(name) else {
+            continue;
+        };
+        let reachable = has(
+            host,
+            &[DNSRecordType::A, DNSRecordType::AAAA, DNSRecordType::CNAME],
+        );
+        if in_zone(host, apex) && !delegated(host) && !reachable {
+            found.push(finding(
+                LintSeverity::Warning,
+                LintCode::TlsaWithoutAddress,
+                name,
+                Some(DNSRecordType::TLSA),
+                format!(
+                    "TLSA at {} is for {}, which has no A, AAAA or CNAME records",
+                    name, host
+                ),
+            ));
+        }
+    }
+
+    found.sort_by(|a, b| (a.severity, &a.name, a.code).cmp(&(b.severity, &b.name, b.code)));
+    found
+}
+
+/// Group records by owner name and type.
+fn names(records: &[DNSRecord]) -> Names<'_> {
+    let mut names: Names = BTreeMap::new();
+    for record in records {
+        names
+            .entry(record.name.as_str())
+            .or_default()
+            .entry(record.record_type)
+            .or_default()
+            .push(record);
+    }
+    names
+}
+
+/// The in-zone form of the name a record points at, for the types whose
+/// target must resolve; `None` for the root, which means no service or the
+/// owner itself.
+fn target(record: &DNSRecord) -> Option<String> {
+    let field = match record.record_type {
+        DNSRecordType::CNAME | DNSRecordType::NS => 0,
+        DNSRecordType::MX | DNSRecordType::HTTPS | DNSRecordType::SVCB => 1,
+        DNSRecordType::SRV => 3,
+        _ => return None,
+    };
+    let target = record.value.split_whitespace().nth(field)?;
+    (target != ".").then(|| normalize_name(target))
+}
+
+/// Host a TLSA owner name `_port._protocol.host` is for (RFC 6698 §3).
+fn tlsa_host(name: &str) -> Option<&str> {
+    let mut labels = name.splitn(3, '.');
+    let port = labels.next()?;
+    let protocol = labels.next()?;
+    let host = labels.next()?;
+    (port.starts_with('_') && protocol.starts_with('_')).then_some(host)
+}
+
+fn finding(
+    severity: LintSeverity,
+    code: LintCode,
+    name: &str,
+    record_type: Option<DNSRecordType>,
+    message: String,
+) -> LintWarning {
+    LintWarning {
+        severity,
+        code,
+        name: name.to_string(),
+        record_type,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(records: &[(&str, DNSRecordType, i32, &str)]) -> Vec<DNSRecord> {
+        records
+            .iter()
+            .map(|(name, record_type, ttl, value)| {
+                DNSRecord::new(name.to_string(), *record_type, *ttl, value.to_string())
+            })
+            .collect()
+    }
+
+    fn codes(found: &[LintWarning]) -> Vec<(LintCode, &str)> {
+        found.iter().map(|f| (f.code, f.name.as_str())).collect()
+    }
+
+    fn healthy() -> Vec<(&'static str, DNSRecordType, i32, &'static str)> {
+        vec![
+            (
+                "example.com",
+                DNSRecordType::SOA,
+                3600,
+                "ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 3600",
+            ),
+            ("example.com", DNSRecordType::NS, 3600, "ns1.example.com."),
+            ("example.com", DNSRecordType::NS, 3600, "ns.example.net."),
+            (
+                "example.com",
+                DNSRecordType::MX,
+                3600,
+                "10 mail.example.com.",
+            ),
+            (
+                "example.com",
+                DNSRecordType::CAA,
+                3600,
+                "0 issue \"letsencrypt.org\"",
+            ),
+            ("ns1.example.com", DNSRecordType::A, 3600, "192.0.2.1"),
+            ("mail.example.com", DNSRecordType::A, 300, "192.0.2.2"),
+            ("mail.example.com", DNSRecordType::A, 300, "192.0.2.3"),
+            (
+                "www.example.com",
+                DNSRecordType::CNAME,
+                300,
+                "mail.example.com.",
+            ),
+            (
+                "_443._tcp.mail.example.com",
+                DNSRecordType::TLSA,
+                300,
+                "3 1 1 AABB",
+            ),
+            (
+                "sub.example.com",
+                DNSRecordType::NS,
+                3600,
+                "ns.sub.example.com.",
+            ),
+            ("ns.sub.example.com", DNSRecordType::A, 3600, "192.0.2.4"),
+        ]
+    }
+
+    #[test]
+    fn test_healthy_zone_is_clean() {
+        assert_eq!(lint("example.com", &zone(&healthy())), []);
+    }
+
+    #[test]
+    fn test_invariant_violations() {
+        let records = zone(&[
+            ("example.com", DNSRecordType::SOA, 3600, "a. b. 1 2 3 4 5"),
+            ("example.com", DNSRecordType::SOA, 3600, "a. b. 2 2 3 4 5"),
+            ("www.example.com", DNSRecordType::CNAME, 300, "example.com."),
+            ("www.example.com", DNSRecordType::A, 300, "192.0.2.1"),
+            ("ftp.example.com", DNSRecordType::CNAME, 300, "example.com."),
+            ("ftp.example.com", DNSRecordType::CNAME, 300, "example.net."),
+            (
+                "sub.example.com",
+                DNSRecordType::SOA,
+                3600,
+                "a. b. 1 2 3 4 5",
+            ),
+            ("mail.example.com", DNSRecordType::A, 300, "192.0.2.2"),
+            ("mail.example.com", DNSRecordType::A, 600, "192.0.2.3"),
+        ]);
+        let found = violations("example.com", &records);
+        assert_eq!(
+            codes(&found),
+            [
+                (LintCode::SoaConflict, "example.com"),
+                (LintCode::CnameConflict, "ftp.example.com"),
+                (LintCode::TtlMismatch, "mail.example.com"),
+                (LintCode::SoaConflict, "sub.example.com"),
+                (LintCode::CnameConflict, "www.example.com"),
+            ]
+        );
+        assert!(found.iter().all(|f| f.severity == LintSeverity::Error));
+        assert_eq!(
+            found[4].message,
+            "CNAME at www.example.com cannot share its name with other data (A)"
+        );
+
+        let err = check("example.com", &records[7..]).unwrap_err();
+        assert!(err.to_string().contains("mixes TTLs 300, 600"));
+        assert!(check("example.com", &records[..1]).is_ok());
+    }
+
+    #[test]
+    fn test_lint_warnings() {
+        let mut records = healthy();
+        records.retain(|(_, t, _, _)| *t != DNSRecordType::CAA);
+        records.retain(|(name, _, _, _)| *name != "ns.sub.example.com");
+        records.extend([
+            (
+                "old.example.com",
+                DNSRecordType::CNAME,
+                300,
+                "gone.example.com.",
+            ),
+            (
+                "example.com",
+                DNSRecordType::MX,
+                3600,
+                "20 www.example.com.",
+            ),
+            (
+                "_25._tcp.smtp.example.com",
+                DNSRecordType::TLSA,
+                300,
+                "3 1 1 AABB",
+            ),
+            (
+                "_sip._udp.example.com",
+                DNSRecordType::SRV,
+                300,
+                "0 5 5060 sip.example.com.",
+            ),
+            ("sip.example.com", DNSRecordType::TXT, 300, "\"no address\""),
+            // Out of zone, and below the delegation: not looked at
+            (
+                "ext.example.com",
+                DNSRecordType::CNAME,
+                300,
+                "www.example.org.",
+            ),
+            (
+                "svc.example.com",
+                DNSRecordType::CNAME,
+                300,
+                "host.sub.example.com.",
+            ),
+        ]);
+        let found = lint("example.com", &zone(&records));
+        assert_eq!(
+            codes(&found),
+            [
+                (LintCode::TlsaWithoutAddress, "_25._tcp.smtp.example.com"),
+                (LintCode::DanglingTarget, "_sip._udp.example.com"),
+                (LintCode::TargetIsCname, "example.com"),
+                (LintCode::MissingCaa, "example.com"),
+                (LintCode::DanglingTarget, "old.example.com"),
+                (LintCode::MissingGlue, "sub.example.com"),
+            ]
+        );
+        assert!(found.iter().all(|f| f.severity == LintSeverity::Warning));
+        assert_eq!(
+            found[2].message,
+            "MX at example.com points at www.example.com, which is an alias"
+        );
+    }
+}
@@ -7,34 +7,41 @@
 //! - Blockchain provenance anchoring (Ethereum/Polygon)
 //! - SurrealDB graph storage
 //! - Reverse DNS lookups
+//! - Authoritative DNS (UDP/TCP) served from the record store
+//! - GraphQL subscriptions over WebSocket
+//! - TLS 1.3 termination with client-certificate identity (mTLS)
+//! - IndieAuth bearer tokens resolved by token introspection
 
-use async_graphql::{http::GraphiQLSource, EmptySubscription, Schema};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::{
+    http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS},
+    Data, Schema,
+};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse},
+    extract::{State, WebSocketUpgrade},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
 use std::net::SocketAddr;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing::{info, Level};
 
-mod blockchain;
-mod consent;
-mod db;
-mod dnssec;
-mod error;
-mod models;
-mod policy;
-mod resolvers;
-mod schema;
-
-use crate::{
+use graphql_dns_api::{
+    authority,
     consent::ConsentClient,
     db::Database,
-    policy::PolicyEnforcer,
-    resolvers::{MutationRoot, QueryRoot},
+    dnssec::DNSSECManager,
+    history,
+    indieauth::{self, IndieAuthClient},
+    keystore,
+    policy::{self, PolicyEnforcer},
+    resolvers::{MutationRoot, QueryRoot, SubscriptionRoot},
+    schema::AppSchema,
+    signer,
+    tls::{self, ClientIdentity},
+    zones,
 };
 
 use std::sync::Arc;
@@ -46,25 +53,105 @@ pub struct AppState {
     pub db: Database,
     pub policy: Arc<RwLock<PolicyEnforcer>>,
     pub consent: Arc<ConsentClient>,
+    pub dnssec: Arc<DNSSECManager>,
+    pub indieauth: Option<Arc<IndieAuthClient>>,
 }
 
-/// GraphQL handler
-async fn graphql_handler(
-    State(state): State<AppState>,
-    req: GraphQLRequest,
-) -> GraphQLResponse {
-    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+/// Build the GraphQL schema over the shared state
+fn build_schema(state: AppState) -> AppSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .extension(history::MutationLog)
+        .extension(indieauth::ScopeGuard)
+        .extension(policy::PolicyGuard)
         .data(state.db)
         .data(state.policy)
         .data(state.consent)
-        .finish();
+        .data(state.dnssec)
+        .finish()
+}
+
+/// The `Authorization` header of a request, if it is readable
+fn authorization(headers: &HeaderMap) -> Option<&str> {
+    headers.get(http::header::AUTHORIZATION)?.to_str().ok()
+}
 
-    schema.execute(req.into_inner()).await.into()
+/// GraphQL handler; the identity of the client certificate, else of the
+/// bearer token, becomes the request's
+async fn graphql_handler(
+    State(state): State<AppState>,
+    client: Option<Extension<ClientIdentity>>,
+    headers: HeaderMap,
+    req: GraphQLRequest,
+) -> Response {
+    let certificate = client.map(|Extension(identity)| identity);
+    let indieauth = state.indieauth.clone();
+    let header = authorization(&headers);
+    let caller = match indieauth::authenticate(indieauth.as_deref(), certificate, header).await {
+        Ok(caller) => caller,
+        Err(e) => return e.into_response(),
+    };
+    let mut req = req.into_inner();
+    if let Some(caller) = caller {
+        caller.insert_into(&mut req.data);
+    }
+    GraphQLResponse::from(build_schema(state).execute(req).await).into_response()
+}
+
+/// GraphQL subscription handler (graphql-transport-ws or legacy graphql-ws over WebSocket)
+///
+/// Browsers cannot set headers on WebSocket upgrades, so a bearer token may
+/// also be sent as `Authorization` in the `connection_init` payload.
+async fn graphql_ws_handler(
+    State(state): State<AppState>,
+    client: Option<Extension<ClientIdentity>>,
+    headers: HeaderMap,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let certificate = client.map(|Extension(identity)| identity);
+    let indieauth = state.indieauth.clone();
+    let header = authorization(&headers);
+    let caller = match indieauth::authenticate(indieauth.as_deref(), certificate, header).await {
+        Ok(caller) => caller,
+        Err(e) => return e.into_response(),
+    };
+    let schema = build_schema(state);
+    let mut data = Data::default();
+    let identified = caller.is_some();
+    if let Some(caller) = caller {
+        caller.insert_into(&mut data);
+    }
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            GraphQLWebSocket::new(stream, schema, protocol)
+                .with_data(data)
+                .on_connection_init(move |payload| async move {
+                    let mut data = Data::default();
+                    if identified {
+                        return Ok(data);
+                    }
+                    let authorization = payload.get("Authorization").and_then(|v| v.as_str());
+                    let caller = indieauth::authenticate(indieauth.as_deref(), None, authorization)
+                        .await
+                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    if let Some(caller) = caller {
+                        caller.insert_into(&mut data);
+                    }
+                    Ok(data)
+                })
+                .serve()
+        })
 }
 
 /// GraphiQL playground handler
 async fn graphiql() -> impl IntoResponse {
-    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+    Html(
+        GraphiQLSource::build()
+            .endpoint("/graphql")
+            .subscription_endpoint("/graphql/ws")
+            .finish(),
+    )
 }
 
 /// Health check handler
@@ -89,6 +176,12 @@ async fn main() -> anyhow::Result<()> {
     let db = Database::connect("memory").await?;
     info!("Connected to SurrealDB");
 
+    // Give records stored before zones existed a zone
+    let adopted = zones::adopt_legacy(&db).await?;
+    if adopted > 0 {
+        info!("Adopted {} zones from stored SOA records", adopted);
+    }
+
     // Load Nickel policy configuration
     let policy_path = std::path::Path::new("../policy/curps/policy.ncl");
     let policy_enforcer = match PolicyEnforcer::from_nickel_file(policy_path) {
@@ -119,6 +212,29 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    // Terminate TLS with client certificates when configured; the policy can
+    // require it
+    let require_mtls = policy_enforcer.read().await.policy.constraints.require_mtls;
+    let tls_config = tls::config_from_env(require_mtls)?;
+    if tls_config.is_none() {
+        if require_mtls {
+            anyhow::bail!(
+                "Policy requires mTLS: set {}, {} and {}",
+                tls::CERT_ENV,
+                tls::KEY_ENV,
+                tls::CLIENT_CA_ENV
+            );
+        }
+        tracing::warn!("TLS not configured: serving plain HTTP, no client certificates");
+    }
+
+    // Resolve bearer tokens when an IndieAuth introspection endpoint is set
+    let indieauth = IndieAuthClient::from_env().map(Arc::new);
+    match &indieauth {
+        Some(_) => info!("Accepting IndieAuth bearer tokens"),
+        None => info!("{} not set: bearer tokens are refused", indieauth::ENDPOINT_ENV),
+    }
+
     // Initialize consent client
     let consent_api_url = std::env::var("CONSENT_API_URL")
         .unwrap_or_else(|_| "http://localhost:8082".to_string());
@@ -131,11 +247,39 @@ async fn main() -> anyhow::Result<()> {
         Err(e) => tracing::warn!("Could not connect to consent API ({}), consent checks will fail", e),
     }
 
+    // Unlock the DNSSEC key store, then re-sign expiring RRSIGs periodically
+    let dnssec = match keystore::passphrase_from_env()? {
+        Some(passphrase) => Arc::new(DNSSECManager::unlock(db.clone(), &passphrase).await?),
+        None => {
+            tracing::warn!(
+                "{} not set: DNSSEC key store locked, zones cannot be signed",
+                keystore::PASSPHRASE_ENV
+            );
+            Arc::new(DNSSECManager::new())
+        }
+    };
+    signer::spawn_resigner(db.clone(), dnssec.clone(), std::time::Duration::from_secs(3600));
+
+    // Start authoritative DNS listener, accepting transfers and dynamic updates
+    let dns_addr: SocketAddr = std::env::var("DNS_LISTEN_ADDR")
+        .unwrap_or_else(|_| "0.0.0.0:5353".to_string())
+        .parse()?;
+    authority::spawn(
+        db.clone(),
+        dnssec.clone(),
+        policy_enforcer.clone(),
+        consent_client.clone(),
+        dns_addr,
+    )
+    .await?;
+
     // Create application state
     let state = AppState {
         db,
         policy: policy_enforcer,
         consent: consent_client,
+        dnssec,
+        indieauth,
     };
 
     // Build router with restrictive CORS per security policy
@@ -152,6 +296,7 @@ async fn main() -> anyhow::Result<()> {
 
     let app = Router::new()
         .route("/graphql", post(graphql_handler))
+        .route("/graphql/ws", get(graphql_ws_handler))
         .route("/graphiql", get(graphiql))
         .route("/health", get(health))
         .layer(cors)
@@ -159,11 +304,19 @@ async fn main() -> anyhow::Result<()> {
 
     // Start server
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    info!("GraphQL server listening on http://{}", addr);
-    info!("GraphiQL playground: http://{}/graphiql", addr);
+    let (http, ws) = match tls_config {
+        Some(_) => ("https", "wss"),
+        None => ("http", "ws"),
+    };
+    info!("GraphQL server listening on {}://{}", http, addr);
+    info!("GraphiQL playground: {}://{}/graphiql", http, addr);
+    info!("Subscriptions: {}://{}/graphql/ws", ws, addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    match tls_config {
+        Some(config) => tls::serve(listener, config, app).await?,
+        None => axum::serve(listener, app).await?,
+    }
 
     Ok(())
 }
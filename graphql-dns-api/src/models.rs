@@ -1,13 +1,16 @@
 // SPDX-License-Identifier: PMPL-1.0-or-later
 //! Data models for DNS records and blockchain provenance
 
-use async_graphql::{Enum, InputObject, SimpleObject, ID};
-use chrono::{DateTime, Utc};
+use async_graphql::{connection::CursorType, Enum, InputObject, SimpleObject, ID};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::AppError;
+
 /// DNS record type enumeration - full RR coverage
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Enum)]
 pub enum DNSRecordType {
     /// IPv4 address
     A,
@@ -31,6 +34,30 @@ pub enum DNSRecordType {
     SOA,
     /// Pointer (reverse DNS)
     PTR,
+    /// HTTPS service binding
+    HTTPS,
+    /// General service binding
+    SVCB,
+    /// Delegation signer
+    DS,
+    /// DNSSEC public key
+    DNSKEY,
+    /// Child copy of DS
+    CDS,
+    /// Child copy of DNSKEY
+    CDNSKEY,
+    /// SSH host key fingerprint
+    SSHFP,
+    /// Naming authority pointer
+    NAPTR,
+    /// Uniform resource identifier
+    URI,
+    /// OpenPGP public key
+    OPENPGPKEY,
+    /// S/MIME certificate association
+    SMIMEA,
+    /// Geographic location
+    LOC,
 }
 
 impl DNSRecordType {
@@ -48,6 +75,59 @@ pub fn to_type_code(&self) -> u16 {
             Self::SRV => 33,
             Self::CAA => 257,
             Self::TLSA => 52,
+            Self::LOC => 29,
+            Self::NAPTR => 35,
+            Self::DS => 43,
+            Self::SSHFP => 44,
+            Self::DNSKEY => 48,
+            Self::SMIMEA => 53,
+            Self::CDS => 59,
+            Self::CDNSKEY => 60,
+            Self::OPENPGPKEY => 61,
+            Self::SVCB => 64,
+            Self::HTTPS => 65,
+            Self::URI => 256,
+        }
+    }
+}
+
+impl std::fmt::Display for DNSRecordType {
+    /// Presentation-format type mnemonic
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::str::FromStr for DNSRecordType {
+    type Err = AppError;
+
+    /// Parse a presentation-format type mnemonic (case-insensitive)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "AAAA" => Ok(Self::AAAA),
+            "CNAME" => Ok(Self::CNAME),
+            "MX" => Ok(Self::MX),
+            "TXT" => Ok(Self::TXT),
+            "SRV" => Ok(Self::SRV),
+            "CAA" => Ok(Self::CAA),
+            "TLSA" => Ok(Self::TLSA),
+            "NS" => Ok(Self::NS),
+            "SOA" => Ok(Self::SOA),
+            "PTR" => Ok(Self::PTR),
+            "HTTPS" => Ok(Self::HTTPS),
+            "SVCB" => Ok(Self::SVCB),
+            "DS" => Ok(Self::DS),
+            "DNSKEY" => Ok(Self::DNSKEY),
+            "CDS" => Ok(Self::CDS),
+            "CDNSKEY" => Ok(Self::CDNSKEY),
+            "SSHFP" => Ok(Self::SSHFP),
+            "NAPTR" => Ok(Self::NAPTR),
+            "URI" => Ok(Self::URI),
+            "OPENPGPKEY" => Ok(Self::OPENPGPKEY),
+            "SMIMEA" => Ok(Self::SMIMEA),
+            "LOC" => Ok(Self::LOC),
+            other => Err(AppError::InvalidRecord(format!("unsupported record type {}", other))),
         }
     }
 }
@@ -56,6 +136,7 @@ pub fn to_type_code(&self) -> u16 {
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct DNSRecord {
     /// Unique record identifier
+    #[serde(with = "crate::db::record_id")]
     pub id: ID,
     /// Fully qualified domain name
     pub name: String,
@@ -66,15 +147,20 @@ pub struct DNSRecord {
     pub ttl: i32,
     /// Record value (format depends on type)
     pub value: String,
+    /// Apex of the zone the record belongs to
+    #[serde(default)]
+    pub zone: Option<String>,
     /// DNSSEC enabled for this record
     pub dnssec: bool,
-    /// DNSSEC signature (if enabled)
+    /// RRSIGs covering this record's RRset, one per algorithm and line, in presentation format
     pub rrsig: Option<String>,
     /// Blockchain transaction hash (provenance anchor)
     pub blockchain_tx_hash: Option<String>,
     /// Creation timestamp
+    #[serde(with = "crate::db::datetime")]
     pub created_at: DateTime<Utc>,
     /// Last update timestamp
+    #[serde(with = "crate::db::datetime")]
     pub updated_at: DateTime<Utc>,
 }
 
@@ -88,6 +174,7 @@ pub fn new(name: String, record_type: DNSRecordType, ttl: i32, value: String) ->
             record_type,
             ttl,
             value,
+            zone: None,
             dnssec: false,
             rrsig: None,
             blockchain_tx_hash: None,
@@ -107,15 +194,534 @@ pub fn content_hash(&self) -> String {
     }
 }
 
+/// How a record changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum RecordChangeKind {
+    /// The record was written for the first time
+    Created,
+    /// The record was rewritten in place
+    Updated,
+    /// The record was removed
+    Deleted,
+}
+
+/// A committed change to one record, as published to subscribers
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RecordChange {
+    /// How the record changed
+    pub kind: RecordChangeKind,
+    /// The record as written, or as it was before deletion
+    pub record: DNSRecord,
+}
+
+/// One entry in a record's change history. Entries are written in the
+/// transaction that changed the record and never modified.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RecordHistoryEntry {
+    /// Record the entry is about
+    pub record_id: ID,
+    /// 1 for the record's creation, counting up with each change
+    pub version: i32,
+    /// How the record changed
+    pub kind: RecordChangeKind,
+    /// Identity the change was made as; none for changes the server made itself
+    pub actor: Option<String>,
+    /// The record before the change; none when it was created
+    pub before: Option<DNSRecord>,
+    /// The record after the change; none when it was deleted
+    pub after: Option<DNSRecord>,
+    /// When the change was committed
+    #[serde(with = "crate::db::datetime")]
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Conditions on the records paged through by `dnsRecordsConnection`; every
+/// condition given must hold
+#[derive(Debug, Clone, Default, InputObject)]
+#[graphql(name = "DNSRecordFilter")]
+pub struct DNSRecordFilter {
+    /// Apex of the zone the records belong to
+    pub zone: Option<String>,
+    /// Exact owner name
+    pub name: Option<String>,
+    /// Owner names ending in this suffix, e.g. ".example.com"
+    pub name_suffix: Option<String>,
+    /// Owner names starting with this prefix, e.g. "_acme-challenge."
+    pub name_prefix: Option<String>,
+    /// Owner names matching a pattern with at most one `*`, which stands for
+    /// any run of characters, dots included: "*.example.com", "mail*.example.com"
+    pub name_pattern: Option<String>,
+    #[graphql(name = "type")]
+    pub record_type: Option<DNSRecordType>,
+    /// Values containing this substring (case-sensitive)
+    pub value_contains: Option<String>,
+    pub dnssec: Option<bool>,
+    /// Only records anchored to a blockchain, or only records not anchored
+    pub anchored: Option<bool>,
+    /// Created at or after this time
+    pub created_after: Option<DateTime<Utc>>,
+    /// Created strictly before this time
+    pub created_before: Option<DateTime<Utc>>,
+    /// Last updated at or after this time
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Last updated strictly before this time
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+/// Field records are paged through in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Enum)]
+#[graphql(name = "DNSRecordSortField")]
+pub enum DNSRecordSortField {
+    /// Owner name
+    #[default]
+    Name,
+    /// Time to live
+    Ttl,
+    /// Creation timestamp
+    CreatedAt,
+    /// Last update timestamp
+    UpdatedAt,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Enum)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Order records are paged through in; ties are broken by record ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, InputObject)]
+#[graphql(name = "DNSRecordOrder")]
+pub struct DNSRecordOrder {
+    #[graphql(default)]
+    pub field: DNSRecordSortField,
+    #[graphql(default)]
+    pub direction: SortDirection,
+}
+
+/// Position of a record in one order, as an opaque pagination cursor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordCursor {
+    /// The order the cursor was issued for
+    pub order: DNSRecordOrder,
+    /// The record's sort field, in string form
+    pub key: String,
+    /// The record's ID
+    pub id: String,
+}
+
+impl RecordCursor {
+    /// Cursor pointing at `record` in `order`
+    pub fn new(record: &DNSRecord, order: DNSRecordOrder) -> Self {
+        let key = match order.field {
+            DNSRecordSortField::Name => record.name.clone(),
+            DNSRecordSortField::Ttl => record.ttl.to_string(),
+            DNSRecordSortField::CreatedAt => {
+                record.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true)
+            }
+            DNSRecordSortField::UpdatedAt => {
+                record.updated_at.to_rfc3339_opts(SecondsFormat::Nanos, true)
+            }
+        };
+        Self {
+            order,
+            key,
+            id: record.id.to_string(),
+        }
+    }
+}
+
+impl CursorType for RecordCursor {
+    type Error = AppError;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        let invalid = || AppError::InvalidQuery(format!("malformed cursor {}", s));
+        let json = URL_SAFE_NO_PAD.decode(s).map_err(|_| invalid())?;
+        serde_json::from_slice(&json).map_err(|_| invalid())
+    }
+
+    fn encode_cursor(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serialises");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+}
+
+/// Fields of a `DNSRecordConnection` besides its edges and page info
+#[derive(Debug, Clone, SimpleObject)]
+pub struct DNSRecordConnectionFields {
+    /// Records matching the filter, across all pages
+    pub total_count: i32,
+}
+
 /// Input for creating/updating DNS records
+///
+/// The RDATA is given either as `value` or, for MX, SRV, CAA and TLSA, as the
+/// typed input for the record type; it is validated for the type and stored
+/// in normalised presentation form.
 #[derive(Debug, Clone, InputObject)]
 pub struct DNSRecordInput {
     pub name: String,
     #[graphql(name = "type")]
     pub record_type: DNSRecordType,
-    pub ttl: i32,
-    pub value: String,
+    /// Defaults to the zone's default TTL
+    pub ttl: Option<i32>,
+    /// RDATA in presentation format; a TXT value not starting with a quote is one text
+    pub value: Option<String>,
     pub dnssec: Option<bool>,
+    /// MX RDATA, instead of `value`
+    pub mx: Option<MXInput>,
+    /// SRV RDATA, instead of `value`
+    pub srv: Option<SRVInput>,
+    /// CAA RDATA, instead of `value`
+    pub caa: Option<CAAInput>,
+    /// TLSA RDATA, instead of `value`
+    pub tlsa: Option<TLSAInput>,
+}
+
+/// MX RDATA (RFC 1035 §3.3.9)
+#[derive(Debug, Clone, InputObject)]
+#[graphql(name = "MXInput")]
+pub struct MXInput {
+    /// Lower values are preferred (0-65535)
+    pub preference: i32,
+    /// Mail server host name, or "." for a null MX (RFC 7505)
+    pub exchange: String,
+}
+
+/// SRV RDATA (RFC 2782)
+#[derive(Debug, Clone, InputObject)]
+#[graphql(name = "SRVInput")]
+pub struct SRVInput {
+    /// Lower values are tried first (0-65535)
+    pub priority: i32,
+    /// Relative weight among targets of equal priority (0-65535)
+    pub weight: i32,
+    /// Service port (0-65535)
+    pub port: i32,
+    /// Target host name, or "." when the service is not offered
+    pub target: String,
+}
+
+/// CAA RDATA (RFC 8659)
+#[derive(Debug, Clone, InputObject)]
+#[graphql(name = "CAAInput")]
+pub struct CAAInput {
+    /// 0, or 128 for issuer-critical; defaults to 0
+    pub flags: Option<i32>,
+    /// Property tag: issue, issuewild, iodef, ...
+    pub tag: String,
+    /// Property value, unquoted
+    pub value: String,
+}
+
+/// TLSA RDATA (RFC 6698)
+#[derive(Debug, Clone, InputObject)]
+#[graphql(name = "TLSAInput")]
+pub struct TLSAInput {
+    /// Certificate usage (0-3)
+    pub usage: i32,
+    /// Selector: 0 full certificate, 1 SubjectPublicKeyInfo
+    pub selector: i32,
+    /// Matching type: 0 exact, 1 SHA-256, 2 SHA-512
+    pub matching_type: i32,
+    /// Certificate association data in hex
+    pub certificate_data: String,
+}
+
+/// How a zone's SOA serial advances on each change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Enum)]
+pub enum SerialPolicy {
+    /// `YYYYMMDDnn`: today's date with a two-digit change counter (RFC 1912 §2.2)
+    #[default]
+    Date,
+    /// Previous serial plus one, in RFC 1982 serial number arithmetic
+    Increment,
+}
+
+/// Authoritative zone: apex, SOA parameters, default TTL and owner.
+///
+/// The apex SOA record in `dns_records` is written from these fields.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct Zone {
+    /// Apex name, lowercase without a trailing dot
+    pub apex: String,
+    /// SOA MNAME: primary name server, fully qualified
+    pub primary_ns: String,
+    /// SOA RNAME: responsible mailbox in domain-name form (`hostmaster.example.com.`)
+    pub admin_mailbox: String,
+    /// SOA serial
+    pub serial: i64,
+    /// How the serial advances
+    pub serial_policy: SerialPolicy,
+    /// SOA refresh interval (seconds)
+    pub refresh: i32,
+    /// SOA retry interval (seconds)
+    pub retry: i32,
+    /// SOA expire time (seconds)
+    pub expire: i32,
+    /// SOA minimum: negative caching TTL (seconds, RFC 2308)
+    pub minimum: i32,
+    /// TTL given to records created without one, and to the SOA itself
+    pub default_ttl: i32,
+    /// Identity that created the zone
+    pub owner: String,
+    /// Creation timestamp
+    #[serde(with = "crate::db::datetime")]
+    pub created_at: DateTime<Utc>,
+    /// Last change to the zone or any of its records
+    #[serde(with = "crate::db::datetime")]
+    pub updated_at: DateTime<Utc>,
+    /// Secondaries allowed to transfer the zone (AXFR/IXFR)
+    #[serde(default)]
+    pub transfer_acl: Vec<TransferPeer>,
+}
+
+/// Input for creating a zone
+#[derive(Debug, Clone, InputObject)]
+pub struct ZoneInput {
+    /// Apex name
+    pub apex: String,
+    /// Primary name server
+    pub primary_ns: String,
+    /// Responsible mailbox, as `hostmaster@example.com` or `hostmaster.example.com.`
+    pub admin_mailbox: String,
+    #[graphql(default)]
+    pub serial_policy: SerialPolicy,
+    #[graphql(default = 7200)]
+    pub refresh: i32,
+    #[graphql(default = 900)]
+    pub retry: i32,
+    #[graphql(default = 1209600)]
+    pub expire: i32,
+    #[graphql(default = 300)]
+    pub minimum: i32,
+    #[graphql(default = 3600)]
+    pub default_ttl: i32,
+}
+
+/// Secondary allowed to transfer a zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct TransferPeer {
+    /// Source address or CIDR prefix (`192.0.2.53`, `2001:db8::/64`)
+    pub address: String,
+    /// TSIG key the transfer request must be signed with
+    pub tsig_key: String,
+    /// Where NOTIFY is sent when the serial changes (`192.0.2.53`,
+    /// `[2001:db8::53]:5300`; port 53 by default), signed with `tsig_key`
+    #[serde(default)]
+    pub notify: Option<String>,
+}
+
+/// Input for a zone transfer ACL entry
+#[derive(Debug, Clone, InputObject)]
+pub struct TransferPeerInput {
+    /// Source address or CIDR prefix
+    pub address: String,
+    /// Name of an existing TSIG key
+    pub tsig_key: String,
+    /// Address to send NOTIFY to, if any
+    pub notify: Option<String>,
+}
+
+/// Delivery state of a NOTIFY to one secondary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum NotifyState {
+    /// Sent or waiting to be resent, no answer yet
+    Pending,
+    /// The secondary answered NOERROR
+    Acknowledged,
+    /// The secondary answered with an error, or never answered
+    Failed,
+}
+
+/// NOTIFY status of one secondary for a zone's latest serial
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ZoneNotification {
+    /// Zone apex
+    pub zone: String,
+    /// Address the NOTIFY is sent to
+    pub target: String,
+    /// TSIG key the NOTIFY is signed with
+    pub tsig_key: String,
+    /// SOA serial announced
+    pub serial: i64,
+    pub state: NotifyState,
+    /// Messages sent so far
+    pub attempts: i32,
+    #[serde(default, with = "crate::db::datetime::option")]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::db::datetime::option")]
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// Why the last attempt did not succeed
+    pub last_error: Option<String>,
+}
+
+/// TSIG MAC algorithm (RFC 8945 §6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Enum)]
+pub enum TSIGAlgorithm {
+    /// hmac-sha256, mandatory to implement
+    #[default]
+    HmacSha256,
+    /// hmac-sha512
+    HmacSha512,
+}
+
+impl TSIGAlgorithm {
+    /// MAC size in bytes, also the size of generated secrets (RFC 8945 §6).
+    pub fn mac_len(self) -> usize {
+        match self {
+            Self::HmacSha256 => 32,
+            Self::HmacSha512 => 64,
+        }
+    }
+}
+
+/// Shared TSIG key authorising zone transfers and dynamic updates (RFC 8945)
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct TSIGKey {
+    /// Key name, lowercase without a trailing dot
+    pub name: String,
+    /// MAC algorithm
+    pub algorithm: TSIGAlgorithm,
+    /// Identity that created the key; dynamic updates signed with it act as this identity
+    #[serde(default)]
+    pub owner: String,
+    /// Creation timestamp
+    #[serde(with = "crate::db::datetime")]
+    pub created_at: DateTime<Utc>,
+    /// Whether the key was generated by a proposal and its owner has yet to
+    /// claim the secret with `claimTsigKeySecret`
+    #[serde(default)]
+    pub secret_unclaimed: bool,
+    /// Shared secret sealed with XChaCha20-Poly1305 (base64 nonce || ciphertext)
+    #[graphql(skip)]
+    pub sealed_secret: String,
+}
+
+/// A TSIG key with its secret, returned once: when generated, or when claimed
+/// by the owner of a key a proposal generated
+#[derive(Debug, Clone, SimpleObject)]
+pub struct TSIGKeySecret {
+    /// The stored key
+    pub key: TSIGKey,
+    /// Shared secret (base64), for the secondary's configuration
+    pub secret: String,
+}
+
+/// One committed change to a zone between two SOA serials, served by IXFR (RFC 1995)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneJournalEntry {
+    /// Zone apex
+    pub zone: String,
+    /// Serial before the change
+    pub serial_from: i64,
+    /// Serial after the change
+    pub serial_to: i64,
+    /// RRs deleted, the old SOA first (base64 wire format)
+    pub removed: Vec<String>,
+    /// RRs added, the new SOA first (base64 wire format)
+    pub added: Vec<String>,
+    /// When the change was journaled
+    #[serde(with = "crate::db::datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A zone's RRs as of its last journaled serial, diffed against on the next change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneSnapshot {
+    /// Zone apex
+    pub zone: String,
+    /// Serial the snapshot was taken at
+    pub serial: i64,
+    /// Every RR of the zone, the SOA first, then sorted (base64 wire format)
+    pub records: Vec<String>,
+}
+
+/// Everything a changeset writes to one zone, stored in one transaction
+#[derive(Debug, Clone)]
+pub struct ZoneWrite {
+    /// The zone with its next serial
+    pub zone: Zone,
+    /// Serial the changes were planned against
+    pub serial: i64,
+    /// Stored records the changes replace or delete, as they were read
+    pub seen: Vec<DNSRecord>,
+    /// IDs of the records to delete
+    pub deletes: Vec<String>,
+    /// Records replacing the stored records with their IDs
+    pub updates: Vec<DNSRecord>,
+    /// Records to create
+    pub creates: Vec<DNSRecord>,
+    /// Changes to the zone's denial chain, when it is signed
+    pub chain: Option<ChainPatch>,
+}
+
+/// DNSSEC signing algorithm (IANA DNS Security Algorithm Numbers)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Enum)]
+pub enum DNSSECAlgorithm {
+    /// ECDSA P-256 with SHA-256 (13, RFC 6605)
+    EcdsaP256Sha256,
+    /// Ed25519 (15, RFC 8080)
+    Ed25519,
+    /// Ed448 (16, RFC 8080)
+    Ed448,
+    /// Hybrid Ed448 + Dilithium5 (253, private use — CPR-005)
+    HybridEd448Dilithium5,
+}
+
+impl DNSSECAlgorithm {
+    /// DNSKEY/RRSIG algorithm number
+    pub fn number(&self) -> u8 {
+        match self {
+            Self::EcdsaP256Sha256 => 13,
+            Self::Ed25519 => 15,
+            Self::Ed448 => 16,
+            Self::HybridEd448Dilithium5 => 253,
+        }
+    }
+
+    /// Look up an algorithm by number
+    pub fn from_number(number: u8) -> Option<Self> {
+        match number {
+            13 => Some(Self::EcdsaP256Sha256),
+            15 => Some(Self::Ed25519),
+            16 => Some(Self::Ed448),
+            253 => Some(Self::HybridEd448Dilithium5),
+            _ => None,
+        }
+    }
+
+    /// Default DS digest: SHA-256 for standard algorithms, BLAKE3 for the private hybrid
+    pub fn default_digest(&self) -> DSDigestType {
+        match self {
+            Self::HybridEd448Dilithium5 => DSDigestType::Blake3,
+            _ => DSDigestType::Sha256,
+        }
+    }
+}
+
+/// DS digest type (IANA Delegation Signer Digest Algorithms)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum DSDigestType {
+    /// SHA-256 (2, RFC 4509)
+    Sha256,
+    /// SHA-384 (4, RFC 6605)
+    Sha384,
+    /// BLAKE3 (253, private use — CPR-009)
+    Blake3,
+}
+
+impl DSDigestType {
+    /// DS digest type number
+    pub fn number(&self) -> u8 {
+        match self {
+            Self::Sha256 => 2,
+            Self::Sha384 => 4,
+            Self::Blake3 => 253,
+        }
+    }
 }
 
 /// DNSSEC zone configuration
@@ -131,10 +737,247 @@ pub struct DNSSECZone {
     pub zsk: Option<String>,
     /// DS record for parent zone
     pub ds_record: Option<String>,
+    /// Signing algorithms, run in parallel (KSK/ZSK above belong to the first)
+    #[serde(default)]
+    pub algorithms: Vec<DNSSECAlgorithm>,
+    /// DS records for every algorithm's KSK
+    #[serde(default)]
+    pub ds_records: Vec<String>,
+    /// NSEC3 parameters; `None` when the zone uses plain NSEC
+    #[serde(default)]
+    pub nsec3: Option<NSEC3Config>,
+    /// KSK RRSIGs over the apex DNSKEY RRset, one per line in presentation format
+    #[serde(default)]
+    pub dnskey_rrsig: Option<String>,
+    /// KSK RRSIGs over the apex CDS RRset
+    #[serde(default)]
+    pub cds_rrsig: Option<String>,
+    /// KSK RRSIGs over the apex CDNSKEY RRset
+    #[serde(default)]
+    pub cdnskey_rrsig: Option<String>,
+    /// CDS/CDNSKEY carry the delete signal: the parent should remove every DS (RFC 8078 §4)
+    #[serde(default)]
+    pub cds_delete: bool,
+    /// TTL of the DS RRset at the parent, used to time KSK rollovers
+    #[serde(default)]
+    pub parent_ds_ttl: Option<i32>,
     /// Last key rotation
+    #[serde(default, with = "crate::db::datetime::option")]
     pub last_rotation: Option<DateTime<Utc>>,
 }
 
+/// NSEC3 chain parameters (RFC 5155 §3)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "NSEC3Config")]
+pub struct NSEC3Config {
+    /// Salt (hex, empty for none)
+    pub salt: String,
+    /// Additional hash iterations
+    pub iterations: i32,
+    /// Opt-out: insecure delegations are left out of the chain
+    pub opt_out: bool,
+}
+
+/// Input for NSEC3 chain parameters; RFC 9276 recommends the defaults
+#[derive(Debug, Clone, Default, InputObject)]
+#[graphql(name = "NSEC3Input")]
+pub struct NSEC3Input {
+    /// Salt (hex, empty or "-" for none)
+    #[graphql(default)]
+    pub salt: String,
+    /// Additional hash iterations
+    #[graphql(default)]
+    pub iterations: i32,
+    /// Opt-out: insecure delegations are left out of the chain
+    #[graphql(default)]
+    pub opt_out: bool,
+}
+
+/// Kind of authenticated denial record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum DenialKind {
+    /// Next secure record (RFC 4034 §4)
+    NSEC,
+    /// Hashed next secure record (RFC 5155 §3)
+    NSEC3,
+    /// NSEC3 parameters at the zone apex (RFC 5155 §4)
+    NSEC3PARAM,
+}
+
+/// One record of a signed zone's NSEC or NSEC3 chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct DenialRecord {
+    /// Zone apex
+    pub zone: String,
+    /// Record kind
+    pub kind: DenialKind,
+    /// Owner name (the hashed name for NSEC3)
+    pub owner: String,
+    /// Name the record describes (equal to the owner except for NSEC3)
+    pub name: String,
+    /// Next owner name (NSEC) or next hashed label (NSEC3) in the chain
+    pub next: String,
+    /// Types present at the name (type bitmap)
+    pub types: Vec<String>,
+    /// Time to live (seconds)
+    pub ttl: i32,
+    /// RRSIGs over the record, one per line in presentation format
+    pub rrsig: Option<String>,
+    /// Chain position: canonical name order for NSEC, hash order for NSEC3
+    #[graphql(skip)]
+    pub sort_key: String,
+}
+
+/// Changes to a signed zone's NSEC or NSEC3 chain, written together with the
+/// record changes they follow from
+#[derive(Debug, Clone)]
+pub struct ChainPatch {
+    /// Apex of the signed zone
+    pub zone: String,
+    /// Kind of chain the zone uses
+    pub kind: DenialKind,
+    /// Whether the whole stored chain is replaced rather than patched
+    pub replace: bool,
+    /// Positions of the chain records to delete
+    pub remove: Vec<String>,
+    /// Chain records to write, signed
+    pub records: Vec<DenialRecord>,
+}
+
+/// DNSSEC key role
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum DNSSECKeyRole {
+    /// Key signing key (signs the DNSKEY RRset, referenced by the parent DS)
+    KSK,
+    /// Zone signing key (signs all other RRsets)
+    ZSK,
+}
+
+/// DNSSEC key lifecycle state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum DNSSECKeyState {
+    /// Introduced ahead of use: a ZSK in the DNSKEY RRset, or a KSK whose DS goes to the parent
+    Published,
+    /// In use for signing
+    Active,
+    /// Superseded but still published until cached data referring to it expires
+    Retired,
+    /// Withdrawn from the DNSKEY RRset and the parent, kept for audit
+    Removed,
+}
+
+/// DNSSEC signing key held in the encrypted key store
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct DNSSECKey {
+    /// Key identifier
+    pub key_id: ID,
+    /// Zone the key belongs to
+    pub zone: String,
+    /// KSK or ZSK
+    pub role: DNSSECKeyRole,
+    /// DNSSEC algorithm number
+    pub algorithm: i32,
+    /// Key tag
+    pub key_tag: i32,
+    /// Public key (base64)
+    pub public_key: String,
+    /// Lifecycle state
+    pub state: DNSSECKeyState,
+    /// Creation timestamp
+    #[serde(with = "crate::db::datetime")]
+    pub created_at: DateTime<Utc>,
+    /// When the key entered its current state
+    #[serde(default, with = "crate::db::datetime::option")]
+    pub state_changed_at: Option<DateTime<Utc>>,
+    /// DS record for a KSK
+    #[serde(default)]
+    pub ds_record: Option<String>,
+    /// When the operator confirmed a published KSK's DS was submitted to the parent
+    #[serde(default, with = "crate::db::datetime::option")]
+    pub ds_submitted_at: Option<DateTime<Utc>>,
+    /// Secret key sealed with XChaCha20-Poly1305 (base64 nonce || ciphertext)
+    #[graphql(skip)]
+    pub sealed_secret: String,
+}
+
+impl DNSSECKey {
+    /// Whether the key belongs in the zone's DNSKEY RRset.
+    ///
+    /// ZSKs are published before they sign and stay until their signatures
+    /// expire from caches; a double-DS KSK rollover swaps KSKs in one step.
+    pub fn in_dnskey_rrset(&self) -> bool {
+        match self.role {
+            DNSSECKeyRole::ZSK => self.state != DNSSECKeyState::Removed,
+            DNSSECKeyRole::KSK => self.state == DNSSECKeyState::Active,
+        }
+    }
+}
+
+/// Step of a staged key rollover (RFC 6781 §4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum KeyRolloverStep {
+    /// Start signing with the pre-published ZSK and retire the old one
+    ActivateZsk,
+    /// Drop the retired ZSK from the DNSKEY RRset
+    RemoveZsk,
+    /// Submit the new KSK's DS to the parent, then confirm
+    SubmitDs,
+    /// Swap the old KSK for the new one in the DNSKEY RRset
+    ActivateKsk,
+    /// Withdraw the retired KSK's DS from the parent, then confirm
+    WithdrawDs,
+}
+
+/// Next action required to progress a key rollover
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct KeyRolloverAction {
+    /// Zone name
+    pub zone: String,
+    /// Role of the keys being rolled
+    pub role: DNSSECKeyRole,
+    /// Step to take
+    pub step: KeyRolloverStep,
+    /// Human-readable instruction
+    pub description: String,
+    /// Key tags the step applies to
+    pub key_tags: Vec<i32>,
+    /// DS records to submit to or withdraw from the parent
+    pub ds_records: Vec<String>,
+    /// Earliest safe time for the step
+    pub not_before: DateTime<Utc>,
+    /// Whether `not_before` has passed
+    pub ready: bool,
+}
+
+/// DS RRset the parent zone should publish, as signalled by the child's CDS/CDNSKEY
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "ParentDSSet")]
+pub struct ParentDSSet {
+    /// Zone name
+    pub zone: String,
+    /// DS records the parent should hold, one per KSK (empty with `delete`)
+    pub ds_records: Vec<String>,
+    /// Key tags of the KSKs the DS records refer to
+    pub key_tags: Vec<i32>,
+    /// The parent should remove every DS record (DNSSEC is being disabled)
+    pub delete: bool,
+}
+
+/// Key store KEK parameters: Argon2id salt and cost, plus a sealed check value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStoreMeta {
+    /// Argon2id salt (base64)
+    pub salt: String,
+    /// Argon2id memory cost (KiB)
+    pub m_cost: u32,
+    /// Argon2id iterations
+    pub t_cost: u32,
+    /// Argon2id lanes
+    pub p_cost: u32,
+    /// Known plaintext sealed under the KEK, to detect a wrong passphrase
+    pub check: String,
+}
+
 /// Reverse DNS lookup result
 #[derive(Debug, Clone, SimpleObject)]
 pub struct ReverseDNSResult {
@@ -160,6 +1003,7 @@ pub struct BlockchainProvenance {
     /// Block number
     pub block_number: i64,
     /// Timestamp
+    #[serde(with = "crate::db::datetime")]
     pub timestamp: DateTime<Utc>,
 }
 
@@ -183,3 +1027,137 @@ pub struct RecordTypeCount {
     pub record_type: DNSRecordType,
     pub count: i32,
 }
+
+/// Zone import strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Enum)]
+pub enum ZoneImportMode {
+    /// Add records not already present, keep everything else
+    #[default]
+    Merge,
+    /// Remove every existing record in the zone first
+    Replace,
+}
+
+/// Zone file parse error tied to a source line
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct ZoneFileError {
+    /// 1-based line number (0 for whole-file errors)
+    pub line: i32,
+    /// Error description
+    pub message: String,
+}
+
+impl ZoneFileError {
+    /// Attach a line number to an application error
+    pub fn new(line: usize, error: AppError) -> Self {
+        Self {
+            line: line as i32,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Zone import outcome
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct ZoneImportResult {
+    /// Zone apex
+    pub zone: String,
+    /// Records written
+    pub imported: i32,
+    /// Records removed (REPLACE mode)
+    pub removed: i32,
+    /// Per-line errors; nothing is written when non-empty
+    pub errors: Vec<ZoneFileError>,
+}
+
+/// How serious a zone lint finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Enum)]
+pub enum LintSeverity {
+    /// Breaks an RFC invariant; writes that would introduce it are refused
+    Error,
+    /// Legal, but likely to misbehave for clients
+    Warning,
+}
+
+/// What a zone lint finding is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Enum)]
+pub enum LintCode {
+    /// A CNAME shares its name with other data, or with another CNAME (RFC 2181 §10.1)
+    CnameConflict,
+    /// An SOA away from the apex, or more than one at it (RFC 1035 §5.2)
+    SoaConflict,
+    /// The RRs of one RRset have different TTLs (RFC 2181 §5.2)
+    TtlMismatch,
+    /// A target name inside the zone has no records, or no address where one is needed
+    DanglingTarget,
+    /// An MX, NS or SRV target is an alias (RFC 2181 §10.3, RFC 2782)
+    TargetIsCname,
+    /// A delegation's name server lies below the cut and has no address records
+    MissingGlue,
+    /// The apex has no CAA RRset, so any CA may issue (RFC 8659)
+    MissingCaa,
+    /// A TLSA record's host has no A, AAAA or CNAME records (RFC 6698 §3)
+    TlsaWithoutAddress,
+}
+
+/// One finding of a zone lint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct LintWarning {
+    /// Error or warning
+    pub severity: LintSeverity,
+    /// Kind of finding
+    pub code: LintCode,
+    /// Owner name the finding is about
+    pub name: String,
+    /// Record type the finding is about, if it concerns one RRset
+    #[graphql(name = "type")]
+    pub record_type: Option<DNSRecordType>,
+    /// Human-readable explanation
+    pub message: String,
+}
+
+/// What one change of a changeset does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ChangeAction {
+    /// Add a record
+    Create,
+    /// Rewrite a record in place
+    Update,
+    /// Remove a record
+    Delete,
+}
+
+/// One change of a changeset: CREATE takes `record`, UPDATE takes `id` and
+/// `record`, DELETE takes `id`
+#[derive(Debug, Clone, InputObject)]
+pub struct RecordChangeInput {
+    pub action: ChangeAction,
+    /// Record to update or delete
+    pub id: Option<ID>,
+    /// Record to create, or the new data of the record updated
+    pub record: Option<DNSRecordInput>,
+}
+
+/// A record a changeset creates, updates or deletes
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct RecordDiff {
+    /// Created, updated or deleted
+    pub kind: RecordChangeKind,
+    /// The record before the changeset; null when created
+    pub before: Option<DNSRecord>,
+    /// The record after the changeset; null when deleted
+    pub after: Option<DNSRecord>,
+}
+
+/// A changeset applied to a zone, or previewed
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct ChangesetResult {
+    /// Zone apex
+    pub zone: String,
+    /// Whether the changes were committed
+    pub applied: bool,
+    /// Records changed, including those of RRsets whose TTL a change set
+    pub changes: Vec<RecordDiff>,
+    /// Findings for the zone as it is after the changeset
+    pub lint: Vec<LintWarning>,
+}
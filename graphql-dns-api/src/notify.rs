@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! DNS NOTIFY (RFC 1996) to secondaries when a zone's serial changes
+//!
+//! Every transfer ACL entry with a `notify` address is sent a NOTIFY for the
+//! zone's new SOA, signed with the entry's TSIG key, each time the serial is
+//! bumped. An unanswered NOTIFY is resent, waiting twice as long each time
+//! (RFC 1996 §3.6), until the secondary answers or the attempts run out. The
+//! outcome per secondary is kept in `zone_notifications`; a newer serial
+//! supersedes a NOTIFY still being retried.
+//!
+//! Responses must carry a valid TSIG chained on the NOTIFY's (RFC 8945 §5.3.2);
+//! anything else is treated as no answer.
+
+use crate::{
+    authority::to_wire_record,
+    db::Database,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    models::{DNSRecord, DNSRecordType, NotifyState, TSIGKey, Zone, ZoneNotification},
+    xfr, zones,
+};
+use chrono::Utc;
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name, RecordType},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::{
+    net::UdpSocket,
+    time::{timeout_at, Instant},
+};
+use tracing::{info, warn};
+
+/// NOTIFY messages sent to a secondary before giving up.
+const ATTEMPTS: i32 = 5;
+
+/// Wait for an answer to the first NOTIFY, doubled for each resend
+/// (2 + 4 + 8 + 16 + 32 seconds in all).
+const INITIAL_WAIT: Duration = Duration::from_secs(2);
+
+/// Port NOTIFY is sent to when the address has none.
+const NOTIFY_PORT: u16 = 53;
+
+/// Largest response read.
+const RESPONSE_SIZE: usize = 4096;
+
+/// Result of one NOTIFY message.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// NOERROR, correctly signed
+    Acknowledged,
+    /// Signed error response: the secondary will not act on it
+    Rejected(ResponseCode),
+    /// Nothing trustworthy came back in time
+    Unanswered(String),
+}
+
+/// Notify the zone's secondaries of its current serial in the background.
+///
+/// Failures are recorded per secondary rather than returned: an unreachable
+/// secondary never fails the change that bumped the serial.
+pub async fn schedule(db: &Database, manager: &DNSSECManager, zone: &Zone) {
+    for peer in &zone.transfer_acl {
+        let Some(target) = &peer.notify else {
+            continue;
+        };
+        let mut notification = ZoneNotification {
+            zone: zone.apex.clone(),
+            target: target.clone(),
+            tsig_key: peer.tsig_key.clone(),
+            serial: zone.serial,
+            state: NotifyState::Pending,
+            attempts: 0,
+            last_attempt_at: None,
+            acknowledged_at: None,
+            last_error: None,
+        };
+
+        let key = match db.get_tsig_key(&peer.tsig_key).await {
+            Ok(key) => manager.tsig_secret(&key).map(|secret| (key, secret)),
+            Err(e) => Err(e),
+        };
+        let (key, secret) = match key {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("Cannot NOTIFY {} of {}: {}", target, zone.apex, e);
+                notification.state = NotifyState::Failed;
+                notification.last_error = Some(e.to_string());
+                store(db, notification).await;
+                continue;
+            }
+        };
+
+        if !store(db, notification.clone()).await {
+            continue;
+        }
+        tokio::spawn(deliver(db.clone(), zone.clone(), key, secret, notification));
+    }
+}
+
+/// Send NOTIFY until the secondary answers, the attempts run out, or a newer
+/// serial takes over.
+async fn deliver(
+    db: Database,
+    zone: Zone,
+    key: TSIGKey,
+    secret: Vec<u8>,
+    mut notification: ZoneNotification,
+) {
+    let target = match parse_target(&notification.target) {
+        Ok(target) => target,
+        Err(e) => return warn!("Cannot NOTIFY {}: {}", notification.target, e),
+    };
+
+    let mut wait = INITIAL_WAIT;
+    while notification.state == NotifyState::Pending {
+        match db.get_notification(&zone.apex, &notification.target).await {
+            Ok(Some(current)) if current.serial == notification.serial => {}
+            // Superseded by a newer serial, or no longer a secondary
+            _ => return,
+        }
+
+        notification.attempts += 1;
+        notification.last_attempt_at = Some(Utc::now());
+        match attempt(&zone, &key, &secret, target, wait).await {
+            Ok(Outcome::Acknowledged) => {
+                info!(
+                    "{} acknowledged NOTIFY of {} serial {}",
+                    target, zone.apex, zone.serial
+                );
+                notification.state = NotifyState::Acknowledged;
+                notification.acknowledged_at = Some(Utc::now());
+                notification.last_error = None;
+            }
+            Ok(Outcome::Rejected(code)) => {
+                notification.state = NotifyState::Failed;
+                notification.last_error = Some(format!("rejected with {}", code));
+            }
+            Ok(Outcome::Unanswered(reason)) => {
+                if notification.attempts >= ATTEMPTS {
+                    notification.state = NotifyState::Failed;
+                }
+                notification.last_error = Some(reason);
+            }
+            Err(e) => {
+                notification.state = NotifyState::Failed;
+                notification.last_error = Some(e.to_string());
+            }
+        }
+        if notification.state == NotifyState::Failed {
+            warn!(
+                "NOTIFY of {} to {} failed after {} attempts: {}",
+                zone.apex,
+                target,
+                notification.attempts,
+                notification.last_error.as_deref().unwrap_or_default()
+            );
+        }
+
+        store(&db, notification.clone()).await;
+        wait *= 2;
+    }
+}
+
+/// Send one signed NOTIFY and wait up to `wait` for its answer.
+async fn attempt(
+    zone: &Zone,
+    key: &TSIGKey,
+    secret: &[u8],
+    target: SocketAddr,
+    wait: Duration,
+) -> Result<Outcome> {
+    let id = rand::random::<u16>();
+    let mut message = notify_message(zone, id)?;
+    let request_mac = xfr::sign_request(&mut message, key, secret, Utc::now().timestamp() as u64)?;
+    let bytes = message
+        .to_vec()
+        .map_err(|e| AppError::Internal(format!("Encoding NOTIFY failed: {}", e)))?;
+
+    let local: SocketAddr = match target.ip() {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = match UdpSocket::bind(local).await {
+        Ok(socket) => socket,
+        Err(e) => return Ok(Outcome::Unanswered(e.to_string())),
+    };
+    if let Err(e) = socket.send_to(&bytes, target).await {
+        return Ok(Outcome::Unanswered(e.to_string()));
+    }
+
+    let deadline = Instant::now() + wait;
+    let mut buf = vec![0; RESPONSE_SIZE];
+    let mut reason = "no response".to_string();
+    loop {
+        let (len, from) = match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Ok(Ok(received)) => received,
+            Ok(Err(e)) => return Ok(Outcome::Unanswered(e.to_string())),
+            Err(_) => return Ok(Outcome::Unanswered(reason)),
+        };
+        let Ok(response) = Message::from_vec(&buf[..len]) else {
+            continue;
+        };
+        if from != target
+            || response.id() != id
+            || response.message_type() != MessageType::Response
+            || response.op_code() != OpCode::Notify
+        {
+            continue;
+        }
+
+        let now = Utc::now().timestamp() as u64;
+        if let Err(error) =
+            xfr::verify_message(&buf[..len], Some(&request_mac), key.algorithm, secret, now)
+        {
+            reason = format!(
+                "{} response failed TSIG verification ({})",
+                response.response_code(),
+                error
+            );
+            continue;
+        }
+        return Ok(match response.response_code() {
+            ResponseCode::NoError => Outcome::Acknowledged,
+            code => Outcome::Rejected(code),
+        });
+    }
+}
+
+/// NOTIFY for a zone's SOA, with the new SOA as the answer (RFC 1996 §3.7).
+fn notify_message(zone: &Zone, id: u16) -> Result<Message> {
+    let apex = Name::from_str(&format!("{}.", zone.apex))
+        .map_err(|e| AppError::InvalidRecord(format!("{}: {}", zone.apex, e)))?;
+    let soa = DNSRecord::new(
+        zone.apex.clone(),
+        DNSRecordType::SOA,
+        zone.default_ttl,
+        zones::soa_value(zone),
+    );
+
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Notify)
+        .set_authoritative(true)
+        .add_query(Query::query(apex, RecordType::SOA))
+        .add_answer(to_wire_record(&soa)?);
+    Ok(message)
+}
+
+/// Parse a NOTIFY address: an IP address, or a socket address with a port
+/// (`[2001:db8::53]:5300`).
+pub(crate) fn parse_target(address: &str) -> Result<SocketAddr> {
+    if let Ok(ip) = address.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, NOTIFY_PORT));
+    }
+    address
+        .parse::<SocketAddr>()
+        .map_err(|_| AppError::InvalidIP(address.to_string()))
+}
+
+/// Store a NOTIFY status, logging failures; returns whether it was stored.
+async fn store(db: &Database, notification: ZoneNotification) -> bool {
+    match db.upsert_notification(notification).await {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Could not store NOTIFY status: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SerialPolicy, TSIGAlgorithm};
+    use hickory_proto::rr::dnssec::rdata::tsig::{
+        make_tsig_record, message_tbs, TsigAlgorithm, TSIG,
+    };
+    use hmac::{digest::KeyInit, Hmac, Mac};
+    use sha2::Sha256;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn zone() -> Zone {
+        Zone {
+            apex: "example.com".to_string(),
+            primary_ns: "ns1.example.com.".to_string(),
+            admin_mailbox: "hostmaster.example.com.".to_string(),
+            serial: 2026101601,
+            serial_policy: SerialPolicy::Date,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum: 300,
+            default_ttl: 3600,
+            owner: String::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            transfer_acl: Vec::new(),
+        }
+    }
+
+    fn key() -> TSIGKey {
+        TSIGKey {
+            name: "ns2.example.net".to_string(),
+            algorithm: TSIGAlgorithm::HmacSha256,
+            owner: "identity:alice".to_string(),
+            created_at: Utc::now(),
+            secret_unclaimed: false,
+            sealed_secret: String::new(),
+        }
+    }
+
+    /// A secondary answering one NOTIFY with `code`, signed with `secret`.
+    async fn secondary(code: ResponseCode, secret: &'static [u8]) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0; RESPONSE_SIZE];
+            let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+            let now = Utc::now().timestamp() as u64;
+            let request_mac =
+                xfr::verify_message(&buf[..len], None, TSIGAlgorithm::HmacSha256, SECRET, now)
+                    .unwrap();
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            assert_eq!(request.op_code(), OpCode::Notify);
+            assert_eq!(request.answers()[0].record_type(), RecordType::SOA);
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_op_code(OpCode::Notify)
+                .set_response_code(code)
+                .add_query(request.queries()[0].clone());
+            let key_name = Name::from_str("ns2.example.net.").unwrap();
+            let pre_tsig = TSIG::new(
+                TsigAlgorithm::HmacSha256,
+                now,
+                300,
+                Vec::new(),
+                request.id(),
+                0,
+                Vec::new(),
+            );
+            let tbs = message_tbs(Some(&request_mac), &response, &pre_tsig, &key_name).unwrap();
+            let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(secret).unwrap();
+            mac.update(&tbs);
+            let mac = mac.finalize().into_bytes().to_vec();
+            response.add_tsig(make_tsig_record(key_name, pre_tsig.set_mac(mac)));
+            socket
+                .send_to(&response.to_vec().unwrap(), from)
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_notify_acknowledged() {
+        let target = secondary(ResponseCode::NoError, SECRET).await;
+        let wait = Duration::from_secs(5);
+        let outcome = attempt(&zone(), &key(), SECRET, target, wait).await;
+        assert_eq!(outcome.unwrap(), Outcome::Acknowledged);
+    }
+
+    #[tokio::test]
+    async fn test_notify_rejected() {
+        let target = secondary(ResponseCode::NotAuth, SECRET).await;
+        let wait = Duration::from_secs(5);
+        let outcome = attempt(&zone(), &key(), SECRET, target, wait).await;
+        assert_eq!(outcome.unwrap(), Outcome::Rejected(ResponseCode::NotAuth));
+    }
+
+    #[tokio::test]
+    async fn test_notify_badly_signed_answer_is_ignored() {
+        let target = secondary(ResponseCode::NoError, b"not the shared secret").await;
+        let wait = Duration::from_millis(500);
+        let outcome = attempt(&zone(), &key(), SECRET, target, wait).await;
+        assert!(matches!(outcome.unwrap(), Outcome::Unanswered(reason) if reason.contains("TSIG")));
+    }
+
+    #[tokio::test]
+    async fn test_notify_unanswered() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = silent.local_addr().unwrap();
+        let wait = Duration::from_millis(100);
+        let outcome = attempt(&zone(), &key(), SECRET, target, wait).await;
+        assert_eq!(
+            outcome.unwrap(),
+            Outcome::Unanswered("no response".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("192.0.2.53").unwrap(),
+            "192.0.2.53:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_target("2001:db8::53").unwrap(),
+            "[2001:db8::53]:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_target("[2001:db8::53]:5300").unwrap(),
+            "[2001:db8::53]:5300".parse().unwrap()
+        );
+        assert!(parse_target("ns2.example.net").is_err());
+    }
+}
@@ -9,14 +9,66 @@
 // - Rate limiting
 
 use anyhow::{anyhow, Result};
-use async_graphql::{Enum, SimpleObject, Scalar, ScalarType};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::{Enum, OutputType, ServerError, ServerResult, SimpleObject, Scalar, ScalarType};
 use async_graphql::Value as GraphQLValue;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use crate::consent::{self, ConsentClient};
+use crate::proposals::ProposalPayload;
+use crate::resolvers::MutationRoot;
+
+/// Privilege needed to change DNS records
+pub const MUTATE_DNS: &str = "mutate_dns";
+/// Privilege needed to generate, roll over or retire DNSSEC keys
+pub const ROTATE_KEYS: &str = "rotate_keys";
+
+/// The privilege each GraphQL mutation needs. The proposal workflow's own
+/// mutations have none here: they check the privilege of the proposal, and
+/// only the owner of a key a proposal generated may claim its secret.
+pub(crate) const MUTATION_PRIVILEGES: &[(&str, Option<&str>)] = &[
+    ("createDnsRecord", Some(MUTATE_DNS)),
+    ("updateDnsRecord", Some(MUTATE_DNS)),
+    ("deleteDnsRecord", Some(MUTATE_DNS)),
+    ("revertRecord", Some(MUTATE_DNS)),
+    ("applyChangeset", Some(MUTATE_DNS)),
+    ("createZone", Some(MUTATE_DNS)),
+    ("deleteZone", Some(MUTATE_DNS)),
+    ("setZoneTransferAcl", Some(MUTATE_DNS)),
+    ("createTsigKey", Some(MUTATE_DNS)),
+    ("deleteTsigKey", Some(MUTATE_DNS)),
+    ("importZone", Some(MUTATE_DNS)),
+    ("anchorToBlockchain", Some(MUTATE_DNS)),
+    ("enableDnssec", Some(ROTATE_KEYS)),
+    ("rotateDnssecKeys", Some(ROTATE_KEYS)),
+    ("disableDnssec", Some(ROTATE_KEYS)),
+    ("startKeyRollover", Some(ROTATE_KEYS)),
+    ("advanceKeyRollover", Some(ROTATE_KEYS)),
+    ("setDenialOfExistence", Some(ROTATE_KEYS)),
+    ("proposeMutation", None),
+    ("approveMutation", None),
+    ("executeMutation", None),
+    ("anchorProposal", None),
+    ("claimTsigKeySecret", None),
+];
+
+/// The privilege GraphQL mutation `field` needs: `Some(None)` for the
+/// proposal workflow, `None` if the mutation is unknown to the policy.
+pub fn mutation_privilege(field: &str) -> Option<Option<&'static str>> {
+    MUTATION_PRIVILEGES
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, privilege)| *privilege)
+}
 
 /// JSON scalar for GraphQL
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +210,38 @@ pub struct MutationProposal {
     pub required_approvals: u32,
     pub status: ProposalStatus,
     pub payload: JsonValue,
+    /// Result of the executed payload
+    #[serde(default)]
+    pub result: Option<JsonValue>,
+    /// Why the executed payload failed
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default, rename = "executed_at")]
+    #[graphql(name = "executedAt")]
+    pub executed_at: Option<u64>,
+}
+
+impl MutationProposal {
+    /// Hash of the proposal and its outcome for blockchain anchoring (BLAKE3
+    /// per CRYPTO-POLICY.adoc CPR-009)
+    pub fn content_hash(&self) -> String {
+        let outcome = match (&self.result, &self.error) {
+            (Some(result), _) => result.to_string(),
+            (None, Some(error)) => error.clone(),
+            (None, None) => String::new(),
+        };
+        let content = format!(
+            "{}:{}:{}:{}:{}:{}",
+            self.id,
+            self.mutation_name,
+            self.proposer,
+            self.approvals.join(","),
+            self.payload,
+            outcome
+        );
+        let hash = blake3::hash(content.as_bytes());
+        hex::encode(hash.as_bytes())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Enum, Copy)]
@@ -167,6 +251,8 @@ pub enum ProposalStatus {
     Approved,
     Rejected,
     Executed,
+    /// Executed, but the payload failed
+    Failed,
 }
 
 /// Policy enforcer
@@ -225,6 +311,43 @@ pub fn get_mutation_policy(&self, mutation_name: &str) -> Option<&MutationPolicy
             .find(|m| m.name == mutation_name)
     }
 
+    /// Mutation policy of `privilege` if it needs more approvals than the
+    /// proposer's own or a timelock, so it may only run as a proposal
+    pub fn requires_proposal(&self, privilege: &str) -> Option<&MutationPolicy> {
+        self.get_mutation_policy(privilege)
+            .filter(|m| m.approvals > 1 || m.timelock_hours > 0)
+    }
+
+    /// Check that `identity` may run GraphQL mutation `field` directly: it
+    /// needs the mutation's privilege, and the privilege must not require
+    /// approvals or a timelock. Mutations unknown to the policy are refused.
+    pub fn authorize_mutation(&self, field: &str, identity: Option<&str>) -> Result<()> {
+        let privilege = match mutation_privilege(field) {
+            Some(Some(privilege)) => privilege,
+            Some(None) => return Ok(()),
+            None => return Err(anyhow!("Mutation {} is not covered by the policy", field)),
+        };
+        let identity = identity
+            .ok_or_else(|| anyhow!("Authentication required: no identity in request context"))?;
+        self.authorize_privilege(identity, privilege)
+    }
+
+    /// Check that `identity` may use `privilege` directly, without a proposal
+    pub fn authorize_privilege(&self, identity: &str, privilege: &str) -> Result<()> {
+        if !self.has_privilege(identity, privilege) {
+            return Err(anyhow!("Identity {} lacks the {} privilege", identity, privilege));
+        }
+        if let Some(policy) = self.requires_proposal(privilege) {
+            return Err(anyhow!(
+                "{} needs {} approval(s) and a {} hour timelock: submit it with proposeMutation",
+                privilege,
+                policy.approvals,
+                policy.timelock_hours
+            ));
+        }
+        Ok(())
+    }
+
     /// Propose a mutation (creates proposal requiring approval)
     pub fn propose_mutation(
         &mut self,
@@ -240,6 +363,9 @@ pub fn propose_mutation(
         let policy = self.get_mutation_policy(mutation_name)
             .ok_or_else(|| anyhow!("Unknown mutation: {}", mutation_name))?;
 
+        // Refuse payloads that could never execute
+        ProposalPayload::parse(mutation_name, &payload)?;
+
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)?
             .as_secs();
@@ -254,6 +380,9 @@ pub fn propose_mutation(
             timelock_until,
             approvals: vec![proposer.to_string()], // Proposer auto-approves
             required_approvals: policy.approvals,
+            result: None,
+            error: None,
+            executed_at: None,
             status: if policy.timelock_hours > 0 {
                 ProposalStatus::TimelockActive
             } else if policy.approvals <= 1 {
@@ -298,10 +427,10 @@ pub fn approve_proposal(
         proposal.approvals.push(approver.to_string());
 
         // Update status if enough approvals
-        if proposal.approvals.len() as u32 >= proposal.required_approvals {
-            if proposal.status == ProposalStatus::Pending {
-                proposal.status = ProposalStatus::Approved;
-            }
+        if proposal.approvals.len() as u32 >= proposal.required_approvals
+            && proposal.status == ProposalStatus::Pending
+        {
+            proposal.status = ProposalStatus::Approved;
         }
 
         Ok(proposal.clone())
@@ -335,7 +464,8 @@ pub fn can_execute_proposal(&self, proposal_id: &str) -> Result<bool> {
         Ok(true)
     }
 
-    /// Execute proposal (marks as executed)
+    /// Execute proposal (marks as executed, before its payload runs, so that
+    /// it runs once)
     pub fn execute_proposal(&mut self, proposal_id: &str) -> Result<MutationProposal> {
         if !self.can_execute_proposal(proposal_id)? {
             return Err(anyhow!("Proposal cannot be executed yet"));
@@ -348,6 +478,30 @@ pub fn execute_proposal(&mut self, proposal_id: &str) -> Result<MutationProposal
         Ok(proposal.clone())
     }
 
+    /// Record the outcome of an executed proposal's payload; a failed one
+    /// leaves the proposal `Failed`
+    pub fn record_outcome(
+        &mut self,
+        proposal_id: &str,
+        outcome: std::result::Result<JsonValue, String>,
+    ) -> Result<MutationProposal> {
+        let proposal = self.proposals.get_mut(proposal_id)
+            .ok_or_else(|| anyhow!("Proposal not found: {}", proposal_id))?;
+        if proposal.status != ProposalStatus::Executed || proposal.executed_at.is_some() {
+            return Err(anyhow!("Proposal {} is not being executed", proposal_id));
+        }
+
+        proposal.executed_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+        match outcome {
+            Ok(result) => proposal.result = Some(result),
+            Err(error) => {
+                proposal.error = Some(error);
+                proposal.status = ProposalStatus::Failed;
+            }
+        }
+        Ok(proposal.clone())
+    }
+
     /// Get all proposals
     pub fn get_proposals(&self) -> Vec<MutationProposal> {
         self.proposals.values().cloned().collect()
@@ -359,7 +513,7 @@ pub fn get_proposal(&self, proposal_id: &str) -> Option<&MutationProposal> {
     }
 
     /// Check rate limit for identity
-    pub fn check_rate_limit(&self, identity: &str, requests_in_window: u32) -> bool {
+    pub fn check_rate_limit(&self, _identity: &str, requests_in_window: u32) -> bool {
         requests_in_window <= self.policy.constraints.max_rate_rpm
     }
 
@@ -369,6 +523,55 @@ pub fn policy(&self) -> &Policy {
     }
 }
 
+/// Authorize a DNS record change by identity: it needs the `mutate_dns`
+/// privilege, without approvals or a timelock, and DNS operations consent.
+///
+/// GraphQL record mutations and RFC 2136 UPDATE both go through here, so a
+/// change is authorized the same way whichever way it arrives; the privilege
+/// check is the one [`PolicyGuard`] makes.
+pub async fn authorize_dns_change(
+    enforcer: &RwLock<PolicyEnforcer>,
+    consent: &ConsentClient,
+    identity: &str,
+) -> Result<()> {
+    enforcer.read().await.authorize_privilege(identity, MUTATE_DNS)?;
+    consent::require_dns_consent(consent, identity).await
+}
+
+/// Checks every GraphQL mutation against the policy before it runs; see
+/// [`PolicyEnforcer::authorize_mutation`].
+pub struct PolicyGuard;
+
+impl ExtensionFactory for PolicyGuard {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PolicyGuardExtension)
+    }
+}
+
+struct PolicyGuardExtension;
+
+#[async_trait::async_trait]
+impl Extension for PolicyGuardExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<GraphQLValue>> {
+        if info.parent_type == MutationRoot::type_name() {
+            let identity = ctx.data_opt::<String>().map(String::as_str);
+            let authorized = match ctx.data_opt::<Arc<RwLock<PolicyEnforcer>>>() {
+                Some(enforcer) => enforcer.read().await.authorize_mutation(info.name, identity),
+                None => Err(anyhow!("No policy loaded")),
+            };
+            if let Err(e) = authorized {
+                return Err(ServerError::new(e.to_string(), None));
+            }
+        }
+        next.run(ctx, info).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -418,4 +621,155 @@ fn test_privilege_check() {
         assert!(enforcer.has_privilege("identity:alice", "mutate_dns"));
         assert!(!enforcer.has_privilege("identity:bob", "mutate_dns"));
     }
+
+    #[tokio::test]
+    async fn test_authorize_dns_change_requires_privilege() {
+        let enforcer = RwLock::new(PolicyEnforcer {
+            policy: Policy {
+                version: "0.1.0".to_string(),
+                capabilities: HashMap::new(),
+                mutations: vec![],
+                roles: vec![],
+                routes: vec![],
+                consent_bindings: vec![],
+                constraints: Constraints {
+                    require_mtls: true,
+                    log_all_mutations: true,
+                    max_rate_rpm: 120,
+                },
+            },
+            proposals: HashMap::new(),
+        });
+        // Refused before the consent API is asked
+        let consent = ConsentClient::new("http://127.0.0.1:9".to_string());
+
+        let err = authorize_dns_change(&enforcer, &consent, "identity:bob").await.unwrap_err();
+        assert!(err.to_string().contains("mutate_dns"));
+
+        // A timelocked privilege is refused as the guard refuses the mutations
+        {
+            let mut enforcer = enforcer.write().await;
+            enforcer.policy.mutations.push(MutationPolicy {
+                name: "mutate_dns".to_string(),
+                description: "Change DNS records".to_string(),
+                approvals: 1,
+                timelock_hours: 1,
+            });
+            enforcer.policy.roles.push(Role {
+                name: "operator".to_string(),
+                members: vec!["identity:alice".to_string()],
+                privileges: vec!["mutate_dns".to_string()],
+            });
+        }
+        let err = authorize_dns_change(&enforcer, &consent, "identity:alice").await.unwrap_err();
+        assert!(err.to_string().contains("proposeMutation"));
+        let guard = enforcer
+            .read()
+            .await
+            .authorize_mutation("createDnsRecord", Some("identity:alice"));
+        assert_eq!(guard.unwrap_err().to_string(), err.to_string());
+    }
+
+    #[test]
+    fn test_authorize_mutation() {
+        let mut enforcer = PolicyEnforcer {
+            policy: Policy {
+                version: "0.1.0".to_string(),
+                capabilities: HashMap::new(),
+                mutations: vec![MutationPolicy {
+                    name: "rotate_keys".to_string(),
+                    description: "Rotate DNSSEC keys".to_string(),
+                    approvals: 2,
+                    timelock_hours: 24,
+                }],
+                roles: vec![Role {
+                    name: "maintainer".to_string(),
+                    members: vec!["identity:alice".to_string()],
+                    privileges: vec!["mutate_dns".to_string(), "rotate_keys".to_string()],
+                }],
+                routes: vec![],
+                consent_bindings: vec![],
+                constraints: Constraints {
+                    require_mtls: true,
+                    log_all_mutations: true,
+                    max_rate_rpm: 120,
+                },
+            },
+            proposals: HashMap::new(),
+        };
+
+        assert!(enforcer.authorize_mutation("deleteDnsRecord", Some("identity:alice")).is_ok());
+        let err = enforcer.authorize_mutation("deleteDnsRecord", Some("identity:bob")).unwrap_err();
+        assert!(err.to_string().contains("mutate_dns"));
+        let err = enforcer.authorize_mutation("anchorToBlockchain", None).unwrap_err();
+        assert!(err.to_string().contains("Authentication required"));
+
+        // Approvals or a timelock force the proposal workflow
+        let err = enforcer
+            .authorize_mutation("rotateDnssecKeys", Some("identity:alice"))
+            .unwrap_err();
+        assert!(err.to_string().contains("proposeMutation"));
+        assert!(enforcer.authorize_mutation("proposeMutation", Some("identity:bob")).is_ok());
+        enforcer.policy.mutations.push(MutationPolicy {
+            name: "mutate_dns".to_string(),
+            description: "Apply GraphQL DNS mutations".to_string(),
+            approvals: 1,
+            timelock_hours: 1,
+        });
+        assert!(enforcer.authorize_mutation("createDnsRecord", Some("identity:alice")).is_err());
+
+        // Mutations the policy does not cover are refused
+        assert!(enforcer.authorize_mutation("dropDatabase", Some("identity:alice")).is_err());
+    }
+
+    #[test]
+    fn test_proposal_payload_and_outcome() {
+        let mut enforcer = PolicyEnforcer {
+            policy: Policy {
+                version: "0.1.0".to_string(),
+                capabilities: HashMap::new(),
+                mutations: vec![MutationPolicy {
+                    name: "mutate_dns".to_string(),
+                    description: "Apply GraphQL DNS mutations".to_string(),
+                    approvals: 1,
+                    timelock_hours: 0,
+                }],
+                roles: vec![Role {
+                    name: "maintainer".to_string(),
+                    members: vec!["identity:alice".to_string()],
+                    privileges: vec!["mutate_dns".to_string()],
+                }],
+                routes: vec![],
+                consent_bindings: vec![],
+                constraints: Constraints {
+                    require_mtls: true,
+                    log_all_mutations: true,
+                    max_rate_rpm: 120,
+                },
+            },
+            proposals: HashMap::new(),
+        };
+
+        // The payload is checked against the policy's schema when proposed
+        let malformed = serde_json::json!({ "deleteZone": 42 });
+        assert!(enforcer.propose_mutation("mutate_dns", "identity:alice", malformed).is_err());
+        assert!(enforcer.get_proposals().is_empty());
+
+        let payload = serde_json::json!({ "deleteZone": "example.com" });
+        let proposal = enforcer.propose_mutation("mutate_dns", "identity:alice", payload).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert!(enforcer.record_outcome(&proposal.id, Ok(JsonValue::Null)).is_err());
+
+        enforcer.execute_proposal(&proposal.id).unwrap();
+        let failed = enforcer
+            .record_outcome(&proposal.id, Err("Zone not found: example.com".to_string()))
+            .unwrap();
+        assert_eq!(failed.status, ProposalStatus::Failed);
+        assert!(failed.executed_at.is_some());
+        assert_ne!(failed.content_hash(), proposal.content_hash());
+
+        // Executed once only
+        assert!(enforcer.execute_proposal(&proposal.id).is_err());
+        assert!(enforcer.record_outcome(&proposal.id, Ok(JsonValue::Null)).is_err());
+    }
 }
@@ -0,0 +1,439 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Mutation proposal payloads and their execution
+//!
+//! Each mutation policy has a payload schema: a GraphQL input type the JSON
+//! payload is parsed into when the proposal is made, so nothing malformed
+//! waits out its approvals and timelock. A `mutate_dns` payload names one DNS
+//! operation and a `rotate_keys` payload one DNSSEC key operation, each keyed
+//! by the mutation it stands for:
+//!
+//! ```json
+//! { "rotateDnssecKeys": { "zone": "example.com", "immediate": true } }
+//! ```
+//!
+//! Executing an approved proposal dispatches its payload to the operation the
+//! mutation runs, on behalf of the proposer; the result or the error is
+//! recorded on the proposal. Proposals are readable by anyone, so a TSIG key
+//! generated by one is recorded without its secret, which the proposer claims
+//! once with `claimTsigKeySecret`.
+
+use crate::{
+    authority::normalize_name,
+    blockchain, changeset,
+    consent::{self, ConsentClient},
+    db::Database,
+    denial,
+    dnssec::DNSSECManager,
+    models::{
+        DNSRecordInput, DNSSECAlgorithm, DNSSECKeyRole, DSDigestType, NSEC3Input, RecordChangeInput,
+        TSIGAlgorithm, TransferPeerInput, ZoneImportMode, ZoneInput,
+    },
+    policy::{MUTATE_DNS, ROTATE_KEYS},
+    records, rollover, xfr, zonefile, zones,
+};
+use anyhow::{anyhow, Result};
+use async_graphql::{InputObject, InputType, OneofObject, Pos, ID};
+use serde_json::{json, Value as JsonValue};
+
+/// Payload of a `mutate_dns` proposal
+#[derive(Debug, Clone, OneofObject)]
+pub enum DnsOperation {
+    /// A record to create, as by `createDnsRecord`
+    CreateDnsRecord(DNSRecordInput),
+    /// A record to rewrite, as by `updateDnsRecord`
+    UpdateDnsRecord(UpdateRecordPayload),
+    /// ID of a record to delete, as by `deleteDnsRecord`
+    DeleteDnsRecord(ID),
+    /// Record changes to one zone, applied atomically as by `applyChangeset`
+    ApplyChangeset(ChangesetPayload),
+    /// A zone to create, as by `createZone`
+    CreateZone(ZoneInput),
+    /// Apex of a zone to delete, as by `deleteZone`
+    DeleteZone(String),
+    /// A master file to import, as by `importZone`
+    ImportZone(ZoneImportPayload),
+    /// A record version to restore, as by `revertRecord`
+    RevertRecord(RevertRecordPayload),
+    /// A zone's new transfer ACL, as by `setZoneTransferAcl`
+    SetZoneTransferAcl(TransferAclPayload),
+    /// A TSIG key to generate, as by `createTsigKey`
+    CreateTsigKey(CreateTsigKeyPayload),
+    /// Name of a TSIG key to delete, as by `deleteTsigKey`
+    DeleteTsigKey(String),
+    /// A record to anchor, as by `anchorToBlockchain`
+    AnchorToBlockchain(AnchorPayload),
+}
+
+/// Arguments of `updateDnsRecord`
+#[derive(Debug, Clone, InputObject)]
+pub struct UpdateRecordPayload {
+    pub id: ID,
+    pub input: DNSRecordInput,
+}
+
+/// Arguments of `applyChangeset`
+#[derive(Debug, Clone, InputObject)]
+pub struct ChangesetPayload {
+    pub zone: String,
+    pub changes: Vec<RecordChangeInput>,
+}
+
+/// Arguments of `importZone`
+#[derive(Debug, Clone, InputObject)]
+pub struct ZoneImportPayload {
+    pub zone: String,
+    pub zone_file: String,
+    #[graphql(default)]
+    pub mode: ZoneImportMode,
+}
+
+/// Arguments of `revertRecord`
+#[derive(Debug, Clone, InputObject)]
+pub struct RevertRecordPayload {
+    pub id: ID,
+    pub version: i32,
+}
+
+/// Arguments of `setZoneTransferAcl`
+#[derive(Debug, Clone, InputObject)]
+pub struct TransferAclPayload {
+    pub apex: String,
+    pub peers: Vec<TransferPeerInput>,
+}
+
+/// Arguments of `createTsigKey`
+#[derive(Debug, Clone, InputObject)]
+pub struct CreateTsigKeyPayload {
+    pub name: String,
+    #[graphql(default)]
+    pub algorithm: TSIGAlgorithm,
+}
+
+/// Arguments of `anchorToBlockchain`
+#[derive(Debug, Clone, InputObject)]
+pub struct AnchorPayload {
+    pub record_id: ID,
+    pub network: String,
+}
+
+/// Payload of a `rotate_keys` proposal
+#[derive(Debug, Clone, OneofObject)]
+pub enum KeyOperation {
+    /// As by `enableDnssec`
+    EnableDnssec(EnableDnssecPayload),
+    /// As by `rotateDnssecKeys`
+    RotateDnssecKeys(RotateKeysPayload),
+    /// As by `disableDnssec`
+    DisableDnssec(DisableDnssecPayload),
+    /// As by `startKeyRollover`
+    StartKeyRollover(StartRolloverPayload),
+    /// As by `advanceKeyRollover`
+    AdvanceKeyRollover(AdvanceRolloverPayload),
+    /// As by `setDenialOfExistence`
+    SetDenialOfExistence(DenialPayload),
+}
+
+impl KeyOperation {
+    /// The zone the operation acts on
+    pub fn zone(&self) -> &str {
+        match self {
+            Self::EnableDnssec(p) => &p.zone,
+            Self::RotateDnssecKeys(p) => &p.zone,
+            Self::DisableDnssec(p) => &p.zone,
+            Self::StartKeyRollover(p) => &p.zone,
+            Self::AdvanceKeyRollover(p) => &p.zone,
+            Self::SetDenialOfExistence(p) => &p.zone,
+        }
+    }
+}
+
+/// Arguments of `enableDnssec`
+#[derive(Debug, Clone, InputObject)]
+pub struct EnableDnssecPayload {
+    pub zone: String,
+    #[graphql(default_with = "vec![DNSSECAlgorithm::HybridEd448Dilithium5]")]
+    pub algorithms: Vec<DNSSECAlgorithm>,
+    pub digest_type: Option<DSDigestType>,
+    pub nsec3: Option<NSEC3Input>,
+}
+
+/// Arguments of `rotateDnssecKeys`
+#[derive(Debug, Clone, InputObject)]
+pub struct RotateKeysPayload {
+    pub zone: String,
+    pub algorithms: Option<Vec<DNSSECAlgorithm>>,
+    pub digest_type: Option<DSDigestType>,
+    #[graphql(default)]
+    pub immediate: bool,
+}
+
+/// Arguments of `disableDnssec`
+#[derive(Debug, Clone, InputObject)]
+pub struct DisableDnssecPayload {
+    pub zone: String,
+    #[graphql(default)]
+    pub ds_removed: bool,
+}
+
+/// Arguments of `startKeyRollover`
+#[derive(Debug, Clone, InputObject)]
+pub struct StartRolloverPayload {
+    pub zone: String,
+    pub role: DNSSECKeyRole,
+    pub digest_type: Option<DSDigestType>,
+    pub parent_ds_ttl: Option<i32>,
+}
+
+/// Arguments of `advanceKeyRollover`
+#[derive(Debug, Clone, InputObject)]
+pub struct AdvanceRolloverPayload {
+    pub zone: String,
+    pub role: DNSSECKeyRole,
+}
+
+/// Arguments of `setDenialOfExistence`
+#[derive(Debug, Clone, InputObject)]
+pub struct DenialPayload {
+    pub zone: String,
+    pub nsec3: Option<NSEC3Input>,
+}
+
+/// A proposal payload, parsed by the schema of its mutation policy
+#[derive(Debug, Clone)]
+pub enum ProposalPayload {
+    MutateDns(DnsOperation),
+    RotateKeys(KeyOperation),
+}
+
+impl ProposalPayload {
+    /// Parse the payload of a proposal for mutation policy `mutation_name`
+    pub fn parse(mutation_name: &str, payload: &JsonValue) -> Result<Self> {
+        match mutation_name {
+            MUTATE_DNS => parse_input(payload).map(Self::MutateDns),
+            ROTATE_KEYS => parse_input(payload).map(Self::RotateKeys),
+            _ => Err(anyhow!("No payload schema for {}", mutation_name)),
+        }
+    }
+}
+
+fn parse_input<T: InputType>(payload: &JsonValue) -> Result<T> {
+    let value = async_graphql::Value::from_json(payload.clone())?;
+    T::parse(Some(value)).map_err(|e| {
+        let message = e.into_server_error(Pos::default()).message;
+        anyhow!("Invalid payload: {}", message)
+    })
+}
+
+/// Run `payload` on behalf of `proposer` and return its result. `db` should
+/// act as the proposer, so record history names them.
+pub async fn execute(
+    db: &Database,
+    manager: &DNSSECManager,
+    consent: &ConsentClient,
+    proposer: &str,
+    payload: ProposalPayload,
+) -> Result<JsonValue> {
+    match payload {
+        ProposalPayload::MutateDns(operation) => {
+            consent::require_dns_consent(consent, proposer).await?;
+            execute_dns(db, manager, proposer, operation).await
+        }
+        ProposalPayload::RotateKeys(operation) => {
+            execute_keys(db, manager, proposer, operation).await
+        }
+    }
+}
+
+async fn execute_dns(
+    db: &Database,
+    manager: &DNSSECManager,
+    proposer: &str,
+    operation: DnsOperation,
+) -> Result<JsonValue> {
+    let result = match operation {
+        DnsOperation::CreateDnsRecord(input) => {
+            serde_json::to_value(records::insert(db, manager, input, None).await?)?
+        }
+        DnsOperation::UpdateDnsRecord(p) => {
+            let record = db.get_record(&p.id).await?;
+            serde_json::to_value(records::rewrite(db, manager, record, p.input).await?)?
+        }
+        DnsOperation::DeleteDnsRecord(id) => {
+            let record = db.get_record(&id).await?;
+            records::remove(db, manager, record).await?;
+            json!({ "deleted": id })
+        }
+        DnsOperation::ApplyChangeset(p) => {
+            let result = changeset::commit(db, manager, &p.zone, p.changes).await?;
+            serde_json::to_value(result)?
+        }
+        DnsOperation::CreateZone(input) => {
+            serde_json::to_value(zones::create(db, input, proposer).await?)?
+        }
+        DnsOperation::DeleteZone(apex) => {
+            let zone = zones::require_owner(db, &apex, proposer).await?;
+            zones::delete(db, &zone.apex).await?;
+            json!({ "deleted": zone.apex })
+        }
+        DnsOperation::ImportZone(p) => {
+            let result =
+                zonefile::import_zone_as(db, manager, &p.zone, &p.zone_file, p.mode, proposer)
+                    .await?;
+            serde_json::to_value(result)?
+        }
+        DnsOperation::RevertRecord(p) => {
+            serde_json::to_value(records::revert(db, manager, &p.id, p.version).await?)?
+        }
+        DnsOperation::SetZoneTransferAcl(p) => {
+            let zone = zones::require_owner(db, &p.apex, proposer).await?;
+            serde_json::to_value(xfr::set_acl(db, &zone.apex, p.peers).await?)?
+        }
+        DnsOperation::CreateTsigKey(p) => {
+            // The secret stays out of the proposal until the proposer claims it
+            let key = xfr::create_key(db, manager, &p.name, p.algorithm, proposer, true).await?;
+            serde_json::to_value(key.key)?
+        }
+        DnsOperation::DeleteTsigKey(name) => {
+            json!({ "deleted": xfr::delete_key(db, &name, proposer).await? })
+        }
+        DnsOperation::AnchorToBlockchain(p) => {
+            let provenance = blockchain::anchor_record(db, &p.record_id, &p.network).await?;
+            serde_json::to_value(provenance)?
+        }
+    };
+    Ok(result)
+}
+
+async fn execute_keys(
+    db: &Database,
+    manager: &DNSSECManager,
+    proposer: &str,
+    operation: KeyOperation,
+) -> Result<JsonValue> {
+    zones::require_owner(db, operation.zone(), proposer).await?;
+    let zone = match operation {
+        KeyOperation::EnableDnssec(p) => {
+            let nsec3 = p.nsec3.map(denial::nsec3_config).transpose()?;
+            rollover::enable(db, manager, &p.zone, &p.algorithms, p.digest_type, nsec3).await?
+        }
+        KeyOperation::RotateDnssecKeys(p) => {
+            rollover::rotate(
+                db,
+                manager,
+                &p.zone,
+                p.algorithms,
+                p.digest_type,
+                p.immediate,
+            )
+            .await?
+        }
+        KeyOperation::DisableDnssec(p) => {
+            rollover::disable(db, manager, &p.zone, p.ds_removed).await?
+        }
+        KeyOperation::StartKeyRollover(p) => {
+            let (zone, role) = (&p.zone, p.role);
+            rollover::start(db, manager, zone, role, p.digest_type, p.parent_ds_ttl).await?;
+            zones::bump_serial(db, manager, zone).await?;
+            db.get_dnssec_zone(&normalize_name(zone)).await?
+        }
+        KeyOperation::AdvanceKeyRollover(p) => {
+            rollover::advance(db, manager, &p.zone, p.role).await?;
+            zones::bump_serial(db, manager, &p.zone).await?;
+            db.get_dnssec_zone(&normalize_name(&p.zone)).await?
+        }
+        KeyOperation::SetDenialOfExistence(p) => {
+            let zone = denial::set_denial_of_existence(db, manager, &p.zone, p.nsec3).await?;
+            zones::bump_serial(db, manager, &zone.zone).await?;
+            zone
+        }
+    };
+    Ok(serde_json::to_value(zone)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::ChangeAction, policy::MUTATION_PRIVILEGES};
+    use async_graphql::registry::{MetaType, Registry};
+
+    /// The mutations a payload type has a variant for
+    fn payload_mutations<T: InputType>() -> Vec<String> {
+        let mut registry = Registry::default();
+        T::create_type_info(&mut registry);
+        let name = T::type_name();
+        match &registry.types[name.as_ref()] {
+            MetaType::InputObject { input_fields, .. } => input_fields.keys().cloned().collect(),
+            other => panic!("{} is not an input object: {:?}", name, other.name()),
+        }
+    }
+
+    #[test]
+    fn test_every_guarded_mutation_has_a_payload() {
+        let dns = payload_mutations::<DnsOperation>();
+        let keys = payload_mutations::<KeyOperation>();
+        for (mutation, privilege) in MUTATION_PRIVILEGES {
+            let payloads = match *privilege {
+                Some(MUTATE_DNS) => &dns,
+                Some(ROTATE_KEYS) => &keys,
+                Some(other) => panic!("{} needs {}, which has no payload schema", mutation, other),
+                None => continue,
+            };
+            assert!(payloads.iter().any(|p| p == mutation), "no payload for {}", mutation);
+        }
+        // The payloads and the guard agree on each mutation's privilege
+        for (payloads, privilege) in [(&dns, MUTATE_DNS), (&keys, ROTATE_KEYS)] {
+            for payload in payloads {
+                let guarded = crate::policy::mutation_privilege(payload);
+                assert_eq!(guarded, Some(Some(privilege)), "{}", payload);
+            }
+        }
+    }
+
+    #[test]
+    fn test_payloads_are_parsed_by_their_policy() {
+        let payload = json!({
+            "applyChangeset": {
+                "zone": "example.com",
+                "changes": [{
+                    "action": "CREATE",
+                    "record": { "name": "www.example.com", "type": "A", "value": "192.0.2.1" },
+                }],
+            },
+        });
+        match ProposalPayload::parse(MUTATE_DNS, &payload).unwrap() {
+            ProposalPayload::MutateDns(DnsOperation::ApplyChangeset(p)) => {
+                assert_eq!(p.zone, "example.com");
+                assert_eq!(p.changes[0].action, ChangeAction::Create);
+            }
+            other => panic!("unexpected payload {:?}", other),
+        }
+
+        let payload = json!({ "rotateDnssecKeys": { "zone": "example.com" } });
+        match ProposalPayload::parse(ROTATE_KEYS, &payload).unwrap() {
+            ProposalPayload::RotateKeys(KeyOperation::RotateDnssecKeys(p)) => {
+                assert!(!p.immediate);
+                assert!(p.algorithms.is_none());
+            }
+            other => panic!("unexpected payload {:?}", other),
+        }
+
+        // A payload must match its policy's schema
+        assert!(ProposalPayload::parse(MUTATE_DNS, &payload).is_err());
+        assert!(ProposalPayload::parse("publish_manifest", &payload).is_err());
+    }
+
+    #[test]
+    fn test_malformed_payloads_are_refused() {
+        let malformed = [
+            json!({}),
+            json!({ "deleteZone": "example.com", "createZone": { "apex": "example.org" } }),
+            json!({ "dropZone": "example.com" }),
+            json!({ "importZone": { "zone": "example.com" } }),
+            json!({ "deleteZone": 42 }),
+        ];
+        for payload in malformed {
+            let err = ProposalPayload::parse(MUTATE_DNS, &payload).unwrap_err();
+            assert!(err.to_string().starts_with("Invalid payload"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,1508 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Per-type RDATA parsing and normalisation
+//!
+//! Record values are parsed strictly for their type and stored in a single
+//! presentation form: names lowercase and absolute, numbers checked against
+//! their field widths, TXT and CAA strings quoted, TLSA data in lowercase hex.
+//! A normalised value must also parse into wire RDATA, so whatever is stored
+//! can be served.
+//!
+//! hickory parses the presentation form of the classic types. SVCB/HTTPS,
+//! DNSSEC keys and digests, SSHFP, NAPTR, URI, OPENPGPKEY, SMIMEA and LOC are
+//! encoded here instead and served as opaque RDATA: their normalised form is
+//! the value decoded back from the wire, which is also how RRs of those types
+//! arriving in an UPDATE are read.
+
+use crate::{
+    authority::wire_type,
+    dnssec::{dnskey_rdata, DNSKEY_PROTOCOL},
+    error::{AppError, Result},
+    models::{DNSRecordInput, DNSRecordType},
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hickory_proto::{
+    rr::{rdata::NULL, Name, RData},
+    serialize::txt::RDataParser,
+};
+use std::{
+    collections::BTreeMap,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// Longest character-string (RFC 1035 §3.3).
+const MAX_STRING: usize = 255;
+
+/// Longest label, and longest name in presentation form without the root dot
+/// (RFC 1035 §2.3.4).
+const MAX_LABEL: usize = 63;
+const MAX_NAME: usize = 253;
+
+/// SvcParamKeys 0-6 by name (RFC 9460 §14.3.2); others are `keyNNNNN`.
+const SVC_PARAM_KEYS: [&str; 7] = [
+    "mandatory",
+    "alpn",
+    "no-default-alpn",
+    "port",
+    "ipv4hint",
+    "ech",
+    "ipv6hint",
+];
+
+/// LOC latitude and longitude count thousandths of an arc-second from 2^31 at
+/// the equator and prime meridian, altitude centimetres from 100 km below the
+/// WGS 84 spheroid (RFC 1876 §2).
+const LOC_EQUATOR: i64 = 1 << 31;
+const LOC_ALTITUDE_BASE: i64 = 10_000_000;
+
+/// The normalised RDATA of a record input, from `value` or the typed input
+/// for its type; exactly one of them must be given.
+pub fn input_value(input: &DNSRecordInput) -> Result<String> {
+    let typed = [
+        (
+            DNSRecordType::MX,
+            input
+                .mx
+                .as_ref()
+                .map(|mx| format!("{} {}", mx.preference, mx.exchange)),
+        ),
+        (
+            DNSRecordType::SRV,
+            input.srv.as_ref().map(|srv| {
+                format!(
+                    "{} {} {} {}",
+                    srv.priority, srv.weight, srv.port, srv.target
+                )
+            }),
+        ),
+        (
+            DNSRecordType::CAA,
+            input.caa.as_ref().map(|caa| {
+                format!(
+                    "{} {} {}",
+                    caa.flags.unwrap_or(0),
+                    caa.tag,
+                    quote(&caa.value)
+                )
+            }),
+        ),
+        (
+            DNSRecordType::TLSA,
+            input.tlsa.as_ref().map(|tlsa| {
+                format!(
+                    "{} {} {} {}",
+                    tlsa.usage, tlsa.selector, tlsa.matching_type, tlsa.certificate_data
+                )
+            }),
+        ),
+    ];
+
+    let mut given = typed.into_iter().filter_map(|(t, value)| Some((t, value?)));
+    let value = match (given.next(), &input.value) {
+        (None, Some(value)) => value.clone(),
+        (None, None) => {
+            return Err(AppError::InvalidRecord(format!(
+                "{} record needs a value",
+                input.record_type
+            )))
+        }
+        (Some(_), Some(_)) => {
+            return Err(AppError::InvalidRecord(
+                "give either value or a typed input, not both".into(),
+            ))
+        }
+        (Some((record_type, value)), None) => {
+            if record_type != input.record_type || given.next().is_some() {
+                return Err(AppError::InvalidRecord(format!(
+                    "typed input does not match record type {}",
+                    input.record_type
+                )));
+            }
+            value
+        }
+    };
+    normalize(input.record_type, &value)
+}
+
+/// Parse a value as RDATA of `record_type` and return it in normalised
+/// presentation form.
+pub fn normalize(record_type: DNSRecordType, value: &str) -> Result<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(AppError::InvalidRecord(
+            "Record value cannot be empty".into(),
+        ));
+    }
+
+    let normalized = match record_type {
+        DNSRecordType::A => Ipv4Addr::from_str(value)
+            .map_err(|_| invalid("IPv4 address", value))?
+            .to_string(),
+        DNSRecordType::AAAA => Ipv6Addr::from_str(value)
+            .map_err(|_| invalid("IPv6 address", value))?
+            .to_string(),
+        DNSRecordType::CNAME => domain_name(value)?,
+        DNSRecordType::NS | DNSRecordType::PTR => hostname(value)?,
+        DNSRecordType::MX => {
+            let [preference, exchange] = fields(record_type, value)?;
+            format!(
+                "{} {}",
+                number::<u16>("MX preference", preference)?,
+                target(exchange)?
+            )
+        }
+        DNSRecordType::SRV => {
+            let [priority, weight, port, target_name] = fields(record_type, value)?;
+            format!(
+                "{} {} {} {}",
+                number::<u16>("SRV priority", priority)?,
+                number::<u16>("SRV weight", weight)?,
+                number::<u16>("SRV port", port)?,
+                target(target_name)?
+            )
+        }
+        DNSRecordType::CAA => caa(value)?,
+        DNSRecordType::TLSA => tlsa(value)?,
+        DNSRecordType::SOA => {
+            let [mname, rname, serial, refresh, retry, expire, minimum] =
+                fields(record_type, value)?;
+            let mut soa = vec![hostname(mname)?, mailbox(rname)?];
+            for (field, token) in [
+                ("SOA serial", serial),
+                ("SOA refresh", refresh),
+                ("SOA retry", retry),
+                ("SOA expire", expire),
+                ("SOA minimum", minimum),
+            ] {
+                soa.push(number::<u32>(field, token)?.to_string());
+            }
+            soa.join(" ")
+        }
+        DNSRecordType::TXT => char_strings(value)?
+            .iter()
+            .map(|s| quote(s))
+            .collect::<Vec<_>>()
+            .join(" "),
+        DNSRecordType::HTTPS
+        | DNSRecordType::SVCB
+        | DNSRecordType::DS
+        | DNSRecordType::DNSKEY
+        | DNSRecordType::CDS
+        | DNSRecordType::CDNSKEY
+        | DNSRecordType::SSHFP
+        | DNSRecordType::NAPTR
+        | DNSRecordType::URI
+        | DNSRecordType::OPENPGPKEY
+        | DNSRecordType::SMIMEA
+        | DNSRecordType::LOC => from_wire(record_type, &to_wire(record_type, value)?)?,
+    };
+
+    to_rdata(record_type, &normalized)?;
+    Ok(normalized)
+}
+
+/// Wire RDATA for a stored value.
+pub fn to_rdata(record_type: DNSRecordType, value: &str) -> Result<RData> {
+    if opaque(record_type) {
+        return Ok(RData::Unknown {
+            code: wire_type(record_type),
+            rdata: NULL::with(to_wire(record_type, value)?),
+        });
+    }
+    RData::try_from_str(wire_type(record_type), value)
+        .map_err(|e| AppError::InvalidRecord(format!("{} {}: {}", record_type, value, e)))
+}
+
+/// Whether RDATA of `record_type` is encoded here rather than by hickory.
+pub fn opaque(record_type: DNSRecordType) -> bool {
+    !matches!(
+        record_type,
+        DNSRecordType::A
+            | DNSRecordType::AAAA
+            | DNSRecordType::CNAME
+            | DNSRecordType::MX
+            | DNSRecordType::TXT
+            | DNSRecordType::SRV
+            | DNSRecordType::CAA
+            | DNSRecordType::TLSA
+            | DNSRecordType::NS
+            | DNSRecordType::SOA
+            | DNSRecordType::PTR
+    )
+}
+
+/// Quote a string for presentation format, escaping `"` and `\`.
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A domain name: lowercase and absolute. Labels may hold letters, digits,
+/// hyphens and underscores (service labels such as `_dmarc`).
+fn domain_name(value: &str) -> Result<String> {
+    let name = value
+        .strip_suffix('.')
+        .unwrap_or(value)
+        .to_ascii_lowercase();
+    if name.is_empty() || name.len() > MAX_NAME {
+        return Err(invalid("domain name", value));
+    }
+    for label in name.split('.') {
+        let valid = !label.is_empty()
+            && label.len() <= MAX_LABEL
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(invalid("domain name", value));
+        }
+    }
+    Ok(format!("{}.", name))
+}
+
+/// A host name: a domain name of letters, digits and hyphens only (RFC 1123 §2.1).
+fn hostname(value: &str) -> Result<String> {
+    let name = domain_name(value).map_err(|_| invalid("host name", value))?;
+    if name.contains('_') {
+        return Err(invalid("host name", value));
+    }
+    Ok(name)
+}
+
+/// An MX exchange or SRV target: a host name, or `.` for none (RFC 7505, RFC 2782).
+fn target(value: &str) -> Result<String> {
+    if value == "." {
+        return Ok(".".to_string());
+    }
+    hostname(value)
+}
+
+/// An SOA RNAME: a mailbox in domain-name form, whose first label may hold
+/// escaped dots (RFC 1035 §8).
+fn mailbox(value: &str) -> Result<String> {
+    let name = value
+        .strip_suffix('.')
+        .unwrap_or(value)
+        .to_ascii_lowercase();
+    if name.is_empty() || Name::from_str(&format!("{}.", name)).is_err() {
+        return Err(invalid("mailbox", value));
+    }
+    Ok(format!("{}.", name))
+}
+
+/// CAA: `flags tag value` (RFC 8659 §4.1). Only the issuer-critical flag is
+/// defined, and tags are ASCII letters and digits.
+fn caa(value: &str) -> Result<String> {
+    let mut parts = value.splitn(3, char::is_whitespace);
+    let (Some(flags), Some(tag), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("CAA record", value));
+    };
+    let flags = number::<u8>("CAA flags", flags)?;
+    if flags != 0 && flags != 128 {
+        return Err(invalid("CAA flags", &flags.to_string()));
+    }
+    if tag.is_empty() || tag.len() > 15 || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid("CAA tag", tag));
+    }
+
+    let rest = rest.trim();
+    let text = if rest.starts_with('"') {
+        match char_strings(rest)?.as_slice() {
+            [text] => text.clone(),
+            _ => return Err(invalid("CAA value", rest)),
+        }
+    } else {
+        rest.to_string()
+    };
+    Ok(format!(
+        "{} {} {}",
+        flags,
+        tag.to_ascii_lowercase(),
+        quote(&text)
+    ))
+}
+
+/// TLSA: `usage selector matching-type data` (RFC 6698 §2.2), the data in hex
+/// and as long as the matching type's digest.
+fn tlsa(value: &str) -> Result<String> {
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let [usage, selector, matching, data @ ..] = tokens.as_slice() else {
+        return Err(invalid("TLSA record", value));
+    };
+    let usage = number::<u8>("TLSA usage", usage)?;
+    let selector = number::<u8>("TLSA selector", selector)?;
+    let matching = number::<u8>("TLSA matching type", matching)?;
+    if usage > 3 {
+        return Err(invalid("TLSA usage", &usage.to_string()));
+    }
+    if selector > 1 {
+        return Err(invalid("TLSA selector", &selector.to_string()));
+    }
+
+    let data = data.concat().to_ascii_lowercase();
+    let valid_hex =
+        !data.is_empty() && data.len() % 2 == 0 && data.chars().all(|c| c.is_ascii_hexdigit());
+    if !valid_hex {
+        return Err(invalid("TLSA certificate data", &data));
+    }
+    let length = match matching {
+        0 => None,
+        1 => Some(32),
+        2 => Some(64),
+        _ => return Err(invalid("TLSA matching type", &matching.to_string())),
+    };
+    if let Some(length) = length.filter(|length| data.len() != length * 2) {
+        return Err(AppError::InvalidRecord(format!(
+            "TLSA matching type {} needs a {}-byte digest",
+            matching, length
+        )));
+    }
+    Ok(format!("{} {} {} {}", usage, selector, matching, data))
+}
+
+/// Wire RDATA of one of the [`opaque`] types from presentation form. Only the
+/// syntax is checked here; [`from_wire`] checks the fields.
+fn to_wire(record_type: DNSRecordType, value: &str) -> Result<Vec<u8>> {
+    let malformed = || invalid(&format!("{} record", record_type), value);
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let mut wire = Vec::new();
+    match record_type {
+        DNSRecordType::HTTPS | DNSRecordType::SVCB => {
+            let words = words(value)?;
+            let [priority, target, params @ ..] = words.as_slice() else {
+                return Err(malformed());
+            };
+            wire.extend(number::<u16>("SVCB priority", priority)?.to_be_bytes());
+            put_name(&mut wire, &name_or_root(target)?);
+            let mut encoded = BTreeMap::new();
+            for param in params {
+                let (key, value) = match param.split_once('=') {
+                    Some((key, value)) => (key, Some(value)),
+                    None => (param.as_str(), None),
+                };
+                let key = svc_key(key)?;
+                if encoded.insert(key, svc_value(key, value)?).is_some() {
+                    return Err(invalid("duplicate SvcParamKey", &svc_key_name(key)));
+                }
+            }
+            for (key, value) in encoded {
+                let length = u16::try_from(value.len())
+                    .map_err(|_| invalid("SvcParamValue for", &svc_key_name(key)))?;
+                wire.extend(key.to_be_bytes());
+                wire.extend(length.to_be_bytes());
+                wire.extend(value);
+            }
+        }
+        DNSRecordType::DS | DNSRecordType::CDS => {
+            let [tag, algorithm, digest_type, digest @ ..] = tokens.as_slice() else {
+                return Err(malformed());
+            };
+            wire.extend(number::<u16>("DS key tag", tag)?.to_be_bytes());
+            wire.push(number("DS algorithm", algorithm)?);
+            wire.push(number("DS digest type", digest_type)?);
+            wire.extend(hex::decode(digest.concat()).map_err(|_| invalid("DS digest", value))?);
+        }
+        DNSRecordType::DNSKEY | DNSRecordType::CDNSKEY => {
+            let [flags, protocol, algorithm, key @ ..] = tokens.as_slice() else {
+                return Err(malformed());
+            };
+            if number::<u8>("DNSKEY protocol", protocol)? != DNSKEY_PROTOCOL {
+                return Err(invalid("DNSKEY protocol", protocol));
+            }
+            let key = STANDARD
+                .decode(key.concat())
+                .map_err(|_| invalid("DNSKEY public key", value))?;
+            wire = dnskey_rdata(
+                number("DNSKEY flags", flags)?,
+                number("DNSKEY algorithm", algorithm)?,
+                &key,
+            );
+        }
+        DNSRecordType::SSHFP => {
+            let [algorithm, fingerprint_type, fingerprint @ ..] = tokens.as_slice() else {
+                return Err(malformed());
+            };
+            wire.push(number("SSHFP algorithm", algorithm)?);
+            wire.push(number("SSHFP fingerprint type", fingerprint_type)?);
+            wire.extend(
+                hex::decode(fingerprint.concat())
+                    .map_err(|_| invalid("SSHFP fingerprint", value))?,
+            );
+        }
+        DNSRecordType::SMIMEA => {
+            let normalized = tlsa(value)?;
+            let [usage, selector, matching, data] = fields(record_type, &normalized)?;
+            for (field, token) in [
+                ("SMIMEA usage", usage),
+                ("SMIMEA selector", selector),
+                ("SMIMEA matching type", matching),
+            ] {
+                wire.push(number(field, token)?);
+            }
+            wire.extend(hex::decode(data).map_err(|_| invalid("SMIMEA data", data))?);
+        }
+        DNSRecordType::NAPTR => {
+            let words = words(value)?;
+            let [order, preference, flags, services, regexp, replacement] = words.as_slice() else {
+                return Err(malformed());
+            };
+            wire.extend(number::<u16>("NAPTR order", order)?.to_be_bytes());
+            wire.extend(number::<u16>("NAPTR preference", preference)?.to_be_bytes());
+            for text in [flags, services, regexp] {
+                let length = u8::try_from(text.len()).map_err(|_| invalid("NAPTR string", text))?;
+                wire.push(length);
+                wire.extend(text.as_bytes());
+            }
+            put_name(&mut wire, &name_or_root(replacement)?);
+        }
+        DNSRecordType::URI => {
+            let words = words(value)?;
+            let [priority, weight, target] = words.as_slice() else {
+                return Err(malformed());
+            };
+            wire.extend(number::<u16>("URI priority", priority)?.to_be_bytes());
+            wire.extend(number::<u16>("URI weight", weight)?.to_be_bytes());
+            wire.extend(target.as_bytes());
+        }
+        DNSRecordType::OPENPGPKEY => {
+            wire = STANDARD
+                .decode(tokens.concat())
+                .map_err(|_| invalid("OpenPGP key", value))?;
+        }
+        DNSRecordType::LOC => {
+            let (latitude, rest) = loc_coordinate(&tokens, ["N", "S"], 90)?;
+            let (longitude, rest) = loc_coordinate(rest, ["E", "W"], 180)?;
+            let [altitude, sizes @ ..] = rest else {
+                return Err(AppError::InvalidRecord("LOC needs an altitude".into()));
+            };
+            if sizes.len() > 3 {
+                return Err(malformed());
+            }
+            let altitude =
+                u32::try_from(centimetres("LOC altitude", altitude)? + LOC_ALTITUDE_BASE)
+                    .map_err(|_| invalid("LOC altitude", altitude))?;
+            // Size 1m, horizontal precision 10km and vertical precision 10m unless given
+            let mut precisions = [0x12, 0x16, 0x13];
+            for (precision, size) in precisions.iter_mut().zip(sizes) {
+                *precision = u64::try_from(centimetres("LOC size", size)?)
+                    .ok()
+                    .and_then(loc_precision)
+                    .ok_or_else(|| invalid("LOC size", size))?;
+            }
+            wire.push(0);
+            wire.extend(precisions);
+            wire.extend(latitude.to_be_bytes());
+            wire.extend(longitude.to_be_bytes());
+            wire.extend(altitude.to_be_bytes());
+        }
+        _ => {
+            return Err(AppError::InvalidRecord(format!(
+                "{} RDATA is not encoded here",
+                record_type
+            )))
+        }
+    }
+    Ok(wire)
+}
+
+/// Normalised presentation form of wire RDATA of one of the [`opaque`]
+/// types, checking each field.
+pub fn from_wire(record_type: DNSRecordType, wire: &[u8]) -> Result<String> {
+    let mut reader = Reader { record_type, wire };
+    let value = match record_type {
+        DNSRecordType::HTTPS | DNSRecordType::SVCB => svcb(&mut reader)?,
+        DNSRecordType::DS | DNSRecordType::CDS => {
+            let (tag, algorithm, digest_type) = (reader.u16()?, reader.u8()?, reader.u8()?);
+            let digest = reader.rest();
+            // RFC 8078 §4: "CDS 0 0 0 00" asks the parent to remove the DS RRset
+            let delete = record_type == DNSRecordType::CDS
+                && (tag, algorithm, digest_type, digest) == (0, 0, 0, &[0][..]);
+            if !delete {
+                if algorithm == 0 {
+                    return Err(invalid("DS algorithm", "0"));
+                }
+                let length = match digest_type {
+                    0 => return Err(invalid("DS digest type", "0")),
+                    1 => Some(20),
+                    2 => Some(32),
+                    4 => Some(48),
+                    _ => None,
+                };
+                check_digest(record_type, "digest type", digest_type, digest, length)?;
+            }
+            format!(
+                "{} {} {} {}",
+                tag,
+                algorithm,
+                digest_type,
+                hex::encode(digest)
+            )
+        }
+        DNSRecordType::DNSKEY | DNSRecordType::CDNSKEY => {
+            let (flags, protocol, algorithm) = (reader.u16()?, reader.u8()?, reader.u8()?);
+            let key = reader.rest();
+            if protocol != DNSKEY_PROTOCOL {
+                return Err(invalid("DNSKEY protocol", &protocol.to_string()));
+            }
+            // RFC 8078 §4: "CDNSKEY 0 3 0 AA==" asks the parent to remove the DS RRset
+            let delete = record_type == DNSRecordType::CDNSKEY
+                && (flags, algorithm, key) == (0, 0, &[0][..]);
+            if !delete && (algorithm == 0 || key.is_empty()) {
+                return Err(invalid(
+                    &format!("{} record", record_type),
+                    &STANDARD.encode(key),
+                ));
+            }
+            format!(
+                "{} {} {} {}",
+                flags,
+                protocol,
+                algorithm,
+                STANDARD.encode(key)
+            )
+        }
+        DNSRecordType::SSHFP => {
+            let (algorithm, fingerprint_type) = (reader.u8()?, reader.u8()?);
+            let fingerprint = reader.rest();
+            if algorithm == 0 {
+                return Err(invalid("SSHFP algorithm", "0"));
+            }
+            let length = match fingerprint_type {
+                0 => return Err(invalid("SSHFP fingerprint type", "0")),
+                1 => Some(20),
+                2 => Some(32),
+                _ => None,
+            };
+            check_digest(
+                record_type,
+                "fingerprint type",
+                fingerprint_type,
+                fingerprint,
+                length,
+            )?;
+            format!(
+                "{} {} {}",
+                algorithm,
+                fingerprint_type,
+                hex::encode(fingerprint)
+            )
+        }
+        DNSRecordType::SMIMEA => {
+            let (usage, selector, matching) = (reader.u8()?, reader.u8()?, reader.u8()?);
+            let data = hex::encode(reader.rest());
+            tlsa(&format!("{} {} {} {}", usage, selector, matching, data))?
+        }
+        DNSRecordType::NAPTR => {
+            let (order, preference) = (reader.u16()?, reader.u16()?);
+            let (flags, services, regexp) = (reader.text()?, reader.text()?, reader.text()?);
+            let replacement = reader.name()?;
+            if !flags.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(invalid("NAPTR flags", &flags));
+            }
+            // RFC 3403 §4.1: a rule rewrites by regexp or by replacement, not both
+            if !regexp.is_empty() && replacement != "." {
+                return Err(AppError::InvalidRecord(
+                    "NAPTR takes a regexp or a replacement, not both".into(),
+                ));
+            }
+            format!(
+                "{} {} {} {} {} {}",
+                order,
+                preference,
+                quote(&flags.to_ascii_lowercase()),
+                quote(&services),
+                quote(&regexp),
+                replacement
+            )
+        }
+        DNSRecordType::URI => {
+            let (priority, weight) = (reader.u16()?, reader.u16()?);
+            let target = text(reader.rest()).unwrap_or_default();
+            let scheme = target.split_once(':').map(|(scheme, _)| scheme);
+            let valid = !target.contains(char::is_whitespace)
+                && scheme.is_some_and(|scheme| {
+                    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                        && scheme
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                });
+            if !valid {
+                return Err(invalid("URI target", &target));
+            }
+            format!("{} {} {}", priority, weight, quote(&target))
+        }
+        DNSRecordType::OPENPGPKEY => {
+            let key = reader.rest();
+            if key.is_empty() {
+                return Err(AppError::InvalidRecord("OPENPGPKEY needs a key".into()));
+            }
+            STANDARD.encode(key)
+        }
+        DNSRecordType::LOC => loc(&mut reader)?,
+        _ => {
+            return Err(AppError::InvalidRecord(format!(
+                "{} RDATA is not decoded here",
+                record_type
+            )))
+        }
+    };
+    reader.finish()?;
+    Ok(value)
+}
+
+/// A cursor over wire RDATA.
+struct Reader<'a> {
+    record_type: DNSRecordType,
+    wire: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.wire.len() < length {
+            return Err(AppError::InvalidRecord(format!(
+                "truncated {} RDATA",
+                self.record_type
+            )));
+        }
+        let (taken, rest) = self.wire.split_at(length);
+        self.wire = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.wire)
+    }
+
+    /// A character-string of printable text.
+    fn text(&mut self) -> Result<String> {
+        let length = self.u8()?;
+        let bytes = self.take(length.into())?;
+        text(bytes).ok_or_else(|| {
+            AppError::InvalidRecord(format!("unprintable text in {} RDATA", self.record_type))
+        })
+    }
+
+    /// An uncompressed domain name, as [`name_or_root`] returns it.
+    fn name(&mut self) -> Result<String> {
+        let mut labels = Vec::new();
+        loop {
+            let length = usize::from(self.u8()?);
+            if length == 0 {
+                break;
+            }
+            if length > MAX_LABEL {
+                return Err(AppError::InvalidRecord(format!(
+                    "compressed or overlong name in {} RDATA",
+                    self.record_type
+                )));
+            }
+            labels.push(String::from_utf8_lossy(self.take(length)?).into_owned());
+        }
+        if labels.is_empty() {
+            return Ok(".".to_string());
+        }
+        domain_name(&labels.join("."))
+    }
+
+    fn finish(self) -> Result<()> {
+        if !self.wire.is_empty() {
+            return Err(AppError::InvalidRecord(format!(
+                "trailing bytes in {} RDATA",
+                self.record_type
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Printable UTF-8 text.
+fn text(bytes: &[u8]) -> Option<String> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.chars().any(char::is_control))
+        .map(str::to_string)
+}
+
+/// A digest as long as its type requires, or non-empty for types with no
+/// fixed length.
+fn check_digest(
+    record_type: DNSRecordType,
+    field: &str,
+    digest_type: u8,
+    digest: &[u8],
+    length: Option<usize>,
+) -> Result<()> {
+    if digest.is_empty() {
+        return Err(AppError::InvalidRecord(format!(
+            "{} needs a digest",
+            record_type
+        )));
+    }
+    if let Some(length) = length.filter(|length| digest.len() != *length) {
+        return Err(AppError::InvalidRecord(format!(
+            "{} {} {} needs a {}-byte digest",
+            record_type, field, digest_type, length
+        )));
+    }
+    Ok(())
+}
+
+/// A domain name, or `.` for none (an SVCB target or NAPTR replacement).
+fn name_or_root(value: &str) -> Result<String> {
+    if value == "." {
+        return Ok(".".to_string());
+    }
+    domain_name(value)
+}
+
+/// Append a name returned by [`name_or_root`] in uncompressed wire form.
+fn put_name(wire: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        wire.push(label.len() as u8);
+        wire.extend(label.as_bytes());
+    }
+    wire.push(0);
+}
+
+/// SVCB/HTTPS presentation form: priority, target, then SvcParams in key
+/// order (RFC 9460 §2).
+fn svcb(reader: &mut Reader) -> Result<String> {
+    let priority = reader.u16()?;
+    let mut fields = vec![priority.to_string(), reader.name()?];
+    let mut keys = Vec::new();
+    let mut mandatory: &[u8] = &[];
+    while !reader.wire.is_empty() {
+        let key = reader.u16()?;
+        let length = reader.u16()?;
+        let value = reader.take(length.into())?;
+        if key == u16::MAX || keys.last().is_some_and(|last| *last >= key) {
+            return Err(AppError::InvalidRecord(
+                "SvcParamKeys must be unique and in ascending order".into(),
+            ));
+        }
+        if key == 0 {
+            mandatory = value;
+        }
+        keys.push(key);
+        fields.push(svc_param(key, value)?);
+    }
+
+    // AliasMode (RFC 9460 §2.4.2) carries no SvcParams
+    if priority == 0 && !keys.is_empty() {
+        return Err(AppError::InvalidRecord(
+            "SVCB AliasMode takes no SvcParams".into(),
+        ));
+    }
+    for key in mandatory.chunks_exact(2) {
+        let key = u16::from_be_bytes([key[0], key[1]]);
+        if !keys.contains(&key) {
+            return Err(AppError::InvalidRecord(format!(
+                "mandatory SvcParamKey {} is missing",
+                svc_key_name(key)
+            )));
+        }
+    }
+    if keys.contains(&2) && !keys.contains(&1) {
+        return Err(AppError::InvalidRecord("no-default-alpn needs alpn".into()));
+    }
+    Ok(fields.join(" "))
+}
+
+/// One SvcParam in presentation form, its value checked for its key.
+fn svc_param(key: u16, value: &[u8]) -> Result<String> {
+    let name = svc_key_name(key);
+    let malformed = || invalid("SvcParamValue for", &name);
+    let list = match key {
+        0 if !value.is_empty() && value.len() % 2 == 0 => {
+            let keys: Vec<u16> = value
+                .chunks_exact(2)
+                .map(|key| u16::from_be_bytes([key[0], key[1]]))
+                .collect();
+            if keys.contains(&0) || keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(malformed());
+            }
+            keys.into_iter()
+                .map(svc_key_name)
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        1 => {
+            let mut ids = Vec::new();
+            let mut rest = value;
+            while let [length, tail @ ..] = rest {
+                let length = usize::from(*length);
+                let id = tail
+                    .get(..length)
+                    .and_then(|id| std::str::from_utf8(id).ok())
+                    .filter(|id| !id.is_empty() && id.chars().all(alpn_char))
+                    .ok_or_else(malformed)?;
+                ids.push(id);
+                rest = &tail[length..];
+            }
+            if ids.is_empty() {
+                return Err(malformed());
+            }
+            ids.join(",")
+        }
+        2 if value.is_empty() => return Ok(name),
+        3 if value.len() == 2 => u16::from_be_bytes([value[0], value[1]]).to_string(),
+        4 if !value.is_empty() && value.len() % 4 == 0 => value
+            .chunks_exact(4)
+            .map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        5 if !value.is_empty() => STANDARD.encode(value),
+        6 if !value.is_empty() && value.len() % 16 == 0 => value
+            .chunks_exact(16)
+            .map(|chunk| {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(chunk);
+                Ipv6Addr::from(octets).to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        0..=6 => return Err(malformed()),
+        _ if value.is_empty() => return Ok(name),
+        _ => {
+            let text = std::str::from_utf8(value)
+                .ok()
+                .filter(|text| text.chars().all(|c| c == ' ' || c.is_ascii_graphic()))
+                .ok_or_else(malformed)?;
+            quote(text)
+        }
+    };
+    Ok(format!("{}={}", name, list))
+}
+
+/// Wire value of a SvcParam from its presentation value, if any.
+fn svc_value(key: u16, value: Option<&str>) -> Result<Vec<u8>> {
+    let Some(value) = value else {
+        // Only no-default-alpn and keys without a defined format may be bare
+        if key == 2 || key > 6 {
+            return Ok(Vec::new());
+        }
+        return Err(AppError::InvalidRecord(format!(
+            "SvcParamKey {} needs a value",
+            svc_key_name(key)
+        )));
+    };
+    let mut wire = Vec::new();
+    match key {
+        0 => {
+            let mut keys = value.split(',').map(svc_key).collect::<Result<Vec<_>>>()?;
+            keys.sort_unstable();
+            for key in keys {
+                wire.extend(key.to_be_bytes());
+            }
+        }
+        1 => {
+            for id in value.split(',') {
+                let length = u8::try_from(id.len()).map_err(|_| invalid("ALPN id", id))?;
+                wire.push(length);
+                wire.extend(id.as_bytes());
+            }
+        }
+        2 => {
+            return Err(AppError::InvalidRecord(
+                "no-default-alpn takes no value".into(),
+            ))
+        }
+        3 => wire.extend(number::<u16>("port", value)?.to_be_bytes()),
+        4 => {
+            for address in value.split(',') {
+                let address =
+                    Ipv4Addr::from_str(address).map_err(|_| invalid("IPv4 address", address))?;
+                wire.extend(address.octets());
+            }
+        }
+        5 => {
+            wire = STANDARD
+                .decode(value)
+                .map_err(|_| invalid("ECH config list", value))?
+        }
+        6 => {
+            for address in value.split(',') {
+                let address =
+                    Ipv6Addr::from_str(address).map_err(|_| invalid("IPv6 address", address))?;
+                wire.extend(address.octets());
+            }
+        }
+        _ => wire.extend(value.as_bytes()),
+    }
+    Ok(wire)
+}
+
+/// A SvcParamKey by name or as `keyNNNNN`. `echconfig` is the draft name of
+/// `ech`.
+fn svc_key(name: &str) -> Result<u16> {
+    let name = name.to_ascii_lowercase();
+    if name == "echconfig" {
+        return Ok(5);
+    }
+    if let Some(key) = SVC_PARAM_KEYS.iter().position(|key| *key == name) {
+        return Ok(key as u16);
+    }
+    name.strip_prefix("key")
+        .and_then(|key| key.parse::<u16>().ok())
+        .filter(|key| *key != u16::MAX)
+        .ok_or_else(|| invalid("SvcParamKey", &name))
+}
+
+fn svc_key_name(key: u16) -> String {
+    SVC_PARAM_KEYS
+        .get(usize::from(key))
+        .map_or_else(|| format!("key{}", key), |name| name.to_string())
+}
+
+/// ALPN ids are written comma-separated and unquoted, so they are limited to
+/// visible ASCII other than `,`, `"` and `\`.
+fn alpn_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, ',' | '"' | '\\')
+}
+
+/// One LOC coordinate, `d [m [s.sss]] N|S` or `... E|W`, and the tokens after it.
+fn loc_coordinate<'a>(
+    tokens: &'a [&'a str],
+    hemispheres: [&str; 2],
+    max_degrees: i64,
+) -> Result<(u32, &'a [&'a str])> {
+    let end = tokens
+        .iter()
+        .position(|token| hemispheres.iter().any(|h| token.eq_ignore_ascii_case(h)))
+        .ok_or_else(|| {
+            AppError::InvalidRecord(format!(
+                "LOC needs {} or {}",
+                hemispheres[0], hemispheres[1]
+            ))
+        })?;
+    let (degrees, minutes, seconds) = match tokens[..end] {
+        [degrees] => (degrees, "0", "0"),
+        [degrees, minutes] => (degrees, minutes, "0"),
+        [degrees, minutes, seconds] => (degrees, minutes, seconds),
+        _ => return Err(invalid("LOC coordinate", &tokens[..end].join(" "))),
+    };
+    let degrees = i64::from(number::<u8>("LOC degrees", degrees)?);
+    let minutes = i64::from(number::<u8>("LOC minutes", minutes)?);
+    let seconds = decimal("LOC seconds", seconds, 3)?;
+    let thousandths = (degrees * 3600 + minutes * 60) * 1000 + seconds;
+    if minutes >= 60 || !(0..60_000).contains(&seconds) || thousandths > max_degrees * 3_600_000 {
+        return Err(invalid("LOC coordinate", &tokens[..=end].join(" ")));
+    }
+    let offset = if tokens[end].eq_ignore_ascii_case(hemispheres[1]) {
+        -thousandths
+    } else {
+        thousandths
+    };
+    Ok(((LOC_EQUATOR + offset) as u32, &tokens[end + 1..]))
+}
+
+/// LOC presentation form with every field given (RFC 1876 §3).
+fn loc(reader: &mut Reader) -> Result<String> {
+    let version = reader.u8()?;
+    if version != 0 {
+        return Err(invalid("LOC version", &version.to_string()));
+    }
+    let mut sizes = Vec::new();
+    for _ in 0..3 {
+        let precision = reader.u8()?;
+        let (mantissa, exponent) = (precision >> 4, precision & 0x0f);
+        if mantissa > 9 || exponent > 9 {
+            return Err(invalid("LOC size", &format!("{:#04x}", precision)));
+        }
+        sizes.push(metres(i64::from(mantissa) * 10i64.pow(exponent.into())));
+    }
+    let latitude = loc_angle(reader.u32()?, ["N", "S"], 90)?;
+    let longitude = loc_angle(reader.u32()?, ["E", "W"], 180)?;
+    let altitude = metres(i64::from(reader.u32()?) - LOC_ALTITUDE_BASE);
+    Ok(format!(
+        "{} {} {} {}",
+        latitude,
+        longitude,
+        altitude,
+        sizes.join(" ")
+    ))
+}
+
+/// A LOC latitude or longitude as `d m s.sss H`.
+fn loc_angle(raw: u32, hemispheres: [&str; 2], max_degrees: i64) -> Result<String> {
+    let offset = i64::from(raw) - LOC_EQUATOR;
+    let thousandths = offset.abs();
+    if thousandths > max_degrees * 3_600_000 {
+        return Err(invalid("LOC coordinate", &raw.to_string()));
+    }
+    Ok(format!(
+        "{} {} {}.{:03} {}",
+        thousandths / 3_600_000,
+        thousandths / 60_000 % 60,
+        thousandths / 1000 % 60,
+        thousandths % 1000,
+        hemispheres[usize::from(offset < 0)]
+    ))
+}
+
+/// A size in centimetres as LOC mantissa and exponent nibbles, if it is one
+/// digit times a power of ten.
+fn loc_precision(centimetres: u64) -> Option<u8> {
+    let (mut mantissa, mut exponent) = (centimetres, 0u8);
+    while mantissa > 9 {
+        if mantissa % 10 != 0 {
+            return None;
+        }
+        mantissa /= 10;
+        exponent += 1;
+    }
+    (exponent <= 9).then_some(((mantissa as u8) << 4) | exponent)
+}
+
+/// A length in metres, with an optional `m` suffix, as centimetres.
+fn centimetres(field: &str, token: &str) -> Result<i64> {
+    decimal(field, token.strip_suffix('m').unwrap_or(token), 2)
+}
+
+fn metres(centimetres: i64) -> String {
+    let sign = if centimetres < 0 { "-" } else { "" };
+    format!(
+        "{}{}.{:02}m",
+        sign,
+        centimetres.abs() / 100,
+        centimetres.abs() % 100
+    )
+}
+
+/// A decimal with at most `places` fractional digits, scaled to an integer.
+fn decimal(field: &str, token: &str, places: usize) -> Result<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let valid = !whole.is_empty()
+        && whole.len() <= 12
+        && fraction.len() <= places
+        && whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit());
+    if !valid {
+        return Err(invalid(field, token));
+    }
+    let scaled: i64 = format!("{}{:0<width$}", whole, fraction, width = places)
+        .parse()
+        .map_err(|_| invalid(field, token))?;
+    Ok(if negative { -scaled } else { scaled })
+}
+
+/// Whitespace-separated words, any part of which may be quoted; quotes are
+/// removed and escapes resolved.
+fn words(value: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(words);
+        }
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            match c {
+                '"' => word.push_str(&quoted(&mut chars)?),
+                '\\' => word.push(escaped(&mut chars)?),
+                c => word.push(c),
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// The character-strings of a TXT value. Quoted strings are read in
+/// presentation format; a bare value is one text, split into strings of at
+/// most 255 bytes.
+fn char_strings(value: &str) -> Result<Vec<String>> {
+    let printable = |s: &str| !s.chars().any(char::is_control);
+    if !value.starts_with('"') {
+        if !printable(value) {
+            return Err(invalid("TXT value", value));
+        }
+        let mut strings = vec![String::new()];
+        for c in value.chars() {
+            if strings
+                .last()
+                .is_some_and(|s| s.len() + c.len_utf8() > MAX_STRING)
+            {
+                strings.push(String::new());
+            }
+            if let Some(last) = strings.last_mut() {
+                last.push(c);
+            }
+        }
+        return Ok(strings);
+    }
+
+    let mut strings = Vec::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => continue,
+            '"' => {}
+            _ => return Err(invalid("TXT value", value)),
+        }
+        let text = quoted(&mut chars)?;
+        if text.len() > MAX_STRING || !printable(&text) {
+            return Err(invalid("TXT string", &text));
+        }
+        strings.push(text);
+    }
+    Ok(strings)
+}
+
+/// The rest of a quoted string whose opening `"` has been read, unescaped.
+fn quoted(chars: &mut impl Iterator<Item = char>) -> Result<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => text.push(escaped(chars)?),
+            Some(c) => text.push(c),
+            None => return Err(AppError::InvalidRecord("unterminated quoted string".into())),
+        }
+    }
+}
+
+/// The character of an escape whose `\` has been read: `\X` is `X`, and
+/// `\DDD` must be a printable ASCII code.
+fn escaped(chars: &mut impl Iterator<Item = char>) -> Result<char> {
+    match chars.next() {
+        Some(d) if d.is_ascii_digit() => {
+            let digits: String = [Some(d), chars.next(), chars.next()]
+                .into_iter()
+                .flatten()
+                .collect();
+            match digits.parse::<u8>() {
+                Ok(byte) if (0x20..0x7f).contains(&byte) => Ok(byte as char),
+                _ => Err(invalid("escape", &format!("\\{}", digits))),
+            }
+        }
+        Some(c) => Ok(c),
+        None => Err(AppError::InvalidRecord("unterminated escape".into())),
+    }
+}
+
+/// Exactly `N` whitespace-separated fields.
+fn fields<const N: usize>(record_type: DNSRecordType, value: &str) -> Result<[&str; N]> {
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    tokens.try_into().map_err(|tokens: Vec<&str>| {
+        AppError::InvalidRecord(format!(
+            "{} needs {} fields, got {}",
+            record_type,
+            N,
+            tokens.len()
+        ))
+    })
+}
+
+/// A decimal field within the range of `T`.
+fn number<T: FromStr>(field: &str, token: &str) -> Result<T> {
+    token.parse().map_err(|_| invalid(field, token))
+}
+
+fn invalid(what: &str, value: &str) -> AppError {
+    AppError::InvalidRecord(format!("invalid {} {}", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CAAInput, MXInput};
+
+    fn input(record_type: DNSRecordType, value: Option<&str>) -> DNSRecordInput {
+        DNSRecordInput {
+            name: "example.com".to_string(),
+            record_type,
+            ttl: None,
+            value: value.map(str::to_string),
+            dnssec: None,
+            mx: None,
+            srv: None,
+            caa: None,
+            tlsa: None,
+        }
+    }
+
+    #[test]
+    fn test_addresses() {
+        assert_eq!(
+            normalize(DNSRecordType::A, " 192.0.2.1 ").unwrap(),
+            "192.0.2.1"
+        );
+        assert!(normalize(DNSRecordType::A, "1.2").is_err());
+        assert!(normalize(DNSRecordType::A, "192.0.2.256").is_err());
+        assert_eq!(
+            normalize(DNSRecordType::AAAA, "2001:DB8:0:0:0:0:0:1").unwrap(),
+            "2001:db8::1"
+        );
+        assert!(normalize(DNSRecordType::AAAA, "2001:db8:::1").is_err());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            normalize(DNSRecordType::CNAME, "Target.Example.COM").unwrap(),
+            "target.example.com."
+        );
+        assert_eq!(
+            normalize(DNSRecordType::CNAME, "_acme-challenge.example.net.").unwrap(),
+            "_acme-challenge.example.net."
+        );
+        assert!(normalize(DNSRecordType::NS, "_ns.example.com").is_err());
+        assert!(normalize(DNSRecordType::NS, "ns1..example.com").is_err());
+        assert!(normalize(DNSRecordType::PTR, "-host.example.com").is_err());
+        assert!(normalize(DNSRecordType::PTR, &format!("{}.com", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn test_structured_types() {
+        assert_eq!(
+            normalize(DNSRecordType::MX, "10  Mail.Example.com").unwrap(),
+            "10 mail.example.com."
+        );
+        assert_eq!(normalize(DNSRecordType::MX, "0 .").unwrap(), "0 .");
+        assert!(normalize(DNSRecordType::MX, "70000 mail.example.com").is_err());
+        assert!(normalize(DNSRecordType::MX, "mail.example.com").is_err());
+
+        assert_eq!(
+            normalize(DNSRecordType::SRV, "10 5 5060 sip.example.com").unwrap(),
+            "10 5 5060 sip.example.com."
+        );
+        assert!(normalize(DNSRecordType::SRV, "10 5 sip.example.com").is_err());
+
+        assert_eq!(
+            normalize(DNSRecordType::CAA, "0 ISSUE letsencrypt.org").unwrap(),
+            r#"0 issue "letsencrypt.org""#
+        );
+        assert!(normalize(DNSRecordType::CAA, "1 issue \"letsencrypt.org\"").is_err());
+        assert!(normalize(DNSRecordType::CAA, "0 is-sue \"letsencrypt.org\"").is_err());
+
+        let digest = "AB".repeat(32);
+        assert_eq!(
+            normalize(DNSRecordType::TLSA, &format!("3 1 1 {}", digest)).unwrap(),
+            format!("3 1 1 {}", digest.to_ascii_lowercase())
+        );
+        assert!(normalize(DNSRecordType::TLSA, "3 1 1 abcd").is_err());
+        assert!(normalize(DNSRecordType::TLSA, "4 1 0 abcd").is_err());
+        assert!(normalize(DNSRecordType::TLSA, "3 1 0 xyz0").is_err());
+
+        assert_eq!(
+            normalize(
+                DNSRecordType::SOA,
+                "NS1.example.com hostmaster.example.com 1 7200 900 1209600 300"
+            )
+            .unwrap(),
+            "ns1.example.com. hostmaster.example.com. 1 7200 900 1209600 300"
+        );
+        assert!(normalize(DNSRecordType::SOA, "ns1.example.com. h. 1 2 3 4").is_err());
+        assert!(normalize(DNSRecordType::SOA, "ns1.example.com. h. -1 2 3 4 5").is_err());
+    }
+
+    #[test]
+    fn test_txt() {
+        assert_eq!(
+            normalize(DNSRecordType::TXT, "v=spf1 include:_spf.example.com -all").unwrap(),
+            r#""v=spf1 include:_spf.example.com -all""#
+        );
+        assert_eq!(
+            normalize(DNSRecordType::TXT, r#""a \"b\"" "\099""#).unwrap(),
+            r#""a \"b\"" "c""#
+        );
+        let long = normalize(DNSRecordType::TXT, &"k".repeat(300)).unwrap();
+        assert_eq!(
+            long,
+            format!("\"{}\" \"{}\"", "k".repeat(255), "k".repeat(45))
+        );
+        assert!(normalize(DNSRecordType::TXT, "\"unterminated").is_err());
+        assert!(normalize(DNSRecordType::TXT, &format!("\"{}\"", "k".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn test_service_bindings() {
+        assert_eq!(
+            normalize(
+                DNSRecordType::HTTPS,
+                r#"1 . ipv6hint=2001:DB8::1 alpn="h3,h2" port=443 echconfig=AEX+DQ=="#
+            )
+            .unwrap(),
+            "1 . alpn=h3,h2 port=443 ech=AEX+DQ== ipv6hint=2001:db8::1"
+        );
+        assert_eq!(
+            normalize(
+                DNSRecordType::SVCB,
+                "1 Svc.Example.com mandatory=port,alpn alpn=h2 port=8443 key65000=\"x y\""
+            )
+            .unwrap(),
+            r#"1 svc.example.com. mandatory=alpn,port alpn=h2 port=8443 key65000="x y""#
+        );
+        assert_eq!(
+            normalize(DNSRecordType::SVCB, "0 svc.example.net").unwrap(),
+            "0 svc.example.net."
+        );
+        assert_eq!(
+            to_rdata(DNSRecordType::HTTPS, "1 . alpn=h2").unwrap(),
+            RData::Unknown {
+                code: wire_type(DNSRecordType::HTTPS),
+                rdata: NULL::with(vec![0, 1, 0, 0, 1, 0, 3, 2, b'h', b'2']),
+            }
+        );
+
+        for bad in [
+            "0 svc.example.net alpn=h2",
+            "1 . mandatory=port alpn=h2",
+            "1 . port=443 port=8443",
+            "1 . no-default-alpn",
+            "1 . alpn",
+            "1 . ipv4hint=192.0.2.300",
+            "1 . bogus=1",
+        ] {
+            assert!(normalize(DNSRecordType::HTTPS, bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_dnssec_types() {
+        let digest = "AB".repeat(32);
+        assert_eq!(
+            normalize(DNSRecordType::DS, &format!("12345 13 2 {}", digest)).unwrap(),
+            format!("12345 13 2 {}", digest.to_ascii_lowercase())
+        );
+        assert!(normalize(DNSRecordType::DS, "12345 13 2 abcd").is_err());
+        assert!(normalize(DNSRecordType::DS, "0 0 0 00").is_err());
+        assert_eq!(
+            normalize(DNSRecordType::CDS, "0 0 0 00").unwrap(),
+            "0 0 0 00"
+        );
+
+        assert_eq!(
+            normalize(DNSRecordType::DNSKEY, "257 3 13 AQID BAU=").unwrap(),
+            "257 3 13 AQIDBAU="
+        );
+        assert!(normalize(DNSRecordType::DNSKEY, "257 2 13 AQIDBAU=").is_err());
+        assert!(normalize(DNSRecordType::DNSKEY, "0 3 0 AA==").is_err());
+        assert_eq!(
+            normalize(DNSRecordType::CDNSKEY, "0 3 0 AA==").unwrap(),
+            "0 3 0 AA=="
+        );
+
+        let fingerprint = "CD".repeat(32);
+        assert_eq!(
+            normalize(DNSRecordType::SSHFP, &format!("4 2 {}", fingerprint)).unwrap(),
+            format!("4 2 {}", fingerprint.to_ascii_lowercase())
+        );
+        assert!(normalize(DNSRecordType::SSHFP, "4 1 abcd").is_err());
+
+        assert_eq!(
+            normalize(DNSRecordType::OPENPGPKEY, "mQEN Bw==").unwrap(),
+            "mQENBw=="
+        );
+        assert!(normalize(DNSRecordType::OPENPGPKEY, "not base64!").is_err());
+        assert_eq!(
+            normalize(DNSRecordType::SMIMEA, &format!("3 0 1 {}", digest)).unwrap(),
+            format!("3 0 1 {}", digest.to_ascii_lowercase())
+        );
+        assert!(normalize(DNSRecordType::SMIMEA, "4 0 0 abcd").is_err());
+    }
+
+    #[test]
+    fn test_naptr_uri_loc() {
+        assert_eq!(
+            normalize(
+                DNSRecordType::NAPTR,
+                r#"100 10 "U" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+            )
+            .unwrap(),
+            r#"100 10 "u" "E2U+sip" "!^.*$!sip:info@example.com!" ."#
+        );
+        assert_eq!(
+            normalize(
+                DNSRecordType::NAPTR,
+                r#"100 50 "s" "http+N2L+N2C+N2R" "" _http._tcp.Example.com"#
+            )
+            .unwrap(),
+            r#"100 50 "s" "http+N2L+N2C+N2R" "" _http._tcp.example.com."#
+        );
+        assert!(normalize(
+            DNSRecordType::NAPTR,
+            r#"100 10 "u" "E2U+sip" "!x!y!" a.example."#
+        )
+        .is_err());
+        assert!(normalize(DNSRecordType::NAPTR, r#"100 10 "u*" "E2U+sip" "" ."#).is_err());
+
+        assert_eq!(
+            normalize(DNSRecordType::URI, "10 1 ftp://ftp.example.com/public").unwrap(),
+            r#"10 1 "ftp://ftp.example.com/public""#
+        );
+        assert!(normalize(DNSRecordType::URI, r#"10 1 "not a uri""#).is_err());
+
+        assert_eq!(
+            normalize(DNSRecordType::LOC, "42 21 54 N 71 06 18 W -24m 30m").unwrap(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30.00m 10000.00m 10.00m"
+        );
+        assert_eq!(
+            normalize(DNSRecordType::LOC, "52 22 23.5 n 4 53 E 2.5").unwrap(),
+            "52 22 23.500 N 4 53 0.000 E 2.50m 1.00m 10000.00m 10.00m"
+        );
+        assert!(normalize(DNSRecordType::LOC, "91 0 0 N 0 E 0m").is_err());
+        assert!(normalize(DNSRecordType::LOC, "0 N 0 60 E 0m").is_err());
+        assert!(normalize(DNSRecordType::LOC, "0 N 0 E 0m 1.5m").is_err());
+        assert!(normalize(DNSRecordType::LOC, "0 N 0 E").is_err());
+
+        // Wire RDATA read from an UPDATE must be complete and no longer
+        assert!(from_wire(DNSRecordType::URI, &[0, 1]).is_err());
+        assert!(from_wire(DNSRecordType::SSHFP, &[1, 1]).is_err());
+        let mut sshfp = vec![1, 1];
+        sshfp.extend([0xab; 20]);
+        assert_eq!(
+            from_wire(DNSRecordType::SSHFP, &sshfp).unwrap(),
+            format!("1 1 {}", "ab".repeat(20))
+        );
+        sshfp.push(0);
+        assert!(from_wire(DNSRecordType::SSHFP, &sshfp).is_err());
+    }
+
+    #[test]
+    fn test_typed_inputs() {
+        let mut mx = input(DNSRecordType::MX, None);
+        mx.mx = Some(MXInput {
+            preference: 10,
+            exchange: "Mail.Example.com".to_string(),
+        });
+        assert_eq!(input_value(&mx).unwrap(), "10 mail.example.com.");
+
+        mx.value = Some("10 mail.example.com".to_string());
+        assert!(input_value(&mx).is_err());
+
+        let mut caa = input(DNSRecordType::TXT, None);
+        caa.caa = Some(CAAInput {
+            flags: None,
+            tag: "issue".to_string(),
+            value: "letsencrypt.org".to_string(),
+        });
+        assert!(input_value(&caa).is_err());
+        caa.record_type = DNSRecordType::CAA;
+        assert_eq!(input_value(&caa).unwrap(), r#"0 issue "letsencrypt.org""#);
+
+        assert!(input_value(&input(DNSRecordType::A, None)).is_err());
+    }
+}
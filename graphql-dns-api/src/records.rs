@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Single-record writes
+//!
+//! Creating, rewriting, deleting and reverting one record, as the record
+//! mutations and proposals do. Each write is validated like a changeset's:
+//! RDATA is parsed for its type, a record joining an RRset gives the whole
+//! RRset its TTL, the SOA is left to the zone and a signed zone's apex key
+//! RRsets to its keys, and the RRset invariants ([`lint::check`]) must hold
+//! afterwards. The write goes through [`changeset::store`], so the record, its
+//! RRset's new TTL, the zone's next serial and, in a signed zone, the RRSIGs
+//! and denial chain land in one transaction, refused if the zone changed since
+//! it was read; then the changed records are published.
+
+use crate::{
+    authority::normalize_name,
+    changeset,
+    db::Database,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    lint,
+    models::{DNSRecord, DNSRecordInput, DNSRecordType, DNSSECZone, RecordDiff, Zone},
+    rdata, rollover, signer, zones,
+};
+use async_graphql::ID;
+use chrono::Utc;
+
+/// Create a record from `input`, under `id` if given, in the enclosing zone.
+pub async fn insert(
+    db: &Database,
+    manager: &DNSSECManager,
+    input: DNSRecordInput,
+    id: Option<ID>,
+) -> Result<DNSRecord> {
+    // Validate record
+    let value = validate(&input)?;
+    let zone = zones::enclosing_zone(db, &input.name).await?;
+
+    // Records in a signed zone are always signed
+    let dnssec = require_signed_zone(db, &input).await?;
+    let signed = dnssec.is_some();
+    reject_key_rrset(&input, &zone.apex, signed)?;
+
+    // Create record
+    let before = db.zone_records(&zone.apex).await?;
+    let name = normalize_name(&input.name);
+    let at_name: Vec<DNSRecord> = before.iter().filter(|r| r.name == name).cloned().collect();
+    let ttl = rrset_ttl(&input, &at_name, zone.default_ttl);
+    let mut record = DNSRecord::new(name, input.record_type, ttl, value);
+    if let Some(id) = id {
+        record.id = id;
+    }
+    record.dnssec = signed;
+    check_rrsets(&zone.apex, &at_name, &record)?;
+    record.zone = Some(zone.apex.clone());
+
+    let id = record.id.clone();
+    let after = with_record(&before, record);
+    let changes = changeset::store(db, manager, &zone, dnssec.as_ref(), &before, after).await?;
+    stored(db, changes, &id).await
+}
+
+/// Rewrite a stored record in place from `input`. The record stays in its
+/// zone: moving it to another takes a delete and a create.
+pub async fn rewrite(
+    db: &Database,
+    manager: &DNSSECManager,
+    mut record: DNSRecord,
+    input: DNSRecordInput,
+) -> Result<DNSRecord> {
+    if record.record_type == DNSRecordType::SOA {
+        return Err(soa_managed_by_zone(&record.name));
+    }
+
+    // Validate new data
+    let value = validate(&input)?;
+    let zone = zones::enclosing_zone(db, &input.name).await?;
+    if record.zone.as_deref() != Some(zone.apex.as_str()) {
+        return Err(AppError::InvalidRecord(format!(
+            "{} would move from zone {} to {}; delete it and create it there instead",
+            record.name,
+            record.zone.as_deref().unwrap_or("(none)"),
+            zone.apex
+        )));
+    }
+    let dnssec = require_signed_zone(db, &input).await?;
+    let signed = dnssec.is_some();
+    reject_key_rrset(&input, &zone.apex, signed)?;
+    let before = db.zone_records(&zone.apex).await?;
+    let name = normalize_name(&input.name);
+    let at_name: Vec<DNSRecord> = before
+        .iter()
+        .filter(|r| r.name == name && r.id != record.id)
+        .cloned()
+        .collect();
+
+    // Update fields
+    record.name = name;
+    record.record_type = input.record_type;
+    record.ttl = rrset_ttl(&input, &at_name, zone.default_ttl);
+    record.value = value;
+    record.updated_at = Utc::now();
+    record.dnssec = signed;
+    record.rrsig = None;
+    check_rrsets(&zone.apex, &at_name, &record)?;
+
+    // The RRset the record left is re-signed along with the one it joined
+    let id = record.id.clone();
+    let after = with_record(&before, record);
+    let changes = changeset::store(db, manager, &zone, dnssec.as_ref(), &before, after).await?;
+    stored(db, changes, &id).await
+}
+
+/// Delete a stored record, re-signing what remains of its RRset.
+pub async fn remove(db: &Database, manager: &DNSSECManager, record: DNSRecord) -> Result<()> {
+    if record.record_type == DNSRecordType::SOA {
+        return Err(soa_managed_by_zone(&record.name));
+    }
+    let zone = zone_of(db, &record).await?;
+    let dnssec = signer::find_signed_zone(db, &zone.apex).await?;
+    let before = db.zone_records(&zone.apex).await?;
+    let after = before.iter().filter(|r| r.id != record.id).cloned().collect();
+    changeset::store(db, manager, &zone, dnssec.as_ref(), &before, after).await?;
+    Ok(())
+}
+
+/// Restore a record to the state `version` of its history left it in:
+/// rewritten in place, recreated under its ID if deleted since, or deleted if
+/// that version deleted it. Returns the restored record, or `None` when the
+/// restored state is deleted.
+pub async fn revert(
+    db: &Database,
+    manager: &DNSSECManager,
+    id: &ID,
+    version: i32,
+) -> Result<Option<DNSRecord>> {
+    let entry = db
+        .record_history(id)
+        .await?
+        .into_iter()
+        .find(|entry| entry.version == version)
+        .ok_or_else(|| {
+            AppError::RecordNotFound(format!("{} has no version {}", id.as_str(), version))
+        })?;
+    let current = db.get_record(id).await.ok();
+
+    // The restored state is written like any other change, so it is
+    // validated and signed for the zone as it is now
+    match (entry.after, current) {
+        (Some(restored), current) => {
+            let input = DNSRecordInput {
+                name: restored.name,
+                record_type: restored.record_type,
+                ttl: Some(restored.ttl),
+                value: Some(restored.value),
+                dnssec: None,
+                mx: None,
+                srv: None,
+                caa: None,
+                tlsa: None,
+            };
+            let record = match current {
+                Some(record) => rewrite(db, manager, record, input).await?,
+                None => insert(db, manager, input, Some(id.clone())).await?,
+            };
+            Ok(Some(record))
+        }
+        (None, Some(record)) => {
+            remove(db, manager, record).await?;
+            Ok(None)
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Validate DNS record input, returning its RDATA in normalised form
+fn validate(input: &DNSRecordInput) -> Result<String> {
+    // Validate name (basic check)
+    if input.name.is_empty() {
+        return Err(AppError::InvalidRecord("DNS name cannot be empty".into()));
+    }
+
+    // Validate TTL
+    if input.ttl.is_some_and(|ttl| ttl < 0) {
+        return Err(AppError::InvalidRecord("TTL must be positive".into()));
+    }
+
+    if input.record_type == DNSRecordType::SOA {
+        return Err(soa_managed_by_zone(&input.name));
+    }
+
+    // Parse the value for its type, normalised for storage
+    rdata::input_value(input)
+}
+
+/// The zone a stored record belongs to.
+async fn zone_of(db: &Database, record: &DNSRecord) -> Result<Zone> {
+    match &record.zone {
+        Some(apex) => db.get_zone(apex).await,
+        None => Err(AppError::ZoneNotFound(record.name.clone())),
+    }
+}
+
+/// The record with `id` as a write left it: as stored when it changed,
+/// otherwise read back.
+async fn stored(db: &Database, changes: Vec<RecordDiff>, id: &ID) -> Result<DNSRecord> {
+    match changes
+        .into_iter()
+        .find_map(|change| change.after.filter(|r| r.id == *id))
+    {
+        Some(record) => Ok(record),
+        None => db.get_record(id).await,
+    }
+}
+
+/// The apex SOA is written from the zone, not through the record mutations.
+fn soa_managed_by_zone(name: &str) -> AppError {
+    AppError::InvalidRecord(format!("The SOA of {} is managed by its zone", name))
+}
+
+/// The signed zone a record will live in, if any.
+///
+/// Explicitly requesting `dnssec: true` outside any DNSSEC-enabled zone is an error.
+async fn require_signed_zone(db: &Database, input: &DNSRecordInput) -> Result<Option<DNSSECZone>> {
+    let signed = signer::find_signed_zone(db, &input.name).await?;
+    if input.dnssec == Some(true) && signed.is_none() {
+        return Err(AppError::DNSSEC(format!(
+            "DNSSEC requested but no DNSSEC-enabled zone encloses {}",
+            input.name
+        )));
+    }
+    Ok(signed)
+}
+
+/// Refuse DNSKEY, CDS and CDNSKEY records at the apex of a signed zone, which
+/// publishes those RRsets from its keys.
+fn reject_key_rrset(input: &DNSRecordInput, apex: &str, signed: bool) -> Result<()> {
+    if signed && rollover::is_key_rrset(&normalize_name(&input.name), input.record_type, apex) {
+        return Err(AppError::InvalidRecord(format!(
+            "{} records at {} are published from the zone's DNSSEC keys",
+            input.record_type, apex
+        )));
+    }
+    Ok(())
+}
+
+/// TTL for a record joining an RRset: the one given, else the RRset's own,
+/// else the zone default. `at_name` holds the other records at its name.
+fn rrset_ttl(input: &DNSRecordInput, at_name: &[DNSRecord], default_ttl: i32) -> i32 {
+    input
+        .ttl
+        .or_else(|| {
+            at_name
+                .iter()
+                .find(|r| r.record_type == input.record_type)
+                .map(|r| r.ttl)
+        })
+        .unwrap_or(default_ttl)
+}
+
+/// Refuse a write that would break an RRset invariant at the record's name
+/// ([`lint::check`]). `at_name` holds the other records there; the record's
+/// RRset takes its TTL as a whole.
+fn check_rrsets(apex: &str, at_name: &[DNSRecord], record: &DNSRecord) -> Result<()> {
+    let mut after = at_name.to_vec();
+    for other in after
+        .iter_mut()
+        .filter(|r| r.record_type == record.record_type)
+    {
+        other.ttl = record.ttl;
+    }
+    after.push(record.clone());
+    lint::check(apex, &after)
+}
+
+/// `before` with `record` in place of the stored record with its ID, if any;
+/// the record's RRset takes its TTL as a whole.
+fn with_record(before: &[DNSRecord], record: DNSRecord) -> Vec<DNSRecord> {
+    let mut after: Vec<DNSRecord> = before.iter().filter(|r| r.id != record.id).cloned().collect();
+    for other in after
+        .iter_mut()
+        .filter(|r| r.name == record.name && r.record_type == record.record_type)
+    {
+        other.ttl = record.ttl;
+    }
+    after.push(record);
+    after
+}
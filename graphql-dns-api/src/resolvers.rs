@@ -1,18 +1,46 @@
 // SPDX-License-Identifier: PMPL-1.0-or-later
 //! GraphQL resolvers for DNS queries and mutations
 
-use async_graphql::{Context, Object, Result, ID};
-use chrono::Utc;
+use async_graphql::{
+    connection::{self, Connection, Edge},
+    futures_util::{future, Stream, StreamExt},
+    Context, Object, Result, Subscription, ID,
+};
+use chrono::{DateTime, Utc};
 
 use crate::{
-    blockchain::BlockchainClient,
+    authority::normalize_name,
+    blockchain::{self, BlockchainClient},
+    changeset,
     db::Database,
+    denial,
     dnssec::DNSSECManager,
+    events::Event,
+    history,
+    lint,
     models::{
-        BlockchainProvenance, DNSRecord, DNSRecordInput, DNSRecordType, DNSSECZone, DNSStatistics,
-        ReverseDNSResult,
+        BlockchainProvenance, ChangesetResult, DNSRecord, DNSRecordConnectionFields,
+        DNSRecordFilter, DNSRecordInput, DNSRecordOrder, DNSRecordType, DNSSECAlgorithm,
+        DNSSECKey, DNSSECKeyRole, DNSSECZone, DNSStatistics, DSDigestType, DenialRecord,
+        KeyRolloverAction, LintWarning, NSEC3Input, ParentDSSet, RecordChange, RecordChangeInput,
+        RecordCursor, RecordHistoryEntry, ReverseDNSResult, TSIGAlgorithm, TSIGKey,
+        TSIGKeySecret, TransferPeerInput, Zone, ZoneImportMode, ZoneImportResult, ZoneInput,
+        ZoneNotification,
     },
+    proposals::{self, ProposalPayload},
+    records,
+    rollover,
+    xfr,
+    zonefile,
+    zones,
 };
+use std::sync::Arc;
+
+/// Page size of `dnsRecordsConnection` when neither `first` nor `last` is given
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page `dnsRecordsConnection` returns
+const MAX_PAGE_SIZE: usize = 1000;
 
 /// GraphQL Query root
 pub struct QueryRoot;
@@ -22,7 +50,7 @@ impl QueryRoot {
     /// Get single DNS record by ID
     async fn dns_record(&self, ctx: &Context<'_>, id: ID) -> Result<Option<DNSRecord>> {
         let db = ctx.data::<Database>()?;
-        match db.get_record(&id.to_string()).await {
+        match db.get_record(&id).await {
             Ok(record) => Ok(Some(record)),
             Err(_) => Ok(None),
         }
@@ -42,6 +70,77 @@ async fn dns_records(
         Ok(records)
     }
 
+    /// Page through DNS records matching a filter, Relay-style.
+    ///
+    /// Cursors are keyed on the sort field and record ID, so records written
+    /// between requests neither shift nor repeat the pages that follow. Pass
+    /// `first` (default 100) to page forward or `last` to page backward.
+    #[allow(clippy::too_many_arguments)]
+    async fn dns_records_connection(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: DNSRecordFilter,
+        #[graphql(default)] order: DNSRecordOrder,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<RecordCursor, DNSRecord, DNSRecordConnectionFields>> {
+        let db = ctx.data::<Database>()?;
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<RecordCursor>, before: Option<RecordCursor>, first, last| async move {
+                if first.is_some() && last.is_some() {
+                    return Err("pass either first or last, not both".into());
+                }
+                let from_end = last.is_some();
+                let limit = first.or(last).unwrap_or(DEFAULT_PAGE_SIZE);
+                if limit > MAX_PAGE_SIZE {
+                    return Err(format!("at most {} records per page", MAX_PAGE_SIZE).into());
+                }
+
+                // One record past the page tells whether there are more
+                let mut records = db
+                    .page_records(
+                        &filter,
+                        order,
+                        after.as_ref(),
+                        before.as_ref(),
+                        limit + 1,
+                        from_end,
+                    )
+                    .await?;
+                let more = records.len() > limit;
+                records.truncate(limit);
+                if from_end {
+                    records.reverse();
+                }
+                let (has_previous, has_next) = if from_end {
+                    (more, before.is_some())
+                } else {
+                    (after.is_some(), more)
+                };
+
+                let total_count = db.count_records(&filter).await?;
+                let mut connection = Connection::with_additional_fields(
+                    has_previous,
+                    has_next,
+                    DNSRecordConnectionFields { total_count },
+                );
+                connection.edges.extend(
+                    records
+                        .into_iter()
+                        .map(|record| Edge::new(RecordCursor::new(&record, order), record)),
+                );
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
     /// Reverse DNS lookup (IP to hostname)
     async fn reverse_dns(&self, ctx: &Context<'_>, ip: String) -> Result<ReverseDNSResult> {
         let db = ctx.data::<Database>()?;
@@ -64,6 +163,40 @@ async fn reverse_dns(&self, ctx: &Context<'_>, ip: String) -> Result<ReverseDNSR
         })
     }
 
+    /// List zones ordered by apex
+    async fn zones(&self, ctx: &Context<'_>) -> Result<Vec<Zone>> {
+        let db = ctx.data::<Database>()?;
+        let zones = db.zones().await?;
+        Ok(zones)
+    }
+
+    /// Get a zone by apex
+    async fn zone(&self, ctx: &Context<'_>, apex: String) -> Result<Option<Zone>> {
+        let db = ctx.data::<Database>()?;
+        match db.get_zone(&normalize_name(&apex)).await {
+            Ok(zone) => Ok(Some(zone)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// NOTIFY status of each secondary of a zone for its latest serial
+    async fn zone_notifications(
+        &self,
+        ctx: &Context<'_>,
+        apex: String,
+    ) -> Result<Vec<ZoneNotification>> {
+        let db = ctx.data::<Database>()?;
+        let notifications = db.zone_notifications(&normalize_name(&apex)).await?;
+        Ok(notifications)
+    }
+
+    /// List TSIG keys (secrets are never returned)
+    async fn tsig_keys(&self, ctx: &Context<'_>) -> Result<Vec<TSIGKey>> {
+        let db = ctx.data::<Database>()?;
+        let keys = db.tsig_keys().await?;
+        Ok(keys)
+    }
+
     /// Get DNSSEC configuration for a zone
     async fn dnssec_zone(&self, ctx: &Context<'_>, zone: String) -> Result<Option<DNSSECZone>> {
         let db = ctx.data::<Database>()?;
@@ -73,6 +206,85 @@ async fn dnssec_zone(&self, ctx: &Context<'_>, zone: String) -> Result<Option<DN
         }
     }
 
+    /// List a zone's DNSSEC keys (public metadata only), oldest first
+    async fn dnssec_keys(&self, ctx: &Context<'_>, zone: String) -> Result<Vec<DNSSECKey>> {
+        let db = ctx.data::<Database>()?;
+        let keys = db.dnssec_keys(Some(&zone)).await?;
+        Ok(keys)
+    }
+
+    /// Next step of each key rollover in progress for a zone, with the earliest safe time
+    async fn key_rollover_actions(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+    ) -> Result<Vec<KeyRolloverAction>> {
+        let db = ctx.data::<Database>()?;
+        let actions = rollover::pending_actions(db, &zone).await?;
+        Ok(actions)
+    }
+
+    /// DS records the parent of a signed zone should hold, as its CDS/CDNSKEY signal
+    async fn parent_ds_set(&self, ctx: &Context<'_>, zone: String) -> Result<ParentDSSet> {
+        let db = ctx.data::<Database>()?;
+        let parent = rollover::parent_ds(db, &zone).await?;
+        Ok(parent)
+    }
+
+    /// List a signed zone's NSEC or NSEC3 chain in chain order
+    async fn denial_chain(&self, ctx: &Context<'_>, zone: String) -> Result<Vec<DenialRecord>> {
+        let db = ctx.data::<Database>()?;
+        let records = db.denial_records(Some(&zone)).await?;
+        Ok(records)
+    }
+
+    /// Export a zone as a canonical RFC 1035 master file (with DNSSEC material if signed)
+    async fn zone_file(&self, ctx: &Context<'_>, zone: String) -> Result<String> {
+        let db = ctx.data::<Database>()?;
+        let text = zonefile::export_zone(db, &zone).await?;
+        Ok(text)
+    }
+
+    /// Check a zone for RRset invariant breaches and likely mistakes
+    async fn lint_zone(&self, ctx: &Context<'_>, zone: String) -> Result<Vec<LintWarning>> {
+        let db = ctx.data::<Database>()?;
+        let warnings = lint::lint_zone(db, &zone).await?;
+        Ok(warnings)
+    }
+
+    /// What `applyChangeset` would do to a zone, and the lint of the result,
+    /// without committing anything
+    async fn preview_changeset(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        changes: Vec<RecordChangeInput>,
+    ) -> Result<ChangesetResult> {
+        let db = ctx.data::<Database>()?;
+        let result = changeset::preview(db, &zone, changes).await?;
+        Ok(result)
+    }
+
+    /// Every change to a record, oldest first; kept after the record is deleted
+    async fn record_history(&self, ctx: &Context<'_>, id: ID) -> Result<Vec<RecordHistoryEntry>> {
+        let db = ctx.data::<Database>()?;
+        let entries = db.record_history(&id).await?;
+        Ok(entries)
+    }
+
+    /// The records of a zone as they were at `timestamp`, read from the record
+    /// history
+    async fn zone_at(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Vec<DNSRecord>> {
+        let db = ctx.data::<Database>()?;
+        let records = history::zone_at(db, &normalize_name(&zone), timestamp).await?;
+        Ok(records)
+    }
+
     /// Get blockchain provenance for a record
     async fn blockchain_provenance(
         &self,
@@ -80,7 +292,7 @@ async fn blockchain_provenance(
         record_id: ID,
     ) -> Result<Option<BlockchainProvenance>> {
         let db = ctx.data::<Database>()?;
-        match db.get_provenance(&record_id.to_string()).await {
+        match db.get_provenance(&record_id).await {
             Ok(provenance) => Ok(Some(provenance)),
             Err(_) => Ok(None),
         }
@@ -116,7 +328,7 @@ async fn policy(&self, ctx: &Context<'_>) -> Result<crate::policy::Policy> {
     async fn proposal(&self, ctx: &Context<'_>, id: ID) -> Result<Option<crate::policy::MutationProposal>> {
         let enforcer = ctx.data::<std::sync::Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
         let enforcer = enforcer.read().await;
-        Ok(enforcer.get_proposal(&id.to_string()).cloned())
+        Ok(enforcer.get_proposal(&id).cloned())
     }
 
     /// Get all mutation proposals
@@ -164,27 +376,15 @@ async fn create_dns_record(
             .ok_or_else(|| async_graphql::Error::new(
                 "Authentication required: no identity in request context"
             ))?;
+        let db = &db.acting_as(&identity);
 
-        // Check DNS operations consent
-        crate::consent::require_dns_consent(consent, &identity).await?;
+        // Check the mutate_dns privilege and DNS operations consent
+        let enforcer = ctx.data::<Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        crate::policy::authorize_dns_change(enforcer, consent, &identity).await?;
 
-        // Validate record
-        validate_dns_record(&input)?;
-
-        // Create record
-        let mut record = DNSRecord::new(
-            input.name,
-            input.record_type,
-            input.ttl,
-            input.value,
-        );
-
-        if let Some(dnssec) = input.dnssec {
-            record.dnssec = dnssec;
-        }
-
-        let created = db.create_record(record).await?;
-        Ok(created)
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let record = records::insert(db, dnssec_manager, input, None).await?;
+        Ok(record)
     }
 
     /// Update an existing DNS record
@@ -195,73 +395,434 @@ async fn update_dns_record(
         input: DNSRecordInput,
     ) -> Result<DNSRecord> {
         let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
 
-        // Get existing record
-        let mut record = db.get_record(&id.to_string()).await?;
-
-        // Validate new data
-        validate_dns_record(&input)?;
-
-        // Update fields
-        record.name = input.name;
-        record.record_type = input.record_type;
-        record.ttl = input.ttl;
-        record.value = input.value;
-        record.updated_at = Utc::now();
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
 
-        if let Some(dnssec) = input.dnssec {
-            record.dnssec = dnssec;
-        }
+        // Check the mutate_dns privilege and DNS operations consent
+        let enforcer = ctx.data::<Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        crate::policy::authorize_dns_change(enforcer, consent, &identity).await?;
 
-        let updated = db.update_record(&id.to_string(), record).await?;
-        Ok(updated)
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let record = db.get_record(&id).await?;
+        let record = records::rewrite(db, dnssec_manager, record, input).await?;
+        Ok(record)
     }
 
     /// Delete a DNS record
     async fn delete_dns_record(&self, ctx: &Context<'_>, id: ID) -> Result<bool> {
         let db = ctx.data::<Database>()?;
-        db.delete_record(&id.to_string()).await?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
+
+        // Check the mutate_dns privilege and DNS operations consent
+        let enforcer = ctx.data::<Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        crate::policy::authorize_dns_change(enforcer, consent, &identity).await?;
+
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let record = db.get_record(&id).await?;
+        records::remove(db, dnssec_manager, record).await?;
         Ok(true)
     }
 
-    /// Enable DNSSEC for a zone
-    async fn enable_dnssec(&self, ctx: &Context<'_>, zone: String) -> Result<DNSSECZone> {
+    /// Restore a record to the state a version of its history left it in:
+    /// rewritten in place, recreated under its ID if deleted since, or deleted
+    /// if that version deleted it. The revert is itself a new version. Returns
+    /// the restored record, or null when the restored state is deleted.
+    async fn revert_record(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        version: i32,
+    ) -> Result<Option<DNSRecord>> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
+
+        // Check the mutate_dns privilege and DNS operations consent
+        let enforcer = ctx.data::<Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        crate::policy::authorize_dns_change(enforcer, consent, &identity).await?;
+
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let record = records::revert(db, dnssec_manager, &id, version).await?;
+        Ok(record)
+    }
+
+    /// Create, update and delete records of one zone in order, all in one
+    /// transaction with the serial bump and any signatures: if any change is
+    /// invalid, the result breaks an RRset invariant or the zone changed
+    /// meanwhile, nothing is written.
+    async fn apply_changeset(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        changes: Vec<RecordChangeInput>,
+    ) -> Result<ChangesetResult> {
         let db = ctx.data::<Database>()?;
-        let dnssec_manager = DNSSECManager::new();
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
 
-        // Generate DNSSEC keys
-        let (ksk, zsk, ds_record) = dnssec_manager.generate_keys(&zone)?;
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
 
-        let dnssec_zone = DNSSECZone {
-            zone,
-            enabled: true,
-            ksk: Some(ksk),
-            zsk: Some(zsk),
-            ds_record: Some(ds_record),
-            last_rotation: Some(Utc::now()),
-        };
+        // Check the mutate_dns privilege and DNS operations consent
+        let enforcer = ctx.data::<Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        crate::policy::authorize_dns_change(enforcer, consent, &identity).await?;
+
+        let result = changeset::commit(db, dnssec_manager, &zone, changes).await?;
+        Ok(result)
+    }
+
+    /// Create a zone owned by the caller, with its SOA record; records already
+    /// stored under the apex join it
+    async fn create_zone(&self, ctx: &Context<'_>, input: ZoneInput) -> Result<Zone> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        let zone = zones::create(db, input, &identity).await?;
+        Ok(zone)
+    }
+
+    /// Delete a zone and its records (zones below it keep theirs); only its
+    /// owner may, and a signed zone must have DNSSEC disabled first
+    async fn delete_zone(&self, ctx: &Context<'_>, apex: String) -> Result<bool> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        let zone = zones::require_owner(db, &apex, &identity).await?;
+        zones::delete(db, &zone.apex).await?;
+        Ok(true)
+    }
+
+    /// Replace the secondaries allowed to transfer a zone; only its owner may
+    async fn set_zone_transfer_acl(
+        &self,
+        ctx: &Context<'_>,
+        apex: String,
+        peers: Vec<TransferPeerInput>,
+    ) -> Result<Zone> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        let zone = zones::require_owner(db, &apex, &identity).await?;
+        let zone = xfr::set_acl(db, &zone.apex, peers).await?;
+        Ok(zone)
+    }
+
+    /// Generate a TSIG key for zone transfers and dynamic updates, owned by the
+    /// caller; the secret (base64) is returned only here
+    async fn create_tsig_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default)] algorithm: TSIGAlgorithm,
+    ) -> Result<TSIGKeySecret> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        let key = xfr::create_key(db, dnssec_manager, &name, algorithm, &identity, false).await?;
+        Ok(key)
+    }
+
+    /// Return the secret of a TSIG key a proposal generated, once; only its
+    /// owner may claim it
+    async fn claim_tsig_key_secret(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> Result<TSIGKeySecret> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+
+        let key = db.get_tsig_key(&normalize_name(&name)).await?;
+        if key.owner != identity {
+            return Err(format!("TSIG key {} is owned by {}", key.name, key.owner).into());
+        }
+        let key = xfr::claim_key_secret(db, dnssec_manager, key).await?;
+        Ok(key)
+    }
+
+    /// Delete a TSIG key no transfer ACL refers to; only its owner may
+    async fn delete_tsig_key(&self, ctx: &Context<'_>, name: String) -> Result<bool> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
 
-        let created = db.upsert_dnssec_zone(dnssec_zone).await?;
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        let deleted = xfr::delete_key(db, &name, &identity).await?;
+        Ok(deleted)
+    }
+
+    /// Import an RFC 1035 master zone file (all-or-nothing)
+    async fn import_zone(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        zone_file: String,
+        #[graphql(default)] mode: ZoneImportMode,
+    ) -> Result<ZoneImportResult> {
+        let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        let db = &db.acting_as(&identity);
+
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
+
+        // The zone takes its SOA parameters from the file; a signed zone is
+        // re-signed as a whole, chain included
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let result =
+            zonefile::import_zone_as(db, dnssec_manager, &zone, &zone_file, mode, &identity)
+                .await?;
+        Ok(result)
+    }
+
+    /// Enable DNSSEC for a zone
+    ///
+    /// Each listed algorithm gets its own KSK/ZSK pair and every RRset is signed
+    /// with all of them, so a standard algorithm can run alongside the hybrid one.
+    /// Denial of existence uses NSEC unless `nsec3` parameters are given.
+    async fn enable_dnssec(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        #[graphql(default_with = "vec![DNSSECAlgorithm::HybridEd448Dilithium5]")]
+        algorithms: Vec<DNSSECAlgorithm>,
+        digest_type: Option<DSDigestType>,
+        nsec3: Option<NSEC3Input>,
+    ) -> Result<DNSSECZone> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
+
+        let nsec3 = nsec3.map(denial::nsec3_config).transpose()?;
+        let created =
+            rollover::enable(db, dnssec_manager, &zone, &algorithms, digest_type, nsec3).await?;
         Ok(created)
     }
 
-    /// Rotate DNSSEC keys for a zone
-    async fn rotate_dnssec_keys(&self, ctx: &Context<'_>, zone: String) -> Result<DNSSECZone> {
+    /// Rotate DNSSEC keys for a zone by starting staged ZSK and KSK rollovers.
+    /// `immediate: true` replaces every key at once instead, which is required
+    /// to change algorithms but breaks validators caching the old DNSKEY or DS
+    async fn rotate_dnssec_keys(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        algorithms: Option<Vec<DNSSECAlgorithm>>,
+        digest_type: Option<DSDigestType>,
+        immediate: Option<bool>,
+    ) -> Result<DNSSECZone> {
         let db = ctx.data::<Database>()?;
-        let dnssec_manager = DNSSECManager::new();
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
 
-        // Get existing zone
-        let mut dnssec_zone = db.get_dnssec_zone(&zone).await?;
+        let immediate = immediate.unwrap_or(false);
+        let updated =
+            rollover::rotate(db, dnssec_manager, &zone, algorithms, digest_type, immediate).await?;
+        Ok(updated)
+    }
 
-        // Rotate keys
-        let (ksk, zsk, ds_record) = dnssec_manager.generate_keys(&zone)?;
+    /// Disable DNSSEC for a zone in two steps (RFC 8078 §4). The first call
+    /// publishes the CDS/CDNSKEY delete signal while the zone stays signed; once
+    /// the parent has removed every DS, `dsRemoved: true` unsigns the zone and
+    /// removes its keys
+    async fn disable_dnssec(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        #[graphql(default)] ds_removed: bool,
+    ) -> Result<DNSSECZone> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
 
-        dnssec_zone.ksk = Some(ksk);
-        dnssec_zone.zsk = Some(zsk);
-        dnssec_zone.ds_record = Some(ds_record);
-        dnssec_zone.last_rotation = Some(Utc::now());
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
 
-        let updated = db.upsert_dnssec_zone(dnssec_zone).await?;
+        let updated = rollover::disable(db, dnssec_manager, &zone, ds_removed).await?;
+        Ok(updated)
+    }
+
+    /// Start a staged rollover of a zone's ZSKs (pre-publish) or KSKs (double-DS).
+    /// `parentDsTtl` records the TTL of the DS RRset at the parent for KSK timings
+    async fn start_key_rollover(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        role: DNSSECKeyRole,
+        digest_type: Option<DSDigestType>,
+        parent_ds_ttl: Option<i32>,
+    ) -> Result<Vec<DNSSECKey>> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
+
+        let keys =
+            rollover::start(db, dnssec_manager, &zone, role, digest_type, parent_ds_ttl).await?;
+        zones::bump_serial(db, dnssec_manager, &zone).await?;
+        Ok(keys)
+    }
+
+    /// Take the next step of a key rollover once its wait has elapsed; for
+    /// steps at the parent, confirms the DS was submitted or withdrawn
+    async fn advance_key_rollover(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        role: DNSSECKeyRole,
+    ) -> Result<Vec<DNSSECKey>> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
+
+        let keys = rollover::advance(db, dnssec_manager, &zone, role).await?;
+        zones::bump_serial(db, dnssec_manager, &zone).await?;
+        Ok(keys)
+    }
+
+    /// Switch a signed zone between NSEC (`nsec3: null`) and NSEC3, or change its
+    /// NSEC3 salt, iterations or opt-out; the chain is rebuilt and re-signed
+    async fn set_denial_of_existence(
+        &self,
+        ctx: &Context<'_>,
+        zone: String,
+        nsec3: Option<NSEC3Input>,
+    ) -> Result<DNSSECZone> {
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+        zones::require_owner(db, &zone, &identity).await?;
+
+        let updated = denial::set_denial_of_existence(db, dnssec_manager, &zone, nsec3).await?;
+        zones::bump_serial(db, dnssec_manager, &updated.zone).await?;
         Ok(updated)
     }
 
@@ -273,31 +834,20 @@ async fn anchor_to_blockchain(
         network: String,
     ) -> Result<BlockchainProvenance> {
         let db = ctx.data::<Database>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
 
-        // Get the record
-        let record = db.get_record(&record_id.to_string()).await?;
-
-        // Calculate content hash
-        let content_hash = record.content_hash();
-
-        // Initialize blockchain client
-        let blockchain_client = BlockchainClient::new(&network)?;
-
-        // Anchor hash to blockchain
-        let (tx_hash, block_number) = blockchain_client.anchor_hash(&content_hash).await?;
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
 
-        // Store provenance
-        let provenance = BlockchainProvenance {
-            record_id: record_id.clone(),
-            content_hash,
-            network,
-            tx_hash,
-            block_number,
-            timestamp: Utc::now(),
-        };
+        // Check DNS operations consent
+        crate::consent::require_dns_consent(consent, &identity).await?;
 
-        let stored = db.store_provenance(provenance).await?;
-        Ok(stored)
+        let provenance = blockchain::anchor_record(db, &record_id, &network).await?;
+        Ok(provenance)
     }
 
     /// Propose a mutation (requires approval and timelock)
@@ -318,6 +868,9 @@ async fn propose_mutation(
             ))?;
 
         let proposal = enforcer.propose_mutation(&mutation_name, &identity, payload)?;
+        ctx.data::<Database>()?
+            .events()
+            .publish(Event::ProposalStatusChanged(proposal.clone()));
         Ok(proposal)
     }
 
@@ -337,63 +890,203 @@ async fn approve_mutation(
                 "Authentication required: no identity in request context"
             ))?;
 
-        let proposal = enforcer.approve_proposal(&proposal_id.to_string(), &identity)?;
+        let proposal = enforcer.approve_proposal(&proposal_id, &identity)?;
+        ctx.data::<Database>()?
+            .events()
+            .publish(Event::ProposalStatusChanged(proposal.clone()));
         Ok(proposal)
     }
 
-    /// Execute an approved mutation
+    /// Execute an approved mutation: its payload runs on behalf of the
+    /// proposer, and the result or error is recorded on the proposal
     async fn execute_mutation(
         &self,
         ctx: &Context<'_>,
         proposal_id: ID,
     ) -> Result<crate::policy::MutationProposal> {
         let enforcer = ctx.data::<std::sync::Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
-        let mut enforcer = enforcer.write().await;
+        let db = ctx.data::<Database>()?;
+        let dnssec_manager = ctx.data::<Arc<DNSSECManager>>()?;
+        let consent = ctx.data::<std::sync::Arc<crate::consent::ConsentClient>>()?;
 
-        let proposal = enforcer.execute_proposal(&proposal_id.to_string())?;
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
 
-        // TODO: Actually execute the mutation based on proposal.payload
-        // For now, just mark as executed
+        // Only holders of the proposal's privilege may execute it. It is marked
+        // executed before the payload runs, so it runs once.
+        let proposal = {
+            let mut enforcer = enforcer.write().await;
+            let mutation_name = enforcer
+                .get_proposal(proposal_id.as_str())
+                .map(|proposal| proposal.mutation_name.clone())
+                .ok_or_else(|| format!("Proposal not found: {}", proposal_id.as_str()))?;
+            if !enforcer.has_privilege(&identity, &mutation_name) {
+                let message =
+                    format!("Identity {} lacks the {} privilege", identity, mutation_name);
+                return Err(message.into());
+            }
+            enforcer.execute_proposal(&proposal_id)?
+        };
 
+        let db = &db.acting_as(&proposal.proposer);
+        let outcome = match ProposalPayload::parse(&proposal.mutation_name, &proposal.payload) {
+            Ok(payload) => {
+                proposals::execute(db, dnssec_manager, consent, &proposal.proposer, payload).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &outcome {
+            tracing::warn!("Proposal {} failed: {}", proposal.id, e);
+        }
+        let proposal = enforcer
+            .write()
+            .await
+            .record_outcome(&proposal.id, outcome.map_err(|e| e.to_string()))?;
+
+        db.events().publish(Event::ProposalStatusChanged(proposal.clone()));
         Ok(proposal)
     }
-}
 
-/// Validate DNS record input
-fn validate_dns_record(input: &DNSRecordInput) -> Result<()> {
-    // Validate name (basic check)
-    if input.name.is_empty() {
-        return Err("DNS name cannot be empty".into());
+    /// Anchor the hash of an executed proposal and its outcome to blockchain;
+    /// the provenance is stored under the proposal's ID
+    async fn anchor_proposal(
+        &self,
+        ctx: &Context<'_>,
+        proposal_id: ID,
+        network: String,
+    ) -> Result<BlockchainProvenance> {
+        let enforcer = ctx.data::<std::sync::Arc<tokio::sync::RwLock<crate::policy::PolicyEnforcer>>>()?;
+        let db = ctx.data::<Database>()?;
+
+        // Get identity from context (mTLS cert or auth token) — reject unauthenticated
+        let identity = ctx.data_opt::<String>()
+            .cloned()
+            .ok_or_else(|| async_graphql::Error::new(
+                "Authentication required: no identity in request context"
+            ))?;
+
+        // Only holders of the proposal's privilege may anchor it
+        let proposal = {
+            let enforcer = enforcer.read().await;
+            let proposal = enforcer
+                .get_proposal(proposal_id.as_str())
+                .cloned()
+                .ok_or_else(|| format!("Proposal not found: {}", proposal_id.as_str()))?;
+            if !enforcer.has_privilege(&identity, &proposal.mutation_name) {
+                let message =
+                    format!("Identity {} lacks the {} privilege", identity, proposal.mutation_name);
+                return Err(message.into());
+            }
+            proposal
+        };
+        if proposal.executed_at.is_none() {
+            return Err(format!("Proposal {} has not been executed", proposal.id).into());
+        }
+
+        // Anchor hash to blockchain
+        let content_hash = proposal.content_hash();
+        let blockchain_client = BlockchainClient::new(&network)?;
+        let (tx_hash, block_number) = blockchain_client.anchor_hash(&content_hash).await?;
+
+        // Store provenance
+        let provenance = BlockchainProvenance {
+            record_id: proposal_id,
+            content_hash,
+            network,
+            tx_hash,
+            block_number,
+            timestamp: Utc::now(),
+        };
+
+        let stored = db.store_provenance(provenance).await?;
+        db.events().publish(Event::AnchorConfirmed(stored.clone()));
+        Ok(stored)
     }
+}
+
+/// GraphQL Subscription root, fed by the database's event bus
+pub struct SubscriptionRoot;
 
-    // Validate TTL
-    if input.ttl < 0 {
-        return Err("TTL must be positive".into());
+#[Subscription]
+impl SubscriptionRoot {
+    /// Records as they are created, updated or deleted, optionally in one zone only
+    async fn record_changed(
+        &self,
+        ctx: &Context<'_>,
+        zone: Option<String>,
+    ) -> Result<impl Stream<Item = RecordChange>> {
+        let zone = zone.map(|zone| normalize_name(&zone));
+        events(ctx, move |event| match event {
+            Event::RecordChanged(change) if zone.is_none() || change.record.zone == zone => {
+                Some(change)
+            }
+            _ => None,
+        })
     }
 
-    // Validate value based on type
-    match input.record_type {
-        DNSRecordType::A => {
-            // Basic IPv4 validation
-            if !input.value.split('.').all(|part| part.parse::<u8>().is_ok()) {
-                return Err("Invalid IPv4 address".into());
+    /// Zones as their serial advances, optionally one zone only
+    async fn zone_serial_changed(
+        &self,
+        ctx: &Context<'_>,
+        zone: Option<String>,
+    ) -> Result<impl Stream<Item = Zone>> {
+        let zone = zone.map(|zone| normalize_name(&zone));
+        events(ctx, move |event| match event {
+            Event::ZoneSerialChanged(changed)
+                if zone.is_none() || zone.as_ref() == Some(&changed.apex) =>
+            {
+                Some(changed)
             }
-        }
-        DNSRecordType::AAAA => {
-            // Basic IPv6 validation
-            if !input.value.contains(':') {
-                return Err("Invalid IPv6 address".into());
+            _ => None,
+        })
+    }
+
+    /// Mutation proposals as they are proposed, approved and executed,
+    /// optionally one proposal only
+    async fn proposal_status_changed(
+        &self,
+        ctx: &Context<'_>,
+        id: Option<ID>,
+    ) -> Result<impl Stream<Item = crate::policy::MutationProposal>> {
+        events(ctx, move |event| match event {
+            Event::ProposalStatusChanged(proposal)
+                if id.is_none() || id.as_deref() == Some(&proposal.id) =>
+            {
+                Some(proposal)
             }
-        }
-        _ => {
-            // Other types - basic non-empty check
-            if input.value.is_empty() {
-                return Err("Record value cannot be empty".into());
+            _ => None,
+        })
+    }
+
+    /// Blockchain anchors once their transaction is confirmed, optionally for
+    /// one record only
+    async fn anchor_confirmed(
+        &self,
+        ctx: &Context<'_>,
+        record_id: Option<ID>,
+    ) -> Result<impl Stream<Item = BlockchainProvenance>> {
+        events(ctx, move |event| match event {
+            Event::AnchorConfirmed(provenance)
+                if record_id.is_none() || record_id.as_ref() == Some(&provenance.record_id) =>
+            {
+                Some(provenance)
             }
-        }
+            _ => None,
+        })
     }
+}
 
-    Ok(())
+/// The events `pick` selects from the bus, from now on.
+fn events<T>(
+    ctx: &Context<'_>,
+    pick: impl Fn(Event) -> Option<T> + Send + 'static,
+) -> Result<impl Stream<Item = T>> {
+    let events = ctx.data::<Database>()?.events().subscribe();
+    Ok(events.filter_map(move |event| future::ready(pick(event))))
 }
 
 /// Convert IP address to reverse DNS name
@@ -0,0 +1,1012 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Staged DNSSEC key rollovers and the apex DNSKEY RRset
+//!
+//! Replacing keys in one step breaks every validator still caching the old
+//! DNSKEY or DS RRset, so rollovers are staged (RFC 6781 §4.1, RFC 7583):
+//!
+//! - ZSK pre-publish: the new ZSK joins the DNSKEY RRset. Once the old RRset
+//!   has expired from caches it starts signing and the old ZSK is retired; once
+//!   signatures made by the old ZSK have expired, it is removed.
+//! - KSK double-DS: the new KSK's DS is submitted to the parent next to the old
+//!   one. Once the old DS RRset has expired from caches the KSKs are swapped in
+//!   the DNSKEY RRset; once the old DNSKEY RRset has expired, the old DS is withdrawn.
+//!
+//! Every wait is the relevant TTL plus [`ROLLOVER_MARGIN_SECS`].
+//! [`next_actions`] reports the step each rollover is waiting on and
+//! [`advance`] takes it. Steps at the parent are carried out by the operator
+//! and confirmed with [`advance`].
+//!
+//! The apex CDS and CDNSKEY RRsets (RFC 7344) follow the same lifecycle and
+//! list every KSK the parent should hold a DS for, so parents that scan for
+//! them can take those steps themselves. Disabling DNSSEC publishes the
+//! RFC 8078 delete signal before the zone goes unsigned.
+//!
+//! [`enable`], [`rotate`] and [`disable`] run a zone's key lifecycle as a
+//! whole, for the GraphQL mutations and approved `rotate_keys` proposals alike.
+
+use crate::{
+    authority::{normalize_name, rrsig_wire, wire_type},
+    db::Database,
+    denial,
+    dnssec::{
+        dnskey_rdata, dnskey_record, needs_resign, DNSSECManager, PublishedKeys, DNSKEY_FLAGS_KSK,
+    },
+    error::{AppError, Result},
+    models::{
+        DNSRecord, DNSRecordType, DNSSECAlgorithm, DNSSECKey, DNSSECKeyRole, DNSSECKeyState,
+        DNSSECZone, DSDigestType, KeyRolloverAction, KeyRolloverStep, NSEC3Config, ParentDSSet,
+    },
+    signer, zones,
+};
+use base64::Engine as _;
+use chrono::{DateTime, Duration, Utc};
+use hickory_proto::rr::{rdata::NULL, Name, RData, Record, RecordType};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// Propagation delay plus safety margin added to every rollover wait (seconds).
+pub const ROLLOVER_MARGIN_SECS: i64 = 3600;
+/// Parent DS TTL assumed when the zone does not record one (seconds).
+pub const DEFAULT_PARENT_DS_TTL: i32 = 86400;
+/// TTL used for the DNSKEY RRset when the zone has no SOA (seconds).
+pub const DEFAULT_DNSKEY_TTL: i32 = 3600;
+
+/// Apex types published with the key lifecycle, present in every signed zone.
+pub const APEX_KEY_TYPES: [RecordType; 3] =
+    [RecordType::DNSKEY, RecordType::CDS, RecordType::CDNSKEY];
+
+/// CDS RDATA asking the parent to remove every DS (RFC 8078 §4).
+pub const CDS_DELETE: &str = "0 0 0 00";
+/// CDNSKEY RDATA asking the parent to remove every DS (RFC 8078 §4).
+pub const CDNSKEY_DELETE: &str = "0 3 0 AA==";
+
+/// Maximum records fetched for the apex SOA lookup.
+const RRSET_LIMIT: i32 = 1000;
+
+/// A CDS or CDNSKEY RRset at the zone apex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRRset {
+    /// `CDS` or `CDNSKEY`
+    pub record_type: RecordType,
+    /// RDATA in presentation format, sorted
+    pub rdata: Vec<String>,
+    /// KSK RRSIGs over the RRset, one per line
+    pub rrsig: Option<String>,
+}
+
+impl KeyRRset {
+    /// Wire-format records at `apex`.
+    ///
+    /// hickory has no constructors for CDS/CDNSKEY, so the RDATA is carried
+    /// opaquely; it is identical to DS/DNSKEY RDATA.
+    pub fn records(&self, apex: &str, ttl: u32) -> Result<Vec<Record>> {
+        let owner = Name::from_str(&format!("{}.", apex))
+            .map_err(|e| AppError::DNSSEC(format!("Invalid zone name {}: {}", apex, e)))?;
+        self.rdata
+            .iter()
+            .map(|rdata| {
+                let rdata = RData::Unknown {
+                    code: self.record_type,
+                    rdata: NULL::with(rdata_wire(self.record_type, rdata)?),
+                };
+                Ok(Record::from_rdata(owner.clone(), ttl, rdata))
+            })
+            .collect()
+    }
+}
+
+/// TTL of the apex DNSKEY RRset: the SOA TTL, like the rest of the apex.
+pub fn dnskey_ttl(apex: &str, records: &[DNSRecord]) -> i32 {
+    records
+        .iter()
+        .find(|r| r.record_type == DNSRecordType::SOA && normalize_name(&r.name) == apex)
+        .map_or(DEFAULT_DNSKEY_TTL, |soa| soa.ttl)
+}
+
+/// Next step of each rollover in progress for a zone, ZSK first.
+///
+/// `keys` are the zone's stored keys and `records` its record store contents,
+/// from which the DNSKEY TTL and the longest RRSIG lifetime in caches follow.
+pub fn next_actions(
+    zone: &DNSSECZone,
+    keys: &[DNSSECKey],
+    records: &[DNSRecord],
+    now: DateTime<Utc>,
+) -> Vec<KeyRolloverAction> {
+    [DNSSECKeyRole::ZSK, DNSSECKeyRole::KSK]
+        .into_iter()
+        .filter_map(|role| next_action(zone, role, keys, records, now))
+        .collect()
+}
+
+/// Next step of the `role` rollover, if one is in progress.
+fn next_action(
+    zone: &DNSSECZone,
+    role: DNSSECKeyRole,
+    keys: &[DNSSECKey],
+    records: &[DNSRecord],
+    now: DateTime<Utc>,
+) -> Option<KeyRolloverAction> {
+    let apex = normalize_name(&zone.zone);
+    let in_state = |state| {
+        keys.iter()
+            .filter(|k| k.role == role && k.state == state)
+            .collect::<Vec<_>>()
+    };
+    let published = in_state(DNSSECKeyState::Published);
+    let retired = in_state(DNSSECKeyState::Retired);
+
+    let dnskey_wait = wait(dnskey_ttl(&apex, records));
+    let (step, not_before, acted_on, description) = match role {
+        DNSSECKeyRole::ZSK if !published.is_empty() => (
+            KeyRolloverStep::ActivateZsk,
+            latest(&published, changed_at) + dnskey_wait,
+            published,
+            format!(
+                "Start signing {} with the pre-published ZSK and retire the old one",
+                apex
+            ),
+        ),
+        DNSSECKeyRole::ZSK if !retired.is_empty() => {
+            let max_ttl = records.iter().map(|r| r.ttl).max().unwrap_or(0);
+            let max_ttl = max_ttl.max(dnskey_ttl(&apex, records));
+            (
+                KeyRolloverStep::RemoveZsk,
+                latest(&retired, changed_at) + wait(max_ttl),
+                retired,
+                format!("Remove the retired ZSK from the {} DNSKEY RRset", apex),
+            )
+        }
+        DNSSECKeyRole::KSK if published.iter().any(|k| k.ds_submitted_at.is_none()) => (
+            KeyRolloverStep::SubmitDs,
+            latest(&published, changed_at),
+            published,
+            format!(
+                "Make sure the parent of {} publishes the new KSK's DS next to the current \
+                 one (CDS/CDNSKEY already list it), then confirm with advanceKeyRollover",
+                apex
+            ),
+        ),
+        DNSSECKeyRole::KSK if !published.is_empty() => {
+            let parent_ds_ttl = zone.parent_ds_ttl.unwrap_or(DEFAULT_PARENT_DS_TTL);
+            (
+                KeyRolloverStep::ActivateKsk,
+                latest(&published, |k| k.ds_submitted_at.unwrap_or(k.created_at))
+                    + wait(parent_ds_ttl),
+                published,
+                format!(
+                    "Replace the old KSK with the new one in the {} DNSKEY RRset",
+                    apex
+                ),
+            )
+        }
+        DNSSECKeyRole::KSK if !retired.is_empty() => (
+            KeyRolloverStep::WithdrawDs,
+            latest(&retired, changed_at) + dnskey_wait,
+            retired,
+            format!(
+                "Withdraw the retired KSK's DS from the parent of {}; advanceKeyRollover \
+                 drops it from CDS/CDNSKEY",
+                apex
+            ),
+        ),
+        _ => return None,
+    };
+
+    let ds_records = match role {
+        DNSSECKeyRole::KSK => acted_on
+            .iter()
+            .filter_map(|k| k.ds_record.clone())
+            .collect(),
+        DNSSECKeyRole::ZSK => Vec::new(),
+    };
+    Some(KeyRolloverAction {
+        zone: apex,
+        role,
+        step,
+        description,
+        key_tags: acted_on.iter().map(|k| k.key_tag).collect(),
+        ds_records,
+        not_before,
+        ready: now >= not_before,
+    })
+}
+
+/// Pending rollover steps for a stored zone.
+pub async fn pending_actions(db: &Database, zone: &str) -> Result<Vec<KeyRolloverAction>> {
+    let apex = normalize_name(zone);
+    let config = db.get_dnssec_zone(&apex).await?;
+    let keys = db.dnssec_keys(Some(&apex)).await?;
+    let records = db.zone_records(&apex).await?;
+    Ok(next_actions(&config, &keys, &records, Utc::now()))
+}
+
+/// Start a staged rollover of a signed zone's `role` keys.
+///
+/// A new key is generated for every algorithm the current keys use and stored
+/// as published. A new ZSK joins the DNSKEY RRset immediately; a new KSK waits
+/// for its DS to reach the parent. `parent_ds_ttl` records the parent's DS TTL.
+pub async fn start(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    role: DNSSECKeyRole,
+    digest: Option<DSDigestType>,
+    parent_ds_ttl: Option<i32>,
+) -> Result<Vec<DNSSECKey>> {
+    let apex = normalize_name(zone);
+    let mut config = signed_zone(db, &apex).await?;
+    if config.cds_delete {
+        return Err(AppError::DNSSEC(format!("DNSSEC is being disabled for {}", apex)));
+    }
+    let keys = db.dnssec_keys(Some(&apex)).await?;
+
+    let records = db.zone_records(&apex).await?;
+    if next_action(&config, role, &keys, &records, Utc::now()).is_some() {
+        return Err(AppError::DNSSEC(format!(
+            "A {:?} rollover is already in progress for {}",
+            role, apex
+        )));
+    }
+
+    let mut algorithms: Vec<DNSSECAlgorithm> = keys
+        .iter()
+        .filter(|k| k.role == role && k.state == DNSSECKeyState::Active)
+        .filter_map(|k| {
+            u8::try_from(k.algorithm)
+                .ok()
+                .and_then(DNSSECAlgorithm::from_number)
+        })
+        .collect();
+    algorithms.sort();
+    algorithms.dedup();
+    if algorithms.is_empty() {
+        algorithms = config.algorithms.clone();
+    }
+    if algorithms.is_empty() {
+        return Err(AppError::DNSSEC(format!(
+            "No {:?} keys to roll for {}",
+            role, apex
+        )));
+    }
+
+    if let Some(ttl) = parent_ds_ttl {
+        if ttl <= 0 {
+            return Err(AppError::DNSSEC("Parent DS TTL must be positive".into()));
+        }
+        config.parent_ds_ttl = Some(ttl);
+        db.upsert_dnssec_zone(config).await?;
+    }
+
+    let staged = manager.stage_keys(&apex, role, &algorithms, digest).await?;
+    info!(
+        "Started {:?} rollover for {} ({} keys)",
+        role,
+        apex,
+        staged.len()
+    );
+    publish_key_rrsets(db, manager, &apex).await?;
+    db.dnssec_keys(Some(&apex)).await
+}
+
+/// Take the next step of a zone's `role` rollover.
+///
+/// Fails if no rollover is in progress or the step's wait has not elapsed.
+pub async fn advance(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    role: DNSSECKeyRole,
+) -> Result<Vec<DNSSECKey>> {
+    let apex = normalize_name(zone);
+    let config = signed_zone(db, &apex).await?;
+    let keys = db.dnssec_keys(Some(&apex)).await?;
+    let records = db.zone_records(&apex).await?;
+
+    let now = Utc::now();
+    let action = next_action(&config, role, &keys, &records, now).ok_or_else(|| {
+        AppError::DNSSEC(format!("No {:?} rollover in progress for {}", role, apex))
+    })?;
+    if !action.ready {
+        return Err(AppError::DNSSEC(format!(
+            "{:?} for {} is not safe before {}",
+            action.step, apex, action.not_before
+        )));
+    }
+
+    let of_role = |state| {
+        keys.iter()
+            .filter(move |k| k.role == role && k.state == state)
+    };
+    match action.step {
+        KeyRolloverStep::ActivateZsk | KeyRolloverStep::ActivateKsk => {
+            for key in of_role(DNSSECKeyState::Active) {
+                set_state(db, key, DNSSECKeyState::Retired, now).await?;
+            }
+            for key in of_role(DNSSECKeyState::Published) {
+                set_state(db, key, DNSSECKeyState::Active, now).await?;
+            }
+            manager.reload_zone(&apex).await?;
+
+            let mut config = config;
+            describe_active_keys(&mut config, &db.dnssec_keys(Some(&apex)).await?);
+            config.last_rotation = Some(now);
+            let config = db.upsert_dnssec_zone(config).await?;
+
+            if action.step == KeyRolloverStep::ActivateZsk {
+                signer::sign_zone(db, manager, &apex).await?;
+                denial::rebuild_chain(db, manager, &config).await?;
+            }
+        }
+        KeyRolloverStep::RemoveZsk => {
+            for key in of_role(DNSSECKeyState::Retired) {
+                set_state(db, key, DNSSECKeyState::Removed, now).await?;
+            }
+        }
+        KeyRolloverStep::SubmitDs => {
+            for key in of_role(DNSSECKeyState::Published) {
+                let mut key = key.clone();
+                key.ds_submitted_at = Some(now);
+                db.update_dnssec_key(key).await?;
+            }
+        }
+        KeyRolloverStep::WithdrawDs => {
+            for key in of_role(DNSSECKeyState::Retired) {
+                set_state(db, key, DNSSECKeyState::Removed, now).await?;
+            }
+        }
+    }
+
+    publish_key_rrsets(db, manager, &apex).await?;
+    info!("{:?} rollover for {}: {:?} done", role, apex, action.step);
+    db.dnssec_keys(Some(&apex)).await
+}
+
+/// Enable DNSSEC for a zone: a KSK/ZSK pair per algorithm, every RRset signed
+/// with all of them, the denial chain built (NSEC3 when `nsec3` is given) and
+/// the serial bumped.
+pub async fn enable(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    algorithms: &[DNSSECAlgorithm],
+    digest: Option<DSDigestType>,
+    nsec3: Option<NSEC3Config>,
+) -> Result<DNSSECZone> {
+    let zone = db.get_zone(&normalize_name(zone)).await?.apex;
+
+    // Generate DNSSEC keys
+    let published = manager.provision_keys(&zone, algorithms, digest).await?;
+
+    let mut dnssec_zone = DNSSECZone {
+        zone,
+        enabled: true,
+        ksk: None,
+        zsk: None,
+        ds_record: None,
+        algorithms: Vec::new(),
+        ds_records: Vec::new(),
+        nsec3,
+        dnskey_rrsig: None,
+        cds_rrsig: None,
+        cdnskey_rrsig: None,
+        cds_delete: false,
+        parent_ds_ttl: None,
+        last_rotation: Some(Utc::now()),
+    };
+    apply_published_keys(&mut dnssec_zone, published);
+
+    let created = db.upsert_dnssec_zone(dnssec_zone).await?;
+    signer::sign_zone(db, manager, &created.zone).await?;
+    denial::rebuild_chain(db, manager, &created).await?;
+    let created = publish_key_rrsets(db, manager, &created.zone).await?;
+    zones::bump_serial(db, manager, &created.zone).await?;
+    Ok(created)
+}
+
+/// Rotate a zone's keys by starting staged ZSK and KSK rollovers, or with
+/// `immediate` replace every key at once, which is required to change
+/// algorithms but breaks validators caching the old DNSKEY or DS.
+pub async fn rotate(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    algorithms: Option<Vec<DNSSECAlgorithm>>,
+    digest: Option<DSDigestType>,
+    immediate: bool,
+) -> Result<DNSSECZone> {
+    // Get existing zone
+    let mut dnssec_zone = db.get_dnssec_zone(zone).await?;
+
+    if !immediate {
+        if let Some(mut algorithms) = algorithms {
+            algorithms.sort();
+            algorithms.dedup();
+            if algorithms != dnssec_zone.algorithms {
+                return Err(AppError::DNSSEC(
+                    "Changing algorithms requires an immediate rotation".into(),
+                ));
+            }
+        }
+        for role in [DNSSECKeyRole::ZSK, DNSSECKeyRole::KSK] {
+            start(db, manager, zone, role, digest, None).await?;
+        }
+        zones::bump_serial(db, manager, &dnssec_zone.zone).await?;
+        return db.get_dnssec_zone(&dnssec_zone.zone).await;
+    }
+
+    // Rotate keys
+    let algorithms = match algorithms {
+        Some(algorithms) => algorithms,
+        None if dnssec_zone.algorithms.is_empty() => {
+            vec![DNSSECAlgorithm::HybridEd448Dilithium5]
+        }
+        None => dnssec_zone.algorithms.clone(),
+    };
+    let published = manager.provision_keys(zone, &algorithms, digest).await?;
+
+    apply_published_keys(&mut dnssec_zone, published);
+    dnssec_zone.last_rotation = Some(Utc::now());
+
+    let updated = db.upsert_dnssec_zone(dnssec_zone).await?;
+    signer::sign_zone(db, manager, &updated.zone).await?;
+    denial::rebuild_chain(db, manager, &updated).await?;
+    let updated = publish_key_rrsets(db, manager, &updated.zone).await?;
+    zones::bump_serial(db, manager, &updated.zone).await?;
+    Ok(updated)
+}
+
+/// Disable DNSSEC for a zone in two steps (RFC 8078 §4): first publish the
+/// CDS/CDNSKEY delete signal while the zone stays signed, then, once the
+/// parent has removed every DS (`ds_removed`), unsign the zone and remove its
+/// keys.
+pub async fn disable(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    ds_removed: bool,
+) -> Result<DNSSECZone> {
+    let mut dnssec_zone = db.get_dnssec_zone(zone).await?;
+    if !dnssec_zone.enabled {
+        return Err(AppError::DNSSEC(format!("DNSSEC is not enabled for {}", zone)));
+    }
+
+    if !ds_removed {
+        dnssec_zone.cds_delete = true;
+        let updated = db.upsert_dnssec_zone(dnssec_zone).await?;
+        let updated = publish_key_rrsets(db, manager, &updated.zone).await?;
+        zones::bump_serial(db, manager, &updated.zone).await?;
+        return Ok(updated);
+    }
+    if !dnssec_zone.cds_delete {
+        return Err(AppError::DNSSEC(format!(
+            "Publish the delete signal for {} and wait for the parent to remove its DS first",
+            zone
+        )));
+    }
+
+    db.unsign_zone(&dnssec_zone.zone).await?;
+    manager.remove_keys(&dnssec_zone.zone).await?;
+    zones::bump_serial(db, manager, &dnssec_zone.zone).await?;
+    db.get_dnssec_zone(&dnssec_zone.zone).await
+}
+
+/// Record freshly generated keys on a zone: the first algorithm's KSK/ZSK/DS,
+/// plus every algorithm and DS record.
+fn apply_published_keys(zone: &mut DNSSECZone, published: Vec<PublishedKeys>) {
+    zone.algorithms = published.iter().map(|p| p.algorithm).collect();
+    zone.ds_records = published.iter().map(|p| p.ds_record.clone()).collect();
+    if let Some(first) = published.into_iter().next() {
+        zone.ksk = Some(first.ksk);
+        zone.zsk = Some(first.zsk);
+        zone.ds_record = Some(first.ds_record);
+    }
+}
+
+/// CDS and CDNSKEY RRsets listing the KSKs the parent should hold a DS for.
+///
+/// That is every KSK from the moment it is staged until a double-DS rollover
+/// withdraws it, or the delete signal once DNSSEC is being disabled. The RRsets
+/// are empty when the zone has no KSK; stored RRSIGs are carried over from `zone`.
+pub fn key_rrsets(zone: &DNSSECZone, keys: &[DNSSECKey]) -> Result<Vec<KeyRRset>> {
+    let (cds, cdnskey) = if zone.cds_delete {
+        (vec![CDS_DELETE.to_string()], vec![CDNSKEY_DELETE.to_string()])
+    } else {
+        let ksks = parent_ksks(zone, keys);
+        let cds = ksks
+            .iter()
+            .filter_map(|(_, ds)| ds.as_deref())
+            .map(|ds| {
+                ds.split_whitespace()
+                    .skip(3)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let cdnskey = ksks
+            .iter()
+            .map(|(key, _)| format!("{} 3 {} {}", DNSKEY_FLAGS_KSK, key.algorithm, key.public_key))
+            .collect();
+        (cds, cdnskey)
+    };
+
+    [
+        (RecordType::CDS, cds, &zone.cds_rrsig),
+        (RecordType::CDNSKEY, cdnskey, &zone.cdnskey_rrsig),
+    ]
+    .into_iter()
+    .map(|(record_type, mut rdata, rrsig)| {
+        for value in &rdata {
+            rdata_wire(record_type, value)?;
+        }
+        rdata.sort();
+        Ok(KeyRRset {
+            record_type,
+            rdata,
+            rrsig: rrsig.clone(),
+        })
+    })
+    .collect()
+}
+
+/// The DS set the zone's CDS/CDNSKEY RRsets ask the parent to hold.
+pub fn parent_ds_set(zone: &DNSSECZone, keys: &[DNSSECKey]) -> ParentDSSet {
+    let ksks = if zone.cds_delete {
+        Vec::new()
+    } else {
+        parent_ksks(zone, keys)
+    };
+    ParentDSSet {
+        zone: normalize_name(&zone.zone),
+        ds_records: ksks.iter().filter_map(|(_, ds)| ds.clone()).collect(),
+        key_tags: ksks.iter().map(|(key, _)| key.key_tag).collect(),
+        delete: zone.cds_delete,
+    }
+}
+
+/// Parent DS set of a stored, signed zone.
+pub async fn parent_ds(db: &Database, zone: &str) -> Result<ParentDSSet> {
+    let apex = normalize_name(zone);
+    let config = signed_zone(db, &apex).await?;
+    let keys = db.dnssec_keys(Some(&apex)).await?;
+    Ok(parent_ds_set(&config, &keys))
+}
+
+/// Sign the zone's DNSKEY, CDS and CDNSKEY RRsets with its active KSKs and
+/// store the RRSIGs.
+pub async fn publish_key_rrsets(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+) -> Result<DNSSECZone> {
+    let apex = normalize_name(zone);
+    let mut config = db.get_dnssec_zone(&apex).await?;
+    let keys = db.dnssec_keys(Some(&apex)).await?;
+    let dnskeys: Vec<DNSSECKey> = keys.iter().filter(|k| k.in_dnskey_rrset()).cloned().collect();
+    let ttl = apex_dnskey_ttl(db, &apex).await? as u32;
+    let now = Utc::now();
+
+    config.dnskey_rrsig = Some(manager.sign_dnskeys(&apex, &dnskeys, ttl, now)?);
+    for rrset in key_rrsets(&config, &keys)? {
+        let rrsig = if rrset.rdata.is_empty() {
+            None
+        } else {
+            Some(manager.sign_key_rrset(&apex, &rrset.records(&apex, ttl)?, now)?)
+        };
+        match rrset.record_type {
+            RecordType::CDS => config.cds_rrsig = rrsig,
+            _ => config.cdnskey_rrsig = rrsig,
+        }
+    }
+    db.upsert_dnssec_zone(config).await
+}
+
+/// Re-sign apex key RRsets whose signatures are missing or close to expiry.
+///
+/// Returns the number of zones whose key RRsets were re-signed.
+pub async fn resign_expiring(db: &Database, manager: &DNSSECManager) -> Result<usize> {
+    let now = Utc::now();
+    let mut resigned = 0;
+    for zone in db.signed_zones().await? {
+        let rrsigs = [&zone.dnskey_rrsig, &zone.cds_rrsig, &zone.cdnskey_rrsig];
+        if !rrsigs.iter().any(|rrsig| needs_resign(rrsig.as_deref(), now)) {
+            continue;
+        }
+        match publish_key_rrsets(db, manager, &zone.zone).await {
+            Ok(_) => resigned += 1,
+            Err(e) => warn!("Could not re-sign key RRsets of {}: {}", zone.zone, e),
+        }
+    }
+    Ok(resigned)
+}
+
+/// Whether a record at `name` would stand in for a key RRset of the signed
+/// zone `apex`: its DNSKEY, CDS and CDNSKEY RRsets are published from its
+/// keys, so they cannot be stored as records.
+pub fn is_key_rrset(name: &str, record_type: DNSRecordType, apex: &str) -> bool {
+    name == apex && APEX_KEY_TYPES.contains(&wire_type(record_type))
+}
+
+/// Answer a DNSKEY, CDS or CDNSKEY query at the apex of a signed zone.
+///
+/// Returns nothing for other names and types, or unsigned zones.
+pub async fn answer(
+    db: &Database,
+    apex: &str,
+    qname: &str,
+    qtype: RecordType,
+    dnssec_ok: bool,
+) -> Result<Vec<Record>> {
+    if !APEX_KEY_TYPES.contains(&qtype) || qname != apex {
+        return Ok(Vec::new());
+    }
+    let zone = match db.get_dnssec_zone(apex).await {
+        Ok(zone) if zone.enabled => zone,
+        _ => return Ok(Vec::new()),
+    };
+
+    let ttl = apex_dnskey_ttl(db, apex).await? as u32;
+    let keys = db.dnssec_keys(Some(apex)).await?;
+    let (mut records, rrsig) = if qtype == RecordType::DNSKEY {
+        let records = keys
+            .iter()
+            .filter(|k| k.in_dnskey_rrset())
+            .map(|key| dnskey_record(apex, key, ttl))
+            .collect::<Result<Vec<_>>>()?;
+        (records, zone.dnskey_rrsig.clone())
+    } else {
+        match key_rrsets(&zone, &keys)?
+            .into_iter()
+            .find(|rrset| rrset.record_type == qtype)
+        {
+            Some(rrset) => (rrset.records(apex, ttl)?, rrset.rrsig),
+            None => return Ok(Vec::new()),
+        }
+    };
+    if dnssec_ok && !records.is_empty() {
+        for rrsig in rrsig.iter().flat_map(|s| s.lines()) {
+            records.push(rrsig_wire(apex, ttl, rrsig)?);
+        }
+    }
+    Ok(records)
+}
+
+/// KSKs the parent should hold a DS for, sorted, with their DS record.
+///
+/// Keys stored before DS records were kept per key fall back to the zone's
+/// DS record with the same key tag.
+fn parent_ksks<'a>(
+    zone: &DNSSECZone,
+    keys: &'a [DNSSECKey],
+) -> Vec<(&'a DNSSECKey, Option<String>)> {
+    let mut ksks: Vec<&DNSSECKey> = keys
+        .iter()
+        .filter(|k| k.role == DNSSECKeyRole::KSK && k.state != DNSSECKeyState::Removed)
+        .collect();
+    ksks.sort_by_key(|k| (k.algorithm, k.key_tag));
+    ksks.into_iter()
+        .map(|key| {
+            let ds = key.ds_record.clone().or_else(|| {
+                let tag = key.key_tag.to_string();
+                zone.ds_records
+                    .iter()
+                    .find(|ds| ds.split_whitespace().nth(3) == Some(tag.as_str()))
+                    .cloned()
+            });
+            (key, ds)
+        })
+        .collect()
+}
+
+/// Wire-format CDS (`<tag> <alg> <digest type> <hex>`) or CDNSKEY
+/// (`<flags> 3 <alg> <base64>`) RDATA.
+fn rdata_wire(record_type: RecordType, rdata: &str) -> Result<Vec<u8>> {
+    let invalid = || AppError::DNSSEC(format!("Invalid {} RDATA: {}", record_type, rdata));
+    let fields: Vec<&str> = rdata.split_whitespace().collect();
+    let [first, second, third, data] = fields[..] else {
+        return Err(invalid());
+    };
+    if record_type == RecordType::CDS {
+        let mut wire = first.parse::<u16>().map_err(|_| invalid())?.to_be_bytes().to_vec();
+        wire.push(second.parse().map_err(|_| invalid())?);
+        wire.push(third.parse().map_err(|_| invalid())?);
+        wire.extend(hex::decode(data).map_err(|_| invalid())?);
+        Ok(wire)
+    } else {
+        if second != "3" {
+            return Err(invalid());
+        }
+        let public_key = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|_| invalid())?;
+        Ok(dnskey_rdata(
+            first.parse().map_err(|_| invalid())?,
+            third.parse().map_err(|_| invalid())?,
+            &public_key,
+        ))
+    }
+}
+
+/// DNSKEY TTL from the stored apex SOA.
+async fn apex_dnskey_ttl(db: &Database, apex: &str) -> Result<i32> {
+    let soa = db
+        .query_records(
+            Some(apex.to_string()),
+            Some(DNSRecordType::SOA),
+            RRSET_LIMIT,
+            0,
+        )
+        .await?;
+    Ok(dnskey_ttl(apex, &soa))
+}
+
+/// Zone configuration, which must have DNSSEC enabled.
+async fn signed_zone(db: &Database, apex: &str) -> Result<DNSSECZone> {
+    let zone = db.get_dnssec_zone(apex).await?;
+    if !zone.enabled {
+        return Err(AppError::DNSSEC(format!(
+            "DNSSEC is not enabled for {}",
+            apex
+        )));
+    }
+    Ok(zone)
+}
+
+/// Move a stored key to `state`.
+async fn set_state(
+    db: &Database,
+    key: &DNSSECKey,
+    state: DNSSECKeyState,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let mut key = key.clone();
+    key.state = state;
+    key.state_changed_at = Some(now);
+    db.update_dnssec_key(key).await?;
+    Ok(())
+}
+
+/// Record the zone's active keys on its configuration: the first algorithm's
+/// KSK/ZSK/DS, plus every algorithm and DS record.
+fn describe_active_keys(zone: &mut DNSSECZone, keys: &[DNSSECKey]) {
+    let mut active: Vec<&DNSSECKey> = keys
+        .iter()
+        .filter(|k| k.state == DNSSECKeyState::Active)
+        .collect();
+    active.sort_by_key(|k| k.algorithm);
+    let ksks: Vec<&DNSSECKey> = active
+        .iter()
+        .copied()
+        .filter(|k| k.role == DNSSECKeyRole::KSK)
+        .collect();
+
+    zone.algorithms = ksks
+        .iter()
+        .filter_map(|k| {
+            u8::try_from(k.algorithm)
+                .ok()
+                .and_then(DNSSECAlgorithm::from_number)
+        })
+        .collect();
+    zone.ds_records = ksks.iter().filter_map(|k| k.ds_record.clone()).collect();
+    zone.ksk = ksks.first().map(|k| k.public_key.clone());
+    zone.ds_record = ksks.first().and_then(|k| k.ds_record.clone());
+    zone.zsk = active
+        .iter()
+        .find(|k| k.role == DNSSECKeyRole::ZSK)
+        .map(|k| k.public_key.clone());
+}
+
+/// When a key entered its current state.
+fn changed_at(key: &DNSSECKey) -> DateTime<Utc> {
+    key.state_changed_at.unwrap_or(key.created_at)
+}
+
+/// Latest of a timestamp over a set of keys.
+fn latest(keys: &[&DNSSECKey], at: impl Fn(&DNSSECKey) -> DateTime<Utc>) -> DateTime<Utc> {
+    keys.iter().map(|&k| at(k)).max().unwrap_or_else(Utc::now)
+}
+
+/// Wait for data with `ttl` to expire from caches, plus the safety margin.
+fn wait(ttl: i32) -> Duration {
+    Duration::seconds(i64::from(ttl.max(0)) + ROLLOVER_MARGIN_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::ID;
+
+    fn zone() -> DNSSECZone {
+        DNSSECZone {
+            zone: "example.com".into(),
+            enabled: true,
+            ksk: None,
+            zsk: None,
+            ds_record: None,
+            algorithms: vec![DNSSECAlgorithm::Ed25519],
+            ds_records: Vec::new(),
+            nsec3: None,
+            dnskey_rrsig: None,
+            cds_rrsig: None,
+            cdnskey_rrsig: None,
+            cds_delete: false,
+            parent_ds_ttl: Some(7200),
+            last_rotation: None,
+        }
+    }
+
+    fn records() -> Vec<DNSRecord> {
+        vec![
+            DNSRecord::new(
+                "example.com".into(),
+                DNSRecordType::SOA,
+                600,
+                "ns1.example.com. host.example.com. 1 7200 900 1209600 300".into(),
+            ),
+            DNSRecord::new(
+                "www.example.com".into(),
+                DNSRecordType::A,
+                86400,
+                "192.0.2.1".into(),
+            ),
+        ]
+    }
+
+    fn key(
+        role: DNSSECKeyRole,
+        key_tag: i32,
+        state: DNSSECKeyState,
+        at: DateTime<Utc>,
+    ) -> DNSSECKey {
+        DNSSECKey {
+            key_id: ID(format!("key-{}", key_tag)),
+            zone: "example.com".into(),
+            role,
+            algorithm: 15,
+            key_tag,
+            public_key: "AQID".into(),
+            state,
+            created_at: at,
+            state_changed_at: Some(at),
+            ds_record: (role == DNSSECKeyRole::KSK)
+                .then(|| format!("example.com IN DS {} 15 2 00", key_tag)),
+            ds_submitted_at: None,
+            sealed_secret: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_no_rollover_in_progress() {
+        let now = Utc::now();
+        let keys = vec![
+            key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Active, now),
+            key(DNSSECKeyRole::ZSK, 2, DNSSECKeyState::Active, now),
+            key(DNSSECKeyRole::ZSK, 3, DNSSECKeyState::Removed, now),
+        ];
+        assert!(next_actions(&zone(), &keys, &records(), now).is_empty());
+    }
+
+    #[test]
+    fn test_zsk_prepublish_waits_for_dnskey_ttl() {
+        let start = Utc::now();
+        let mut keys = vec![
+            key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Active, start),
+            key(DNSSECKeyRole::ZSK, 2, DNSSECKeyState::Active, start),
+            key(DNSSECKeyRole::ZSK, 3, DNSSECKeyState::Published, start),
+        ];
+
+        // The new ZSK may sign once the DNSKEY RRset (SOA TTL 600) has expired
+        let actions = next_actions(&zone(), &keys, &records(), start);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].step, KeyRolloverStep::ActivateZsk);
+        assert_eq!(actions[0].key_tags, vec![3]);
+        assert_eq!(actions[0].not_before, start + wait(600));
+        assert!(!actions[0].ready);
+        assert!(next_actions(&zone(), &keys, &records(), start + wait(600))[0].ready);
+
+        // The old ZSK stays until RRSIGs with the longest TTL have expired
+        let activated = start + wait(600);
+        keys[1] = key(DNSSECKeyRole::ZSK, 2, DNSSECKeyState::Retired, activated);
+        keys[2] = key(DNSSECKeyRole::ZSK, 3, DNSSECKeyState::Active, activated);
+        let actions = next_actions(&zone(), &keys, &records(), activated);
+        assert_eq!(actions[0].step, KeyRolloverStep::RemoveZsk);
+        assert_eq!(actions[0].key_tags, vec![2]);
+        assert_eq!(actions[0].not_before, activated + wait(86400));
+        assert!(keys.iter().all(DNSSECKey::in_dnskey_rrset));
+    }
+
+    #[test]
+    fn test_ksk_double_ds_steps() {
+        let start = Utc::now();
+        let mut keys = vec![
+            key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Active, start),
+            key(DNSSECKeyRole::KSK, 4, DNSSECKeyState::Published, start),
+            key(DNSSECKeyRole::ZSK, 2, DNSSECKeyState::Active, start),
+        ];
+        assert!(!keys[1].in_dnskey_rrset());
+
+        // First the new DS goes to the parent
+        let actions = next_actions(&zone(), &keys, &records(), start);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].role, DNSSECKeyRole::KSK);
+        assert_eq!(actions[0].step, KeyRolloverStep::SubmitDs);
+        assert_eq!(actions[0].ds_records, vec!["example.com IN DS 4 15 2 00"]);
+        assert!(actions[0].ready);
+
+        // Then the swap waits out the parent DS TTL
+        let submitted = start + Duration::minutes(5);
+        keys[1].ds_submitted_at = Some(submitted);
+        let actions = next_actions(&zone(), &keys, &records(), submitted);
+        assert_eq!(actions[0].step, KeyRolloverStep::ActivateKsk);
+        assert_eq!(actions[0].not_before, submitted + wait(7200));
+
+        // Finally the old DS is withdrawn once the old DNSKEY RRset has expired
+        let swapped = submitted + wait(7200);
+        keys[0] = key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Retired, swapped);
+        keys[1] = key(DNSSECKeyRole::KSK, 4, DNSSECKeyState::Active, swapped);
+        let actions = next_actions(&zone(), &keys, &records(), swapped);
+        assert_eq!(actions[0].step, KeyRolloverStep::WithdrawDs);
+        assert_eq!(actions[0].ds_records, vec!["example.com IN DS 1 15 2 00"]);
+        assert_eq!(actions[0].not_before, swapped + wait(600));
+        assert!(!keys[0].in_dnskey_rrset());
+    }
+
+    #[test]
+    fn test_cds_follows_ksk_lifecycle() {
+        let now = Utc::now();
+        let mut keys = vec![
+            key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Active, now),
+            key(DNSSECKeyRole::KSK, 4, DNSSECKeyState::Published, now),
+            key(DNSSECKeyRole::ZSK, 2, DNSSECKeyState::Active, now),
+        ];
+
+        // A staged KSK is signalled to the parent alongside the current one
+        let rrsets = key_rrsets(&zone(), &keys).unwrap();
+        assert_eq!(rrsets[0].record_type, RecordType::CDS);
+        assert_eq!(rrsets[0].rdata, vec!["1 15 2 00", "4 15 2 00"]);
+        assert_eq!(rrsets[1].record_type, RecordType::CDNSKEY);
+        assert_eq!(rrsets[1].rdata, vec!["257 3 15 AQID"; 2]);
+        assert_eq!(parent_ds_set(&zone(), &keys).key_tags, vec![1, 4]);
+
+        // A withdrawn KSK is no longer signalled
+        keys[0].state = DNSSECKeyState::Removed;
+        let parent = parent_ds_set(&zone(), &keys);
+        assert_eq!(parent.ds_records, vec!["example.com IN DS 4 15 2 00"]);
+        assert!(!parent.delete);
+
+        let records = key_rrsets(&zone(), &keys).unwrap()[0]
+            .records("example.com", 600)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].record_type(), RecordType::CDS);
+    }
+
+    #[test]
+    fn test_delete_signal() {
+        let now = Utc::now();
+        let keys = vec![key(DNSSECKeyRole::KSK, 1, DNSSECKeyState::Active, now)];
+        let mut zone = zone();
+        zone.cds_delete = true;
+
+        let parent = parent_ds_set(&zone, &keys);
+        assert!(parent.delete);
+        assert!(parent.ds_records.is_empty());
+
+        // RFC 8078 §4: CDS 0 0 0 00 and CDNSKEY 0 3 0 AA==
+        let rrsets = key_rrsets(&zone, &keys).unwrap();
+        assert_eq!(single_rdata(&rrsets[0]), vec![0, 0, 0, 0, 0]);
+        assert_eq!(single_rdata(&rrsets[1]), vec![0, 0, 3, 0, 0]);
+    }
+
+    fn single_rdata(rrset: &KeyRRset) -> Vec<u8> {
+        assert_eq!(rrset.rdata.len(), 1);
+        rdata_wire(rrset.record_type, &rrset.rdata[0]).unwrap()
+    }
+
+    #[test]
+    fn test_dnskey_ttl_defaults_without_soa() {
+        assert_eq!(dnskey_ttl("example.com", &records()), 600);
+        assert_eq!(dnskey_ttl("example.org", &records()), DEFAULT_DNSKEY_TTL);
+    }
+}
@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: PMPL-1.0-or-later
 //! GraphQL schema type definitions
 
-use async_graphql::{EmptySubscription, Schema};
+use async_graphql::Schema;
 
-use crate::resolvers::{MutationRoot, QueryRoot};
+use crate::resolvers::{MutationRoot, QueryRoot, SubscriptionRoot};
 
 /// Application GraphQL schema
-pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+pub type AppSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
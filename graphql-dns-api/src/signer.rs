@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! RRset signing on write and background re-signing
+//!
+//! Whenever a record in a DNSSEC-enabled zone is created, updated or deleted,
+//! the affected RRsets are re-signed with the zone's ZSK and the RRSIG is stored
+//! on every member's `DNSRecord.rrsig`. A background task re-signs RRsets whose
+//! signatures are missing or close to expiry, including the zone's NSEC/NSEC3
+//! chain and DNSKEY RRset, then bumps the serials of signed zones so that
+//! secondaries transfer the fresh signatures.
+
+use crate::{
+    authority::normalize_name,
+    db::Database,
+    denial,
+    dnssec::{needs_resign, DNSSECManager},
+    error::{AppError, Result},
+    models::{DNSRecord, DNSRecordType, DNSSECZone},
+    rollover, zones,
+};
+use chrono::Utc;
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+/// Maximum records fetched for one RRset.
+const RRSET_LIMIT: i32 = 1000;
+
+/// Find the DNSSEC configuration signing `name`: its zone's, when enabled.
+///
+/// A zone below a signed one is signed only if its own DNSSEC is enabled.
+/// Names outside every zone fall back to the closest DNSSEC-enabled name
+/// above them.
+pub async fn find_signed_zone(db: &Database, name: &str) -> Result<Option<DNSSECZone>> {
+    let name = normalize_name(name);
+    match zones::enclosing_zone(db, &name).await {
+        Ok(zone) => {
+            let signed = db.get_dnssec_zone(&zone.apex).await.ok();
+            return Ok(signed.filter(|zone| zone.enabled));
+        }
+        Err(AppError::ZoneNotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    let mut candidate = name.as_str();
+    loop {
+        if let Ok(zone) = db.get_dnssec_zone(candidate).await {
+            return Ok(zone.enabled.then_some(zone));
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if !parent.is_empty() => candidate = parent,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// Re-sign the RRset at `name`/`record_type` if it lives in a signed zone.
+///
+/// Does nothing when the RRset is empty (e.g. its last member was deleted).
+pub async fn resign_rrset(
+    db: &Database,
+    manager: &DNSSECManager,
+    name: &str,
+    record_type: DNSRecordType,
+) -> Result<()> {
+    let Some(zone) = find_signed_zone(db, name).await? else {
+        return Ok(());
+    };
+
+    let rrset = db
+        .query_records(Some(name.to_string()), Some(record_type), RRSET_LIMIT, 0)
+        .await?;
+    if rrset.is_empty() {
+        return Ok(());
+    }
+
+    let rrsig = manager.sign_rrset(&zone.zone, &rrset, Utc::now())?;
+    store_rrsig(db, rrset, &rrsig).await
+}
+
+/// Sign every RRset in a zone (after enabling DNSSEC or rotating keys).
+pub async fn sign_zone(db: &Database, manager: &DNSSECManager, zone: &str) -> Result<usize> {
+    let records = db.zone_records(&normalize_name(zone)).await?;
+    let rrsets: BTreeSet<(String, DNSRecordType)> = records
+        .iter()
+        .map(|r| (r.name.clone(), r.record_type))
+        .collect();
+
+    let now = Utc::now();
+    let mut signed = 0;
+    for (name, record_type) in &rrsets {
+        let rrset: Vec<DNSRecord> = records
+            .iter()
+            .filter(|r| &r.name == name && r.record_type == *record_type)
+            .cloned()
+            .collect();
+        let rrsig = manager.sign_rrset(zone, &rrset, now)?;
+        store_rrsig(db, rrset, &rrsig).await?;
+        signed += 1;
+    }
+
+    Ok(signed)
+}
+
+/// Re-sign RRsets with missing or soon-to-expire signatures.
+///
+/// Returns the number of RRsets re-signed, NSEC/NSEC3 chain records and
+/// DNSKEY RRsets included.
+/// RRsets whose zone has no loaded signing key are skipped with a warning.
+pub async fn resign_expiring(db: &Database, manager: &DNSSECManager) -> Result<usize> {
+    let now = Utc::now();
+    let records = db.signed_records().await?;
+
+    let stale: BTreeSet<(String, DNSRecordType)> = records
+        .iter()
+        .filter(|r| needs_resign(r.rrsig.as_deref(), now))
+        .map(|r| (r.name.clone(), r.record_type))
+        .collect();
+
+    let mut resigned = 0;
+    for (name, record_type) in stale {
+        match resign_rrset(db, manager, &name, record_type).await {
+            Ok(()) => resigned += 1,
+            Err(e) => warn!("Could not re-sign {} {}: {}", name, record_type, e),
+        }
+    }
+
+    resigned += denial::resign_expiring(db, manager).await?;
+    resigned += rollover::resign_expiring(db, manager).await?;
+    Ok(resigned)
+}
+
+/// Periodically re-sign expiring RRsets in the background.
+pub fn spawn_resigner(db: Database, manager: Arc<DNSSECManager>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match resign_expiring(&db, &manager).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    info!("Re-signed {} RRsets nearing expiry", n);
+                    bump_signed_zones(&db, &manager).await;
+                }
+                Err(e) => warn!("Re-signing pass failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Bump the serial of every managed zone that is signed.
+async fn bump_signed_zones(db: &Database, manager: &DNSSECManager) {
+    let managed = match db.zones().await {
+        Ok(managed) => managed,
+        Err(e) => return warn!("Could not list zones to bump serials: {}", e),
+    };
+    for zone in managed {
+        if !db.get_dnssec_zone(&zone.apex).await.is_ok_and(|z| z.enabled) {
+            continue;
+        }
+        if let Err(e) = zones::bump_serial(db, manager, &zone.apex).await {
+            warn!("Could not bump the serial of {}: {}", zone.apex, e);
+        }
+    }
+}
+
+/// Store `rrsig` on every member of an RRset, marking it DNSSEC-signed.
+async fn store_rrsig(db: &Database, rrset: Vec<DNSRecord>, rrsig: &str) -> Result<()> {
+    for mut record in rrset {
+        if record.rrsig.as_deref() == Some(rrsig) && record.dnssec {
+            continue;
+        }
+        record.rrsig = Some(rrsig.to_string());
+        record.dnssec = true;
+        let id = record.id.to_string();
+        db.update_record(&id, record).await?;
+    }
+    Ok(())
+}
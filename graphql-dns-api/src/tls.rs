@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! TLS termination and client-certificate identity (mTLS)
+//!
+//! The GraphQL server terminates TLS 1.3 itself, with the CPR-014 cipher
+//! suites and P-384 key exchange (X25519 is terminated by the crypto policy,
+//! and rustls offers no ML-KEM group), and verifies client certificates
+//! against a configured CA. When the policy sets `require_mtls`, a connection
+//! without a valid client certificate fails the handshake; otherwise one is
+//! optional.
+//!
+//! A verified certificate names its holder in a subjectAltName URI in the
+//! `identity:` scheme (`URI:identity:alice`), or failing that in its subject
+//! common name (`CN=alice` is `identity:alice`). The identity is attached to
+//! every request on the connection as a [`ClientIdentity`], which the GraphQL
+//! handlers put into the request data for the resolvers.
+
+use crate::error::{AppError, Result};
+use axum::{Extension, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    rustls::{
+        cipher_suite::{TLS13_AES_256_GCM_SHA384, TLS13_CHACHA20_POLY1305_SHA256},
+        kx_group::SECP384R1,
+        server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+        version::TLS13,
+        Certificate, PrivateKey, RootCertStore, ServerConfig,
+    },
+    TlsAcceptor,
+};
+use tracing::{debug, warn};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+/// Environment variable naming the server certificate chain (PEM).
+pub const CERT_ENV: &str = "TLS_CERT_PATH";
+/// Environment variable naming the server private key (PEM).
+pub const KEY_ENV: &str = "TLS_KEY_PATH";
+/// Environment variable naming the CA certificates client certificates must
+/// chain to (PEM).
+pub const CLIENT_CA_ENV: &str = "TLS_CLIENT_CA_PATH";
+
+/// URI scheme of identities named in a certificate's subjectAltName.
+const IDENTITY_SCHEME: &str = "identity:";
+
+/// Identity proven by the client certificate of a request's connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity(pub String);
+
+/// Build the server's TLS configuration from the environment.
+///
+/// Returns `None` when none of the variables is set; setting only some of
+/// them is an error.
+pub fn config_from_env(require_client_cert: bool) -> Result<Option<Arc<ServerConfig>>> {
+    let paths = [CERT_ENV, KEY_ENV, CLIENT_CA_ENV].map(|var| std::env::var(var).ok());
+    match paths {
+        [None, None, None] => Ok(None),
+        [Some(cert), Some(key), Some(client_ca)] => {
+            let config = server_config(
+                Path::new(&cert),
+                Path::new(&key),
+                Path::new(&client_ca),
+                require_client_cert,
+            )?;
+            Ok(Some(Arc::new(config)))
+        }
+        _ => Err(AppError::Internal(format!(
+            "TLS needs all of {}, {} and {}",
+            CERT_ENV, KEY_ENV, CLIENT_CA_ENV
+        ))),
+    }
+}
+
+/// TLS 1.3 server configuration presenting `cert` and verifying client
+/// certificates against `client_ca`; they are mandatory if
+/// `require_client_cert`, optional otherwise.
+pub fn server_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: &Path,
+    require_client_cert: bool,
+) -> Result<ServerConfig> {
+    let chain = read_certs(cert)?;
+    let key = read_key(key)?;
+    let mut roots = RootCertStore::empty();
+    for ca in read_certs(client_ca)? {
+        roots
+            .add(&ca)
+            .map_err(|e| tls_error(client_ca, &e.to_string()))?;
+    }
+    let verifier = if require_client_cert {
+        AllowAnyAuthenticatedClient::new(roots).boxed()
+    } else {
+        AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()
+    };
+
+    ServerConfig::builder()
+        .with_cipher_suites(&[TLS13_AES_256_GCM_SHA384, TLS13_CHACHA20_POLY1305_SHA256])
+        .with_kx_groups(&[&SECP384R1])
+        .with_protocol_versions(&[&TLS13])
+        .and_then(|builder| {
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(chain, key)
+        })
+        .map_err(|e| tls_error(cert, &e.to_string()))
+}
+
+/// The identity a client certificate (DER) names, if any.
+pub fn identity(cert: &[u8]) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert).ok()?;
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        let named = san.value.general_names.iter().find_map(|name| match name {
+            GeneralName::URI(uri) => uri
+                .strip_prefix(IDENTITY_SCHEME)
+                .filter(|name| !name.is_empty()),
+            _ => None,
+        });
+        if let Some(name) = named {
+            return Some(format!("{}{}", IDENTITY_SCHEME, name));
+        }
+    }
+    let cn = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    let cn = cn.strip_prefix(IDENTITY_SCHEME).unwrap_or(cn);
+    (!cn.is_empty()).then(|| format!("{}{}", IDENTITY_SCHEME, cn))
+}
+
+/// Serve `app` over TLS on `listener`, attaching each connection's client
+/// identity to its requests.
+pub async fn serve(listener: TcpListener, config: Arc<ServerConfig>, app: Router) -> Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Accepting a connection failed: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+            };
+            let client = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .and_then(|cert| identity(&cert.0));
+            let app = match client {
+                Some(client) => app.layer(Extension(ClientIdentity(client))),
+                None => app,
+            };
+
+            let service = TowerToHyperService::new(app);
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Certificates of a PEM file.
+fn read_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let io_error = |e: std::io::Error| tls_error(path, &e.to_string());
+    let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+    let certs = rustls_pemfile::certs(&mut reader).map_err(io_error)?;
+    if certs.is_empty() {
+        return Err(tls_error(path, "no certificates"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// The first private key of a PEM file: PKCS#8, PKCS#1 (RSA) or SEC1 (EC).
+fn read_key(path: &Path) -> Result<PrivateKey> {
+    let io_error = |e: std::io::Error| tls_error(path, &e.to_string());
+    let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+    loop {
+        match rustls_pemfile::read_one(&mut reader).map_err(io_error)? {
+            Some(
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key),
+            ) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(tls_error(path, "no private key")),
+        }
+    }
+}
+
+/// A TLS configuration error about the file at `path`.
+fn tls_error(path: &Path, message: &str) -> AppError {
+    AppError::Internal(format!("TLS ({}): {}", path.display(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed, `CN=bob` with subjectAltName
+    /// `URI:https://bastion.example/, URI:identity:alice`
+    const ALICE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBqTCCAVCgAwIBAgIUE2ZZYLO3zvB9Z1epm6lWHql9gsAwCgYIKoZIzj0EAwIw
+DjEMMAoGA1UEAwwDYm9iMCAXDTI2MTAxNzAwMjYwMVoYDzIxMjYwOTIzMDAyNjAx
+WjAOMQwwCgYDVQQDDANib2IwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATymnqw
+NNNZSTHanPVglvhyadrrlp7Ff+wqZAf0PKvGykv/mFNSfY7DhFmRs3gZD8jEfhU/
+xAtzxbRYBpr0vzGvo4GJMIGGMB0GA1UdDgQWBBQQh9rL961sUYP0Lhu83A+aCrJB
+fDAfBgNVHSMEGDAWgBQQh9rL961sUYP0Lhu83A+aCrJBfDAPBgNVHRMBAf8EBTAD
+AQH/MDMGA1UdEQQsMCqGGGh0dHBzOi8vYmFzdGlvbi5leGFtcGxlL4YOaWRlbnRp
+dHk6YWxpY2UwCgYIKoZIzj0EAwIDRwAwRAIgJ8yy5lbd4Ymr04WdhXVkCBmQP3+a
+QZcZke4CTGcw7sQCIHhebyviB5G9oJYBlg5pt3qdNhSd/ZVBKdd9Cy7L2GZz
+-----END CERTIFICATE-----
+";
+
+    /// Self-signed, `O=Bastion, CN=carol`, no subjectAltName
+    const CAROL: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBmzCCAUGgAwIBAgIUMDDVJjbWYL2iNSDs/wrVw3L9sB4wCgYIKoZIzj0EAwIw
+IjEQMA4GA1UECgwHQmFzdGlvbjEOMAwGA1UEAwwFY2Fyb2wwIBcNMjYxMDE3MDAy
+NjAxWhgPMjEyNjA5MjMwMDI2MDFaMCIxEDAOBgNVBAoMB0Jhc3Rpb24xDjAMBgNV
+BAMMBWNhcm9sMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEHlHTC4hYInvoh3Vb
+Afnu1nrL3JmC3CofKLHFt+4wvmCpbSZIktqNw8OxazjzP7bj7KnQVYzzEz61l3hC
+5YtZL6NTMFEwHQYDVR0OBBYEFIGdU0o27bhYrBfRpp89v0g3yFHCMB8GA1UdIwQY
+MBaAFIGdU0o27bhYrBfRpp89v0g3yFHCMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZI
+zj0EAwIDSAAwRQIgO+5lOFmbcj0JOCfvEEwRuOV57mPmM8E60fFUU0MEK1UCIQCZ
+XI+ToNKMLcgbYlukwhmsdstjk7bTTJnZC/P7gfKxWA==
+-----END CERTIFICATE-----
+";
+
+    fn der(pem: &str) -> Vec<u8> {
+        rustls_pemfile::certs(&mut pem.as_bytes())
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_identity_from_certificate() {
+        // The identity: URI wins over other URIs and the common name
+        assert_eq!(identity(&der(ALICE)).as_deref(), Some("identity:alice"));
+        assert_eq!(identity(&der(CAROL)).as_deref(), Some("identity:carol"));
+        assert_eq!(identity(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn test_server_config_requires_every_file() {
+        let dir = std::env::temp_dir().join(format!("bastion-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("cert.pem");
+        let ca = dir.join("ca.pem");
+        std::fs::write(&cert, ALICE).unwrap();
+        std::fs::write(&ca, CAROL).unwrap();
+
+        // The certificate file holds no key
+        let err = server_config(&cert, &cert, &ca, true).unwrap_err();
+        assert!(err.to_string().contains("no private key"), "{}", err);
+        let err = server_config(&dir.join("missing.pem"), &cert, &ca, true).unwrap_err();
+        assert!(err.to_string().contains("missing.pem"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
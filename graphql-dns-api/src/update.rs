@@ -0,0 +1,670 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Dynamic updates (RFC 2136)
+//!
+//! UPDATE messages must be TSIG-signed (RFC 8945) with a stored key, and act
+//! as the identity that created the key. That identity is authorised by
+//! [`policy::authorize_dns_change`], the same check the GraphQL record
+//! mutations make, so a change needs the `mutate_dns` privilege and DNS
+//! operations consent whichever way it arrives. Where the policy puts
+//! `mutate_dns` behind approvals or a timelock, UPDATE has no proposal to
+//! wait on and is refused.
+//!
+//! Prerequisites are checked against the zone as stored, then the updates are
+//! applied to a working copy and written by [`changeset::store`] in one
+//! transaction, with the next serial and, for a signed zone, the RRSIGs of
+//! the changed RRsets and the patch to the denial chain: an UPDATE lands whole
+//! or not at all. The transaction is refused if the zone changed since its
+//! prerequisites were checked. Once it commits, the change is journaled for
+//! IXFR, the secondaries notified and the changed records published on the
+//! event bus.
+//!
+//! The SOA is managed by the zone, so SOA additions are refused and deletions
+//! ignored, and the apex NS RRset is never emptied. Likewise a signed zone's
+//! apex DNSKEY, CDS and CDNSKEY RRsets come from its keys and cannot be added.
+
+use crate::{
+    authority::{dns_type, in_zone, normalize_name, wire_type},
+    changeset,
+    consent::ConsentClient,
+    db::Database,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    models::{DNSRecord, DNSRecordType, RecordChangeKind},
+    policy::{self, PolicyEnforcer},
+    rdata, rollover, signer, xfr,
+};
+use chrono::Utc;
+use hickory_proto::{
+    op::{Header, ResponseCode},
+    rr::{DNSClass, RData, Record, RecordType},
+    serialize::{binary::BinEncodable, txt::RDataParser},
+};
+use hickory_server::server::{Request, ResponseHandler, ResponseInfo};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+/// One RR of the zone being updated.
+#[derive(Debug, Clone)]
+struct Rr {
+    name: String,
+    record_type: DNSRecordType,
+    ttl: i32,
+    /// RDATA in normalised presentation form, as stored
+    value: String,
+    /// ID of the stored record; `None` for one this update adds
+    id: Option<String>,
+}
+
+impl Rr {
+    /// A stored RR, its value normalised again so that RDATA compares by value
+    /// even for records stored before values were normalised.
+    fn from_stored(record: DNSRecord) -> Result<Self> {
+        Ok(Self {
+            value: rdata::normalize(record.record_type, &record.value)
+                .map_err(|e| AppError::InvalidRecord(format!("{}: {}", record.name, e)))?,
+            name: record.name,
+            record_type: record.record_type,
+            ttl: record.ttl,
+            id: Some(record.id.to_string()),
+        })
+    }
+
+    fn is(&self, name: &str, record_type: RecordType) -> bool {
+        self.name == name && (record_type.is_any() || wire_type(self.record_type) == record_type)
+    }
+}
+
+/// The names an UPDATE may touch: the zone's, less those of zones below it.
+struct Scope {
+    apex: String,
+    children: Vec<String>,
+    /// Whether the zone is signed, so that its apex key RRsets are managed
+    signed: bool,
+}
+
+impl Scope {
+    fn contains(&self, name: &str) -> bool {
+        in_zone(name, &self.apex) && !self.children.iter().any(|c| in_zone(name, c))
+    }
+}
+
+/// Serve an UPDATE for the zone named in its zone section.
+pub(crate) async fn serve<R: ResponseHandler>(
+    db: &Database,
+    manager: &DNSSECManager,
+    policy: &RwLock<PolicyEnforcer>,
+    consent: &ConsentClient,
+    request: &Request,
+    mut response_handle: R,
+) -> ResponseInfo {
+    let (key, mut signer) = match xfr::verify_request(db, manager, request).await {
+        Ok(verified) => verified,
+        Err(refusal) => return xfr::refuse(request, &mut response_handle, refusal).await,
+    };
+
+    let zone = request.query();
+    let apex = normalize_name(&zone.name().to_string());
+    let response_code =
+        if zone.query_type() != RecordType::SOA || zone.query_class() != DNSClass::IN {
+            ResponseCode::FormErr
+        } else if db.get_zone(&apex).await.is_err() {
+            ResponseCode::NotAuth
+        } else if key.owner.is_empty() {
+            warn!(
+                "UPDATE of {} with key {}, which has no owner",
+                apex, key.name
+            );
+            ResponseCode::Refused
+        } else if let Err(e) = policy::authorize_dns_change(policy, consent, &key.owner).await {
+            warn!("UPDATE of {} by {} refused: {}", apex, key.owner, e);
+            ResponseCode::Refused
+        } else {
+            match apply_update(
+                &db.acting_as(&key.owner),
+                manager,
+                &apex,
+                request.answers(),
+                request.name_servers(),
+            )
+            .await
+            {
+                Ok(code) => code,
+                Err(e) => {
+                    error!("UPDATE of {} by {} failed: {}", apex, key.owner, e);
+                    ResponseCode::ServFail
+                }
+            }
+        };
+
+    let mut header = Header::response_from_request(request.header());
+    header.set_response_code(response_code);
+    match xfr::send_signed(request, &mut response_handle, header, &[], &mut signer).await {
+        Ok(info) => info,
+        Err(e) => {
+            error!("UPDATE response to {} failed: {}", request.src(), e);
+            header.into()
+        }
+    }
+}
+
+/// Check an UPDATE's prerequisites against the zone at `apex` and apply its
+/// updates in one transaction. Returns the response code of the UPDATE.
+pub async fn apply_update(
+    db: &Database,
+    manager: &DNSSECManager,
+    apex: &str,
+    prerequisites: &[Record],
+    updates: &[Record],
+) -> Result<ResponseCode> {
+    let apex = normalize_name(apex);
+    let zone = db.get_zone(&apex).await?;
+    let children = db
+        .zones()
+        .await?
+        .into_iter()
+        .map(|zone| zone.apex)
+        .filter(|child| child != &apex && in_zone(child, &apex))
+        .collect();
+    let dnssec = signer::find_signed_zone(db, &apex).await?;
+    let scope = Scope {
+        apex: apex.clone(),
+        children,
+        signed: dnssec.is_some(),
+    };
+
+    // Read after the zone, so that the serial guard of the write catches any
+    // change the prerequisites were not checked against
+    let stored = db.zone_records(&apex).await?;
+    let mut rrs = stored
+        .iter()
+        .cloned()
+        .map(Rr::from_stored)
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Err(code) = check_prerequisites(&scope, &rrs, prerequisites) {
+        return Ok(code);
+    }
+    if let Err(code) = prescan(&scope, updates) {
+        return Ok(code);
+    }
+    if let Err(code) = apply(&apex, &mut rrs, updates) {
+        return Ok(code);
+    }
+
+    // Kept RRs by ID, with the TTL they now have
+    let kept: HashMap<String, i32> = rrs
+        .iter()
+        .filter_map(|rr| Some((rr.id.clone()?, rr.ttl)))
+        .collect();
+    let now = Utc::now();
+    let mut after = Vec::new();
+    for record in &stored {
+        if let Some(&ttl) = kept.get(record.id.as_str()) {
+            let mut record = record.clone();
+            if ttl != record.ttl {
+                record.ttl = ttl;
+                record.updated_at = now;
+            }
+            after.push(record);
+        }
+    }
+    for rr in rrs.into_iter().filter(|rr| rr.id.is_none()) {
+        let mut record = DNSRecord::new(rr.name, rr.record_type, rr.ttl, rr.value);
+        record.zone = Some(apex.clone());
+        record.dnssec = scope.signed;
+        after.push(record);
+    }
+
+    let changes = changeset::store(db, manager, &zone, dnssec.as_ref(), &stored, after).await?;
+    if !changes.is_empty() {
+        let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+        info!(
+            "UPDATE of {}: {} RRs deleted, {} retimed, {} added",
+            apex,
+            count(RecordChangeKind::Deleted),
+            count(RecordChangeKind::Updated),
+            count(RecordChangeKind::Created)
+        );
+    }
+    Ok(ResponseCode::NoError)
+}
+
+/// Check the prerequisite section (RFC 2136 §3.2).
+fn check_prerequisites(
+    scope: &Scope,
+    rrs: &[Rr],
+    prerequisites: &[Record],
+) -> std::result::Result<(), ResponseCode> {
+    // Value-dependent prerequisites are compared RRset by RRset, RDATA by its
+    // normalised value; RDATA the store cannot hold matches nothing
+    let mut rrsets: Vec<(String, RecordType, Vec<Option<String>>)> = Vec::new();
+
+    for prerequisite in prerequisites {
+        let name = normalize_name(&prerequisite.name().to_string());
+        let record_type = prerequisite.record_type();
+        if prerequisite.ttl() != 0 {
+            return Err(ResponseCode::FormErr);
+        }
+        if !scope.contains(&name) {
+            return Err(ResponseCode::NotZone);
+        }
+        let in_use = rrs.iter().any(|rr| rr.is(&name, record_type));
+
+        match (prerequisite.dns_class(), prerequisite.data()) {
+            (DNSClass::ANY, None) if in_use => {}
+            (DNSClass::ANY, None) if record_type.is_any() => return Err(ResponseCode::NXDomain),
+            (DNSClass::ANY, None) => return Err(ResponseCode::NXRRSet),
+            (DNSClass::NONE, None) if !in_use => {}
+            (DNSClass::NONE, None) if record_type.is_any() => return Err(ResponseCode::YXDomain),
+            (DNSClass::NONE, None) => return Err(ResponseCode::YXRRSet),
+            (DNSClass::IN, Some(rdata)) if !record_type.is_any() => {
+                match rrsets
+                    .iter_mut()
+                    .find(|(n, t, _)| *n == name && *t == record_type)
+                {
+                    Some((_, _, values)) => values.push(rdata_value(rdata)),
+                    None => rrsets.push((name, record_type, vec![rdata_value(rdata)])),
+                }
+            }
+            _ => return Err(ResponseCode::FormErr),
+        }
+    }
+
+    for (name, record_type, expected) in rrsets {
+        let actual: Vec<Option<String>> = rrs
+            .iter()
+            .filter(|rr| rr.is(&name, record_type))
+            .map(|rr| Some(rr.value.clone()))
+            .collect();
+        if !actual.iter().all(|value| expected.contains(value))
+            || !expected.iter().all(|value| actual.contains(value))
+        {
+            return Err(ResponseCode::NXRRSet);
+        }
+    }
+    Ok(())
+}
+
+/// Check the update section before anything is applied (RFC 2136 §3.4.1).
+fn prescan(scope: &Scope, updates: &[Record]) -> std::result::Result<(), ResponseCode> {
+    for update in updates {
+        let name = normalize_name(&update.name().to_string());
+        let record_type = update.record_type();
+        if !scope.contains(&name) {
+            return Err(ResponseCode::NotZone);
+        }
+        let meta = matches!(
+            record_type,
+            RecordType::ANY | RecordType::AXFR | RecordType::IXFR | RecordType::OPT
+        );
+        let valid = match (update.dns_class(), update.data()) {
+            (DNSClass::IN, Some(_)) => !meta,
+            (DNSClass::ANY, None) => update.ttl() == 0 && (!meta || record_type.is_any()),
+            (DNSClass::NONE, Some(_)) => update.ttl() == 0 && !meta,
+            _ => false,
+        };
+        if !valid {
+            return Err(ResponseCode::FormErr);
+        }
+        let managed = record_type == RecordType::SOA
+            || (scope.signed
+                && dns_type(record_type)
+                    .is_some_and(|t| rollover::is_key_rrset(&name, t, &scope.apex)));
+        if update.dns_class() == DNSClass::IN && managed {
+            return Err(ResponseCode::Refused);
+        }
+    }
+    Ok(())
+}
+
+/// Apply the update section to the zone's RRs (RFC 2136 §3.4.2).
+fn apply(
+    apex: &str,
+    rrs: &mut Vec<Rr>,
+    updates: &[Record],
+) -> std::result::Result<(), ResponseCode> {
+    let protected = |rr: &Rr| {
+        rr.name == apex && matches!(rr.record_type, DNSRecordType::SOA | DNSRecordType::NS)
+    };
+
+    for update in updates {
+        let name = normalize_name(&update.name().to_string());
+        let record_type = update.record_type();
+
+        match (update.dns_class(), update.data()) {
+            (DNSClass::IN, Some(rdata)) => {
+                // Types and RDATA the store cannot hold are refused
+                let added = Rr {
+                    name,
+                    record_type: dns_type(record_type).ok_or(ResponseCode::Refused)?,
+                    ttl: i32::try_from(update.ttl()).map_err(|_| ResponseCode::FormErr)?,
+                    value: rdata_value(rdata).ok_or(ResponseCode::Refused)?,
+                    id: None,
+                };
+                add(rrs, added);
+            }
+            // Delete an RRset, or every RRset at the name
+            (DNSClass::ANY, None) => {
+                rrs.retain(|rr| !rr.is(&name, record_type) || protected(rr));
+            }
+            // Delete one RR
+            (DNSClass::NONE, Some(rdata)) => {
+                let apex_ns = rrs.iter().filter(|rr| rr.is(apex, RecordType::NS)).count();
+                if record_type == RecordType::SOA
+                    || (name == apex && record_type == RecordType::NS && apex_ns <= 1)
+                {
+                    continue;
+                }
+                let value = rdata_value(rdata);
+                rrs.retain(|rr| !rr.is(&name, record_type) || Some(&rr.value) != value.as_ref());
+            }
+            _ => return Err(ResponseCode::FormErr),
+        }
+    }
+    Ok(())
+}
+
+/// Add an RR unless it duplicates one already there. A CNAME replaces the
+/// name's CNAME, and is ignored where other data exists; other data is
+/// ignored at a CNAME. The RRset takes the TTL of the RR added, duplicate or
+/// not, so that it keeps one TTL (RFC 2181 §5.2).
+fn add(rrs: &mut Vec<Rr>, added: Rr) {
+    let cname = added.record_type == DNSRecordType::CNAME;
+    let conflict = rrs
+        .iter()
+        .any(|rr| rr.name == added.name && (rr.record_type == DNSRecordType::CNAME) != cname);
+    if conflict {
+        return;
+    }
+
+    // A retimed stored RR keeps its ID, and with it its history
+    let mut duplicate = false;
+    for rr in rrs
+        .iter_mut()
+        .filter(|rr| rr.name == added.name && rr.record_type == added.record_type)
+    {
+        duplicate |= rr.value == added.value;
+        rr.ttl = added.ttl;
+    }
+    if duplicate {
+        return;
+    }
+    if cname {
+        rrs.retain(|rr| !(rr.name == added.name && rr.record_type == DNSRecordType::CNAME));
+    }
+    rrs.push(added);
+}
+
+/// RDATA in the normalised form records are stored in, if it reads back the
+/// same. TXT strings are quoted, since a bare space would split them. The
+/// types [`rdata`] encodes itself are decoded from their wire form instead.
+fn rdata_value(rdata: &RData) -> Option<String> {
+    let record_type = dns_type(rdata.record_type())?;
+    if rdata::opaque(record_type) {
+        let wire = BinEncodable::to_bytes(rdata).ok()?;
+        return rdata::from_wire(record_type, &wire).ok();
+    }
+    let value = match rdata {
+        RData::TXT(txt) => txt
+            .txt_data()
+            .iter()
+            .map(|s| rdata::quote(&String::from_utf8_lossy(s)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        other => other.to_string(),
+    };
+    let value = rdata::normalize(record_type, &value).ok()?;
+    let parsed = RData::try_from_str(rdata.record_type(), &value).ok()?;
+    (parsed == *rdata).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::rr::{rdata::TXT, Name};
+    use std::str::FromStr;
+
+    fn scope() -> Scope {
+        Scope {
+            apex: "example.com".to_string(),
+            children: vec!["sub.example.com".to_string()],
+            signed: false,
+        }
+    }
+
+    fn zone() -> Vec<Rr> {
+        [
+            ("example.com", DNSRecordType::NS, "ns1.example.com."),
+            ("www.example.com", DNSRecordType::A, "192.0.2.1"),
+            ("www.example.com", DNSRecordType::A, "192.0.2.2"),
+            ("ftp.example.com", DNSRecordType::CNAME, "www.example.com."),
+        ]
+        .into_iter()
+        .map(|(name, record_type, value)| {
+            Rr::from_stored(DNSRecord::new(
+                name.to_string(),
+                record_type,
+                300,
+                value.to_string(),
+            ))
+            .unwrap()
+        })
+        .collect()
+    }
+
+    /// An RR with RDATA, or an RDATA-less one when `value` is `None`.
+    fn rr(name: &str, class: DNSClass, record_type: RecordType, value: Option<&str>) -> Record {
+        let name = Name::from_str(&format!("{}.", name)).unwrap();
+        let mut record = match value {
+            Some(value) => {
+                let rdata = RData::try_from_str(record_type, value).unwrap();
+                Record::from_rdata(name, if class == DNSClass::IN { 300 } else { 0 }, rdata)
+            }
+            None => Record::with(name, record_type, 0),
+        };
+        record.set_dns_class(class);
+        record
+    }
+
+    fn values(rrs: &[Rr], name: &str, record_type: RecordType) -> Vec<String> {
+        rrs.iter()
+            .filter(|rr| rr.is(name, record_type))
+            .map(|rr| rr.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_prerequisites() {
+        let rrs = zone();
+        let check = |prerequisite: Record| check_prerequisites(&scope(), &rrs, &[prerequisite]);
+        let www = "www.example.com";
+
+        assert_eq!(check(rr(www, DNSClass::ANY, RecordType::ANY, None)), Ok(()));
+        assert_eq!(check(rr(www, DNSClass::ANY, RecordType::A, None)), Ok(()));
+        assert_eq!(
+            check(rr("new.example.com", DNSClass::ANY, RecordType::ANY, None)),
+            Err(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            check(rr(www, DNSClass::ANY, RecordType::MX, None)),
+            Err(ResponseCode::NXRRSet)
+        );
+        assert_eq!(
+            check(rr(www, DNSClass::NONE, RecordType::ANY, None)),
+            Err(ResponseCode::YXDomain)
+        );
+        assert_eq!(
+            check(rr(www, DNSClass::NONE, RecordType::A, None)),
+            Err(ResponseCode::YXRRSet)
+        );
+        assert_eq!(check(rr(www, DNSClass::NONE, RecordType::MX, None)), Ok(()));
+        assert_eq!(
+            check(rr(
+                "www.sub.example.com",
+                DNSClass::ANY,
+                RecordType::ANY,
+                None
+            )),
+            Err(ResponseCode::NotZone)
+        );
+        assert_eq!(
+            check(rr(www, DNSClass::IN, RecordType::A, Some("192.0.2.1"))),
+            Err(ResponseCode::FormErr)
+        );
+
+        // Value-dependent: the whole RRset must match
+        let rrset = |values: &[&str]| -> Vec<Record> {
+            values
+                .iter()
+                .map(|v| {
+                    let mut record = rr(www, DNSClass::IN, RecordType::A, Some(v));
+                    record.set_ttl(0);
+                    record
+                })
+                .collect()
+        };
+        let check = |prerequisites: &[Record]| check_prerequisites(&scope(), &rrs, prerequisites);
+        assert_eq!(check(&rrset(&["192.0.2.2", "192.0.2.1"])), Ok(()));
+        assert_eq!(check(&rrset(&["192.0.2.1"])), Err(ResponseCode::NXRRSet));
+        assert_eq!(
+            check(&rrset(&["192.0.2.1", "192.0.2.2", "192.0.2.3"])),
+            Err(ResponseCode::NXRRSet)
+        );
+    }
+
+    #[test]
+    fn test_prescan() {
+        let updates = |update: Record| prescan(&scope(), &[update]);
+        let www = "www.example.com";
+
+        assert_eq!(
+            updates(rr(www, DNSClass::IN, RecordType::A, Some("192.0.2.9"))),
+            Ok(())
+        );
+        assert_eq!(
+            updates(rr(www, DNSClass::ANY, RecordType::ANY, None)),
+            Ok(())
+        );
+        assert_eq!(
+            updates(rr("example.net", DNSClass::ANY, RecordType::ANY, None)),
+            Err(ResponseCode::NotZone)
+        );
+        assert_eq!(
+            updates(rr(www, DNSClass::ANY, RecordType::AXFR, None)),
+            Err(ResponseCode::FormErr)
+        );
+        assert_eq!(
+            updates(rr(
+                "example.com",
+                DNSClass::IN,
+                RecordType::SOA,
+                Some("ns1.example.com. h.example.com. 9 7200 900 1209600 300")
+            )),
+            Err(ResponseCode::Refused)
+        );
+
+        // A signed zone's apex key RRsets are published from its keys
+        let cds = Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            300,
+            rdata::to_rdata(DNSRecordType::CDS, "0 0 0 00").unwrap(),
+        );
+        assert_eq!(prescan(&scope(), std::slice::from_ref(&cds)), Ok(()));
+        let signed = Scope {
+            signed: true,
+            ..scope()
+        };
+        assert_eq!(prescan(&signed, &[cds]), Err(ResponseCode::Refused));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut rrs = zone();
+        let www = "www.example.com";
+        let updates = [
+            // Duplicate: ignored
+            rr(www, DNSClass::IN, RecordType::A, Some("192.0.2.1")),
+            rr(www, DNSClass::NONE, RecordType::A, Some("192.0.2.2")),
+            rr(www, DNSClass::IN, RecordType::A, Some("192.0.2.3")),
+            // Data at a CNAME, and a CNAME at data: ignored
+            rr(
+                "ftp.example.com",
+                DNSClass::IN,
+                RecordType::A,
+                Some("192.0.2.4"),
+            ),
+            rr(www, DNSClass::IN, RecordType::CNAME, Some("example.com.")),
+            // A CNAME replaces the CNAME
+            rr(
+                "ftp.example.com",
+                DNSClass::IN,
+                RecordType::CNAME,
+                Some("files.example.com."),
+            ),
+            rr("example.com", DNSClass::ANY, RecordType::ANY, None),
+            rr(
+                "example.com",
+                DNSClass::NONE,
+                RecordType::NS,
+                Some("ns1.example.com."),
+            ),
+        ];
+        apply("example.com", &mut rrs, &updates).unwrap();
+
+        assert_eq!(values(&rrs, www, RecordType::A), ["192.0.2.1", "192.0.2.3"]);
+        assert_eq!(
+            values(&rrs, "ftp.example.com", RecordType::ANY),
+            ["files.example.com."]
+        );
+        // The apex NS RRset is never emptied
+        assert_eq!(
+            values(&rrs, "example.com", RecordType::NS),
+            ["ns1.example.com."]
+        );
+        assert_eq!(rrs.iter().filter(|rr| rr.id.is_none()).count(), 2);
+
+        let unsupported = rr(www, DNSClass::IN, RecordType::HINFO, Some("pc linux"));
+        assert_eq!(
+            apply("example.com", &mut rrs, &[unsupported]),
+            Err(ResponseCode::Refused)
+        );
+    }
+
+    #[test]
+    fn test_add_retimes_rrset() {
+        let mut rrs = zone();
+        let www = "www.example.com";
+        let mut longer = rr(www, DNSClass::IN, RecordType::A, Some("192.0.2.1"));
+        longer.set_ttl(600);
+        apply("example.com", &mut rrs, &[longer]).unwrap();
+
+        // The duplicate adds nothing, but the stored RRset takes its TTL in place
+        let rrset: Vec<&Rr> = rrs.iter().filter(|rr| rr.is(www, RecordType::A)).collect();
+        assert_eq!(rrset.len(), 2);
+        assert!(rrset.iter().all(|rr| rr.ttl == 600));
+        assert!(rrs.iter().all(|rr| rr.id.is_some()));
+    }
+
+    #[test]
+    fn test_opaque_values_read_from_wire() {
+        let https = RData::try_from_str(RecordType::HTTPS, "1 . alpn=h2,h3 port=443").unwrap();
+        assert_eq!(rdata_value(&https).unwrap(), "1 . alpn=h2,h3 port=443");
+
+        let fingerprint = "cd".repeat(32);
+        let sshfp =
+            RData::try_from_str(RecordType::SSHFP, &format!("4 2 {}", fingerprint)).unwrap();
+        assert_eq!(rdata_value(&sshfp).unwrap(), format!("4 2 {}", fingerprint));
+    }
+
+    #[test]
+    fn test_txt_value_roundtrips() {
+        let rdata = RData::TXT(TXT::new(vec![
+            "v=spf1 -all".to_string(),
+            "say \"hi\" \\o/".to_string(),
+        ]));
+        let value = rdata_value(&rdata).unwrap();
+        assert_eq!(value, r#""v=spf1 -all" "say \"hi\" \\o/""#);
+        assert_eq!(RData::try_from_str(RecordType::TXT, &value).unwrap(), rdata);
+    }
+}
@@ -0,0 +1,958 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Outbound zone transfers: AXFR (RFC 5936) and IXFR (RFC 1995)
+//!
+//! Secondaries pull zones over TCP straight from the record store. A request
+//! must be signed with a TSIG key (RFC 8945) and come from an address in the
+//! zone's transfer ACL with that key; every response message is signed in turn,
+//! each MAC chaining on the previous one.
+//!
+//! IXFR is answered from a per-zone journal keyed by SOA serial: every serial
+//! bump diffs the zone against a snapshot taken at the previous serial
+//! ([`record_change`]). When the journal does not reach back to the client's
+//! serial the whole zone is sent instead, AXFR-style. Entries older than the
+//! zone's SOA expire time are pruned, since a secondary that far behind has
+//! expired the zone anyway.
+//!
+//! Secondaries learn of new serials through NOTIFY, see [`crate::notify`].
+//!
+//! TSIG secrets are sealed in the DNSSEC key store, so transfers need it unlocked.
+
+use crate::{
+    authority::{self, normalize_name, rrsig_wire, to_wire_record},
+    db::Database,
+    denial,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    models::{
+        DNSRecord, DNSRecordType, TSIGAlgorithm, TSIGKey, TSIGKeySecret, TransferPeer,
+        TransferPeerInput, Zone, ZoneJournalEntry, ZoneSnapshot,
+    },
+    notify,
+    rollover::{self, APEX_KEY_TYPES},
+    zones::serial_gt,
+};
+use base64::Engine as _;
+use chrono::{Duration, Utc};
+use hickory_proto::{
+    error::ProtoError,
+    op::{Edns, Header, Message, ResponseCode},
+    rr::{
+        dnssec::rdata::{
+            tsig::{make_tsig_record, message_tbs, signed_bitmessage_to_buf, TsigAlgorithm, TSIG},
+            DNSSECRData,
+        },
+        Name, RData, Record, RecordType,
+    },
+    serialize::binary::{BinDecodable, BinEncodable, BinEncoder},
+};
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    server::{Protocol, Request, ResponseHandler, ResponseInfo},
+};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha2::{Sha256, Sha512};
+use std::{collections::HashSet, net::IpAddr, str::FromStr};
+use tracing::{error, info, warn};
+
+/// Wire size after which a transfer starts a new message, well under the
+/// 64 KiB TCP message limit.
+const MESSAGE_SIZE: usize = 16 * 1024;
+
+/// Allowed clock skew for TSIG, and the fudge sent (RFC 8945 §10).
+const TSIG_FUDGE: u16 = 300;
+
+/// Why a transfer (or UPDATE) request is turned away.
+#[derive(Debug)]
+pub(crate) enum Refusal {
+    /// Not a served zone, no TSIG, or the peer is not in the ACL: REFUSED
+    Refused,
+    /// TSIG verification failed: NOTAUTH with the TSIG error (RFC 8945 §5.2)
+    Tsig {
+        key: Name,
+        algorithm: TsigAlgorithm,
+        error: ResponseCode,
+    },
+    /// Internal failure: SERVFAIL
+    Failed(AppError),
+}
+
+impl From<AppError> for Refusal {
+    fn from(e: AppError) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// TSIG state of one transfer: each response MAC covers the previous MAC,
+/// starting from the request's (RFC 8945 §5.3.1).
+pub(crate) struct Signer {
+    key: Name,
+    algorithm: TSIGAlgorithm,
+    secret: Vec<u8>,
+    id: u16,
+    previous_mac: Vec<u8>,
+    first: bool,
+}
+
+impl Signer {
+    /// TSIG record for a response message, given its wire form without the TSIG.
+    fn sign(&mut self, message: &[u8], time: u64) -> Result<Record> {
+        let tsig = TSIG::new(
+            wire_algorithm(self.algorithm),
+            time,
+            TSIG_FUDGE,
+            Vec::new(),
+            self.id,
+            0,
+            Vec::new(),
+        );
+
+        let mut tbs = Vec::with_capacity(message.len() + 128);
+        let mut encoder = BinEncoder::new(&mut tbs);
+        encoder
+            .emit_u16(self.previous_mac.len() as u16)
+            .map_err(proto)?;
+        encoder.emit_vec(&self.previous_mac).map_err(proto)?;
+        encoder.emit_vec(message).map_err(proto)?;
+        if self.first {
+            tsig.emit_tsig_for_mac(&mut encoder, &self.key)
+                .map_err(proto)?;
+        } else {
+            // Later messages cover the timers only
+            encoder.emit_u16((time >> 32) as u16).map_err(proto)?;
+            encoder.emit_u32(time as u32).map_err(proto)?;
+            encoder.emit_u16(TSIG_FUDGE).map_err(proto)?;
+        }
+
+        let mac = mac(self.algorithm, &self.secret, &tbs)?;
+        self.previous_mac.clone_from(&mac);
+        self.first = false;
+        Ok(make_tsig_record(self.key.clone(), tsig.set_mac(mac)))
+    }
+}
+
+/// Serve an AXFR or IXFR request for the zone at the query name.
+///
+/// AXFR is TCP-only (RFC 5936 §4.2); an IXFR over UDP gets the current SOA
+/// alone, which tells the secondary to retry over TCP (RFC 1995 §2).
+pub(crate) async fn serve<R: ResponseHandler>(
+    db: &Database,
+    manager: &DNSSECManager,
+    request: &Request,
+    mut response_handle: R,
+) -> ResponseInfo {
+    let query = request.query();
+    let apex = normalize_name(&query.name().to_string());
+    let qtype = query.query_type();
+    let udp = matches!(request.protocol(), Protocol::Udp);
+
+    let mut signer = match authorize(db, manager, request, &apex).await {
+        Ok(_) if udp && qtype == RecordType::AXFR => {
+            return refuse(request, &mut response_handle, Refusal::Refused).await
+        }
+        Ok(signer) => signer,
+        Err(refusal) => return refuse(request, &mut response_handle, refusal).await,
+    };
+
+    let rrs = match transfer_rrs(db, request, &apex, qtype, udp).await {
+        Ok(rrs) => rrs,
+        Err(e) => return refuse(request, &mut response_handle, Refusal::Failed(e)).await,
+    };
+    let messages = match split_messages(&rrs) {
+        Ok(messages) => messages,
+        Err(e) => return refuse(request, &mut response_handle, Refusal::Failed(e)).await,
+    };
+    info!(
+        "{} {} to {}: {} RRs in {} messages",
+        qtype,
+        apex,
+        request.src(),
+        rrs.len(),
+        messages.len()
+    );
+
+    let mut header = Header::response_from_request(request.header());
+    header.set_authoritative(true);
+    let mut sent: ResponseInfo = header.into();
+    for answers in messages {
+        match send_signed(request, &mut response_handle, header, answers, &mut signer).await {
+            Ok(info) => sent = info,
+            Err(e) => {
+                error!("{} {} to {} failed: {}", qtype, apex, request.src(), e);
+                break;
+            }
+        }
+    }
+    sent
+}
+
+/// Check that the zone is served, the request's TSIG verifies, and the peer
+/// is in the zone's ACL with that key.
+async fn authorize(
+    db: &Database,
+    manager: &DNSSECManager,
+    request: &Request,
+    apex: &str,
+) -> std::result::Result<Signer, Refusal> {
+    let zone = db.get_zone(apex).await.map_err(|_| Refusal::Refused)?;
+    let (key, signer) = verify_request(db, manager, request).await?;
+
+    let ip = request.src().ip();
+    if !zone
+        .transfer_acl
+        .iter()
+        .any(|peer| peer.tsig_key == key.name && address_matches(&peer.address, ip))
+    {
+        warn!(
+            "Transfer of {} refused to {} with key {}",
+            apex, ip, key.name
+        );
+        return Err(Refusal::Refused);
+    }
+    Ok(signer)
+}
+
+/// Verify the TSIG on a request against the stored key it names. Returns the
+/// key and the signer for the response.
+pub(crate) async fn verify_request(
+    db: &Database,
+    manager: &DNSSECManager,
+    request: &Request,
+) -> std::result::Result<(TSIGKey, Signer), Refusal> {
+    let Some(record) = request
+        .sig0()
+        .iter()
+        .find(|r| r.record_type() == RecordType::TSIG)
+    else {
+        warn!(
+            "Unsigned {} request for {} from {}",
+            request.op_code(),
+            request.query().name(),
+            request.src()
+        );
+        return Err(Refusal::Refused);
+    };
+    let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+        return Err(Refusal::Refused);
+    };
+    let refusal = |error| Refusal::Tsig {
+        key: record.name().clone(),
+        algorithm: tsig.algorithm().clone(),
+        error,
+    };
+
+    let key_name = normalize_name(&record.name().to_string());
+    let key = db
+        .get_tsig_key(&key_name)
+        .await
+        .map_err(|_| refusal(ResponseCode::BADKEY))?;
+    if tsig.algorithm() != &wire_algorithm(key.algorithm) {
+        return Err(refusal(ResponseCode::BADKEY));
+    }
+    let secret = manager.tsig_secret(&key)?;
+    let message = request.to_bytes().map_err(proto)?;
+    let now = Utc::now().timestamp() as u64;
+    let request_mac =
+        verify_message(&message, None, key.algorithm, &secret, now).map_err(|error| {
+            warn!("TSIG {} from {} failed: {}", key_name, request.src(), error);
+            refusal(error)
+        })?;
+
+    let signer = Signer {
+        key: record.name().clone(),
+        algorithm: key.algorithm,
+        secret,
+        id: request.id(),
+        previous_mac: request_mac,
+        first: true,
+    };
+    Ok((key, signer))
+}
+
+/// Verify the TSIG on a message in wire form: a request, or with the request's
+/// MAC as `previous_mac` the response to it. Returns the message's MAC, which
+/// the MAC of a response covers.
+pub(crate) fn verify_message(
+    message: &[u8],
+    previous_mac: Option<&[u8]>,
+    algorithm: TSIGAlgorithm,
+    secret: &[u8],
+    now: u64,
+) -> std::result::Result<Vec<u8>, ResponseCode> {
+    let (tbv, record) =
+        signed_bitmessage_to_buf(previous_mac, message, true).map_err(|_| ResponseCode::BADSIG)?;
+    let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+        return Err(ResponseCode::BADSIG);
+    };
+    if !verify_mac(algorithm, secret, &tbv, tsig.mac()).map_err(|_| ResponseCode::BADSIG)? {
+        return Err(ResponseCode::BADSIG);
+    }
+    if now.abs_diff(tsig.time()) > u64::from(tsig.fudge()) {
+        return Err(ResponseCode::BADTIME);
+    }
+    Ok(tsig.mac().to_vec())
+}
+
+/// Sign a request (a NOTIFY) with `key` as its last record; returns its MAC,
+/// which the response's MAC covers.
+pub(crate) fn sign_request(
+    message: &mut Message,
+    key: &TSIGKey,
+    secret: &[u8],
+    time: u64,
+) -> Result<Vec<u8>> {
+    let key_name = Name::from_str(&format!("{}.", key.name))
+        .map_err(|e| AppError::Internal(format!("Invalid TSIG key name {}: {}", key.name, e)))?;
+    let pre_tsig = TSIG::new(
+        wire_algorithm(key.algorithm),
+        time,
+        TSIG_FUDGE,
+        Vec::new(),
+        message.id(),
+        0,
+        Vec::new(),
+    );
+    let tbs = message_tbs(None, message, &pre_tsig, &key_name).map_err(proto)?;
+    let mac = mac(key.algorithm, secret, &tbs)?;
+    message.add_tsig(make_tsig_record(key_name, pre_tsig.set_mac(mac.clone())));
+    Ok(mac)
+}
+
+/// Send an error response; TSIG failures carry the TSIG error, unsigned.
+pub(crate) async fn refuse<R: ResponseHandler>(
+    request: &Request,
+    response_handle: &mut R,
+    refusal: Refusal,
+) -> ResponseInfo {
+    let mut header = Header::response_from_request(request.header());
+    let mut additionals = Vec::new();
+    match refusal {
+        Refusal::Refused => {
+            header.set_response_code(ResponseCode::Refused);
+        }
+        Refusal::Tsig {
+            key,
+            algorithm,
+            error,
+        } => {
+            header.set_response_code(ResponseCode::NotAuth);
+            let time = Utc::now().timestamp() as u64;
+            let tsig = TSIG::new(
+                algorithm,
+                time,
+                TSIG_FUDGE,
+                Vec::new(),
+                request.id(),
+                error.into(),
+                Vec::new(),
+            );
+            additionals.push(make_tsig_record(key, tsig));
+        }
+        Refusal::Failed(e) => {
+            error!(
+                "{} for {} failed: {}",
+                request.op_code(),
+                request.query().name(),
+                e
+            );
+            header.set_response_code(ResponseCode::ServFail);
+        }
+    }
+
+    let response = MessageResponseBuilder::from_message_request(request).build(
+        header,
+        std::iter::empty(),
+        std::iter::empty(),
+        std::iter::empty(),
+        additionals.iter(),
+    );
+    authority::send(response_handle, response).await
+}
+
+/// Send one response message with its TSIG as the last additional record.
+///
+/// The MAC covers the message exactly as emitted without the TSIG, so it is
+/// emitted once to sign and again, TSIG appended, to send.
+pub(crate) async fn send_signed<R: ResponseHandler>(
+    request: &Request,
+    response_handle: &mut R,
+    header: Header,
+    answers: &[Record],
+    signer: &mut Signer,
+) -> Result<ResponseInfo> {
+    // OPT goes in as a plain record so that it precedes the TSIG
+    let mut additionals = Vec::new();
+    if request.edns().is_some() {
+        let mut edns = Edns::new();
+        edns.set_max_payload(authority::EDNS_MAX_PAYLOAD);
+        additionals.push(Record::from(&edns));
+    }
+
+    let mut unsigned = Vec::new();
+    MessageResponseBuilder::from_message_request(request)
+        .build(
+            header,
+            answers.iter(),
+            std::iter::empty(),
+            std::iter::empty(),
+            additionals.iter(),
+        )
+        .destructive_emit(&mut BinEncoder::new(&mut unsigned))
+        .map_err(proto)?;
+    additionals.push(signer.sign(&unsigned, Utc::now().timestamp() as u64)?);
+
+    let response = MessageResponseBuilder::from_message_request(request).build(
+        header,
+        answers.iter(),
+        std::iter::empty(),
+        std::iter::empty(),
+        additionals.iter(),
+    );
+    response_handle
+        .send_response(response)
+        .await
+        .map_err(|e| AppError::Internal(format!("sending signed response: {}", e)))
+}
+
+/// RRs answering a transfer, the current SOA first and last: the journaled
+/// differences for an IXFR the journal covers, otherwise the whole zone.
+async fn transfer_rrs(
+    db: &Database,
+    request: &Request,
+    apex: &str,
+    qtype: RecordType,
+    udp: bool,
+) -> Result<Vec<Record>> {
+    if qtype == RecordType::IXFR {
+        let client_serial = request.name_servers().iter().find_map(|r| match r.data() {
+            Some(RData::SOA(soa)) => Some(soa.serial()),
+            _ => None,
+        });
+        if let Some(client_serial) = client_serial {
+            if let Some(rrs) = ixfr_rrs(db, apex, client_serial).await? {
+                if udp && rrs.len() > 1 {
+                    return Ok(rrs[..1].to_vec());
+                }
+                return Ok(rrs);
+            }
+        }
+        if udp {
+            return Ok(zone_rrs(db, apex).await?.into_iter().take(1).collect());
+        }
+    }
+
+    let mut rrs = zone_rrs(db, apex).await?;
+    rrs.push(rrs[0].clone());
+    Ok(rrs)
+}
+
+/// Every RR of a zone: the apex SOA first, then its records with their RRSIGs,
+/// and for a signed zone the DNSKEY/CDS/CDNSKEY RRsets and the denial chain.
+///
+/// Records of zones below the apex are left to those zones.
+pub async fn zone_rrs(db: &Database, apex: &str) -> Result<Vec<Record>> {
+    let apex = normalize_name(apex);
+    let records = db.zone_records(&apex).await?;
+    let mut records: Vec<&DNSRecord> = records.iter().collect();
+    let is_soa = |r: &DNSRecord| r.record_type == DNSRecordType::SOA && r.name == apex;
+    records.sort_by_key(|r| !is_soa(r));
+    if !records.first().is_some_and(|r| is_soa(r)) {
+        return Err(AppError::ZoneNotFound(apex));
+    }
+
+    let mut rrs = Vec::with_capacity(records.len());
+    let mut signatures = HashSet::new();
+    for record in records {
+        rrs.push(to_wire_record(record)?);
+        for rrsig in record.rrsig.iter().flat_map(|s| s.lines()) {
+            if signatures.insert((record.name.as_str(), rrsig)) {
+                rrs.push(rrsig_wire(&record.name, record.ttl as u32, rrsig)?);
+            }
+        }
+    }
+
+    if let Some(config) = db
+        .get_dnssec_zone(&apex)
+        .await
+        .ok()
+        .filter(|zone| zone.enabled)
+    {
+        for record_type in APEX_KEY_TYPES {
+            rrs.extend(rollover::answer(db, &apex, &apex, record_type, true).await?);
+        }
+        for record in db.denial_records(Some(&apex)).await? {
+            rrs.extend(denial::with_signatures(
+                &record,
+                config.nsec3.as_ref(),
+                true,
+            )?);
+        }
+    }
+    Ok(rrs)
+}
+
+/// RRs answering an IXFR from `client_serial` (RFC 1995 §4): the current SOA
+/// alone when the client is up to date, otherwise each journaled change (old
+/// SOA, deletions, new SOA, additions) between two copies of the current SOA.
+///
+/// Returns `None` when the journal does not reach back to the client's serial.
+pub async fn ixfr_rrs(
+    db: &Database,
+    apex: &str,
+    client_serial: u32,
+) -> Result<Option<Vec<Record>>> {
+    let Some(snapshot) = db.zone_snapshot(apex).await? else {
+        return Ok(None);
+    };
+    let Some(soa) = snapshot.records.first() else {
+        return Ok(None);
+    };
+    let soa = decode_rr(soa)?;
+    let current = snapshot.serial as u32;
+    if !serial_gt(current, client_serial) {
+        return Ok(Some(vec![soa]));
+    }
+
+    let journal = db.zone_journal(apex).await?;
+    let Some(start) = journal
+        .iter()
+        .position(|entry| entry.serial_from as u32 == client_serial)
+    else {
+        return Ok(None);
+    };
+
+    let mut rrs = vec![soa.clone()];
+    let mut serial = client_serial;
+    for entry in &journal[start..] {
+        if entry.serial_from as u32 != serial {
+            return Ok(None);
+        }
+        for rr in entry.removed.iter().chain(&entry.added) {
+            rrs.push(decode_rr(rr)?);
+        }
+        serial = entry.serial_to as u32;
+        if serial == current {
+            break;
+        }
+    }
+    if serial != current {
+        return Ok(None);
+    }
+    rrs.push(soa);
+    Ok(Some(rrs))
+}
+
+/// Journal the difference between a zone's snapshot and its current contents
+/// under the zone's serial, then take a new snapshot.
+///
+/// The first call for a zone only takes the snapshot.
+pub async fn record_change(db: &Database, zone: &Zone) -> Result<()> {
+    let records = snapshot_records(&zone_rrs(db, &zone.apex).await?)?;
+    let now = Utc::now();
+    let entry = match db.zone_snapshot(&zone.apex).await? {
+        Some(previous) if previous.serial != zone.serial => {
+            let (removed, added) = diff(&previous.records, &records);
+            Some(ZoneJournalEntry {
+                zone: zone.apex.clone(),
+                serial_from: previous.serial,
+                serial_to: zone.serial,
+                removed,
+                added,
+                created_at: now,
+            })
+        }
+        _ => None,
+    };
+
+    let snapshot = ZoneSnapshot {
+        zone: zone.apex.clone(),
+        serial: zone.serial,
+        records,
+    };
+    let prune_before = now - Duration::seconds(i64::from(zone.expire));
+    db.record_zone_change(snapshot, entry, prune_before).await
+}
+
+/// Replace the secondaries allowed to transfer a zone.
+///
+/// Each address must be an IP address or CIDR prefix, each NOTIFY address an
+/// IP address with an optional port, and each key must exist. The NOTIFY
+/// status of secondaries no longer notified is dropped.
+pub async fn set_acl(db: &Database, apex: &str, peers: Vec<TransferPeerInput>) -> Result<Zone> {
+    let mut zone = db.get_zone(&normalize_name(apex)).await?;
+    let mut acl = Vec::with_capacity(peers.len());
+    for peer in peers {
+        let address = peer.address.trim().to_string();
+        parse_prefix(&address)?;
+        let tsig_key = normalize_name(&peer.tsig_key);
+        db.get_tsig_key(&tsig_key).await?;
+        let notify = peer.notify.map(|target| target.trim().to_string());
+        if let Some(target) = &notify {
+            notify::parse_target(target)?;
+        }
+        acl.push(TransferPeer {
+            address,
+            tsig_key,
+            notify,
+        });
+    }
+
+    let targets: Vec<String> = acl.iter().filter_map(|peer| peer.notify.clone()).collect();
+    zone.transfer_acl = acl;
+    zone.updated_at = Utc::now();
+    let zone = db.upsert_zone(zone).await?;
+    db.prune_notifications(&zone.apex, &targets).await?;
+    Ok(zone)
+}
+
+/// Generate a TSIG key named `name` for `owner`; the secret is sealed in the
+/// key store and returned only here. An `unclaimed` key's secret is returned
+/// once more, to its owner, by [`claim_key_secret`].
+pub async fn create_key(
+    db: &Database,
+    manager: &DNSSECManager,
+    name: &str,
+    algorithm: TSIGAlgorithm,
+    owner: &str,
+    unclaimed: bool,
+) -> Result<TSIGKeySecret> {
+    let name = normalize_name(name);
+    if name.is_empty() || Name::from_str(&format!("{}.", name)).is_err() {
+        return Err(AppError::InvalidRecord(format!(
+            "invalid TSIG key name {}",
+            name
+        )));
+    }
+    if db.get_tsig_key(&name).await.is_ok() {
+        return Err(AppError::InvalidRecord(format!(
+            "TSIG key {} already exists",
+            name
+        )));
+    }
+
+    let (key, secret) = manager
+        .create_tsig_key(&name, algorithm, owner, unclaimed)
+        .await?;
+    info!("Created TSIG key {}", name);
+    Ok(TSIGKeySecret {
+        key,
+        secret: base64::engine::general_purpose::STANDARD.encode(secret),
+    })
+}
+
+/// Return the secret of an unclaimed TSIG key and mark it claimed, so that it
+/// is returned only once.
+pub async fn claim_key_secret(
+    db: &Database,
+    manager: &DNSSECManager,
+    mut key: TSIGKey,
+) -> Result<TSIGKeySecret> {
+    if !db.claim_tsig_secret(&key.name).await? {
+        return Err(AppError::InvalidQuery(format!(
+            "the secret of TSIG key {} has already been returned",
+            key.name
+        )));
+    }
+    let secret = manager.tsig_secret(&key)?;
+    key.secret_unclaimed = false;
+    info!("Secret of TSIG key {} claimed", key.name);
+    Ok(TSIGKeySecret {
+        key,
+        secret: base64::engine::general_purpose::STANDARD.encode(secret),
+    })
+}
+
+/// Delete a TSIG key that no zone's transfer ACL refers to, on behalf of
+/// `identity`: its owner, or anyone for a key with no recorded owner.
+pub async fn delete_key(db: &Database, name: &str, identity: &str) -> Result<bool> {
+    let name = normalize_name(name);
+    match db.get_tsig_key(&name).await {
+        Ok(key) if !key.owner.is_empty() && key.owner != identity => {
+            return Err(AppError::InvalidQuery(format!(
+                "TSIG key {} is owned by {}",
+                key.name, key.owner
+            )));
+        }
+        Ok(_) | Err(AppError::RecordNotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    let zones = db.zones().await?;
+    if let Some(zone) = zones
+        .iter()
+        .find(|zone| zone.transfer_acl.iter().any(|peer| peer.tsig_key == name))
+    {
+        return Err(AppError::InvalidRecord(format!(
+            "TSIG key {} is in the transfer ACL of {}",
+            name, zone.apex
+        )));
+    }
+    db.delete_tsig_key(&name).await
+}
+
+/// Split RRs into runs of about [`MESSAGE_SIZE`] bytes, one per message
+/// (RFC 5936 §2.2).
+fn split_messages(rrs: &[Record]) -> Result<Vec<&[Record]>> {
+    let mut messages = Vec::new();
+    let (mut start, mut size) = (0, 0);
+    for (i, rr) in rrs.iter().enumerate() {
+        let len = rr.to_bytes().map_err(proto)?.len();
+        if i > start && size + len > MESSAGE_SIZE {
+            messages.push(&rrs[start..i]);
+            start = i;
+            size = 0;
+        }
+        size += len;
+    }
+    messages.push(&rrs[start..]);
+    Ok(messages)
+}
+
+/// Snapshot form of a zone's RRs: the SOA first, the rest sorted and deduplicated.
+fn snapshot_records(rrs: &[Record]) -> Result<Vec<String>> {
+    let mut records = rrs.iter().map(encode_rr).collect::<Result<Vec<_>>>()?;
+    if records.len() > 1 {
+        records[1..].sort_unstable();
+    }
+    records.dedup();
+    Ok(records)
+}
+
+/// RRs only in `old` and RRs only in `new`, each in its original order, so the
+/// SOAs stay first.
+fn diff(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let old_set: HashSet<&String> = old.iter().collect();
+    let new_set: HashSet<&String> = new.iter().collect();
+    let removed = old
+        .iter()
+        .filter(|rr| !new_set.contains(rr))
+        .cloned()
+        .collect();
+    let added = new
+        .iter()
+        .filter(|rr| !old_set.contains(rr))
+        .cloned()
+        .collect();
+    (removed, added)
+}
+
+fn encode_rr(rr: &Record) -> Result<String> {
+    let bytes = rr.to_bytes().map_err(proto)?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+fn decode_rr(text: &str) -> Result<Record> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| AppError::Internal(format!("Invalid journal record: {}", e)))?;
+    Record::from_bytes(&bytes).map_err(proto)
+}
+
+/// Whether `address`, an IP address or CIDR prefix, covers `ip`.
+fn address_matches(address: &str, ip: IpAddr) -> bool {
+    let Ok((network, prefix)) = parse_prefix(address) else {
+        return false;
+    };
+    match (network, ip.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Parse `192.0.2.0/24`, `2001:db8::/32` or a bare address (a full-length prefix).
+fn parse_prefix(address: &str) -> Result<(IpAddr, u8)> {
+    let invalid = || AppError::InvalidIP(address.to_string());
+    let (ip, prefix) = match address.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (address, None),
+    };
+    let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(invalid)?,
+        None => max,
+    };
+    Ok((ip, prefix))
+}
+
+/// hickory's name for a TSIG algorithm.
+fn wire_algorithm(algorithm: TSIGAlgorithm) -> TsigAlgorithm {
+    match algorithm {
+        TSIGAlgorithm::HmacSha256 => TsigAlgorithm::HmacSha256,
+        TSIGAlgorithm::HmacSha512 => TsigAlgorithm::HmacSha512,
+    }
+}
+
+/// HMAC state keyed with `secret`, fed `data`.
+fn keyed<M: Mac + KeyInit>(secret: &[u8], data: &[u8]) -> Result<M> {
+    let mut mac = <M as KeyInit>::new_from_slice(secret)
+        .map_err(|_| AppError::Internal("Invalid TSIG secret length".into()))?;
+    mac.update(data);
+    Ok(mac)
+}
+
+/// TSIG MAC of `data`.
+fn mac(algorithm: TSIGAlgorithm, secret: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    Ok(match algorithm {
+        TSIGAlgorithm::HmacSha256 => keyed::<Hmac<Sha256>>(secret, data)?
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        TSIGAlgorithm::HmacSha512 => keyed::<Hmac<Sha512>>(secret, data)?
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    })
+}
+
+/// Check a TSIG MAC in constant time; truncated MACs are not accepted.
+fn verify_mac(algorithm: TSIGAlgorithm, secret: &[u8], data: &[u8], tag: &[u8]) -> Result<bool> {
+    Ok(match algorithm {
+        TSIGAlgorithm::HmacSha256 => keyed::<Hmac<Sha256>>(secret, data)?
+            .verify_slice(tag)
+            .is_ok(),
+        TSIGAlgorithm::HmacSha512 => keyed::<Hmac<Sha512>>(secret, data)?
+            .verify_slice(tag)
+            .is_ok(),
+    })
+}
+
+fn proto(e: ProtoError) -> AppError {
+    AppError::Internal(format!("DNS message encoding failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::{MessageType, OpCode, Query};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn key_name() -> Name {
+        Name::from_str("xfr.example.net.").unwrap()
+    }
+
+    /// An AXFR request signed the way a secondary signs it.
+    fn signed_request(time: u64) -> (Message, Vec<u8>) {
+        let mut message = Message::new();
+        message
+            .set_id(4242)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .add_query(Query::query(
+                Name::from_str("example.com.").unwrap(),
+                RecordType::AXFR,
+            ));
+        let pre_tsig = TSIG::new(
+            TsigAlgorithm::HmacSha256,
+            time,
+            TSIG_FUDGE,
+            Vec::new(),
+            4242,
+            0,
+            Vec::new(),
+        );
+        let tbs = message_tbs(None, &message, &pre_tsig, &key_name()).unwrap();
+        let mac = mac(TSIGAlgorithm::HmacSha256, SECRET, &tbs).unwrap();
+        message.add_tsig(make_tsig_record(key_name(), pre_tsig.set_mac(mac.clone())));
+        (message, mac)
+    }
+
+    #[test]
+    fn test_verify_message() {
+        let now = 1_790_000_000;
+        let (message, mac) = signed_request(now);
+        let bytes = message.to_vec().unwrap();
+        let alg = TSIGAlgorithm::HmacSha256;
+        assert_eq!(verify_message(&bytes, None, alg, SECRET, now + 10), Ok(mac));
+
+        assert_eq!(
+            verify_message(&bytes, None, alg, b"another secret", now),
+            Err(ResponseCode::BADSIG)
+        );
+        assert_eq!(
+            verify_message(&bytes, None, alg, SECRET, now + 3600),
+            Err(ResponseCode::BADTIME)
+        );
+
+        // Any change to the signed message breaks the MAC (the ID is restored
+        // from the TSIG's original ID, so change the query name)
+        let mut tampered = bytes.clone();
+        tampered[13] ^= 1;
+        assert_eq!(
+            verify_message(&tampered, None, alg, SECRET, now),
+            Err(ResponseCode::BADSIG)
+        );
+    }
+
+    #[test]
+    fn test_response_signatures_chain() {
+        let now = 1_790_000_000;
+        let (_, request_mac) = signed_request(now);
+        let mut signer = Signer {
+            key: key_name(),
+            algorithm: TSIGAlgorithm::HmacSha256,
+            secret: SECRET.to_vec(),
+            id: 4242,
+            previous_mac: request_mac.clone(),
+            first: true,
+        };
+
+        let mut previous = request_mac;
+        for first in [true, false, false] {
+            let mut response = Message::new();
+            response
+                .set_id(4242)
+                .set_message_type(MessageType::Response)
+                .set_authoritative(true);
+            let unsigned = response.to_vec().unwrap();
+            let tsig = signer.sign(&unsigned, now).unwrap();
+            response.add_tsig(tsig);
+
+            // The secondary's view: the MAC over the previous MAC and this message
+            let bytes = response.to_vec().unwrap();
+            let (tbv, record) = signed_bitmessage_to_buf(Some(&previous), &bytes, first).unwrap();
+            let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+                panic!("expected a TSIG record");
+            };
+            assert!(verify_mac(TSIGAlgorithm::HmacSha256, SECRET, &tbv, tsig.mac()).unwrap());
+            previous = tsig.mac().to_vec();
+        }
+    }
+
+    #[test]
+    fn test_address_matches() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert!(address_matches("192.0.2.53", ip("192.0.2.53")));
+        assert!(!address_matches("192.0.2.53", ip("192.0.2.54")));
+        assert!(address_matches("192.0.2.0/24", ip("192.0.2.200")));
+        assert!(!address_matches("192.0.2.0/24", ip("192.0.3.1")));
+        assert!(address_matches("0.0.0.0/0", ip("203.0.113.9")));
+        assert!(address_matches("2001:db8::/32", ip("2001:db8:1::53")));
+        assert!(!address_matches("2001:db8::/32", ip("2001:db9::53")));
+
+        // IPv4-mapped sources from a dual-stack listener match IPv4 entries
+        assert!(address_matches("192.0.2.0/24", ip("::ffff:192.0.2.7")));
+
+        assert!(parse_prefix("192.0.2.0/33").is_err());
+        assert!(parse_prefix("secondary.example.net").is_err());
+    }
+
+    #[test]
+    fn test_diff_keeps_soa_first() {
+        let old = vec!["soa1".to_string(), "a".to_string(), "b".to_string()];
+        let new = vec!["soa2".to_string(), "b".to_string(), "c".to_string()];
+        let (removed, added) = diff(&old, &new);
+        assert_eq!(removed, ["soa1", "a"]);
+        assert_eq!(added, ["soa2", "c"]);
+    }
+}
@@ -0,0 +1,828 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! RFC 1035 §5 master zone file parsing and export
+//!
+//! Supports:
+//! - `$ORIGIN` and `$TTL` directives (`$INCLUDE` is rejected)
+//! - Relative owner names, `@`, and blank owners inheriting the previous one
+//! - TTL/class in either order, BIND unit suffixes (`1h30m`, `2d`, `1w`)
+//! - Parenthesised multi-line entries (e.g. SOA) and `;` comments
+//! - Quoted character-strings with escapes (TXT, NAPTR, URI, SVCB parameters)
+//!
+//! Export renders a deterministic master file: SOA first, then owners in
+//! RFC 4034 §6.1 canonical order, then type code and RDATA. Signed zones also
+//! carry their DNSKEY, CDS and CDNSKEY RRsets, stored RRSIGs and NSEC/NSEC3 chain.
+//!
+//! RDATA is stored in the normalised form of [`crate::rdata`], domain names
+//! fully qualified with a trailing dot; owner names are stored lowercase
+//! without one, matching `DNSRecord.name`.
+
+use crate::{
+    authority::{in_zone, normalize_name},
+    db::Database,
+    denial,
+    dnssec::{DNSSECManager, DNSKEY_FLAGS_KSK, DNSKEY_FLAGS_ZSK},
+    error::{AppError, Result},
+    lint,
+    models::{
+        DNSRecord, DNSRecordType, DNSSECKey, DNSSECKeyRole, RecordChangeKind, ZoneFileError,
+        ZoneImportMode, ZoneImportResult,
+    },
+    rdata,
+    rollover::{self, KeyRRset},
+    signer, zones,
+};
+use std::fmt::Write as _;
+
+/// Result of parsing a zone file: every record that parsed, plus per-line errors.
+#[derive(Debug, Default)]
+pub struct ParsedZone {
+    pub records: Vec<DNSRecord>,
+    pub errors: Vec<ZoneFileError>,
+}
+
+/// One logical entry: tokens joined across parentheses, tagged with its first line.
+struct Entry {
+    line: usize,
+    owner_blank: bool,
+    tokens: Vec<String>,
+}
+
+/// Parse master-file text for `zone` into records.
+///
+/// Parsing continues past bad lines so that every error is reported at once;
+/// callers must not load `records` when `errors` is non-empty.
+pub fn parse_zone_file(zone: &str, text: &str) -> ParsedZone {
+    let mut parsed = ParsedZone::default();
+    let apex = normalize_name(zone);
+    let mut origin = apex.clone();
+    let mut default_ttl: Option<u32> = None;
+    let mut last_owner: Option<String> = None;
+    let mut last_ttl: Option<u32> = None;
+
+    let (entries, lex_errors) = lex(text);
+    parsed.errors.extend(lex_errors);
+
+    for entry in entries {
+        let line = entry.line;
+
+        if entry.tokens[0].starts_with('$') {
+            if let Err(e) = apply_directive(&entry.tokens, &mut origin, &mut default_ttl) {
+                parsed.errors.push(ZoneFileError::new(line, e));
+            }
+            continue;
+        }
+
+        match parse_record(&entry, &origin, last_owner.as_deref(), default_ttl.or(last_ttl)) {
+            Ok(record) => {
+                last_owner = Some(record.name.clone());
+                last_ttl = Some(record.ttl as u32);
+                if !in_zone(&record.name, &apex) {
+                    parsed.errors.push(ZoneFileError::new(
+                        line,
+                        AppError::InvalidRecord(format!("{} is outside zone {}", record.name, apex)),
+                    ));
+                } else if record.record_type == DNSRecordType::SOA && record.name != apex {
+                    parsed.errors.push(ZoneFileError::new(
+                        line,
+                        AppError::InvalidRecord(format!("SOA must be at the zone apex {}", apex)),
+                    ));
+                } else {
+                    parsed.records.push(record);
+                }
+            }
+            Err(e) => parsed.errors.push(ZoneFileError::new(line, e)),
+        }
+    }
+
+    if !parsed.records.iter().any(|r| r.record_type == DNSRecordType::SOA) && parsed.errors.is_empty() {
+        parsed.errors.push(ZoneFileError::new(
+            0,
+            AppError::InvalidRecord(format!("zone {} has no SOA record", apex)),
+        ));
+    }
+
+    parsed
+}
+
+/// Parse `text` and load it into `zone` atomically.
+///
+/// Nothing is written when any line fails to parse, or when the result would break
+/// an RRset invariant ([`lint::violations`]); the errors are returned instead.
+/// In `Merge` mode records already present (same owner, type and RDATA) are skipped,
+/// and an imported SOA is dropped when the zone already has one.
+pub async fn import_zone(
+    db: &Database,
+    zone: &str,
+    text: &str,
+    mode: ZoneImportMode,
+) -> Result<ZoneImportResult> {
+    let apex = normalize_name(zone);
+    let parsed = parse_zone_file(&apex, text);
+    if !parsed.errors.is_empty() {
+        return Ok(ZoneImportResult {
+            zone: apex,
+            imported: 0,
+            removed: 0,
+            errors: parsed.errors,
+        });
+    }
+
+    // A signed zone publishes its apex key RRsets from its keys
+    if signer::find_signed_zone(db, &apex).await?.is_some() {
+        let errors: Vec<ZoneFileError> = parsed
+            .records
+            .iter()
+            .filter(|r| rollover::is_key_rrset(&r.name, r.record_type, &apex))
+            .map(|r| {
+                ZoneFileError::new(
+                    0,
+                    AppError::InvalidRecord(format!(
+                        "{} records at {} are published from the zone's DNSSEC keys",
+                        r.record_type, apex
+                    )),
+                )
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Ok(ZoneImportResult {
+                zone: apex,
+                imported: 0,
+                removed: 0,
+                errors,
+            });
+        }
+    }
+
+    let existing = db.zone_records(&apex).await?;
+    let mut records: Vec<DNSRecord> = Vec::with_capacity(parsed.records.len());
+    for mut record in parsed.records {
+        record.zone = Some(apex.clone());
+        if records.iter().any(|r| same_rr(r, &record)) {
+            continue;
+        }
+        if mode == ZoneImportMode::Merge {
+            let has_soa = record.record_type == DNSRecordType::SOA
+                && existing.iter().any(|r| r.record_type == DNSRecordType::SOA);
+            if has_soa || existing.iter().any(|r| same_rr(r, &record)) {
+                continue;
+            }
+        }
+        records.push(record);
+    }
+
+    // The RRset invariants must hold at every name the file writes to
+    let mut at_names = records.clone();
+    if mode == ZoneImportMode::Merge {
+        at_names.extend(
+            existing
+                .iter()
+                .filter(|r| records.iter().any(|new| new.name == r.name))
+                .cloned(),
+        );
+    }
+    let errors: Vec<ZoneFileError> = lint::violations(&apex, &at_names)
+        .into_iter()
+        .map(|violation| ZoneFileError::new(0, AppError::InvalidRecord(violation.message)))
+        .collect();
+    if !errors.is_empty() {
+        return Ok(ZoneImportResult {
+            zone: apex,
+            imported: 0,
+            removed: 0,
+            errors,
+        });
+    }
+
+    let removed = match mode {
+        ZoneImportMode::Replace => existing.len(),
+        ZoneImportMode::Merge => 0,
+    };
+    let imported = records.len();
+    db.import_zone_records(&apex, records.clone(), mode == ZoneImportMode::Replace)
+        .await?;
+    if mode == ZoneImportMode::Replace {
+        db.events().records(RecordChangeKind::Deleted, existing);
+    }
+    db.events().records(RecordChangeKind::Created, records);
+
+    Ok(ZoneImportResult {
+        zone: apex,
+        imported: imported as i32,
+        removed: removed as i32,
+        errors: Vec::new(),
+    })
+}
+
+/// [`import_zone`] on behalf of `owner`. When the file loads, the zone takes
+/// its SOA parameters from it and is adopted by `owner`; a signed zone is
+/// re-signed as a whole, chain included, and the serial bumped.
+pub async fn import_zone_as(
+    db: &Database,
+    manager: &DNSSECManager,
+    zone: &str,
+    text: &str,
+    mode: ZoneImportMode,
+    owner: &str,
+) -> Result<ZoneImportResult> {
+    // A zone not created yet becomes the importer's
+    match zones::require_owner(db, zone, owner).await {
+        Ok(_) | Err(AppError::ZoneNotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    let result = import_zone(db, zone, text, mode).await?;
+    if result.errors.is_empty() {
+        zones::adopt(db, &result.zone, owner).await?;
+        if let Some(signed) = signer::find_signed_zone(db, &result.zone).await? {
+            signer::sign_zone(db, manager, &signed.zone).await?;
+            denial::rebuild_chain(db, manager, &signed).await?;
+        }
+        zones::bump_serial(db, manager, &result.zone).await?;
+    }
+    Ok(result)
+}
+
+/// Render every record under `zone` as a canonical master file.
+///
+/// DNSKEY/CDS/CDNSKEY, RRSIGs and the NSEC/NSEC3 chain are included when DNSSEC is enabled
+/// for the zone; the chain follows the zone data, in chain order.
+pub async fn export_zone(db: &Database, zone: &str) -> Result<String> {
+    let apex = normalize_name(zone);
+    let records = db.zone_records(&apex).await?;
+    if records.is_empty() {
+        return Err(AppError::ZoneNotFound(apex));
+    }
+
+    let signed = db.get_dnssec_zone(&apex).await.ok().filter(|zone| zone.enabled);
+    let (dnskeys, key_rrsets) = match &signed {
+        Some(zone) => {
+            let keys = db.dnssec_keys(Some(&apex)).await?;
+            let key_rrsets = rollover::key_rrsets(zone, &keys)?;
+            let dnskeys: Vec<DNSSECKey> =
+                keys.into_iter().filter(DNSSECKey::in_dnskey_rrset).collect();
+            (Some(dnskeys), key_rrsets)
+        }
+        None => (None, Vec::new()),
+    };
+    let dnskey_rrsig = signed.as_ref().and_then(|zone| zone.dnskey_rrsig.as_deref());
+    let mut text =
+        render_zone_file(&apex, &records, dnskeys.as_deref(), dnskey_rrsig, &key_rrsets);
+
+    if let Some(zone) = signed {
+        let chain = db.denial_records(Some(&apex)).await?;
+        denial::write_chain(&mut text, &chain, zone.nsec3.as_ref());
+    }
+    Ok(text)
+}
+
+/// Render records as a deterministic master file with absolute owner names.
+///
+/// `dnskeys` holds the zone's published keys when it is signed, with the KSK
+/// RRSIGs over them in `dnskey_rrsig`; `None` for an unsigned zone. The CDS
+/// and CDNSKEY RRsets in `key_rrsets` follow the DNSKEY RRset.
+pub fn render_zone_file(
+    apex: &str,
+    records: &[DNSRecord],
+    dnskeys: Option<&[DNSSECKey]>,
+    dnskey_rrsig: Option<&str>,
+    key_rrsets: &[KeyRRset],
+) -> String {
+    let mut sorted: Vec<&DNSRecord> = records.iter().collect();
+    sorted.sort_by(|a, b| {
+        let soa_first = (b.record_type == DNSRecordType::SOA).cmp(&(a.record_type == DNSRecordType::SOA));
+        soa_first
+            .then_with(|| canonical_key(&a.name).cmp(&canonical_key(&b.name)))
+            .then_with(|| a.record_type.to_type_code().cmp(&b.record_type.to_type_code()))
+            .then_with(|| a.value.cmp(&b.value))
+    });
+
+    let mut out = String::new();
+    let _ = writeln!(out, "$ORIGIN {}.", apex);
+
+    let dnskey_ttl = rollover::dnskey_ttl(apex, records);
+    let mut dnskeys_written = dnskeys.is_none();
+
+    let mut i = 0;
+    while i < sorted.len() {
+        // One RRset: same owner and type
+        let start = i;
+        while i < sorted.len()
+            && sorted[i].name == sorted[start].name
+            && sorted[i].record_type == sorted[start].record_type
+        {
+            i += 1;
+        }
+        let rrset = &sorted[start..i];
+
+        // DNSKEYs sort after the apex SOA/NS/MX (type 48), before any descendant
+        if !dnskeys_written && (rrset[0].name != apex || rrset[0].record_type.to_type_code() > 48) {
+            if let Some(keys) = dnskeys {
+                write_dnskeys(&mut out, apex, dnskey_ttl, keys, dnskey_rrsig, key_rrsets);
+            }
+            dnskeys_written = true;
+        }
+
+        for record in rrset {
+            let _ = writeln!(
+                out,
+                "{}.\t{}\tIN\t{}\t{}",
+                record.name, record.ttl, record.record_type, record.value
+            );
+        }
+
+        if dnskeys.is_some() {
+            let mut signatures: Vec<&str> = rrset
+                .iter()
+                .filter_map(|r| r.rrsig.as_deref())
+                .flat_map(str::lines)
+                .collect();
+            signatures.sort_unstable();
+            signatures.dedup();
+            for rrsig in signatures {
+                let _ = writeln!(out, "{}.\t{}\tIN\tRRSIG\t{}", rrset[0].name, rrset[0].ttl, rrsig);
+            }
+        }
+    }
+
+    if !dnskeys_written {
+        if let Some(keys) = dnskeys {
+            write_dnskeys(&mut out, apex, dnskey_ttl, keys, dnskey_rrsig, key_rrsets);
+        }
+    }
+
+    out
+}
+
+/// Append the apex DNSKEY RRset (KSK flags 257, ZSK flags 256), ordered by RDATA,
+/// followed by its RRSIGs, then the non-empty CDS and CDNSKEY RRsets likewise.
+fn write_dnskeys(
+    out: &mut String,
+    apex: &str,
+    ttl: i32,
+    keys: &[DNSSECKey],
+    rrsig: Option<&str>,
+    key_rrsets: &[KeyRRset],
+) {
+    let mut rdata: Vec<(u16, i32, &str)> = keys
+        .iter()
+        .map(|key| {
+            let flags = match key.role {
+                DNSSECKeyRole::KSK => DNSKEY_FLAGS_KSK,
+                DNSSECKeyRole::ZSK => DNSKEY_FLAGS_ZSK,
+            };
+            (flags, key.algorithm, key.public_key.as_str())
+        })
+        .collect();
+    rdata.sort_unstable();
+
+    for (flags, algorithm, public_key) in rdata {
+        let _ = writeln!(
+            out,
+            "{}.\t{}\tIN\tDNSKEY\t{} 3 {} {}",
+            apex, ttl, flags, algorithm, public_key
+        );
+    }
+
+    write_rrsigs(out, apex, ttl, rrsig);
+
+    for rrset in key_rrsets.iter().filter(|rrset| !rrset.rdata.is_empty()) {
+        for rdata in &rrset.rdata {
+            let _ = writeln!(out, "{}.\t{}\tIN\t{}\t{}", apex, ttl, rrset.record_type, rdata);
+        }
+        write_rrsigs(out, apex, ttl, rrset.rrsig.as_deref());
+    }
+}
+
+/// Append stored RRSIG lines for an apex RRset, sorted.
+fn write_rrsigs(out: &mut String, apex: &str, ttl: i32, rrsig: Option<&str>) {
+    let mut signatures: Vec<&str> = rrsig.into_iter().flat_map(str::lines).collect();
+    signatures.sort_unstable();
+    for rrsig in signatures {
+        let _ = writeln!(out, "{}.\t{}\tIN\tRRSIG\t{}", apex, ttl, rrsig);
+    }
+}
+
+/// RFC 4034 §6.1 canonical ordering key: lowercase labels, rightmost first.
+fn canonical_key(name: &str) -> Vec<String> {
+    normalize_name(name)
+        .split('.')
+        .filter(|l| !l.is_empty())
+        .rev()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether two records are the same RR (owner, type and RDATA).
+///
+/// Values are compared as normalised, which already lowercases the names in
+/// them; the rest of RDATA, such as TXT strings, is case-sensitive.
+fn same_rr(a: &DNSRecord, b: &DNSRecord) -> bool {
+    a.name == b.name && a.record_type == b.record_type && a.value == b.value
+}
+
+/// Apply a `$` directive to the parser state.
+fn apply_directive(tokens: &[String], origin: &mut String, default_ttl: &mut Option<u32>) -> Result<()> {
+    let arg = tokens
+        .get(1)
+        .ok_or_else(|| AppError::InvalidRecord(format!("{} requires an argument", tokens[0])))?;
+
+    match tokens[0].to_ascii_uppercase().as_str() {
+        "$ORIGIN" => {
+            if !arg.ends_with('.') {
+                return Err(AppError::InvalidRecord(format!("$ORIGIN {} must be absolute", arg)));
+            }
+            *origin = normalize_name(arg);
+        }
+        "$TTL" => *default_ttl = Some(parse_ttl(arg)?),
+        other => {
+            return Err(AppError::InvalidRecord(format!("unsupported directive {}", other)));
+        }
+    }
+    Ok(())
+}
+
+/// Parse a resource record entry.
+fn parse_record(
+    entry: &Entry,
+    origin: &str,
+    last_owner: Option<&str>,
+    fallback_ttl: Option<u32>,
+) -> Result<DNSRecord> {
+    let mut tokens = entry.tokens.iter().map(String::as_str).peekable();
+
+    let owner = if entry.owner_blank {
+        last_owner
+            .map(str::to_string)
+            .ok_or_else(|| AppError::InvalidRecord("blank owner with no previous owner".to_string()))?
+    } else {
+        let token = tokens.next().ok_or_else(|| AppError::InvalidRecord("empty entry".to_string()))?;
+        qualify(token, origin)
+    };
+
+    // TTL and class may appear in either order before the type.
+    let mut ttl: Option<u32> = None;
+    for _ in 0..2 {
+        match tokens.peek() {
+            Some(t) if t.eq_ignore_ascii_case("IN") => {
+                tokens.next();
+            }
+            Some(t) if ["CH", "HS", "CS"].iter().any(|c| t.eq_ignore_ascii_case(c)) => {
+                return Err(AppError::InvalidRecord(format!("unsupported class {}", t)));
+            }
+            Some(t) if t.starts_with(|c: char| c.is_ascii_digit()) && ttl.is_none() => {
+                ttl = Some(parse_ttl(t)?);
+                tokens.next();
+            }
+            _ => break,
+        }
+    }
+
+    let type_token = tokens
+        .next()
+        .ok_or_else(|| AppError::InvalidRecord(format!("{}: missing record type", owner)))?;
+    let record_type: DNSRecordType = type_token.parse()?;
+
+    let mut rdata: Vec<String> = tokens.map(str::to_string).collect();
+    if rdata.is_empty() {
+        return Err(AppError::InvalidRecord(format!("{} {}: missing RDATA", owner, type_token)));
+    }
+    qualify_rdata(record_type, &mut rdata, origin)?;
+
+    let ttl = match ttl.or(fallback_ttl) {
+        Some(ttl) => ttl,
+        // RFC 1035 falls back to the SOA MINIMUM when no TTL has been set
+        None if record_type == DNSRecordType::SOA && rdata.len() == 7 => parse_ttl(&rdata[6])?,
+        None => {
+            return Err(AppError::InvalidRecord(format!(
+                "{} {}: no TTL and no $TTL in effect",
+                owner, type_token
+            )))
+        }
+    };
+    let ttl = i32::try_from(ttl)
+        .map_err(|_| AppError::InvalidRecord(format!("TTL {} out of range", ttl)))?;
+
+    let value = rdata::normalize(record_type, &rdata.join(" "))?;
+    Ok(DNSRecord::new(owner, record_type, ttl, value))
+}
+
+/// Qualify domain names, expand timer units and quote TXT strings inside RDATA, per type.
+fn qualify_rdata(record_type: DNSRecordType, rdata: &mut [String], origin: &str) -> Result<()> {
+    let name_fields: &[usize] = match record_type {
+        DNSRecordType::CNAME | DNSRecordType::NS | DNSRecordType::PTR => &[0],
+        DNSRecordType::MX => &[1],
+        DNSRecordType::SRV => &[3],
+        DNSRecordType::SOA => &[0, 1],
+        DNSRecordType::HTTPS | DNSRecordType::SVCB => &[1],
+        DNSRecordType::NAPTR => &[5],
+        _ => &[],
+    };
+
+    for &i in name_fields {
+        if let Some(field) = rdata.get_mut(i) {
+            *field = absolute(field, origin);
+        }
+    }
+
+    // Each unquoted token of a TXT record is a string of its own
+    if record_type == DNSRecordType::TXT {
+        for field in rdata.iter_mut().filter(|field| !field.starts_with('"')) {
+            *field = rdata::quote(field);
+        }
+    }
+
+    if record_type == DNSRecordType::SOA {
+        if rdata.len() != 7 {
+            return Err(AppError::InvalidRecord(format!(
+                "SOA needs 7 fields, got {}",
+                rdata.len()
+            )));
+        }
+        for field in &mut rdata[2..] {
+            *field = parse_ttl(field)?.to_string();
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a possibly-relative name against `origin` (stored form: no trailing dot).
+fn qualify(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if name.ends_with('.') || origin.is_empty() {
+        normalize_name(name)
+    } else {
+        format!("{}.{}", name.to_ascii_lowercase(), origin)
+    }
+}
+
+/// Resolve a name against `origin` into absolute presentation form (trailing dot).
+fn absolute(name: &str, origin: &str) -> String {
+    format!("{}.", qualify(name, origin))
+}
+
+/// Parse a TTL: plain seconds or BIND units (`1w2d3h4m5s`).
+pub fn parse_ttl(value: &str) -> Result<u32> {
+    let invalid = || AppError::InvalidRecord(format!("invalid TTL {}", value));
+
+    if let Ok(seconds) = value.parse::<u32>() {
+        return Ok(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit: u64 = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = number.parse().map_err(|_| invalid())?;
+        total = n
+            .checked_mul(unit)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    u32::try_from(total).map_err(|_| invalid())
+}
+
+/// Split text into logical entries, joining parenthesised continuations.
+fn lex(text: &str) -> (Vec<Entry>, Vec<ZoneFileError>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut current: Option<Entry> = None;
+    let mut depth = 0usize;
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let entry = current.get_or_insert_with(|| Entry {
+            line: line_no,
+            owner_blank: line.starts_with([' ', '\t']),
+            tokens: Vec::new(),
+        });
+
+        let mut token = String::new();
+        let mut in_quotes = false;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    token.push(c);
+                    if let Some(escaped) = chars.next() {
+                        token.push(escaped);
+                    }
+                }
+                '"' => {
+                    token.push(c);
+                    in_quotes = !in_quotes;
+                }
+                _ if in_quotes => token.push(c),
+                ';' => break,
+                '(' => {
+                    flush(&mut token, &mut entry.tokens);
+                    depth += 1;
+                }
+                ')' => {
+                    flush(&mut token, &mut entry.tokens);
+                    if depth == 0 {
+                        errors.push(ZoneFileError::new(
+                            line_no,
+                            AppError::InvalidRecord("unbalanced ')'".to_string()),
+                        ));
+                    } else {
+                        depth -= 1;
+                    }
+                }
+                c if c.is_whitespace() => flush(&mut token, &mut entry.tokens),
+                _ => token.push(c),
+            }
+        }
+        if in_quotes {
+            errors.push(ZoneFileError::new(
+                line_no,
+                AppError::InvalidRecord("unterminated quoted string".to_string()),
+            ));
+        }
+        flush(&mut token, &mut entry.tokens);
+
+        if depth == 0 {
+            if let Some(entry) = current.take() {
+                if !entry.tokens.is_empty() {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    if let Some(entry) = current {
+        errors.push(ZoneFileError::new(
+            entry.line,
+            AppError::InvalidRecord("unterminated '('".to_string()),
+        ));
+    }
+
+    (entries, errors)
+}
+
+/// Push a completed token, if any.
+fn flush(token: &mut String, tokens: &mut Vec<String>) {
+    if !token.is_empty() {
+        tokens.push(std::mem::take(token));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = r#"
+$ORIGIN example.com.
+$TTL 1h
+@   IN  SOA ns1 hostmaster (
+            2026101601 ; serial
+            2h         ; refresh
+            15m        ; retry
+            1w         ; expire
+            300 )      ; minimum
+    IN  NS  ns1
+    IN  MX  10 mail.example.com.
+ns1 300 IN A 192.0.2.53
+www     A   192.0.2.1
+        AAAA 2001:db8::1
+txt     TXT "v=spf1 -all" "quoted \" text"
+"#;
+
+    #[test]
+    fn test_parse_zone_file() {
+        let parsed = parse_zone_file("example.com", ZONE);
+        assert!(parsed.errors.is_empty(), "errors: {:?}", parsed.errors);
+        assert_eq!(parsed.records.len(), 7);
+
+        let soa = &parsed.records[0];
+        assert_eq!(soa.name, "example.com");
+        assert_eq!(soa.ttl, 3600);
+        assert_eq!(
+            soa.value,
+            "ns1.example.com. hostmaster.example.com. 2026101601 7200 900 604800 300"
+        );
+
+        let ns = &parsed.records[1];
+        assert_eq!(ns.name, "example.com");
+        assert_eq!(ns.value, "ns1.example.com.");
+
+        let aaaa = &parsed.records[5];
+        assert_eq!(aaaa.name, "www.example.com");
+        assert_eq!(aaaa.record_type, DNSRecordType::AAAA);
+
+        let txt = &parsed.records[6];
+        assert_eq!(txt.value, r#""v=spf1 -all" "quoted \" text""#);
+    }
+
+    #[test]
+    fn test_same_rr_keeps_rdata_case() {
+        let text = "$TTL 300\n@ SOA ns1 hostmaster 1 7200 900 604800 300\n\
+                    @ NS NS1\n@ NS ns1\nwww TXT \"Hello\"\nwww TXT \"hello\"\n";
+        let parsed = parse_zone_file("example.com", text);
+        assert!(parsed.errors.is_empty(), "errors: {:?}", parsed.errors);
+        let records = &parsed.records;
+        assert!(same_rr(&records[1], &records[2]));
+        assert!(!same_rr(&records[3], &records[4]));
+    }
+
+    #[test]
+    fn test_parse_reports_line_errors() {
+        let text = "$TTL 300\n@ SOA ns1 host 1 2 3 4 5\nbad 300 IN A not-an-ip\nother.org. A 192.0.2.1\n";
+        let parsed = parse_zone_file("example.com", text);
+        let lines: Vec<i32> = parsed.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(parsed.errors[0].message.starts_with("Invalid DNS record"));
+    }
+
+    #[test]
+    fn test_render_zone_file_roundtrip() {
+        let parsed = parse_zone_file("example.com", ZONE);
+        let mut reversed = parsed.records.clone();
+        reversed.reverse();
+
+        let rendered = render_zone_file("example.com", &parsed.records, None, None, &[]);
+        assert_eq!(rendered, render_zone_file("example.com", &reversed, None, None, &[]));
+        assert!(rendered.lines().nth(1).unwrap().contains("\tSOA\t"));
+
+        let reparsed = parse_zone_file("example.com", &rendered);
+        assert!(reparsed.errors.is_empty(), "errors: {:?}", reparsed.errors);
+        assert_eq!(reparsed.records.len(), parsed.records.len());
+    }
+
+    #[test]
+    fn test_modern_types_roundtrip() {
+        let text = r#"$ORIGIN example.com.
+@        300 IN SOA ns1 hostmaster 1 7200 900 1209600 300
+@            HTTPS 1 . alpn="h3,h2" ipv4hint=192.0.2.1
+_svc         SVCB  2 svc port=8443
+sip          NAPTR 100 10 "S" "SIP+D2U" "" _sip._udp
+_ftp._tcp    URI   10 1 "ftp://ftp.example.com/public"
+host         SSHFP 4 2 ( CDCDCDCDCDCDCDCDCDCDCDCDCDCDCDCD
+                         CDCDCDCDCDCDCDCDCDCDCDCDCDCDCDCD )
+here         LOC   52 22 23 N 4 53 32 E -2m
+child        DS    12345 13 2 ABABABABABABABABABABABABABABABABABABABABABABABABABABABABABABABAB
+"#;
+        let parsed = parse_zone_file("example.com", text);
+        assert!(parsed.errors.is_empty(), "errors: {:?}", parsed.errors);
+        let value = |record_type| {
+            parsed
+                .records
+                .iter()
+                .find(|r| r.record_type == record_type)
+                .map(|r| r.value.clone())
+                .unwrap()
+        };
+        assert_eq!(value(DNSRecordType::HTTPS), "1 . alpn=h3,h2 ipv4hint=192.0.2.1");
+        assert_eq!(value(DNSRecordType::SVCB), "2 svc.example.com. port=8443");
+        assert_eq!(
+            value(DNSRecordType::NAPTR),
+            r#"100 10 "s" "SIP+D2U" "" _sip._udp.example.com."#
+        );
+        assert_eq!(value(DNSRecordType::SSHFP), format!("4 2 {}", "cd".repeat(32)));
+
+        let rendered = render_zone_file("example.com", &parsed.records, None, None, &[]);
+        let reparsed = parse_zone_file("example.com", &rendered);
+        assert!(reparsed.errors.is_empty(), "errors: {:?}", reparsed.errors);
+        let values = |records: &[DNSRecord]| {
+            let mut values: Vec<String> = records.iter().map(|r| r.value.clone()).collect();
+            values.sort();
+            values
+        };
+        assert_eq!(values(&reparsed.records), values(&parsed.records));
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let mut names = vec!["z.example.com", "example.com", "a.example.com", "yljkjljk.a.example.com"];
+        names.sort_by_key(|n| canonical_key(n));
+        assert_eq!(names, vec!["example.com", "a.example.com", "yljkjljk.a.example.com", "z.example.com"]);
+    }
+
+    #[test]
+    fn test_parse_ttl_units() {
+        assert_eq!(parse_ttl("3600").unwrap(), 3600);
+        assert_eq!(parse_ttl("1h30m").unwrap(), 5400);
+        assert_eq!(parse_ttl("1W").unwrap(), 604_800);
+        assert!(parse_ttl("1x").is_err());
+        assert!(parse_ttl("5h3").is_err());
+        // Overflow is refused rather than wrapped
+        assert!(parse_ttl("40000000000000w").is_err());
+        assert!(parse_ttl("18446744073709551615s1s").is_err());
+    }
+}
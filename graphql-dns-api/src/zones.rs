@@ -0,0 +1,424 @@
+// SPDX-License-Identifier: PMPL-1.0-or-later
+//! Zones: apex, SOA parameters, default TTL and owner
+//!
+//! Every record belongs to its closest enclosing zone. The apex SOA record is
+//! written from the zone, and its serial advances on every committed change
+//! ([`bump_serial`]) so that secondaries and caches see a new version; the
+//! change is journaled for IXFR, the secondaries are sent NOTIFY and
+//! subscribers are told at the same time.
+//! Zones imported from a master file, or stored before zones existed, are
+//! adopted from their SOA record.
+
+use crate::{
+    authority::normalize_name,
+    db::Database,
+    dnssec::DNSSECManager,
+    error::{AppError, Result},
+    events::Event,
+    models::{DNSRecord, DNSRecordType, RecordChangeKind, SerialPolicy, Zone, ZoneInput},
+    notify,
+    rdata,
+    signer,
+    xfr,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use hickory_proto::rr::Name;
+use std::str::FromStr;
+use tracing::info;
+
+/// Maximum SOA records fetched when adopting zones.
+const SOA_LIMIT: i32 = 10000;
+
+/// Serial following `current` under `policy` (RFC 1982 arithmetic).
+///
+/// A date serial never goes backwards: once ten digits of today's date plus
+/// counter are exhausted (or the serial is already ahead) it simply increments.
+pub fn next_serial(policy: SerialPolicy, current: u32, today: NaiveDate) -> u32 {
+    match policy {
+        SerialPolicy::Increment => current.wrapping_add(1),
+        SerialPolicy::Date => {
+            let base = today.year() as u32 * 1_000_000 + today.month() * 10_000 + today.day() * 100;
+            if current < base {
+                base
+            } else {
+                current.wrapping_add(1)
+            }
+        }
+    }
+}
+
+/// SOA RDATA in presentation form.
+pub fn soa_value(zone: &Zone) -> String {
+    format!(
+        "{} {} {} {} {} {} {}",
+        zone.primary_ns,
+        zone.admin_mailbox,
+        zone.serial,
+        zone.refresh,
+        zone.retry,
+        zone.expire,
+        zone.minimum
+    )
+}
+
+/// Create a zone owned by `owner`, write its SOA record and move the records
+/// already stored under its apex into it.
+pub async fn create(db: &Database, input: ZoneInput, owner: &str) -> Result<Zone> {
+    let apex = normalize_name(&input.apex);
+    check_name(&apex)?;
+    if db.get_zone(&apex).await.is_ok() || !apex_soa(db, &apex).await?.is_empty() {
+        return Err(AppError::InvalidRecord(format!(
+            "zone {} already exists",
+            apex
+        )));
+    }
+    for (field, value) in [
+        ("refresh", input.refresh),
+        ("retry", input.retry),
+        ("expire", input.expire),
+        ("minimum", input.minimum),
+        ("defaultTtl", input.default_ttl),
+    ] {
+        if value < 0 {
+            return Err(AppError::InvalidRecord(format!(
+                "{} must not be negative",
+                field
+            )));
+        }
+    }
+
+    let now = Utc::now();
+    let zone = Zone {
+        apex: apex.clone(),
+        primary_ns: fqdn(&input.primary_ns)?,
+        admin_mailbox: mailbox_name(&input.admin_mailbox)?,
+        serial: i64::from(next_serial(input.serial_policy, 0, now.date_naive())),
+        serial_policy: input.serial_policy,
+        refresh: input.refresh,
+        retry: input.retry,
+        expire: input.expire,
+        minimum: input.minimum,
+        default_ttl: input.default_ttl,
+        owner: owner.to_string(),
+        created_at: now,
+        updated_at: now,
+        transfer_acl: Vec::new(),
+    };
+    rdata::normalize(DNSRecordType::SOA, &soa_value(&zone))?;
+    let zone = db.upsert_zone(zone).await?;
+
+    let mut soa = DNSRecord::new(
+        apex.clone(),
+        DNSRecordType::SOA,
+        zone.default_ttl,
+        soa_value(&zone),
+    );
+    soa.zone = Some(apex.clone());
+    let soa = db.create_record(soa).await?;
+    let moved = db.assign_zone_records(&apex).await?;
+    xfr::record_change(db, &zone).await?;
+    db.events().records(RecordChangeKind::Created, [soa]);
+    db.events().records(RecordChangeKind::Updated, moved);
+
+    info!("Created zone {} for {}", apex, owner);
+    Ok(zone)
+}
+
+/// The closest zone enclosing `name`.
+pub async fn enclosing_zone(db: &Database, name: &str) -> Result<Zone> {
+    let name = normalize_name(name);
+    let mut candidate = name.as_str();
+    loop {
+        if let Ok(zone) = db.get_zone(candidate).await {
+            return Ok(zone);
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if !parent.is_empty() => candidate = parent,
+            _ => return Err(AppError::ZoneNotFound(name)),
+        }
+    }
+}
+
+/// The zone at `apex`, provided `identity` may change it: its owner, or anyone
+/// for a zone with no recorded owner.
+pub async fn require_owner(db: &Database, apex: &str, identity: &str) -> Result<Zone> {
+    let zone = db.get_zone(&normalize_name(apex)).await?;
+    if !zone.owner.is_empty() && zone.owner != identity {
+        return Err(AppError::InvalidQuery(format!(
+            "Zone {} is owned by {}",
+            zone.apex, zone.owner
+        )));
+    }
+    Ok(zone)
+}
+
+/// Advance a zone's serial after a committed change and rewrite its SOA record,
+/// re-signing it when the zone is signed, then journal the change and notify
+/// the secondaries.
+pub async fn bump_serial(db: &Database, manager: &DNSSECManager, apex: &str) -> Result<Zone> {
+    let apex = normalize_name(apex);
+    let mut zone = db.get_zone(&apex).await?;
+    let now = Utc::now();
+    advance_serial(&mut zone, now);
+    let zone = db.upsert_zone(zone).await?;
+
+    let value = soa_value(&zone);
+    match apex_soa(db, &apex).await?.into_iter().next() {
+        Some(mut soa) => {
+            soa.value = value;
+            soa.updated_at = now;
+            let id = soa.id.clone();
+            db.update_record(&id, soa).await?;
+        }
+        None => {
+            let mut soa = DNSRecord::new(apex.clone(), DNSRecordType::SOA, zone.default_ttl, value);
+            soa.zone = Some(apex.clone());
+            soa.dnssec = signer::find_signed_zone(db, &apex).await?.is_some();
+            db.create_record(soa).await?;
+        }
+    }
+    signer::resign_rrset(db, manager, &apex, DNSRecordType::SOA).await?;
+    announce(db, manager, &zone).await?;
+    Ok(zone)
+}
+
+/// Give `zone` the serial following its own as of `now`, without storing it.
+pub fn advance_serial(zone: &mut Zone, now: DateTime<Utc>) {
+    zone.serial = i64::from(next_serial(
+        zone.serial_policy,
+        zone.serial as u32,
+        now.date_naive(),
+    ));
+    zone.updated_at = now;
+}
+
+/// Journal a zone's new serial once stored, notify the secondaries and tell
+/// subscribers.
+pub async fn announce(db: &Database, manager: &DNSSECManager, zone: &Zone) -> Result<()> {
+    xfr::record_change(db, zone).await?;
+    notify::schedule(db, manager, zone).await;
+    db.events().publish(Event::ZoneSerialChanged(zone.clone()));
+    Ok(())
+}
+
+/// Delete a zone and its records. Zones below it keep theirs.
+///
+/// A signed zone must have DNSSEC disabled first, so the parent's DS goes away
+/// before the keys do.
+pub async fn delete(db: &Database, apex: &str) -> Result<()> {
+    let apex = normalize_name(apex);
+    db.get_zone(&apex).await?;
+    if db
+        .get_dnssec_zone(&apex)
+        .await
+        .is_ok_and(|zone| zone.enabled)
+    {
+        return Err(AppError::DNSSEC(format!(
+            "Disable DNSSEC for {} before deleting it",
+            apex
+        )));
+    }
+    let records = db.zone_records(&apex).await?;
+    db.delete_zone(&apex).await?;
+    info!("Deleted zone {}", apex);
+    db.events().records(RecordChangeKind::Deleted, records);
+    Ok(())
+}
+
+/// Bring a zone in line with the SOA record stored at `apex`, creating it for
+/// `owner` if needed; used after importing a master file.
+///
+/// The serial never goes backwards, and the records under the apex join the zone.
+pub async fn adopt(db: &Database, apex: &str, owner: &str) -> Result<Zone> {
+    let apex = normalize_name(apex);
+    let soa = apex_soa(db, &apex)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::InvalidRecord(format!("zone {} has no SOA record", apex)))?;
+    let fields: Vec<&str> = soa.value.split_whitespace().collect();
+    let invalid = || AppError::InvalidRecord(format!("invalid SOA for {}: {}", apex, soa.value));
+    let [mname, rname, serial, refresh, retry, expire, minimum] = fields[..] else {
+        return Err(invalid());
+    };
+    let number = |field: &str| field.parse::<u32>().map_err(|_| invalid());
+    let interval = |field: &str| field.parse::<i32>().map_err(|_| invalid());
+
+    let now = Utc::now();
+    let mut zone = match db.get_zone(&apex).await {
+        Ok(zone) => zone,
+        Err(_) => Zone {
+            apex: apex.clone(),
+            primary_ns: String::new(),
+            admin_mailbox: String::new(),
+            serial: 0,
+            serial_policy: SerialPolicy::default(),
+            refresh: 0,
+            retry: 0,
+            expire: 0,
+            minimum: 0,
+            default_ttl: soa.ttl,
+            owner: owner.to_string(),
+            created_at: now,
+            updated_at: now,
+            transfer_acl: Vec::new(),
+        },
+    };
+    zone.primary_ns = mname.to_string();
+    zone.admin_mailbox = rname.to_string();
+    let serial = number(serial)?;
+    if zone.serial == 0 || serial_gt(serial, zone.serial as u32) {
+        zone.serial = i64::from(serial);
+    }
+    zone.refresh = interval(refresh)?;
+    zone.retry = interval(retry)?;
+    zone.expire = interval(expire)?;
+    zone.minimum = interval(minimum)?;
+    zone.updated_at = now;
+
+    let zone = db.upsert_zone(zone).await?;
+    let moved = db.assign_zone_records(&apex).await?;
+    xfr::record_change(db, &zone).await?;
+    db.events().records(RecordChangeKind::Updated, moved);
+    Ok(zone)
+}
+
+/// Adopt a zone for every apex SOA stored before zones existed.
+///
+/// Returns the number of zones adopted; they have no recorded owner.
+pub async fn adopt_legacy(db: &Database) -> Result<usize> {
+    let soas = db
+        .query_records(None, Some(DNSRecordType::SOA), SOA_LIMIT, 0)
+        .await?;
+    let mut adopted = 0;
+    for soa in soas {
+        let apex = normalize_name(&soa.name);
+        if db.get_zone(&apex).await.is_ok() {
+            continue;
+        }
+        adopt(db, &apex, "").await?;
+        info!("Adopted zone {} from its SOA record", apex);
+        adopted += 1;
+    }
+    Ok(adopted)
+}
+
+/// SOA records stored at `apex`.
+async fn apex_soa(db: &Database, apex: &str) -> Result<Vec<DNSRecord>> {
+    db.query_records(Some(apex.to_string()), Some(DNSRecordType::SOA), 1, 0)
+        .await
+}
+
+/// `a` is greater than `b` in RFC 1982 serial number arithmetic.
+pub(crate) fn serial_gt(a: u32, b: u32) -> bool {
+    a != b && a.wrapping_sub(b) < 1 << 31
+}
+
+/// Reject names that are not valid domain names.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(AppError::InvalidRecord("zone apex cannot be empty".into()));
+    }
+    Name::from_str(&format!("{}.", name))
+        .map(|_| ())
+        .map_err(|e| AppError::InvalidRecord(format!("invalid name {}: {}", name, e)))
+}
+
+/// Fully qualified, lowercase form of a name.
+fn fqdn(name: &str) -> Result<String> {
+    let name = normalize_name(name);
+    check_name(&name)?;
+    Ok(format!("{}.", name))
+}
+
+/// SOA RNAME for a mailbox given as `user@domain` or already in domain-name form;
+/// dots in the local part are escaped (RFC 1035 §8).
+fn mailbox_name(mailbox: &str) -> Result<String> {
+    match mailbox.split_once('@') {
+        Some((local, domain)) if !local.is_empty() => {
+            let domain = fqdn(domain)?;
+            Ok(format!("{}.{}", local.replace('.', "\\."), domain))
+        }
+        Some(_) => Err(AppError::InvalidRecord(format!(
+            "invalid mailbox {}",
+            mailbox
+        ))),
+        None => fqdn(mailbox),
+    }
+}
+
+/// Create the zone `example.com`, owned by `identity:alice`, at serial 1
+#[cfg(test)]
+pub(crate) async fn example_zone(db: &Database) -> Zone {
+    let input = ZoneInput {
+        apex: "example.com".to_string(),
+        primary_ns: "ns1.example.com".to_string(),
+        admin_mailbox: "hostmaster@example.com".to_string(),
+        serial_policy: SerialPolicy::Increment,
+        refresh: 7200,
+        retry: 900,
+        expire: 1209600,
+        minimum: 300,
+        default_ttl: 3600,
+    };
+    create(db, input, "identity:alice").await.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_date_serial() {
+        let today = day(2026, 10, 16);
+        assert_eq!(next_serial(SerialPolicy::Date, 0, today), 2026101600);
+        assert_eq!(
+            next_serial(SerialPolicy::Date, 2026101600, today),
+            2026101601
+        );
+        assert_eq!(
+            next_serial(SerialPolicy::Date, 2026101502, today),
+            2026101600
+        );
+
+        // A serial ahead of today's date keeps counting rather than going back
+        assert_eq!(
+            next_serial(SerialPolicy::Date, 2026101699, today),
+            2026101700
+        );
+        assert_eq!(
+            next_serial(SerialPolicy::Date, 2030010100, today),
+            2030010101
+        );
+    }
+
+    #[test]
+    fn test_increment_serial_wraps() {
+        let today = day(2026, 10, 16);
+        assert_eq!(next_serial(SerialPolicy::Increment, 41, today), 42);
+        assert_eq!(next_serial(SerialPolicy::Increment, u32::MAX, today), 0);
+        assert!(serial_gt(0, u32::MAX));
+        assert!(!serial_gt(41, 42));
+    }
+
+    #[test]
+    fn test_mailbox_name() {
+        assert_eq!(
+            mailbox_name("hostmaster@Example.com").unwrap(),
+            "hostmaster.example.com."
+        );
+        assert_eq!(
+            mailbox_name("john.doe@example.com").unwrap(),
+            "john\\.doe.example.com."
+        );
+        assert_eq!(
+            mailbox_name("hostmaster.example.com.").unwrap(),
+            "hostmaster.example.com."
+        );
+        assert!(mailbox_name("@example.com").is_err());
+    }
+}
@@ -3,9 +3,63 @@
 
 use graphql_dns_api::{
     db::Database,
-    models::{DNSRecord, DNSRecordType},
+    denial,
+    dnssec::DNSSECManager,
+    models::{
+        DNSRecord, DNSRecordType, DNSSECAlgorithm, DNSSECZone, SerialPolicy, Zone, ZoneInput,
+    },
+    signer, zones,
 };
 
+/// Create the zone `example.com`, owned by `identity:alice`, at serial 1
+async fn example_zone(db: &Database) -> Zone {
+    zone_at(db, "example.com").await
+}
+
+/// Create the zone at `apex`, owned by `identity:alice`, at serial 1
+async fn zone_at(db: &Database, apex: &str) -> Zone {
+    let input = ZoneInput {
+        apex: apex.to_string(),
+        primary_ns: "ns1.example.com".to_string(),
+        admin_mailbox: "hostmaster@example.com".to_string(),
+        serial_policy: SerialPolicy::Increment,
+        refresh: 7200,
+        retry: 900,
+        expire: 1209600,
+        minimum: 300,
+        default_ttl: 3600,
+    };
+    zones::create(db, input, "identity:alice").await.unwrap()
+}
+
+/// Sign the zone at `apex` with NSEC and keys held in memory only, so no key
+/// store is needed
+async fn sign_in_memory(db: &Database, manager: &DNSSECManager, apex: &str) -> DNSSECZone {
+    manager
+        .generate_zone_keys(apex, &[DNSSECAlgorithm::EcdsaP256Sha256], None)
+        .unwrap();
+    let signed = DNSSECZone {
+        zone: apex.to_string(),
+        enabled: true,
+        ksk: None,
+        zsk: None,
+        ds_record: None,
+        algorithms: Vec::new(),
+        ds_records: Vec::new(),
+        nsec3: None,
+        dnskey_rrsig: None,
+        cds_rrsig: None,
+        cdnskey_rrsig: None,
+        cds_delete: false,
+        parent_ds_ttl: None,
+        last_rotation: None,
+    };
+    let signed = db.upsert_dnssec_zone(signed).await.unwrap();
+    signer::sign_zone(db, manager, apex).await.unwrap();
+    denial::rebuild_chain(db, manager, &signed).await.unwrap();
+    signed
+}
+
 #[tokio::test]
 async fn test_database_create_record() {
     let db = Database::connect("memory").await.unwrap();
@@ -128,3 +182,654 @@ async fn test_content_hash_consistency() {
     // Different content should produce different hash
     assert_ne!(record1.content_hash(), record3.content_hash());
 }
+
+#[tokio::test]
+async fn test_import_zone_replace_is_atomic() {
+    use graphql_dns_api::{models::ZoneImportMode, zonefile::import_zone};
+
+    let db = Database::connect("memory").await.unwrap();
+
+    let zone = "$TTL 300\n@ SOA ns1 hostmaster 1 7200 900 604800 300\n@ NS ns1\nns1 A 192.0.2.53\n";
+    let result = import_zone(&db, "example.com", zone, ZoneImportMode::Replace)
+        .await
+        .unwrap();
+    assert!(result.errors.is_empty());
+    assert_eq!(result.imported, 3);
+
+    // A bad line rejects the whole file and leaves the zone untouched
+    let broken = "$TTL 300\n@ SOA ns1 hostmaster 2 7200 900 604800 300\nwww A 999.0.0.1\n";
+    let result = import_zone(&db, "example.com", broken, ZoneImportMode::Replace)
+        .await
+        .unwrap();
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].line, 3);
+
+    let records = db.zone_records("example.com").await.unwrap();
+    assert_eq!(records.len(), 3);
+
+    // Replacing the zone leaves the records of a zone below it alone
+    zone_at(&db, "sub.example.com").await;
+    let result = import_zone(&db, "example.com", zone, ZoneImportMode::Replace)
+        .await
+        .unwrap();
+    assert_eq!(result.removed, 3);
+    assert_eq!(db.zone_records("sub.example.com").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_zone_lifecycle_bumps_serial() {
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+
+    // A record stored before its zone existed joins it
+    let www = DNSRecord::new(
+        "www.example.com".to_string(),
+        DNSRecordType::A,
+        300,
+        "192.0.2.1".to_string(),
+    );
+    let www = db.create_record(www).await.unwrap();
+
+    let input = ZoneInput {
+        apex: "Example.COM.".to_string(),
+        primary_ns: "ns1.example.com".to_string(),
+        admin_mailbox: "hostmaster@example.com".to_string(),
+        serial_policy: SerialPolicy::Increment,
+        refresh: 7200,
+        retry: 900,
+        expire: 1209600,
+        minimum: 300,
+        default_ttl: 3600,
+    };
+    let zone = zones::create(&db, input, "identity:alice").await.unwrap();
+    assert_eq!(zone.apex, "example.com");
+    assert_eq!(zone.serial, 1);
+    let www = db.get_record(&www.id).await.unwrap();
+    assert_eq!(www.zone.as_deref(), Some("example.com"));
+
+    let zone = zones::bump_serial(&db, &manager, "example.com").await.unwrap();
+    assert_eq!(zone.serial, 2);
+    let soa = db
+        .query_records(Some("example.com".to_string()), Some(DNSRecordType::SOA), 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(soa.len(), 1);
+    assert_eq!(soa[0].value, "ns1.example.com. hostmaster.example.com. 2 7200 900 1209600 300");
+
+    assert!(zones::enclosing_zone(&db, "a.b.example.com").await.is_ok());
+    assert!(zones::enclosing_zone(&db, "example.org").await.is_err());
+
+    zones::delete(&db, "example.com").await.unwrap();
+    assert!(db.zone_records("example.com").await.unwrap().is_empty());
+    assert!(db.zones().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_zone_changes_are_journaled_for_ixfr() {
+    use graphql_dns_api::xfr;
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+
+    example_zone(&db).await;
+
+    let mut www = DNSRecord::new(
+        "www.example.com".to_string(),
+        DNSRecordType::A,
+        300,
+        "192.0.2.1".to_string(),
+    );
+    www.zone = Some("example.com".to_string());
+    db.create_record(www).await.unwrap();
+    zones::bump_serial(&db, &manager, "example.com").await.unwrap();
+
+    // The whole zone, SOA first
+    let axfr = xfr::zone_rrs(&db, "example.com").await.unwrap();
+    assert_eq!(axfr.len(), 2);
+
+    // IXFR from 1: current SOA, old SOA, new SOA, the addition, current SOA
+    let ixfr = xfr::ixfr_rrs(&db, "example.com", 1).await.unwrap().unwrap();
+    assert_eq!(ixfr.len(), 5);
+    assert_eq!(ixfr[0], ixfr[4]);
+    assert_eq!(ixfr[0], ixfr[2]);
+    assert_eq!(ixfr[3], axfr[1]);
+
+    // Up to date or ahead: the SOA alone; unknown serial: fall back to AXFR
+    assert_eq!(xfr::ixfr_rrs(&db, "example.com", 2).await.unwrap().unwrap().len(), 1);
+    assert_eq!(xfr::ixfr_rrs(&db, "example.com", 7).await.unwrap().unwrap().len(), 1);
+    assert!(xfr::ixfr_rrs(&db, "example.com", 0).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_dynamic_update_is_applied_atomically() {
+    use graphql_dns_api::update;
+    use hickory_proto::{
+        op::ResponseCode,
+        rr::{rdata::A, DNSClass, Name, RData, Record, RecordType},
+    };
+    use std::str::FromStr;
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+
+    example_zone(&db).await;
+
+    let www = Name::from_str("www.example.com.").unwrap();
+    let mut absent = Record::with(www.clone(), RecordType::A, 0);
+    absent.set_dns_class(DNSClass::NONE);
+    let add = Record::from_rdata(www.clone(), 300, RData::A(A::new(192, 0, 2, 1)));
+
+    // RRset must not exist: met, so the A is added and the serial bumped once
+    let prerequisites = [absent.clone()];
+    let updates = std::slice::from_ref(&add);
+    let code = update::apply_update(&db, &manager, "example.com", &prerequisites, updates)
+        .await
+        .unwrap();
+    assert_eq!(code, ResponseCode::NoError);
+    let records = db
+        .query_records(Some("www.example.com".to_string()), Some(DNSRecordType::A), 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].value, "192.0.2.1");
+    assert_eq!(records[0].zone.as_deref(), Some("example.com"));
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+
+    // Now it exists: nothing is applied
+    let mut other = add.clone();
+    other.set_data(Some(RData::A(A::new(192, 0, 2, 2))));
+    let code = update::apply_update(&db, &manager, "example.com", &[absent], &[other])
+        .await
+        .unwrap();
+    assert_eq!(code, ResponseCode::YXRRSet);
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+
+    // A duplicate changes nothing and leaves the serial alone
+    let updates = std::slice::from_ref(&add);
+    let code = update::apply_update(&db, &manager, "example.com", &[], updates)
+        .await
+        .unwrap();
+    assert_eq!(code, ResponseCode::NoError);
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+
+    // A duplicate with a new TTL retimes the stored record in place
+    let mut longer = add;
+    longer.set_ttl(600);
+    let code = update::apply_update(&db, &manager, "example.com", &[], &[longer])
+        .await
+        .unwrap();
+    assert_eq!(code, ResponseCode::NoError);
+    let retimed = db.get_record(&records[0].id).await.unwrap();
+    assert_eq!(retimed.ttl, 600);
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 3);
+
+    // In a signed zone the added RRset is signed and chained in the same write
+    sign_in_memory(&db, &manager, "example.com").await;
+    let mail = Name::from_str("mail.example.com.").unwrap();
+    let add = Record::from_rdata(mail, 300, RData::A(A::new(192, 0, 2, 3)));
+    let code = update::apply_update(&db, &manager, "example.com", &[], &[add])
+        .await
+        .unwrap();
+    assert_eq!(code, ResponseCode::NoError);
+    let added = db.records_at("mail.example.com").await.unwrap();
+    assert!(added[0].dnssec && added[0].rrsig.is_some());
+    let chain = db.denial_records(Some("example.com")).await.unwrap();
+    assert!(chain.iter().any(|r| r.owner == "mail.example.com" && r.rrsig.is_some()));
+}
+
+#[tokio::test]
+async fn test_zone_invariants_and_lint() {
+    use graphql_dns_api::{
+        lint,
+        models::{LintCode, ZoneImportMode},
+        zonefile::import_zone,
+        zones,
+    };
+
+    let db = Database::connect("memory").await.unwrap();
+
+    // A CNAME next to other data, or an RRset with two TTLs, rejects the file
+    let conflicting = "$TTL 300\n@ SOA ns1 hostmaster 1 7200 900 604800 300\n@ NS ns1\n\
+                       ns1 A 192.0.2.53\nwww CNAME @\nwww TXT hello\n\
+                       mail 300 A 192.0.2.1\nmail 600 A 192.0.2.2\n";
+    let result = import_zone(&db, "example.com", conflicting, ZoneImportMode::Replace)
+        .await
+        .unwrap();
+    assert_eq!(result.errors.len(), 2);
+    assert!(result.errors[0].message.contains("mixes TTLs 300, 600"));
+    assert!(result.errors[1].message.contains("CNAME at www.example.com"));
+    assert!(db.zone_records("example.com").await.unwrap().is_empty());
+
+    let zone = "$TTL 300\n@ SOA ns1 hostmaster 1 7200 900 604800 300\n@ NS ns1\n\
+                ns1 A 192.0.2.53\n@ MX 10 mail\nmail CNAME ns1\n";
+    let result = import_zone(&db, "example.com", zone, ZoneImportMode::Replace)
+        .await
+        .unwrap();
+    assert!(result.errors.is_empty());
+
+    // Merging into it is checked against what is stored at the same names
+    let merge = "$TTL 600\n@ SOA ns1 hostmaster 1 7200 900 604800 300\nns1 A 192.0.2.54\n";
+    let result = import_zone(&db, "example.com", merge, ZoneImportMode::Merge)
+        .await
+        .unwrap();
+    assert_eq!(result.errors.len(), 1);
+
+    // Lint: the MX target is an alias and nothing restricts certificate issuance
+    zones::adopt(&db, "example.com", "identity:alice").await.unwrap();
+    let found = lint::lint_zone(&db, "example.com").await.unwrap();
+    let codes: Vec<LintCode> = found.iter().map(|f| f.code).collect();
+    assert_eq!(codes, [LintCode::TargetIsCname, LintCode::MissingCaa]);
+}
+
+#[tokio::test]
+async fn test_records_page_by_cursor() {
+    use graphql_dns_api::models::{
+        DNSRecordFilter, DNSRecordOrder, DNSRecordSortField, RecordCursor, SortDirection,
+    };
+
+    let db = Database::connect("memory").await.unwrap();
+    for (name, value) in [
+        ("b.example.com", "192.0.2.2"),
+        ("d.example.com", "192.0.2.4"),
+        ("a.example.com", "192.0.2.1"),
+        ("c.example.com", "198.51.100.3"),
+        ("example.org", "192.0.2.9"),
+    ] {
+        let record = DNSRecord::new(name.to_string(), DNSRecordType::A, 300, value.to_string());
+        db.create_record(record).await.unwrap();
+    }
+    let names = |records: &[DNSRecord]| -> Vec<String> {
+        records.iter().map(|r| r.name.clone()).collect()
+    };
+
+    let filter = DNSRecordFilter {
+        name_suffix: Some(".Example.com.".to_string()),
+        ..Default::default()
+    };
+    let order = DNSRecordOrder::default();
+    let page = db.page_records(&filter, order, None, None, 2, false).await.unwrap();
+    assert_eq!(names(&page), ["a.example.com", "b.example.com"]);
+    assert_eq!(db.count_records(&filter).await.unwrap(), 4);
+
+    // A record written before the cursor neither shifts nor repeats the next page
+    let cursor = RecordCursor::new(&page[1], order);
+    let record = DNSRecord::new("aa.example.com".into(), DNSRecordType::A, 300, "192.0.2.5".into());
+    db.create_record(record).await.unwrap();
+    let page = db
+        .page_records(&filter, order, Some(&cursor), None, 2, false)
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["c.example.com", "d.example.com"]);
+
+    // Paging from the end comes back nearest-first
+    let page = db.page_records(&filter, order, None, Some(&cursor), 2, true).await.unwrap();
+    assert_eq!(names(&page), ["aa.example.com", "a.example.com"]);
+
+    let descending = DNSRecordOrder {
+        field: DNSRecordSortField::Name,
+        direction: SortDirection::Desc,
+    };
+    let page = db.page_records(&filter, descending, None, None, 1, false).await.unwrap();
+    assert_eq!(names(&page), ["d.example.com"]);
+    assert!(db
+        .page_records(&filter, descending, Some(&cursor), None, 1, false)
+        .await
+        .is_err());
+
+    let filter = DNSRecordFilter {
+        name_pattern: Some("*.example.com".to_string()),
+        value_contains: Some("192.0.2.".to_string()),
+        anchored: Some(false),
+        ..Default::default()
+    };
+    assert_eq!(db.count_records(&filter).await.unwrap(), 4);
+    let filter = DNSRecordFilter {
+        name_pattern: Some("*.*.com".to_string()),
+        ..Default::default()
+    };
+    assert!(db.count_records(&filter).await.is_err());
+}
+
+#[tokio::test]
+async fn test_record_history_and_zone_at() {
+    use graphql_dns_api::{history, models::RecordChangeKind};
+
+    let db = Database::connect("memory").await.unwrap();
+    let alice = db.acting_as("identity:alice");
+
+    let mut record = DNSRecord::new(
+        "www.example.com".to_string(),
+        DNSRecordType::A,
+        300,
+        "192.0.2.1".to_string(),
+    );
+    record.zone = Some("example.com".to_string());
+    let created = alice.create_record(record).await.unwrap();
+    let id = created.id.to_string();
+    let between = chrono::Utc::now();
+
+    let mut changed = created.clone();
+    changed.value = "192.0.2.2".to_string();
+    alice.update_record(&id, changed).await.unwrap();
+
+    // Re-signing alone adds no version
+    let mut signed = db.get_record(&id).await.unwrap();
+    signed.rrsig = Some("signature".to_string());
+    db.update_record(&id, signed).await.unwrap();
+
+    db.delete_record(&id).await.unwrap();
+
+    let history = db.record_history(&id).await.unwrap();
+    let versions: Vec<(i32, RecordChangeKind)> =
+        history.iter().map(|e| (e.version, e.kind)).collect();
+    assert_eq!(
+        versions,
+        [
+            (1, RecordChangeKind::Created),
+            (2, RecordChangeKind::Updated),
+            (3, RecordChangeKind::Deleted),
+        ]
+    );
+    assert_eq!(history[1].actor.as_deref(), Some("identity:alice"));
+    assert_eq!(history[2].actor, None);
+    assert_eq!(history[1].before.as_ref().unwrap().value, "192.0.2.1");
+    assert_eq!(history[1].after.as_ref().unwrap().value, "192.0.2.2");
+    assert!(history[2].after.is_none());
+
+    let then = history::zone_at(&db, "example.com", between).await.unwrap();
+    assert_eq!(then.len(), 1);
+    assert_eq!(then[0].value, "192.0.2.1");
+    let now = history::zone_at(&db, "example.com", chrono::Utc::now()).await.unwrap();
+    assert!(now.is_empty());
+}
+
+#[tokio::test]
+async fn test_changeset_is_applied_atomically() {
+    use graphql_dns_api::{
+        changeset,
+        models::{ChangeAction, DNSRecordInput, RecordChangeInput},
+    };
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+    example_zone(&db).await;
+
+    let create = |name: &str, record_type: DNSRecordType, value: &str| RecordChangeInput {
+        action: ChangeAction::Create,
+        id: None,
+        record: Some(DNSRecordInput {
+            name: name.to_string(),
+            record_type,
+            ttl: None,
+            value: Some(value.to_string()),
+            dnssec: None,
+            mx: None,
+            srv: None,
+            caa: None,
+            tlsa: None,
+        }),
+    };
+    let changes = vec![
+        create("www.example.com", DNSRecordType::A, "192.0.2.1"),
+        create("www.example.com", DNSRecordType::AAAA, "2001:db8::1"),
+    ];
+
+    // A preview writes nothing
+    let preview = changeset::preview(&db, "example.com", changes.clone()).await.unwrap();
+    assert!(!preview.applied);
+    assert_eq!(preview.changes.len(), 2);
+    assert!(db.records_at("www.example.com").await.unwrap().is_empty());
+
+    let result = changeset::commit(&db, &manager, "example.com", changes).await.unwrap();
+    assert!(result.applied);
+    assert_eq!(db.records_at("www.example.com").await.unwrap().len(), 2);
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+
+    // A CNAME beside the addresses breaks an invariant: nothing is written,
+    // not even the valid change before it
+    let changes = vec![
+        create("mail.example.com", DNSRecordType::A, "192.0.2.25"),
+        create("www.example.com", DNSRecordType::CNAME, "mail.example.com."),
+    ];
+    let preview = changeset::preview(&db, "example.com", changes.clone()).await.unwrap();
+    assert!(preview.lint.iter().any(|w| w.name == "www.example.com"));
+    assert!(changeset::commit(&db, &manager, "example.com", changes).await.is_err());
+    assert!(db.records_at("mail.example.com").await.unwrap().is_empty());
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, 2);
+}
+
+#[tokio::test]
+async fn test_changeset_signs_a_signed_zone_in_the_same_write() {
+    use graphql_dns_api::{
+        changeset,
+        models::{ChangeAction, DNSRecordInput, DenialRecord, RecordChangeInput},
+    };
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+    example_zone(&db).await;
+
+    let signed = sign_in_memory(&db, &manager, "example.com").await;
+    let serial = db.get_zone("example.com").await.unwrap().serial;
+
+    let changes = vec![RecordChangeInput {
+        action: ChangeAction::Create,
+        id: None,
+        record: Some(DNSRecordInput {
+            name: "www.example.com".to_string(),
+            record_type: DNSRecordType::A,
+            ttl: None,
+            value: Some("192.0.2.1".to_string()),
+            dnssec: None,
+            mx: None,
+            srv: None,
+            caa: None,
+            tlsa: None,
+        }),
+    }];
+    changeset::commit(&db, &manager, "example.com", changes).await.unwrap();
+
+    // The record, the SOA with its new serial and their signatures landed together
+    let www = db.records_at("www.example.com").await.unwrap();
+    assert!(www[0].dnssec && www[0].rrsig.is_some());
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, serial + 1);
+    let soa = db.records_at("example.com").await.unwrap();
+    let soa = soa.iter().find(|r| r.record_type == DNSRecordType::SOA).unwrap();
+    assert!(soa.value.contains(&format!(" {} ", serial + 1)));
+    assert!(soa.rrsig.is_some());
+
+    // The patched chain is the one a rebuild gives
+    let links = |chain: Vec<DenialRecord>| {
+        let mut links: Vec<_> = chain.into_iter().map(|r| (r.owner, r.next, r.types)).collect();
+        links.sort();
+        links
+    };
+    let patched = db.denial_records(Some("example.com")).await.unwrap();
+    assert!(patched.iter().all(|r| r.rrsig.is_some()));
+    assert!(patched.iter().any(|r| r.owner == "www.example.com"));
+    denial::rebuild_chain(&db, &manager, &signed).await.unwrap();
+    let rebuilt = db.denial_records(Some("example.com")).await.unwrap();
+    assert_eq!(links(patched), links(rebuilt));
+}
+
+#[tokio::test]
+async fn test_zone_below_is_left_to_itself() {
+    use graphql_dns_api::zonefile;
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+    example_zone(&db).await;
+    zone_at(&db, "sub.example.com").await;
+    let mut www = DNSRecord::new(
+        "www.sub.example.com".to_string(),
+        DNSRecordType::A,
+        300,
+        "192.0.2.1".to_string(),
+    );
+    www.zone = Some("sub.example.com".to_string());
+    let www = db.create_record(www).await.unwrap();
+
+    // Signing the parent signs none of the child's records
+    sign_in_memory(&db, &manager, "example.com").await;
+    assert!(db.get_record(&www.id).await.unwrap().rrsig.is_none());
+    assert!(signer::find_signed_zone(&db, "www.sub.example.com").await.unwrap().is_none());
+    assert!(signer::find_signed_zone(&db, "www.example.com").await.unwrap().is_some());
+    let exported = zonefile::export_zone(&db, "example.com").await.unwrap();
+    assert!(!exported.contains("sub.example.com"), "{}", exported);
+
+    // Nor does unsigning it strip the child's signatures
+    sign_in_memory(&db, &manager, "sub.example.com").await;
+    let signed = db.get_record(&www.id).await.unwrap().rrsig;
+    assert!(signed.is_some());
+    db.unsign_zone("example.com").await.unwrap();
+    assert_eq!(db.get_record(&www.id).await.unwrap().rrsig, signed);
+}
+
+#[tokio::test]
+async fn test_only_the_owner_changes_a_zone() {
+    use graphql_dns_api::{models::ZoneImportMode, zonefile};
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+    example_zone(&db).await;
+
+    assert!(zones::require_owner(&db, "Example.COM.", "identity:alice").await.is_ok());
+    let refused = zones::require_owner(&db, "example.com", "identity:bob").await.unwrap_err();
+    assert!(refused.to_string().contains("owned by identity:alice"), "{}", refused);
+
+    // Importing over someone else's zone is refused before anything is written
+    let text = "$ORIGIN example.com.\n\
+                @ 3600 IN SOA ns1.example.com. hostmaster.example.com. 1 7200 900 1209600 300\n\
+                www 300 IN A 192.0.2.1\n";
+    let result = zonefile::import_zone_as(
+        &db,
+        &manager,
+        "example.com",
+        text,
+        ZoneImportMode::Replace,
+        "identity:bob",
+    )
+    .await;
+    assert!(result.is_err());
+    let records = db.zone_records("example.com").await.unwrap();
+    assert!(records.iter().all(|record| record.name != "www.example.com"));
+
+    // A zone nobody has created yet goes to the importer
+    let imported = zonefile::import_zone_as(
+        &db,
+        &manager,
+        "example.org",
+        &text.replace("example.com", "example.org"),
+        ZoneImportMode::Replace,
+        "identity:bob",
+    )
+    .await
+    .unwrap();
+    assert!(imported.errors.is_empty());
+    assert_eq!(db.get_zone("example.org").await.unwrap().owner, "identity:bob");
+}
+
+#[tokio::test]
+async fn test_record_writes_go_through_the_zone_write() {
+    use graphql_dns_api::{models::DNSRecordInput, records};
+
+    let db = Database::connect("memory").await.unwrap();
+    let manager = DNSSECManager::new();
+    example_zone(&db).await;
+    zone_at(&db, "sub.example.com").await;
+    sign_in_memory(&db, &manager, "example.com").await;
+    let serial = db.get_zone("example.com").await.unwrap().serial;
+
+    let input = |name: &str, ttl: Option<i32>, value: &str| DNSRecordInput {
+        name: name.to_string(),
+        record_type: DNSRecordType::A,
+        ttl,
+        value: Some(value.to_string()),
+        dnssec: None,
+        mx: None,
+        srv: None,
+        caa: None,
+        tlsa: None,
+    };
+
+    // Created signed and chained, with the serial, in one write
+    let first = records::insert(&db, &manager, input("www.example.com", None, "192.0.2.1"), None)
+        .await
+        .unwrap();
+    assert!(first.dnssec && first.rrsig.is_some());
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, serial + 1);
+    let chain = db.denial_records(Some("example.com")).await.unwrap();
+    assert!(chain.iter().any(|r| r.owner == "www.example.com"));
+
+    // A record joining the RRset retimes and re-signs it as a whole
+    let second = input("www.example.com", Some(600), "192.0.2.2");
+    let second = records::insert(&db, &manager, second, None).await.unwrap();
+    let first = db.get_record(&first.id).await.unwrap();
+    assert_eq!(first.ttl, 600);
+    assert_eq!(first.rrsig, second.rrsig);
+
+    // Rewriting keeps the record in its zone
+    let moved = input("www.sub.example.com", None, "192.0.2.1");
+    assert!(records::rewrite(&db, &manager, first.clone(), moved).await.is_err());
+    let renamed = input("web.example.com", None, "192.0.2.1");
+    let renamed = records::rewrite(&db, &manager, first, renamed).await.unwrap();
+    assert_eq!(renamed.zone.as_deref(), Some("example.com"));
+    assert!(renamed.rrsig.is_some());
+
+    // The name leaves the chain with its last record
+    records::remove(&db, &manager, renamed).await.unwrap();
+    let chain = db.denial_records(Some("example.com")).await.unwrap();
+    assert!(chain.iter().all(|r| r.owner != "web.example.com"));
+    assert_eq!(db.get_zone("example.com").await.unwrap().serial, serial + 4);
+}
+
+#[tokio::test]
+async fn test_zone_create_and_delete_publish_record_changes() {
+    use async_graphql::futures_util::{FutureExt, StreamExt};
+    use graphql_dns_api::{events::Event, models::RecordChangeKind};
+
+    let db = Database::connect("memory").await.unwrap();
+    example_zone(&db).await;
+    let mut www = DNSRecord::new(
+        "www.sub.example.com".to_string(),
+        DNSRecordType::A,
+        300,
+        "192.0.2.1".to_string(),
+    );
+    www.zone = Some("example.com".to_string());
+    db.create_record(www).await.unwrap();
+
+    let mut events = Box::pin(db.events().subscribe());
+    let mut published = || {
+        let mut changes = Vec::new();
+        while let Some(Some(event)) = events.next().now_or_never() {
+            if let Event::RecordChanged(change) = event {
+                changes.push((change.kind, change.record.name, change.record.zone));
+            }
+        }
+        changes.sort_by(|a, b| a.1.cmp(&b.1));
+        changes
+    };
+
+    // The new zone's SOA is created and the record it takes over moves to it
+    zone_at(&db, "sub.example.com").await;
+    let sub = Some("sub.example.com".to_string());
+    assert_eq!(
+        published(),
+        vec![
+            (RecordChangeKind::Created, "sub.example.com".to_string(), sub.clone()),
+            (RecordChangeKind::Updated, "www.sub.example.com".to_string(), sub.clone()),
+        ]
+    );
+
+    // Deleting it deletes both
+    zones::delete(&db, "sub.example.com").await.unwrap();
+    assert_eq!(
+        published(),
+        vec![
+            (RecordChangeKind::Deleted, "sub.example.com".to_string(), sub.clone()),
+            (RecordChangeKind::Deleted, "www.sub.example.com".to_string(), sub),
+        ]
+    );
+}